@@ -0,0 +1,142 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Procedural macros for the Exonum testkit. The macros are re-exported by
+//! the `exonum-testkit` crate with the `macros` feature and should be used from there.
+
+#![deny(missing_debug_implementations, missing_docs)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{Error, Expr, Ident, ItemFn, LitInt};
+use syn::parse::{Parse, ParseStream, Result};
+use syn::punctuated::Punctuated;
+
+/// Arguments of the `test` attribute.
+#[derive(Default)]
+struct TestArgs {
+    services: Vec<Expr>,
+    validators: Option<LitInt>,
+    seed: Option<LitInt>,
+}
+
+impl Parse for TestArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = TestArgs::default();
+        while !input.is_empty() {
+            let name: Ident = input.parse()?;
+            if name == "services" {
+                let content;
+                parenthesized!(content in input);
+                let services = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+                args.services.extend(services);
+            } else if name == "validators" {
+                input.parse::<Token![=]>()?;
+                args.validators = Some(input.parse()?);
+            } else if name == "seed" {
+                input.parse::<Token![=]>()?;
+                args.seed = Some(input.parse()?);
+            } else {
+                let message = format!(
+                    "unknown argument `{}`; expected `services`, `validators` or `seed`",
+                    name
+                );
+                return Err(Error::new(name.span(), message));
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Marks a function as a test run on a testkit with the given services.
+///
+/// The function should take `&mut TestKit` and, optionally, `TestKitApi` of the same
+/// testkit. The testkit is created with a single validator node, unless specified otherwise.
+/// For example, a function marked with
+/// `#[exonum_testkit::test(services(CurrencyService), validators = 4, seed = 42)]`
+/// may be declared as `fn test_transfer(testkit: &mut TestKit, api: TestKitApi)`.
+///
+/// Attribute arguments:
+///
+/// - `services(...)`: services of the testkit, as expressions passed to
+///   `TestKitBuilder::with_service()`
+/// - `validators = N`: number of validators, see `TestKitBuilder::with_validators()`
+/// - `seed = N`: seed for the generated keys, see `TestKitBuilder::with_seed()`
+///
+/// Other attributes of the function, such as `#[should_panic]`, are preserved.
+///
+/// The attribute conflicts with the built-in `#[test]` if all macros of the testkit
+/// are imported with `#[macro_use]`; import `txvec!` selectively instead, with
+/// `#[macro_use(txvec)] extern crate exonum_testkit;`.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as TestArgs);
+    let item = parse_macro_input!(item as ItemFn);
+    expand_test(args, item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_test(args: TestArgs, item: ItemFn) -> Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        ident,
+        decl,
+        block,
+        ..
+    } = item;
+    let inputs = &decl.inputs;
+    let output = &decl.output;
+    let (api, call_args) = match inputs.len() {
+        1 => (quote!(), quote!(&mut testkit)),
+        2 => (quote!(let api = testkit.api();), quote!(&mut testkit, api)),
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "test function should take `&mut TestKit` and, optionally, `TestKitApi`",
+            ))
+        }
+    };
+
+    let services = &args.services;
+    let validators = args.validators.map(|count| quote!(.with_validators(#count)));
+    let seed = args.seed.map(|seed| quote!(.with_seed(#seed)));
+
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        fn #ident() #output {
+            fn #ident(#inputs) #output #block
+
+            let mut testkit = ::exonum_testkit::TestKitBuilder::validator()
+                #validators
+                #seed
+                #(.with_service(#services))*
+                .create();
+            #api
+            #ident(#call_args)
+        }
+    })
+}
@@ -0,0 +1,149 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builder of the testkit API with custom middleware.
+
+use std::fmt;
+
+use iron::{AfterMiddleware, BeforeMiddleware, Chain};
+
+use {TestKit, TestKitApi};
+
+/// Builder of `TestKitApi`, which wraps public service endpoints with Iron middleware,
+/// e.g., authentication or error-mapping layers used in production. Obtained
+/// with `TestKit::api_builder()`.
+///
+/// Middleware is applied to requests to `api/services` endpoints of the public API;
+/// the private API and built-in `system` and `explorer` endpoints are not wrapped.
+/// `BeforeMiddleware` and `AfterMiddleware` are run in the order they are added.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # extern crate iron;
+/// # extern crate router;
+/// # use exonum::blockchain::{ApiContext, Service, Transaction};
+/// # use exonum::crypto::Hash;
+/// # use exonum::encoding;
+/// # use exonum::messages::RawTransaction;
+/// # use exonum::storage::Snapshot;
+/// # use exonum_testkit::{ApiKind, TestKitBuilder};
+/// # use iron::{BeforeMiddleware, Handler, IronResult, Request, Response};
+/// # use iron::status::Status;
+/// # use router::Router;
+/// #
+/// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+/// #
+/// # // Service with an endpoint returning the identifier of the request.
+/// # struct EchoService;
+/// #
+/// # impl Service for EchoService {
+/// #     fn service_name(&self) -> &'static str {
+/// #         "echo"
+/// #     }
+/// #
+/// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+/// #         Vec::new()
+/// #     }
+/// #
+/// #     fn service_id(&self) -> u16 {
+/// #         1
+/// #     }
+/// #
+/// #     fn tx_from_raw(&self, _: RawTransaction) -> FromRawResult {
+/// #         unimplemented!();
+/// #     }
+/// #
+/// #     fn public_api_handler(&self, _: &ApiContext) -> Option<Box<Handler>> {
+/// #         let request_id = |req: &mut Request| -> IronResult<Response> {
+/// #             let id = req.headers.get_raw("X-Request-Id").unwrap()[0].clone();
+/// #             let id = String::from_utf8(id).unwrap();
+/// #             Ok(Response::with((Status::Ok, format!("\"{}\"", id))))
+/// #         };
+/// #         let mut router = Router::new();
+/// #         router.get("/v1/request-id", request_id, "request_id");
+/// #         Some(Box::new(router))
+/// #     }
+/// # }
+/// #
+/// // Middleware used by the node in production.
+/// struct RequestIdInjector;
+///
+/// impl BeforeMiddleware for RequestIdInjector {
+///     fn before(&self, req: &mut Request) -> IronResult<()> {
+///         req.headers.set_raw("X-Request-Id", vec![b"42".to_vec()]);
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() {
+/// let testkit = TestKitBuilder::validator().with_service(EchoService).create();
+/// let api = testkit
+///     .api_builder()
+///     .with_before(RequestIdInjector)
+///     .create();
+/// let request_id: String = api.get(ApiKind::Service("echo"), "v1/request-id");
+/// assert_eq!(request_id, "42");
+/// # }
+/// ```
+pub struct TestKitApiBuilder<'a> {
+    testkit: &'a TestKit,
+    before: Vec<Box<BeforeMiddleware>>,
+    after: Vec<Box<AfterMiddleware>>,
+}
+
+impl<'a> fmt::Debug for TestKitApiBuilder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TestKitApiBuilder")
+            .field("before", &self.before.len())
+            .field("after", &self.after.len())
+            .finish()
+    }
+}
+
+impl<'a> TestKitApiBuilder<'a> {
+    pub(crate) fn new(testkit: &'a TestKit) -> Self {
+        TestKitApiBuilder {
+            testkit,
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+
+    /// Adds middleware processing requests before they reach service handlers.
+    pub fn with_before<M: BeforeMiddleware>(mut self, middleware: M) -> Self {
+        self.before.push(Box::new(middleware));
+        self
+    }
+
+    /// Adds middleware processing responses of service handlers.
+    pub fn with_after<M: AfterMiddleware>(mut self, middleware: M) -> Self {
+        self.after.push(Box::new(middleware));
+        self
+    }
+
+    /// Creates the API.
+    pub fn create(self) -> TestKitApi {
+        let mut chain = Chain::new(self.testkit.public_api_mount());
+        for middleware in self.before {
+            chain.link_before(middleware);
+        }
+        for middleware in self.after {
+            chain.link_after(middleware);
+        }
+        TestKitApi::with_service_handler(self.testkit, Box::new(chain))
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Expectations on erroneous API responses.
+
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json::{self, Value};
+
+/// HTTP method of a request checked with `TestKitApi::assert_error()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiMethod {
+    /// GET request.
+    Get,
+    /// POST request with the given JSON body.
+    Post(Value),
+}
+
+/// Expectation on the body of a response checked with `TestKitApi::assert_error()`.
+#[derive(Clone)]
+pub enum BodyMatcher {
+    /// Any body is accepted.
+    Any,
+    /// The body is JSON equal to the given value, e.g., `json!("Wallet not found")`
+    /// for a JSON string.
+    Json(Value),
+    /// The body contains the given substring.
+    Contains(String),
+    /// The body satisfies the given predicate.
+    Custom(Arc<Fn(&[u8]) -> bool + Send + Sync>),
+}
+
+impl fmt::Debug for BodyMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BodyMatcher::Any => f.write_str("Any"),
+            BodyMatcher::Json(ref value) => f.debug_tuple("Json").field(value).finish(),
+            BodyMatcher::Contains(ref text) => f.debug_tuple("Contains").field(text).finish(),
+            BodyMatcher::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl BodyMatcher {
+    /// Creates a matcher checking that the body contains the given substring.
+    pub fn contains(text: &str) -> Self {
+        BodyMatcher::Contains(text.to_owned())
+    }
+
+    /// Creates a matcher with the given predicate on the body.
+    pub fn custom<F>(predicate: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        BodyMatcher::Custom(Arc::new(predicate))
+    }
+
+    /// Checks whether the body matches the expectation.
+    pub fn matches(&self, body: &[u8]) -> bool {
+        match *self {
+            BodyMatcher::Any => true,
+            BodyMatcher::Json(ref expected) => {
+                serde_json::from_slice::<Value>(body)
+                    .map(|value| value == *expected)
+                    .unwrap_or(false)
+            }
+            BodyMatcher::Contains(ref text) => String::from_utf8_lossy(body).contains(&**text),
+            BodyMatcher::Custom(ref predicate) => predicate(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_matchers() {
+        let body = br#""Wallet not found""#;
+        assert!(BodyMatcher::Any.matches(body));
+        assert!(BodyMatcher::Json(Value::from("Wallet not found")).matches(body));
+        assert!(!BodyMatcher::Json(Value::from("Wallet")).matches(body));
+        assert!(!BodyMatcher::Json(Value::Null).matches(b"not json"));
+        assert!(BodyMatcher::contains("not found").matches(body));
+        assert!(!BodyMatcher::contains("Invalid").matches(body));
+        assert!(BodyMatcher::custom(|body| body.len() == 18).matches(body));
+    }
+}
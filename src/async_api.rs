@@ -0,0 +1,198 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Futures-based interface to the testkit and its API.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures::{Async, Future, Poll};
+use futures::sync::oneshot;
+use iron::headers::Headers;
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+
+use {ApiKind, TestKitApi};
+
+/// Future resolving to the result of a testkit operation, which is performed
+/// in a dedicated thread. Polling the future never blocks, so it can be run on a reactor
+/// (e.g., `tokio-core`) together with other futures.
+///
+/// The future never resolves to an error; if the operation panics, the panic
+/// is propagated to the thread polling the future, just like with the blocking methods.
+#[must_use = "futures do nothing unless polled"]
+pub struct TestKitFuture<T> {
+    receiver: oneshot::Receiver<thread::Result<T>>,
+}
+
+impl<T> fmt::Debug for TestKitFuture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TestKitFuture").finish()
+    }
+}
+
+impl<T: Send + 'static> TestKitFuture<T> {
+    /// Runs the operation in a new thread.
+    pub(crate) fn spawn<F>(operation: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(operation));
+            // The receiver may be dropped if the future is no longer needed.
+            let _ = sender.send(result);
+        });
+        TestKitFuture { receiver }
+    }
+}
+
+impl<T> Future for TestKitFuture<T> {
+    type Item = T;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<T, ()> {
+        match self.receiver.poll() {
+            Ok(Async::Ready(Ok(value))) => Ok(Async::Ready(value)),
+            Ok(Async::Ready(Err(payload))) => panic::resume_unwind(payload),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(oneshot::Canceled) => panic!("Testkit operation thread has terminated"),
+        }
+    }
+}
+
+/// Futures-based wrapper around `TestKitApi`. Obtained with `TestKitApi::into_async()`.
+///
+/// Each request is processed in a dedicated thread, so handlers that block or wait
+/// for asynchronous work do not block the reactor polling the returned futures.
+/// Requests are processed one at a time. Panic conditions are the same as for the
+/// corresponding methods of `TestKitApi`; panics are propagated when the futures are polled.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # extern crate futures;
+/// # use exonum::blockchain::Block;
+/// # use exonum_testkit::{ApiKind, TestKitBuilder};
+/// # use futures::Future;
+/// # fn main() {
+/// let testkit = TestKitBuilder::validator().create();
+/// let api = testkit.api().into_async();
+/// // The future can be run on a reactor; here, it is simply waited for.
+/// let blocks: Vec<Block> = api.get(ApiKind::Explorer, "v1/blocks?count=10").wait().unwrap();
+/// assert_eq!(blocks.len(), 1);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncTestKitApi {
+    inner: Arc<Mutex<TestKitApi>>,
+}
+
+impl AsyncTestKitApi {
+    pub(crate) fn new(api: TestKitApi) -> Self {
+        AsyncTestKitApi { inner: Arc::new(Mutex::new(api)) }
+    }
+
+    fn spawn<D, F>(&self, request: F) -> TestKitFuture<D>
+    where
+        D: Send + 'static,
+        F: FnOnce(&TestKitApi) -> D + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        TestKitFuture::spawn(move || {
+            let api = inner.lock().unwrap_or_else(|e| e.into_inner());
+            request(&api)
+        })
+    }
+
+    fn get_internal<D>(&self, private: bool, kind: ApiKind, endpoint: &str) -> TestKitFuture<D>
+    where
+        for<'de> D: Deserialize<'de> + Send + 'static,
+    {
+        let endpoint = format!("{}/{}", kind.into_prefix(), endpoint);
+        self.spawn(move |api| {
+            api.get_internal(private, &endpoint, Headers::new(), false)
+        })
+    }
+
+    fn post_internal<T, D>(
+        &self,
+        private: bool,
+        kind: ApiKind,
+        endpoint: &str,
+        data: &T,
+    ) -> TestKitFuture<D>
+    where
+        T: Serialize,
+        for<'de> D: Deserialize<'de> + Send + 'static,
+    {
+        let endpoint = format!("{}/{}", kind.into_prefix(), endpoint);
+        let data: Value = serde_json::to_value(data).expect("Cannot serialize data to JSON");
+        self.spawn(move |api| {
+            api.post_internal(private, &endpoint, Headers::new(), &data)
+        })
+    }
+
+    /// Gets information from a public endpoint of the node. See `TestKitApi::get()`.
+    pub fn get<D>(&self, kind: ApiKind, endpoint: &str) -> TestKitFuture<D>
+    where
+        for<'de> D: Deserialize<'de> + Send + 'static,
+    {
+        self.get_internal(false, kind, endpoint)
+    }
+
+    /// Gets information from a private endpoint of the node. See `TestKitApi::get_private()`.
+    pub fn get_private<D>(&self, kind: ApiKind, endpoint: &str) -> TestKitFuture<D>
+    where
+        for<'de> D: Deserialize<'de> + Send + 'static,
+    {
+        self.get_internal(true, kind, endpoint)
+    }
+
+    /// Posts a transaction to the service using the public API. See `TestKitApi::post()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `transaction` cannot be serialized to JSON.
+    pub fn post<T, D>(&self, kind: ApiKind, endpoint: &str, transaction: &T) -> TestKitFuture<D>
+    where
+        T: Serialize,
+        for<'de> D: Deserialize<'de> + Send + 'static,
+    {
+        self.post_internal(false, kind, endpoint, transaction)
+    }
+
+    /// Posts a transaction to the service using the private API.
+    /// See `TestKitApi::post_private()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `transaction` cannot be serialized to JSON.
+    pub fn post_private<T, D>(
+        &self,
+        kind: ApiKind,
+        endpoint: &str,
+        transaction: &T,
+    ) -> TestKitFuture<D>
+    where
+        T: Serialize,
+        for<'de> D: Deserialize<'de> + Send + 'static,
+    {
+        self.post_internal(true, kind, endpoint, transaction)
+    }
+}
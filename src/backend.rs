@@ -0,0 +1,245 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage backends of the testkit.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use exonum::storage::{Database, MemoryDB, Patch, Result as StorageResult, RocksDB,
+                      RocksDBOptions, Snapshot};
+use tempdir::TempDir;
+
+/// Kind of the storage backend specified in `TestKitBuilder`.
+#[derive(Debug, Clone)]
+pub(crate) enum StorageKind {
+    /// In-memory storage.
+    Memory,
+    /// RocksDB storage in the given directory, or in a temporary directory if the path
+    /// is not specified.
+    RocksDb(Option<PathBuf>),
+    /// Existing RocksDB storage in the given directory, which is not modified by the testkit.
+    Preloaded(PathBuf),
+}
+
+impl StorageKind {
+    /// Opens a storage of this kind.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the RocksDB storage cannot be opened.
+    pub fn open(&self) -> StorageBackend {
+        match *self {
+            StorageKind::Memory => StorageBackend::Memory(MemoryDB::new()),
+            StorageKind::RocksDb(Some(ref path)) => StorageBackend::open_rocksdb(path, None),
+            StorageKind::RocksDb(None) => StorageBackend::open_temp_rocksdb(),
+            StorageKind::Preloaded(ref path) => StorageBackend::open_preloaded(path),
+        }
+    }
+}
+
+/// Database used by the testkit.
+#[derive(Clone)]
+pub(crate) enum StorageBackend {
+    Memory(MemoryDB),
+    RocksDb {
+        db: RocksDB,
+        path: PathBuf,
+        // Temporary directory is removed once all copies of the database are dropped.
+        temp_dir: Option<Arc<TempDir>>,
+    },
+    /// Read-only RocksDB storage with changes kept in memory.
+    Overlay {
+        base: RocksDB,
+        path: PathBuf,
+        changes: Arc<RwLock<Patch>>,
+    },
+}
+
+impl StorageBackend {
+    fn open_rocksdb(path: &Path, temp_dir: Option<TempDir>) -> Self {
+        let mut options = RocksDBOptions::default();
+        options.create_if_missing(true);
+        let db = RocksDB::open(path, options).unwrap_or_else(|e| {
+            panic!("Cannot open RocksDB at {}: {}", path.display(), e)
+        });
+        StorageBackend::RocksDb {
+            db,
+            path: path.to_owned(),
+            temp_dir: temp_dir.map(Arc::new),
+        }
+    }
+
+    fn open_preloaded(path: &Path) -> Self {
+        let mut options = RocksDBOptions::default();
+        options.create_if_missing(false);
+        let base = RocksDB::open(path, options).unwrap_or_else(|e| {
+            panic!("Cannot open RocksDB at {}: {}", path.display(), e)
+        });
+        StorageBackend::Overlay {
+            base,
+            path: path.to_owned(),
+            changes: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+
+    fn open_temp_rocksdb() -> Self {
+        let temp_dir = TempDir::new("exonum-testkit").expect(
+            "Cannot create temporary directory for RocksDB",
+        );
+        let path = temp_dir.path().to_owned();
+        StorageBackend::open_rocksdb(&path, Some(temp_dir))
+    }
+
+    /// Creates a storage of the same kind without changes made by the testkit. A RocksDB
+    /// storage is created in a temporary directory; a preloaded storage retains the data
+    /// it has been opened with.
+    pub fn empty_copy(&self) -> Self {
+        match *self {
+            StorageBackend::Memory(_) => StorageBackend::Memory(MemoryDB::new()),
+            StorageBackend::RocksDb { .. } => StorageBackend::open_temp_rocksdb(),
+            StorageBackend::Overlay { ref base, ref path, .. } => StorageBackend::Overlay {
+                base: Clone::clone(base),
+                path: path.clone(),
+                changes: Arc::new(RwLock::new(BTreeMap::new())),
+            },
+        }
+    }
+
+    /// Returns `true` if the storage contains data not written by the testkit.
+    pub fn is_preloaded(&self) -> bool {
+        match *self {
+            StorageBackend::Overlay { .. } => true,
+            StorageBackend::Memory(_) |
+            StorageBackend::RocksDb { .. } => false,
+        }
+    }
+}
+
+impl fmt::Debug for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            StorageBackend::Memory(_) => f.write_str("MemoryDB"),
+            StorageBackend::RocksDb { ref path, .. } => {
+                f.debug_tuple("RocksDB").field(path).finish()
+            }
+            StorageBackend::Overlay { ref path, .. } => {
+                f.debug_tuple("PreloadedRocksDB").field(path).finish()
+            }
+        }
+    }
+}
+
+impl Database for StorageBackend {
+    fn clone(&self) -> Box<Database> {
+        Box::new(Clone::clone(self))
+    }
+
+    fn snapshot(&self) -> Box<Snapshot> {
+        match *self {
+            StorageBackend::Memory(ref db) => db.snapshot(),
+            StorageBackend::RocksDb { ref db, .. } => db.snapshot(),
+            StorageBackend::Overlay {
+                ref base,
+                ref changes,
+                ..
+            } => {
+                let mut fork = base.fork();
+                fork.merge(changes.read().expect("Cannot read storage changes").clone());
+                Box::new(fork)
+            }
+        }
+    }
+
+    fn merge(&mut self, patch: Patch) -> StorageResult<()> {
+        match *self {
+            StorageBackend::Memory(ref mut db) => db.merge(patch),
+            StorageBackend::RocksDb { ref mut db, .. } => db.merge(patch),
+            StorageBackend::Overlay { ref changes, .. } => {
+                let mut changes = changes.write().expect("Cannot write storage changes");
+                for (name, table_changes) in patch {
+                    changes
+                        .entry(name)
+                        .or_insert_with(BTreeMap::new)
+                        .extend(table_changes);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn merge_sync(&mut self, patch: Patch) -> StorageResult<()> {
+        self.merge(patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_rocksdb() {
+        let mut db = StorageKind::RocksDb(None).open();
+        let path = match db {
+            StorageBackend::RocksDb { ref path, .. } => path.clone(),
+            _ => unreachable!(),
+        };
+        assert!(path.exists());
+
+        let mut fork = db.fork();
+        fork.put("foo", vec![1], vec![2]);
+        db.merge(fork.into_patch()).unwrap();
+        let copy = Clone::clone(&db);
+        assert_eq!(copy.snapshot().get("foo", &[1]), Some(vec![2]));
+
+        drop(db);
+        assert!(path.exists());
+        drop(copy);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_preloaded_rocksdb() {
+        let dir = TempDir::new("exonum-testkit-test").unwrap();
+        {
+            let mut db = StorageKind::RocksDb(Some(dir.path().to_owned())).open();
+            let mut fork = db.fork();
+            fork.put("foo", vec![1], vec![2]);
+            fork.put("foo", vec![2], vec![3]);
+            db.merge(fork.into_patch()).unwrap();
+        }
+
+        let mut db = StorageKind::Preloaded(dir.path().to_owned()).open();
+        let mut fork = db.fork();
+        fork.put("foo", vec![1], vec![4]);
+        fork.remove("foo", vec![2]);
+        fork.put("bar", vec![], vec![5]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let snapshot = db.snapshot();
+        assert_eq!(snapshot.get("foo", &[1]), Some(vec![4]));
+        assert_eq!(snapshot.get("foo", &[2]), None);
+        assert_eq!(snapshot.get("bar", &[]), Some(vec![5]));
+        assert_eq!(db.empty_copy().snapshot().get("foo", &[2]), Some(vec![3]));
+        drop(db);
+
+        // The preloaded storage is not modified.
+        let db = StorageKind::Preloaded(dir.path().to_owned()).open();
+        let snapshot = db.snapshot();
+        assert_eq!(snapshot.get("foo", &[1]), Some(vec![2]));
+        assert_eq!(snapshot.get("bar", &[]), None);
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for benchmarking services, e.g., with [`criterion`].
+//!
+//! `chain_fixture()` builds a blockchain, on top of which the benchmarks are run,
+//! and `TxBatch` holds pre-signed transactions, so that neither is measured. `BenchKit`
+//! creates an independent copy of the fixture for each benchmark iteration; the copy
+//! should be created in the setup phase of the iteration.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # extern crate exonum_testkit;
+//! # use exonum::blockchain::Transaction;
+//! # use exonum::crypto;
+//! # use exonum::helpers::Height;
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::bench::{self, BenchKit, TxBatch};
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! #
+//! # fn main() {
+//! let (pubkey, key) = crypto::gen_keypair();
+//! let tx = |msg: String| Box::new(MyTransaction::new(&pubkey, &msg, &key)) as Box<Transaction>;
+//! let fixture = bench::chain_fixture(
+//!     TestKitBuilder::validator().with_service_factory(|| Box::new(MyService)),
+//!     Height(10),
+//!     |height| TxBatch::generate(5, |i| tx(format!("{} at {}", i, height.0))),
+//! );
+//! let mut benchkit = BenchKit::new(fixture);
+//! let batch = TxBatch::generate(100, |i| tx(i.to_string()));
+//! // With `criterion`, the same steps are performed by
+//! // `b.iter_with_setup(|| benchkit.setup_with_batch(&batch), ...)`.
+//! let elapsed = benchkit.measure(&batch, |testkit, txs| {
+//!     testkit.create_block_with_transactions(txs);
+//! });
+//! println!("100 transactions: {:?}", elapsed);
+//! # }
+//! ```
+//!
+//! [`criterion`]: https://crates.io/crates/criterion
+
+use std::time::{Duration, Instant};
+
+use exonum::blockchain::Transaction;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawMessage};
+
+use {TestKit, TestKitBuilder};
+
+/// Batch of pre-signed transactions. Transactions are stored as raw messages and are parsed
+/// anew for each use, so the same batch can be committed in many benchmark iterations.
+#[derive(Debug, Clone)]
+pub struct TxBatch {
+    messages: Vec<RawMessage>,
+}
+
+impl TxBatch {
+    /// Creates a batch from the given transactions.
+    pub fn new<I>(txs: I) -> Self
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        TxBatch { messages: txs.into_iter().map(|tx| tx.raw().clone()).collect() }
+    }
+
+    /// Creates a batch of `size` transactions produced by the `factory` from the index
+    /// of a transaction in the batch.
+    pub fn generate<F>(size: usize, factory: F) -> Self
+    where
+        F: FnMut(usize) -> Box<Transaction>,
+    {
+        TxBatch::new((0..size).map(factory))
+    }
+
+    /// Returns the number of transactions in the batch.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns `true` if the batch contains no transactions.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Parses transactions of the batch with the services of the testkit.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions cannot be parsed by the testkit services.
+    pub fn transactions(&self, testkit: &TestKit) -> Vec<Box<Transaction>> {
+        self.messages
+            .iter()
+            .map(|raw| {
+                testkit.blockchain.tx_from_raw(raw.clone()).unwrap_or_else(|| {
+                    panic!("Transaction {:?} cannot be parsed by the services", raw.hash())
+                })
+            })
+            .collect()
+    }
+}
+
+/// Creates a testkit with the `builder` and fills the blockchain with blocks up to
+/// the given `height`. Transactions for each block are produced by `block_txs` from
+/// the height of the block.
+///
+/// Building a long chain is slow; consider saving the fixture with `TestKit::save()`
+/// and restoring it with `TestKitBuilder::from_saved()`.
+pub fn chain_fixture<F>(builder: TestKitBuilder, height: Height, mut block_txs: F) -> TestKit
+where
+    F: FnMut(Height) -> TxBatch,
+{
+    let mut testkit = builder.create();
+    while testkit.height() < height {
+        let txs = block_txs(testkit.height().next()).transactions(&testkit);
+        testkit.create_block_with_transactions(txs);
+    }
+    testkit
+}
+
+/// Source of independent testkits for benchmark iterations, all of which start from
+/// the state of the fixture testkit.
+#[derive(Debug)]
+pub struct BenchKit {
+    fixture: TestKit,
+}
+
+impl BenchKit {
+    /// Creates a benchmarking kit with the given fixture.
+    pub fn new(fixture: TestKit) -> Self {
+        BenchKit { fixture }
+    }
+
+    /// Returns the fixture testkit.
+    pub fn fixture(&self) -> &TestKit {
+        &self.fixture
+    }
+
+    /// Creates a copy of the fixture (see `TestKit::fork()`). The method should be called
+    /// in the setup phase of a benchmark iteration, e.g., in the setup closure of
+    /// `criterion::Bencher::iter_with_setup()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the fixture cannot be copied, i.e., if it has services added
+    ///   with `TestKitBuilder::with_service()`.
+    pub fn setup(&mut self) -> TestKit {
+        self.fixture.fork()
+    }
+
+    /// Creates a copy of the fixture together with parsed transactions of the `batch`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions cannot be parsed by the testkit services.
+    pub fn setup_with_batch(&mut self, batch: &TxBatch) -> (TestKit, Vec<Box<Transaction>>) {
+        let testkit = self.setup();
+        let txs = batch.transactions(&testkit);
+        (testkit, txs)
+    }
+
+    /// Measures the wall time of the `routine` run on a copy of the fixture with parsed
+    /// transactions of the `batch`. Creating the copy, parsing transactions and dropping
+    /// the copy are not measured. The method is useful for ad-hoc measurements outside
+    /// of a benchmarking framework.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions cannot be parsed by the testkit services.
+    pub fn measure<F>(&mut self, batch: &TxBatch, routine: F) -> Duration
+    where
+        F: FnOnce(&mut TestKit, Vec<Box<Transaction>>),
+    {
+        let (mut testkit, txs) = self.setup_with_batch(batch);
+        let start = Instant::now();
+        routine(&mut testkit, txs);
+        start.elapsed()
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Consensus messages authorizing committed blocks.
+
+use std::collections::BTreeSet;
+
+use exonum::blockchain::{Block, ValidatorKeys};
+use exonum::crypto;
+use exonum::helpers::ValidatorId;
+use exonum::messages::{Message, MessageBuffer, Precommit, Propose, RawMessage};
+use exonum::node::State as NodeState;
+
+use TestNode;
+
+/// Block header together with the consensus messages the testkit has generated
+/// for the block. See `TestKit::block_proof()`.
+#[derive(Debug, Clone)]
+pub struct BlockProof {
+    /// Block header.
+    pub block: Block,
+    /// Propose of the block, or `None` if the block has not been created by this testkit
+    /// with the full consensus emulation (e.g., it has been created with `create_block_fast()`
+    /// or loaded from a saved state).
+    pub propose: Option<Propose>,
+    /// Precommits authorizing the block.
+    pub precommits: Vec<Precommit>,
+}
+
+impl BlockProof {
+    /// Checks the proof against the given validator set, which is usually taken from
+    /// the configuration actual at the height of the block. The proof is valid if:
+    ///
+    /// - Precommits refer to the block and are signed with consensus keys of distinct
+    ///   validators
+    /// - Precommits are signed by the Byzantine majority of validators
+    /// - The propose, if present, is signed by the block proposer, and the precommits
+    ///   refer to it
+    pub fn verify(&self, validators: &[ValidatorKeys]) -> bool {
+        let block_hash = self.block.hash();
+        let mut signers = BTreeSet::new();
+        for precommit in &self.precommits {
+            let id = precommit.validator().0;
+            let keys = match validators.get(id as usize) {
+                Some(keys) => keys,
+                None => return false,
+            };
+            if precommit.block_hash() != &block_hash ||
+                precommit.height() != self.block.height() ||
+                !precommit.verify_signature(&keys.consensus_key) || !signers.insert(id)
+            {
+                return false;
+            }
+        }
+        if signers.len() < NodeState::byzantine_majority_count(validators.len()) {
+            return false;
+        }
+
+        match self.propose {
+            Some(ref propose) => {
+                let proposer = propose.validator();
+                propose.height() == self.block.height() &&
+                    proposer == self.block.proposer_id() &&
+                    validators.get(proposer.0 as usize).map_or(false, |keys| {
+                        propose.verify_signature(&keys.consensus_key)
+                    }) &&
+                    self.precommits.iter().all(|precommit| {
+                        precommit.propose_hash() == &propose.hash() &&
+                            precommit.round() == propose.round()
+                    })
+            }
+            None => true,
+        }
+    }
+}
+
+/// Way, in which precommits of a block are tampered to obtain an invalid block proof.
+/// See `TestKit::create_block_with_tampered_proof()` and `TestKit::tampered_block_proof()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofTampering {
+    /// The signature of the precommit by the given validator is corrupted.
+    InvalidSignature(ValidatorId),
+    /// The precommit by the given validator is correctly signed, but refers to another block.
+    WrongBlockHash(ValidatorId),
+    /// Only the given number of first precommits is kept, e.g., fewer than the Byzantine
+    /// majority of validators.
+    TruncatedPrecommits(usize),
+}
+
+impl ProofTampering {
+    /// Applies the tampering to the precommits of a block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the tampering refers to a validator, which precommit is absent.
+    pub(crate) fn apply(
+        self,
+        precommits: Vec<Precommit>,
+        validators: &[TestNode],
+    ) -> Vec<Precommit> {
+        let target = match self {
+            ProofTampering::InvalidSignature(id) |
+            ProofTampering::WrongBlockHash(id) => id,
+            ProofTampering::TruncatedPrecommits(count) => {
+                return precommits.into_iter().take(count).collect();
+            }
+        };
+        assert!(
+            precommits.iter().any(|precommit| precommit.validator() == target),
+            "Cannot tamper the block proof: no precommit by validator {:?}",
+            target
+        );
+
+        precommits
+            .into_iter()
+            .map(|precommit| if precommit.validator() == target {
+                self.tamper(&precommit, validators)
+            } else {
+                precommit
+            })
+            .collect()
+    }
+
+    fn tamper(self, precommit: &Precommit, validators: &[TestNode]) -> Precommit {
+        match self {
+            ProofTampering::WrongBlockHash(id) => {
+                let node = validators.get(id.0 as usize).unwrap_or_else(|| {
+                    panic!("Validator with id {:?} is absent in test network", id)
+                });
+                Precommit::new(
+                    id,
+                    precommit.height(),
+                    precommit.round(),
+                    precommit.propose_hash(),
+                    &crypto::hash(precommit.block_hash().as_ref()),
+                    precommit.time(),
+                    node.consensus_keypair().1,
+                )
+            }
+            _ => {
+                let mut bytes = precommit.raw().as_ref().as_ref().to_vec();
+                *bytes.last_mut().unwrap() ^= 1;
+                Precommit::from_raw(RawMessage::new(MessageBuffer::from_vec(bytes)))
+                    .expect("Cannot parse tampered precommit")
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Competing branches of the testkit blockchain.
+
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+
+use explorer::BlockWithTransactions;
+
+/// Blocks detached from the testkit blockchain, which form a branch growing from a common
+/// ancestor block. A branch can be reapplied to the blockchain with `TestKit::switch_to_branch()`.
+///
+/// See `TestKit::detach_branch()` for details.
+#[derive(Debug)]
+pub struct Branch {
+    ancestor: Height,
+    ancestor_hash: Hash,
+    blocks: Vec<BlockWithTransactions>,
+}
+
+impl Branch {
+    pub(crate) fn new(
+        ancestor: Height,
+        ancestor_hash: Hash,
+        blocks: Vec<BlockWithTransactions>,
+    ) -> Self {
+        Branch {
+            ancestor,
+            ancestor_hash,
+            blocks,
+        }
+    }
+
+    /// Returns the height of the common ancestor block the branch grows from.
+    pub fn ancestor(&self) -> Height {
+        self.ancestor
+    }
+
+    /// Returns the hash of the common ancestor block.
+    pub fn ancestor_hash(&self) -> &Hash {
+        &self.ancestor_hash
+    }
+
+    /// Returns the height of the latest block in the branch.
+    pub fn height(&self) -> Height {
+        Height(self.ancestor.0 + self.blocks.len() as u64)
+    }
+
+    /// Returns the number of blocks in the branch.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns `true` if the branch contains no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns blocks of the branch in the order of increasing height.
+    pub fn blocks(&self) -> &[BlockWithTransactions] {
+        &self.blocks
+    }
+
+    pub(crate) fn into_blocks(self) -> Vec<BlockWithTransactions> {
+        self.blocks
+    }
+}
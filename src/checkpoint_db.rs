@@ -14,7 +14,7 @@
 
 use std::sync::{Arc, RwLock};
 
-use exonum::storage::{Database, Patch, Result as StorageResult, Snapshot};
+use exonum::storage::{Change, Database, Patch, Result as StorageResult, Snapshot};
 
 /// Implementation of a `Database`, which allows to rollback commits introduced by the `merge()`
 /// function.
@@ -36,6 +36,16 @@ impl<T: Database + Clone> CheckpointDb<T> {
         }
     }
 
+    /// Creates a checkpointed database from the underlying data storage and the journal
+    /// of patches reverting `merge()` operations, which have led to the current state
+    /// of the storage.
+    pub fn from_parts(db: T, journal: Vec<Patch>) -> Self {
+        CheckpointDb {
+            inner: db,
+            journal: Arc::new(RwLock::new(journal)),
+        }
+    }
+
     /// Rolls back this database by udoing the latest `count` `merge()` operations.
     ///
     /// # Panics
@@ -71,6 +81,81 @@ impl<T: Database + Clone> CheckpointDb<T> {
         journal_len < count
     }
 
+    /// Returns the number of `merge()` operations in the DB journal.
+    pub fn journal_len(&self) -> usize {
+        self.journal
+            .read()
+            .expect("Cannot acquire read lock on journal")
+            .len()
+    }
+
+    /// Returns a snapshot of the database state after the first `journal_len` `merge()`
+    /// operations in the DB journal.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `journal_len` exceeds the number of operations in the DB journal.
+    pub fn snapshot_at(&self, journal_len: usize) -> Box<Snapshot> {
+        let journal = self.journal.read().expect(
+            "Cannot acquire read lock on journal",
+        );
+        assert!(
+            journal_len <= journal.len(),
+            "Cannot get snapshot after {} changes; only {} checkpoints in the journal",
+            journal_len,
+            journal.len()
+        );
+
+        let mut fork = self.inner.fork();
+        for patch in journal[journal_len..].iter().rev() {
+            for (name, changes) in patch.iter() {
+                for (key, change) in changes.iter() {
+                    match *change {
+                        Change::Put(ref value) => fork.put(name, key.clone(), value.clone()),
+                        Change::Delete => fork.remove(name, key.clone()),
+                    }
+                }
+            }
+        }
+        Box::new(fork)
+    }
+
+    /// Returns the journal of patches reverting `merge()` operations.
+    pub fn journal(&self) -> Vec<Patch> {
+        self.journal
+            .read()
+            .expect("Cannot acquire read lock on journal")
+            .clone()
+    }
+
+    /// Returns the current contents of the database as a patch.
+    pub fn state(&self) -> Patch {
+        let journal = self.journal.read().expect(
+            "Cannot acquire read lock on journal",
+        );
+
+        // Each key ever written to the database is mentioned in the journal.
+        let snapshot = self.inner.snapshot();
+        let mut fork = self.inner.fork();
+        for patch in journal.iter() {
+            for (name, changes) in patch.iter() {
+                for key in changes.keys() {
+                    if let Some(value) = snapshot.get(name, key) {
+                        fork.put(name, key.clone(), value);
+                    }
+                }
+            }
+        }
+        fork.into_patch()
+    }
+
+    /// Copies the current state of this database together with its journal into the given
+    /// empty database. Changes to the copy do not affect this database, and vice versa.
+    pub fn copy_to(&self, mut inner: T) -> Self {
+        inner.merge(self.state()).expect("Cannot copy the database");
+        CheckpointDb::from_parts(inner, self.journal())
+    }
+
     /// Returns a handler to the database. The handler could be used to roll the database back
     /// without having the ownership to it.
     pub fn handler(&self) -> CheckpointDbHandler<T> {
@@ -128,8 +213,45 @@ impl<T: Database + Clone> CheckpointDbHandler<T> {
     pub fn rollback(&mut self, count: usize) -> bool {
         self.0.rollback(count)
     }
+
+    /// Returns the number of `merge()` operations in the DB journal.
+    pub fn journal_len(&self) -> usize {
+        self.0.journal_len()
+    }
+
+    /// Returns a snapshot of the database state after the first `journal_len` `merge()`
+    /// operations in the DB journal.
+    pub fn snapshot_at(&self, journal_len: usize) -> Box<Snapshot> {
+        self.0.snapshot_at(journal_len)
+    }
+
+    /// Returns the database the handler refers to.
+    pub fn database(&self) -> CheckpointDb<T> {
+        Clone::clone(&self.0)
+    }
+
+    /// Returns the underlying data storage.
+    pub fn inner(&self) -> &T {
+        &self.0.inner
+    }
+
+    /// Returns the journal of patches reverting `merge()` operations.
+    pub fn journal(&self) -> Vec<Patch> {
+        self.0.journal()
+    }
+
+    /// Returns the current contents of the database as a patch.
+    pub fn state(&self) -> Patch {
+        self.0.state()
+    }
+
+    /// Copies the database together with its journal into the given empty database.
+    pub fn copy_to(&self, inner: T) -> CheckpointDb<T> {
+        self.0.copy_to(inner)
+    }
 }
 
+
 #[cfg(test)]
 mod tests {
     use exonum::storage::{Change, MemoryDB};
@@ -325,6 +447,35 @@ mod tests {
         assert_eq!(snapshot.get("foo", &[]), None);
     }
 
+    #[test]
+    fn test_checkpointdb_snapshot_at() {
+        let mut db = CheckpointDb::new(MemoryDB::new());
+        let mut fork = db.fork();
+        fork.put("foo", vec![], vec![2]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let mut fork = db.fork();
+        fork.put("foo", vec![], vec![3]);
+        fork.put("bar", vec![1], vec![4]);
+        db.merge(fork.into_patch()).unwrap();
+        assert_eq!(db.journal_len(), 2);
+
+        let snapshot = db.snapshot_at(0);
+        assert_eq!(snapshot.get("foo", &[]), None);
+        assert_eq!(snapshot.get("bar", &[1]), None);
+        let snapshot = db.snapshot_at(1);
+        assert_eq!(snapshot.get("foo", &[]), Some(vec![2]));
+        assert_eq!(snapshot.get("bar", &[1]), None);
+        let snapshot = db.snapshot_at(2);
+        assert_eq!(snapshot.get("foo", &[]), Some(vec![3]));
+        assert_eq!(snapshot.get("bar", &[1]), Some(vec![4]));
+
+        // Check that historical snapshots do not affect the database
+        let snapshot = db.snapshot();
+        assert_eq!(snapshot.get("foo", &[]), Some(vec![3]));
+        assert_eq!(db.journal_len(), 2);
+    }
+
     #[test]
     fn test_checkpointdb_handler() {
         let mut db = CheckpointDb::new(MemoryDB::new());
@@ -340,4 +491,33 @@ mod tests {
         let snapshot = db.snapshot();
         assert_eq!(snapshot.get("foo", &[]), None);
     }
+
+    #[test]
+    fn test_checkpointdb_copy() {
+        let mut db = CheckpointDb::new(MemoryDB::new());
+        let mut fork = db.fork();
+        fork.put("foo", vec![], vec![2]);
+        fork.put("bar", vec![1], vec![3]);
+        db.merge(fork.into_patch()).unwrap();
+        let mut fork = db.fork();
+        fork.remove("bar", vec![1]);
+        db.merge(fork.into_patch()).unwrap();
+
+        let mut copy = db.copy_to(MemoryDB::new());
+        assert_eq!(copy.journal_len(), 2);
+        let snapshot = copy.snapshot();
+        assert_eq!(snapshot.get("foo", &[]), Some(vec![2]));
+        assert_eq!(snapshot.get("bar", &[1]), None);
+
+        let mut fork = copy.fork();
+        fork.put("foo", vec![], vec![4]);
+        copy.merge(fork.into_patch()).unwrap();
+        assert_eq!(copy.snapshot().get("foo", &[]), Some(vec![4]));
+        assert_eq!(db.snapshot().get("foo", &[]), Some(vec![2]));
+        assert_eq!(db.journal_len(), 2);
+
+        copy.rollback(2);
+        assert_eq!(copy.snapshot().get("bar", &[1]), Some(vec![3]));
+        assert_eq!(db.snapshot().get("bar", &[1]), None);
+    }
 }
@@ -0,0 +1,36 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed clients for service APIs.
+
+use {ApiKind, TestKitApi};
+
+/// Typed client for the API of a service, which wraps `TestKitApi`. Clients are usually
+/// declared with the `service_api_client!` macro and obtained with `TestKitApi::client()`
+/// or the accessor method generated by the macro.
+pub trait ServiceApiClient<'a> {
+    /// Name of the service, which is used as the prefix of the service endpoints.
+    const SERVICE_NAME: &'static str;
+
+    /// Creates the client from the testkit API.
+    fn from_api(api: &'a TestKitApi) -> Self;
+
+    /// Returns the wrapped testkit API.
+    fn api(&self) -> &'a TestKitApi;
+
+    /// Returns the kind of the service endpoints.
+    fn kind(&self) -> ApiKind {
+        ApiKind::Service(Self::SERVICE_NAME)
+    }
+}
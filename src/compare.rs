@@ -14,7 +14,12 @@
 
 //! Routines for comparison between 2 states.
 
-use exonum::storage::Snapshot;
+use std::collections::{BTreeMap, BTreeSet};
+
+use exonum::encoding::serialize::ToHex;
+use exonum::storage::{Iterator as StorageIterator, Snapshot};
+
+use StorageChange;
 
 /// Facilitation of comparison between 2 states.
 #[derive(Debug)]
@@ -224,9 +229,136 @@ impl ComparableSnapshot<Box<Snapshot>> for Box<Snapshot> {
     }
 }
 
+/// Returns the contents of the index with the given name.
+fn index_contents(snapshot: &Snapshot, index: &str) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let mut iter = snapshot.iter(index, &[]);
+    let mut contents = BTreeMap::new();
+    while let Some((key, value)) = iter.next() {
+        contents.insert(key.to_vec(), value.to_vec());
+    }
+    contents
+}
+
+/// Formats an optional value of an index entry.
+fn format_value(value: &Option<Vec<u8>>) -> String {
+    match *value {
+        Some(ref value) => value.to_hex(),
+        None => "(absent)".to_owned(),
+    }
+}
+
+/// Asserts that the entries of indices mentioned in `expected_changes` differ between
+/// two snapshots exactly as specified. Indices not mentioned in `expected_changes`
+/// are not compared; to check that an index is not modified at all, use
+/// `Comparison::assert_eq()` on its contents.
+///
+/// # Panics
+///
+/// - Panics if the changes differ from the expected ones. The panic message lists
+///   the mismatched entries with hex-encoded keys and values.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::crypto;
+/// # use exonum::helpers::Height;
+/// # use exonum_testkit::{assert_storage_diff, StorageChange, TestKitBuilder};
+/// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+/// #
+/// # fn main() {
+/// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+/// let (alice, key) = crypto::gen_keypair();
+/// testkit.create_block_with_transaction(MyTransaction::new(&alice, "Hello", &key));
+/// testkit.create_block_with_transaction(MyTransaction::new(&alice, "Bye", &key));
+/// let expected_changes = vec![
+///     StorageChange {
+///         index: "documentation.messages".to_owned(),
+///         key: alice.as_ref().to_vec(),
+///         old: Some(b"Hello".to_vec()),
+///         new: Some(b"Bye".to_vec()),
+///     },
+/// ];
+/// assert_storage_diff(
+///     &*testkit.snapshot_at(Height(1)),
+///     &*testkit.snapshot_at(Height(2)),
+///     &expected_changes,
+/// );
+/// # }
+/// ```
+pub fn assert_storage_diff(old: &Snapshot, new: &Snapshot, expected_changes: &[StorageChange]) {
+    let expected: BTreeMap<_, _> = expected_changes
+        .iter()
+        .map(|change| {
+            ((&change.index, change.key.clone()), (change.old.clone(), change.new.clone()))
+        })
+        .collect();
+
+    let indices: BTreeSet<_> = expected_changes.iter().map(|change| &change.index).collect();
+    let mut actual = BTreeMap::new();
+    for index in indices {
+        let old_contents = index_contents(old, index);
+        let new_contents = index_contents(new, index);
+        let keys: BTreeSet<_> = old_contents.keys().chain(new_contents.keys()).collect();
+        for key in keys {
+            let (old_value, new_value) = (old_contents.get(key), new_contents.get(key));
+            if old_value != new_value {
+                actual.insert(
+                    (index, key.clone()),
+                    (old_value.cloned(), new_value.cloned()),
+                );
+            }
+        }
+    }
+
+    let keys: BTreeSet<_> = expected.keys().chain(actual.keys()).collect();
+    let mismatches: Vec<_> = keys.into_iter()
+        .filter_map(|key| {
+            let entry = format!("index `{}`, key {}", key.0, key.1.to_hex());
+            match (expected.get(key), actual.get(key)) {
+                (Some(expected), Some(actual)) if expected != actual => Some(format!(
+                    "  {}: expected {} -> {}, got {} -> {}",
+                    entry,
+                    format_value(&expected.0),
+                    format_value(&expected.1),
+                    format_value(&actual.0),
+                    format_value(&actual.1)
+                )),
+                (Some(expected), None) => {
+                    if expected.0 == expected.1 {
+                        None
+                    } else {
+                        Some(format!(
+                            "  {}: expected {} -> {}, got no change",
+                            entry,
+                            format_value(&expected.0),
+                            format_value(&expected.1)
+                        ))
+                    }
+                }
+                (None, Some(actual)) => Some(format!(
+                    "  {}: unexpected change {} -> {}",
+                    entry,
+                    format_value(&actual.0),
+                    format_value(&actual.1)
+                )),
+                _ => None,
+            }
+        })
+        .collect();
+    if !mismatches.is_empty() {
+        panic!(
+            "Storage diff does not match the expected one:\n{}",
+            mismatches.join("\n")
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use exonum::storage::{Database, MemoryDB};
 
     #[test]
     fn test_assertions() {
@@ -276,4 +408,80 @@ mod tests {
         let comp = Comparison::new(vec![1, 2, 3], vec![4]);
         comp.assert_inv("Array length more than 1", |v| v.len() > 1);
     }
+
+    fn change(index: &str, key: u8, old: Option<u8>, new: Option<u8>) -> StorageChange {
+        StorageChange {
+            index: index.to_owned(),
+            key: vec![key],
+            old: old.map(|value| vec![value]),
+            new: new.map(|value| vec![value]),
+        }
+    }
+
+    fn storage_diff_fixture() -> (Box<Snapshot>, Box<Snapshot>) {
+        let mut db = MemoryDB::new();
+        let mut fork = db.fork();
+        fork.put("foo", vec![1], vec![1]);
+        fork.put("foo", vec![2], vec![2]);
+        fork.put("bar", vec![1], vec![1]);
+        db.merge(fork.into_patch()).unwrap();
+        let old = db.snapshot();
+
+        let mut fork = db.fork();
+        fork.put("foo", vec![1], vec![3]);
+        fork.remove("foo", vec![2]);
+        fork.put("foo", vec![3], vec![3]);
+        fork.put("bar", vec![2], vec![2]);
+        db.merge(fork.into_patch()).unwrap();
+        (old, db.snapshot())
+    }
+
+    #[test]
+    fn test_storage_diff() {
+        let (old, new) = storage_diff_fixture();
+        assert_storage_diff(
+            &*old,
+            &*new,
+            &[
+                change("foo", 3, None, Some(3)),
+                change("foo", 1, Some(1), Some(3)),
+                change("foo", 2, Some(2), None),
+            ],
+        );
+        assert_storage_diff(&*old, &*new, &[change("bar", 2, None, Some(2))]);
+        assert_storage_diff(&*old, &*old, &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index `foo`, key 02: expected 02 -> (absent), got no change")]
+    fn test_storage_diff_missing_change() {
+        let (old, _) = storage_diff_fixture();
+        assert_storage_diff(&*old, &*old, &[change("foo", 2, Some(2), None)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index `foo`, key 03: unexpected change (absent) -> 03")]
+    fn test_storage_diff_unexpected_change() {
+        let (old, new) = storage_diff_fixture();
+        assert_storage_diff(
+            &*old,
+            &*new,
+            &[change("foo", 1, Some(1), Some(3)), change("foo", 2, Some(2), None)],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "index `foo`, key 01: expected 01 -> 02, got 01 -> 03")]
+    fn test_storage_diff_wrong_value() {
+        let (old, new) = storage_diff_fixture();
+        assert_storage_diff(
+            &*old,
+            &*new,
+            &[
+                change("foo", 1, Some(1), Some(2)),
+                change("foo", 2, Some(2), None),
+                change("foo", 3, None, Some(3)),
+            ],
+        );
+    }
 }
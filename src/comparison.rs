@@ -0,0 +1,402 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Differential testing of two versions of a service.
+
+use std::collections::BTreeSet;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum::storage::{Change, Patch, Snapshot};
+
+use {TestKit, TestKitBuilder};
+use compare::Comparison;
+use time::MockTimeProvider;
+
+/// Seed of the keys used by the testkits created with `ComparisonTestKit::new()`.
+const COMPARISON_SEED: u64 = 0;
+/// Prefix of the names of core indices.
+const CORE_PREFIX: &str = "core.";
+
+/// Pair of testkits running two versions of a service on the identical sequence of blocks.
+/// After each block, the storage contents and the state hashes of the testkits are compared;
+/// the first found difference is recorded as a `StateDivergence`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate exonum;
+/// # #[macro_use] extern crate exonum_testkit;
+/// # use exonum::blockchain::{Service, Transaction};
+/// # use exonum::crypto::{self, Hash, PublicKey};
+/// # use exonum::encoding;
+/// # use exonum::messages::{Message, RawTransaction};
+/// # use exonum::storage::{Fork, Snapshot};
+/// # use exonum::storage::MapIndex;
+/// # use exonum_testkit::ComparisonTestKit;
+/// #
+/// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+/// #
+/// # pub struct ServiceV1;
+/// #
+/// # impl Service for ServiceV1 {
+/// #     fn service_name(&self) -> &'static str {
+/// #         "documentation"
+/// #     }
+/// #
+/// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+/// #         Vec::new()
+/// #     }
+/// #
+/// #     fn service_id(&self) -> u16 {
+/// #         0
+/// #     }
+/// #
+/// #     fn tx_from_raw(&self, raw: RawTransaction) -> FromRawResult {
+/// #         Ok(Box::new(MyTransaction::from_raw(raw)?))
+/// #     }
+/// # }
+/// #
+/// # message! {
+/// #     struct MyTransaction {
+/// #         const TYPE = 0;
+/// #         const ID = 0;
+/// #
+/// #         from: &PublicKey,
+/// #         msg: &str,
+/// #     }
+/// # }
+/// #
+/// # impl Transaction for MyTransaction {
+/// #     fn verify(&self) -> bool {
+/// #         self.verify_signature(self.from())
+/// #     }
+/// #
+/// #     fn execute(&self, fork: &mut Fork) {
+/// #         let mut messages = MapIndex::new("documentation.messages", fork);
+/// #         messages.put(self.from(), self.msg().to_owned());
+/// #     }
+/// # }
+/// #
+/// # // Reimplementation of the service, which should behave the same way.
+/// # pub struct ServiceV2;
+/// #
+/// # impl Service for ServiceV2 {
+/// #     fn service_name(&self) -> &'static str {
+/// #         ServiceV1.service_name()
+/// #     }
+/// #
+/// #     fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+/// #         ServiceV1.state_hash(snapshot)
+/// #     }
+/// #
+/// #     fn service_id(&self) -> u16 {
+/// #         ServiceV1.service_id()
+/// #     }
+/// #
+/// #     fn tx_from_raw(&self, raw: RawTransaction) -> FromRawResult {
+/// #         ServiceV1.tx_from_raw(raw)
+/// #     }
+/// # }
+/// #
+/// # fn main() {
+/// let (pubkey, key) = crypto::gen_keypair();
+/// let (other_pubkey, other_key) = crypto::gen_keypair();
+/// let mut testkit = ComparisonTestKit::new(ServiceV1, ServiceV2);
+/// testkit.create_block_with_transactions(txvec![
+///     MyTransaction::new(&pubkey, "Alice", &key),
+///     MyTransaction::new(&other_pubkey, "Bob", &other_key),
+/// ]);
+/// testkit.assert_no_divergence();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ComparisonTestKit {
+    old: TestKit,
+    new: TestKit,
+    divergence: Option<StateDivergence>,
+}
+
+impl ComparisonTestKit {
+    /// Creates testkits with a single validator, each of which runs one of the given
+    /// services. The testkits use the same validator keys and mock time, so that
+    /// the blocks created by them are identical unless the services behave differently.
+    pub fn new<S, T>(old_service: S, new_service: T) -> Self
+    where
+        S: Into<Box<Service>>,
+        T: Into<Box<Service>>,
+    {
+        let time_provider = MockTimeProvider::default();
+        let builder = || {
+            TestKitBuilder::validator()
+                .with_seed(COMPARISON_SEED)
+                .with_time_provider(time_provider.clone())
+        };
+        ComparisonTestKit::from_testkits(
+            builder().with_service(old_service).create(),
+            builder().with_service(new_service).create(),
+        )
+    }
+
+    /// Creates a comparison from the given testkits, e.g., if the services need additional
+    /// configuration. The testkits should use the same validator keys and time provider
+    /// (see `TestKitBuilder::with_seed()` and `TestKitBuilder::with_time_provider()`).
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the testkits are at different heights.
+    pub fn from_testkits(old: TestKit, new: TestKit) -> Self {
+        assert_eq!(
+            old.height(),
+            new.height(),
+            "Compared testkits should be at the same height"
+        );
+        let mut testkit = ComparisonTestKit {
+            old,
+            new,
+            divergence: None,
+        };
+        testkit.compare();
+        testkit
+    }
+
+    /// Returns the testkit with the old version of the service.
+    pub fn old_testkit(&self) -> &TestKit {
+        &self.old
+    }
+
+    /// Returns the testkit with the new version of the service.
+    pub fn new_testkit(&self) -> &TestKit {
+        &self.new
+    }
+
+    /// Returns snapshots of both testkits.
+    pub fn snapshots(&self) -> Comparison<Box<Snapshot>> {
+        Comparison::new(self.old.snapshot(), self.new.snapshot())
+    }
+
+    /// Returns the height of the latest committed block.
+    pub fn height(&self) -> Height {
+        self.old.height()
+    }
+
+    /// Creates a block with the given transactions in both testkits and compares
+    /// the resulting states. Each transaction is parsed by the services of each testkit
+    /// from its raw message.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions cannot be parsed by the services of either testkit.
+    /// - Panics if any of transactions has been already committed to the blockchain.
+    pub fn create_block_with_transactions<I>(&mut self, txs: I)
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let raw: Vec<_> = txs.into_iter().map(|tx| tx.raw().clone()).collect();
+        let parse = |testkit: &TestKit, version: &str| -> Vec<Box<Transaction>> {
+            raw.iter()
+                .map(|raw| {
+                    testkit.blockchain.tx_from_raw(raw.clone()).unwrap_or_else(|| {
+                        panic!(
+                            "Transaction {:?} cannot be parsed by the {} version of the service",
+                            raw.hash(),
+                            version
+                        )
+                    })
+                })
+                .collect()
+        };
+        let old_txs = parse(&self.old, "old");
+        let new_txs = parse(&self.new, "new");
+
+        self.old.create_block_with_transactions(old_txs);
+        self.new.create_block_with_transactions(new_txs);
+        self.compare();
+    }
+
+    /// Creates a block with the given transaction in both testkits and compares
+    /// the resulting states.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the transaction cannot be parsed by the services of either testkit.
+    /// - Panics if the transaction has been already committed to the blockchain.
+    pub fn create_block_with_transaction<T: Transaction>(&mut self, tx: T) {
+        self.create_block_with_transactions(txvec![tx]);
+    }
+
+    /// Creates an empty block in both testkits and compares the resulting states.
+    pub fn create_block(&mut self) {
+        self.create_block_with_transactions(Vec::new());
+    }
+
+    /// Returns the first divergence between the testkits, or `None` if the testkits
+    /// have not diverged.
+    pub fn divergence(&self) -> Option<&StateDivergence> {
+        self.divergence.as_ref()
+    }
+
+    /// Asserts that the testkits have not diverged.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the testkits have diverged. The panic message describes the first
+    ///   divergence.
+    pub fn assert_no_divergence(&self) {
+        if let Some(ref divergence) = self.divergence {
+            panic!("Service versions have diverged: {:?}", divergence);
+        }
+    }
+
+    fn compare(&mut self) {
+        if self.divergence.is_some() {
+            return;
+        }
+
+        let height = self.height();
+        let old_state = self.old.db_handler.state();
+        let new_state = self.new.db_handler.state();
+        let divergence = entry_divergence(height, &old_state, &new_state).or_else(|| {
+            let state_hash = |testkit: &TestKit| {
+                *testkit.explorer().block(height).unwrap().state_hash()
+            };
+            let (old, new) = (state_hash(&self.old), state_hash(&self.new));
+            if old == new {
+                None
+            } else {
+                Some(StateDivergence::StateHash { height, old, new })
+            }
+        });
+        self.divergence = divergence;
+    }
+}
+
+/// Returns the divergence in the first index entry, which differs between the storage states.
+fn entry_divergence(height: Height, old: &Patch, new: &Patch) -> Option<StateDivergence> {
+    let value = |state: &Patch, index: &str, key: &[u8]| match state.get(index).and_then(
+        |changes| changes.get(key),
+    ) {
+        Some(&Change::Put(ref value)) => Some(value.clone()),
+        Some(&Change::Delete) | None => None,
+    };
+
+    // Core indices are compared last, since their differences are usually caused
+    // by the differences in service indices (e.g., block hashes depend on state hashes).
+    let keys: BTreeSet<(bool, &String, &Vec<u8>)> = old.iter()
+        .chain(new.iter())
+        .flat_map(|(index, changes)| {
+            let is_core = index.starts_with(CORE_PREFIX);
+            changes.keys().map(move |key| (is_core, index, key))
+        })
+        .collect();
+    for (_, index, key) in keys {
+        let (old_value, new_value) = (value(old, index, key), value(new, index, key));
+        if old_value != new_value {
+            return Some(StateDivergence::Entry {
+                height,
+                index: index.clone(),
+                key: key.clone(),
+                old: old_value,
+                new: new_value,
+            });
+        }
+    }
+    None
+}
+
+/// Difference between the states of testkits running two versions of a service.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateDivergence {
+    /// An index entry differs. Entries are compared in the order of index names and keys,
+    /// with the core indices compared after the service ones.
+    Entry {
+        /// Height of the block, after which the entry differs.
+        height: Height,
+        /// Name of the index.
+        index: String,
+        /// Key of the entry.
+        key: Vec<u8>,
+        /// Value of the entry with the old version of the service, or `None` if the entry
+        /// is absent.
+        old: Option<Vec<u8>>,
+        /// Value of the entry with the new version of the service, or `None` if the entry
+        /// is absent.
+        new: Option<Vec<u8>>,
+    },
+    /// The storage contents are the same, but the state hashes differ.
+    StateHash {
+        /// Height of the block.
+        height: Height,
+        /// State hash with the old version of the service.
+        old: Hash,
+        /// State hash with the new version of the service.
+        new: Hash,
+    },
+}
+
+impl StateDivergence {
+    /// Returns the height of the block, after which the states have diverged.
+    pub fn height(&self) -> Height {
+        match *self {
+            StateDivergence::Entry { height, .. } |
+            StateDivergence::StateHash { height, .. } => height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::storage::{Database, MemoryDB};
+    use super::*;
+
+    #[test]
+    fn test_entry_divergence() {
+        let db = MemoryDB::new();
+        let state = |entries: &[(&str, u8, u8)]| {
+            let mut fork = db.fork();
+            for &(name, key, value) in entries {
+                fork.put(name, vec![key], vec![value]);
+            }
+            fork.into_patch()
+        };
+
+        let old = state(&[("foo", 1, 1), ("foo", 2, 2)]);
+        assert_eq!(entry_divergence(Height(1), &old, &old), None);
+
+        let new = state(&[("foo", 1, 1), ("foo", 2, 3), ("bar", 2, 3), ("core.foo", 0, 0)]);
+        assert_eq!(
+            entry_divergence(Height(1), &old, &new),
+            Some(StateDivergence::Entry {
+                height: Height(1),
+                index: "bar".into(),
+                key: vec![2],
+                old: None,
+                new: Some(vec![3]),
+            })
+        );
+        let new = state(&[("foo", 1, 1)]);
+        assert_eq!(
+            entry_divergence(Height(2), &old, &new),
+            Some(StateDivergence::Entry {
+                height: Height(2),
+                index: "foo".into(),
+                key: vec![2],
+                old: Some(vec![2]),
+                new: None,
+            })
+        );
+    }
+}
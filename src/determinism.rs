@@ -0,0 +1,111 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routines for checking determinism of block execution.
+
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use exonum::storage::{Change, Patch};
+
+/// Returns a description of the first difference between two patches, or `None` if the patches
+/// are equal.
+fn patch_difference(first: &Patch, second: &Patch) -> Option<String> {
+    for (name, changes) in first.iter() {
+        let other_changes = match second.get(name) {
+            Some(other_changes) => other_changes,
+            None => return Some(format!("index `{}` is changed only in the first execution", name)),
+        };
+        for (key, change) in changes.iter() {
+            let same = match (change, other_changes.get(key)) {
+                (&Change::Put(ref a), Some(&Change::Put(ref b))) => a == b,
+                (&Change::Delete, Some(&Change::Delete)) => true,
+                _ => false,
+            };
+            if !same {
+                return Some(format!("key {:?} in index `{}` differs", key, name));
+            }
+        }
+        if let Some(key) = other_changes.keys().find(|key| !changes.contains_key(*key)) {
+            return Some(format!(
+                "key {:?} in index `{}` is changed only in the second execution",
+                key,
+                name
+            ));
+        }
+    }
+    second.keys().find(|name| !first.contains_key(*name)).map(|name| {
+        format!("index `{}` is changed only in the second execution", name)
+    })
+}
+
+/// Checks that two executions of the block at the given height have produced the same results.
+///
+/// # Panics
+///
+/// - Panics if the block hashes or the storage changes differ.
+pub fn check_determinism(height: Height, first: &(Hash, Patch), second: &(Hash, Patch)) {
+    if let Some(difference) = patch_difference(&first.1, &second.1) {
+        panic!(
+            "Non-deterministic execution of block {}: {}",
+            height.0,
+            difference
+        );
+    }
+    assert_eq!(
+        first.0,
+        second.0,
+        "Non-deterministic execution of block {}: block hashes differ",
+        height.0
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::storage::{Database, MemoryDB};
+    use super::*;
+
+    #[test]
+    fn test_patch_difference() {
+        let db = MemoryDB::new();
+        let patch = |changes: &[(&str, u8, Option<u8>)]| {
+            let mut fork = db.fork();
+            for &(name, key, value) in changes {
+                match value {
+                    Some(value) => fork.put(name, vec![key], vec![value]),
+                    None => fork.remove(name, vec![key]),
+                }
+            }
+            fork.into_patch()
+        };
+
+        let first = patch(&[("foo", 1, Some(1)), ("bar", 2, None)]);
+        let second = patch(&[("foo", 1, Some(1)), ("bar", 2, None)]);
+        assert_eq!(patch_difference(&first, &second), None);
+
+        let second = patch(&[("foo", 1, Some(2)), ("bar", 2, None)]);
+        assert!(patch_difference(&first, &second).unwrap().contains("differs"));
+        let second = patch(&[("foo", 1, Some(1))]);
+        assert!(
+            patch_difference(&first, &second)
+                .unwrap()
+                .contains("only in the first execution")
+        );
+        let second = patch(&[("foo", 1, Some(1)), ("foo", 3, Some(1)), ("bar", 2, None)]);
+        assert!(
+            patch_difference(&first, &second)
+                .unwrap()
+                .contains("only in the second execution")
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Service used in documentation examples. Not a part of the public API.
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{Hash, PublicKey};
+use exonum::encoding;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Fork, MapIndex, Snapshot};
+
+/// Service storing the last message of each author in the `documentation.messages` index.
+#[derive(Debug)]
+pub struct MyService;
+
+impl Service for MyService {
+    fn service_name(&self) -> &'static str {
+        "documentation"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        0
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(MyTransaction::from_raw(raw)?))
+    }
+}
+
+message! {
+    struct MyTransaction {
+        const TYPE = 0;
+        const ID = 0;
+
+        from: &PublicKey,
+        msg: &str,
+    }
+}
+
+impl Transaction for MyTransaction {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.from())
+    }
+
+    /// Stores the message, except for the special messages:
+    ///
+    /// - `"ignored"` is logged and does not change the state
+    /// - `"overflow"` panics
+    fn execute(&self, fork: &mut Fork) {
+        match self.msg() {
+            "ignored" => warn!("Ignored message from {:?}", self.from()),
+            "overflow" => panic!("attempt to add with overflow"),
+            msg => MapIndex::new("documentation.messages", fork).put(self.from(), msg.to_owned()),
+        }
+    }
+}
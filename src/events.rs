@@ -0,0 +1,97 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stream of events happening in the testkit.
+
+use std::sync::{mpsc, Arc, Mutex};
+
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+
+/// Event happening in the testkit. See `TestKit::events()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestKitEvent {
+    /// A transaction has been added to the mempool, either directly or through the API.
+    TransactionAdded {
+        /// Hash of the transaction.
+        hash: Hash,
+    },
+    /// A block has been committed to the blockchain.
+    BlockCommitted {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the block.
+        block_hash: Hash,
+        /// Hashes of the transactions in the block.
+        tx_hashes: Vec<Hash>,
+    },
+    /// The blockchain has been rolled back to the given height.
+    RolledBack {
+        /// Height of the latest block after the rollback.
+        height: Height,
+    },
+}
+
+/// Distributor of events among subscribers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventBus {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<TestKitEvent>>>>,
+}
+
+impl EventBus {
+    /// Creates a new subscription, which receives all events emitted after this call.
+    pub fn subscribe(&self) -> mpsc::Receiver<TestKitEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .expect("Cannot acquire lock on event subscribers")
+            .push(sender);
+        receiver
+    }
+
+    /// Sends the event to all subscribers. Subscribers, which receivers have been dropped,
+    /// are removed.
+    pub fn emit(&self, event: TestKitEvent) {
+        let mut subscribers = self.subscribers.lock().expect(
+            "Cannot acquire lock on event subscribers",
+        );
+        subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_bus() {
+        let bus = EventBus::default();
+        bus.emit(TestKitEvent::RolledBack { height: Height(0) });
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+        bus.emit(TestKitEvent::RolledBack { height: Height(1) });
+        drop(second);
+        bus.emit(TestKitEvent::RolledBack { height: Height(2) });
+
+        let events: Vec<_> = first.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                TestKitEvent::RolledBack { height: Height(1) },
+                TestKitEvent::RolledBack { height: Height(2) },
+            ]
+        );
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+    }
+}
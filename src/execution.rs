@@ -0,0 +1,304 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-transaction results of block execution.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use exonum::blockchain::{Blockchain, Transaction};
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use exonum::messages::Message;
+use exonum::storage::{Change, Patch, Snapshot};
+
+use fuzz::panic_message;
+use logs::{self, LogRecord};
+
+/// Policy of handling transactions, which panic during execution in a block created
+/// by the testkit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// The testkit panics with a message containing the hash and the contents of
+    /// the transaction. The backtrace is not included into the message; the backtrace
+    /// of the original panic is printed to stderr by the panic hook if `RUST_BACKTRACE`
+    /// is set. This is the default policy.
+    Abort,
+    /// The panic is recorded in the `BlockResult` of the block as
+    /// `ExecutionStatus::Panicked`. The transaction is removed from the mempool; the rest
+    /// of transactions are executed as usual.
+    ///
+    /// Note that this differs from a real Exonum node: the panicking transaction is left out
+    /// of the block and its `Propose`, so the block contains fewer transactions than were
+    /// passed to it, and the transaction cannot be found in the blockchain explorer.
+    /// A real node does not catch panics in transactions and stops instead.
+    Record,
+}
+
+/// Outcome of the execution of a transaction passed to a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    /// The transaction has changed the blockchain state.
+    Applied,
+    /// The transaction has been executed, but has not changed the blockchain state,
+    /// e.g., because the service has silently ignored it.
+    NoOp,
+    /// The transaction has panicked during execution. Contains the panic message.
+    ///
+    /// Unlike on a real node, the transaction is silently left out of the created block
+    /// and its `Propose` (see `PanicPolicy::Record`).
+    Panicked(String),
+}
+
+/// Result of the execution of a transaction passed to a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxResult {
+    hash: Hash,
+    status: ExecutionStatus,
+    execution_time: Duration,
+    logs: Vec<LogRecord>,
+}
+
+impl TxResult {
+    /// Returns the hash of the transaction.
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    /// Returns the outcome of the transaction execution.
+    pub fn status(&self) -> &ExecutionStatus {
+        &self.status
+    }
+
+    /// Returns the wall time of `Transaction::execute()` for the transaction.
+    pub fn execution_time(&self) -> Duration {
+        self.execution_time
+    }
+
+    /// Returns log records emitted during the transaction execution. Records are captured
+    /// only if enabled with `TestKitBuilder::with_log_capture()`.
+    pub fn logs(&self) -> &[LogRecord] {
+        &self.logs
+    }
+}
+
+/// Results of the execution of transactions in a block created by the testkit.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::crypto;
+/// # use exonum::messages::Message;
+/// # use exonum_testkit::{ExecutionStatus, TestKitBuilder};
+/// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+/// #
+/// # fn main() {
+/// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+/// let (pubkey, key) = crypto::gen_keypair();
+/// let tx = MyTransaction::new(&pubkey, "ignored", &key);
+/// let result = testkit.create_block_with_transaction(tx.clone());
+/// assert_eq!(result.status(&tx.hash()), Some(&ExecutionStatus::NoOp));
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockResult {
+    height: Height,
+    transactions: Vec<TxResult>,
+    blocks: usize,
+}
+
+impl BlockResult {
+    /// Returns the height of the block. If transactions have been split across several blocks
+    /// (see `TestKitBuilder::with_block_splitting()`), returns the height of the last block.
+    pub fn height(&self) -> Height {
+        self.height
+    }
+
+    /// Returns the number of created blocks, which is greater than 1 only if transactions
+    /// have been split across several blocks.
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Merges results of consecutive blocks.
+    pub(crate) fn merge(results: Vec<BlockResult>) -> BlockResult {
+        let height = results.last().expect("No blocks to merge").height;
+        let blocks = results.iter().map(|result| result.blocks).sum();
+        let transactions = results
+            .into_iter()
+            .flat_map(|result| result.transactions)
+            .collect();
+        BlockResult {
+            height,
+            transactions,
+            blocks,
+        }
+    }
+
+    /// Returns the results of transactions in the order of their execution in the block.
+    pub fn transactions(&self) -> &[TxResult] {
+        &self.transactions
+    }
+
+    /// Returns the outcome of the transaction with the given hash, or `None` if the transaction
+    /// has not been passed to the block.
+    pub fn status(&self, tx_hash: &Hash) -> Option<&ExecutionStatus> {
+        self.transactions
+            .iter()
+            .find(|result| result.hash == *tx_hash)
+            .map(TxResult::status)
+    }
+
+    /// Returns the first transaction, which has panicked during execution.
+    pub(crate) fn first_panicked(&self) -> Option<(&Hash, &str)> {
+        self.transactions
+            .iter()
+            .filter_map(|result| match result.status {
+                ExecutionStatus::Panicked(ref message) => Some((&result.hash, message.as_str())),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Returns hashes of the transactions, which should be included into the block.
+    pub(crate) fn included_hashes(&self) -> Vec<Hash> {
+        self.transactions
+            .iter()
+            .filter(|result| match result.status {
+                ExecutionStatus::Panicked(_) => false,
+                _ => true,
+            })
+            .map(|result| result.hash)
+            .collect()
+    }
+}
+
+/// Executes transactions one by one on top of the current blockchain state, as they would be
+/// executed in a block, and determines the outcome of each transaction. Execution results
+/// are not committed. If `capture_logs` is set, log records emitted by transactions
+/// are captured.
+pub fn execute_block(
+    blockchain: &Blockchain,
+    height: Height,
+    txs: &[&Transaction],
+    capture_logs: bool,
+) -> BlockResult {
+    let snapshot = blockchain.snapshot();
+    let mut state = Patch::new();
+    let transactions = txs.iter()
+        .map(|tx| {
+            let mut fork = blockchain.fork();
+            fork.merge(state.clone());
+            let ((execution, execution_time), records) = {
+                let mut execute = || {
+                    let start = Instant::now();
+                    let execution =
+                        panic::catch_unwind(AssertUnwindSafe(|| tx.execute(&mut fork)));
+                    (execution, start.elapsed())
+                };
+                if capture_logs {
+                    logs::capture(execute)
+                } else {
+                    (execute(), Vec::new())
+                }
+            };
+            let status = match execution {
+                Ok(()) => {
+                    let new_state = fork.into_patch();
+                    let applied = changes_state(&*snapshot, &state, &new_state);
+                    state = new_state;
+                    if applied {
+                        ExecutionStatus::Applied
+                    } else {
+                        ExecutionStatus::NoOp
+                    }
+                }
+                Err(payload) => ExecutionStatus::Panicked(panic_message(&*payload)),
+            };
+            let hash = tx.hash();
+            TxResult {
+                hash,
+                status,
+                execution_time,
+                logs: records
+                    .into_iter()
+                    .map(|record| record.into_record(height, Some(hash)))
+                    .collect(),
+            }
+        })
+        .collect();
+    BlockResult {
+        height,
+        transactions,
+        blocks: 1,
+    }
+}
+
+/// Checks whether the `new` state differs from the `old` one. Both states are changes made
+/// on top of the snapshot; the `new` state contains all entries changed in the `old` one.
+fn changes_state(snapshot: &Snapshot, old: &Patch, new: &Patch) -> bool {
+    let value = |state: &Patch, index: &str, key: &[u8]| match state.get(index).and_then(
+        |changes| changes.get(key),
+    ) {
+        Some(&Change::Put(ref value)) => Some(value.clone()),
+        Some(&Change::Delete) => None,
+        None => snapshot.get(index, key),
+    };
+
+    new.iter().any(|(index, changes)| {
+        changes.keys().any(
+            |key| value(old, index, key) != value(new, index, key),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::storage::{Database, MemoryDB};
+    use super::*;
+
+    #[test]
+    fn test_changes_state() {
+        let mut db = MemoryDB::new();
+        let mut fork = db.fork();
+        fork.put("foo", vec![1], vec![1]);
+        db.merge(fork.into_patch()).unwrap();
+        let snapshot = db.snapshot();
+
+        let state = |puts: &[(u8, u8)], deletes: &[u8]| {
+            let mut fork = db.fork();
+            for &(key, value) in puts {
+                fork.put("foo", vec![key], vec![value]);
+            }
+            for &key in deletes {
+                fork.remove("foo", vec![key]);
+            }
+            fork.into_patch()
+        };
+
+        let empty = state(&[], &[]);
+        assert!(!changes_state(&*snapshot, &empty, &empty));
+        // Overwriting an entry with the same value does not change the state.
+        assert!(!changes_state(&*snapshot, &empty, &state(&[(1, 1)], &[])));
+        assert!(!changes_state(&*snapshot, &empty, &state(&[], &[2])));
+        assert!(changes_state(&*snapshot, &empty, &state(&[(1, 2)], &[])));
+        assert!(changes_state(&*snapshot, &empty, &state(&[], &[1])));
+
+        let old = state(&[(2, 2)], &[]);
+        assert!(!changes_state(&*snapshot, &old, &state(&[(2, 2), (1, 1)], &[])));
+        assert!(changes_state(&*snapshot, &old, &state(&[], &[2])));
+    }
+}
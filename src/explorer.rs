@@ -0,0 +1,173 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed access to blocks and transactions committed to the testkit blockchain.
+
+use std::fmt;
+use std::ops::Range;
+
+use exonum::blockchain::{Block, Blockchain, Schema as CoreSchema, Transaction, TxLocation};
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use exonum::messages::Precommit;
+use exonum::storage::Snapshot;
+
+/// Block together with its transactions and precommits.
+#[derive(Debug)]
+pub struct BlockWithTransactions {
+    /// Block header.
+    pub header: Block,
+    /// Transactions in the order of their appearance in the block.
+    pub transactions: Vec<Box<Transaction>>,
+    /// Precommits authorizing the block.
+    pub precommits: Vec<Precommit>,
+}
+
+impl BlockWithTransactions {
+    /// Returns the height of the block.
+    pub fn height(&self) -> Height {
+        self.header.height()
+    }
+
+    /// Returns the number of transactions in the block.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Returns `true` if the block contains no transactions.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+}
+
+/// Transaction committed to the blockchain.
+#[derive(Debug)]
+pub struct CommittedTransaction {
+    /// Location of the transaction in the blockchain.
+    pub location: TxLocation,
+    /// Transaction contents.
+    pub content: Box<Transaction>,
+}
+
+/// Explorer of the testkit blockchain, operating on the blockchain state at the moment
+/// of its creation.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::helpers::Height;
+/// # use exonum_testkit::TestKitBuilder;
+/// # fn main() {
+/// let mut testkit = TestKitBuilder::validator().create();
+/// testkit.create_blocks_until(Height(3));
+///
+/// let explorer = testkit.explorer();
+/// let block = explorer.block(Height(2)).unwrap();
+/// assert_eq!(block.height(), Height(2));
+/// assert_eq!(explorer.blocks(Height(1)..Height(10)).len(), 3);
+/// # }
+/// ```
+pub struct TestKitExplorer {
+    blockchain: Blockchain,
+    snapshot: Box<Snapshot>,
+}
+
+impl fmt::Debug for TestKitExplorer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TestKitExplorer").finish()
+    }
+}
+
+impl TestKitExplorer {
+    pub(crate) fn new(blockchain: &Blockchain) -> Self {
+        TestKitExplorer {
+            blockchain: blockchain.clone(),
+            snapshot: blockchain.snapshot(),
+        }
+    }
+
+    fn schema(&self) -> CoreSchema<&Snapshot> {
+        CoreSchema::new(self.snapshot.as_ref())
+    }
+
+    /// Returns the header of the block at the given height, or `None` if there is no such block.
+    pub fn block(&self, height: Height) -> Option<Block> {
+        let schema = self.schema();
+        schema.block_hashes_by_height().get(height.0).and_then(
+            |block_hash| schema.blocks().get(&block_hash),
+        )
+    }
+
+    /// Returns the block at the given height together with its transactions and precommits,
+    /// or `None` if there is no such block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if a transaction in the block cannot be parsed by the blockchain services.
+    pub fn block_with_txs(&self, height: Height) -> Option<BlockWithTransactions> {
+        self.block(height).map(|header| {
+            let schema = self.schema();
+            let transactions = schema
+                .block_txs(height)
+                .iter()
+                .map(|tx_hash| self.committed_tx(&tx_hash))
+                .collect();
+            let precommits = schema.precommits(&header.hash()).iter().collect();
+
+            BlockWithTransactions {
+                header,
+                transactions,
+                precommits,
+            }
+        })
+    }
+
+    /// Returns headers of the blocks with heights in the given range. The range is truncated
+    /// to the existing blocks.
+    pub fn blocks(&self, range: Range<Height>) -> Vec<Block> {
+        (range.start.0..range.end.0)
+            .map(|height| self.block(Height(height)))
+            .take_while(Option::is_some)
+            .map(Option::unwrap)
+            .collect()
+    }
+
+    /// Returns a committed transaction with the given hash, or `None` if the transaction
+    /// is not committed to the blockchain.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the transaction cannot be parsed by the blockchain services.
+    pub fn transaction(&self, tx_hash: &Hash) -> Option<CommittedTransaction> {
+        self.schema().tx_location_by_tx_hash().get(tx_hash).map(
+            |location| {
+                CommittedTransaction {
+                    location,
+                    content: self.committed_tx(tx_hash),
+                }
+            },
+        )
+    }
+
+    fn committed_tx(&self, tx_hash: &Hash) -> Box<Transaction> {
+        let raw = self.schema().transactions().get(tx_hash).expect(
+            "Cannot find transaction in the blockchain",
+        );
+        self.blockchain.tx_from_raw(raw).expect(
+            "Cannot parse committed transaction",
+        )
+    }
+}
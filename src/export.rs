@@ -0,0 +1,147 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Portable dumps of the testkit blockchain.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+use exonum::crypto::Hash;
+use exonum::encoding::serialize::ToHex;
+use exonum::messages::Message;
+use exonum::storage::{Change, Patch, StorageValue};
+use serde_json;
+
+use explorer::BlockWithTransactions;
+use saved::decode_hex;
+
+/// Transaction committed to the blockchain.
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedTransaction {
+    hash: Hash,
+    // Hex-encoded transaction message.
+    raw: String,
+}
+
+/// Block header together with the committed transactions. Precommits are not exported,
+/// since they depend on the time of block creation.
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedBlock {
+    height: u64,
+    hash: Hash,
+    prev_hash: Hash,
+    proposer_id: u16,
+    tx_hash: Hash,
+    state_hash: Hash,
+    transactions: Vec<ExportedTransaction>,
+}
+
+impl<'a> From<&'a BlockWithTransactions> for ExportedBlock {
+    fn from(block: &'a BlockWithTransactions) -> Self {
+        let header = &block.header;
+        ExportedBlock {
+            height: header.height().0,
+            hash: header.hash(),
+            prev_hash: *header.prev_hash(),
+            proposer_id: header.proposer_id().0,
+            tx_hash: *header.tx_hash(),
+            state_hash: *header.state_hash(),
+            transactions: block
+                .transactions
+                .iter()
+                .map(|tx| {
+                    ExportedTransaction {
+                        hash: tx.hash(),
+                        raw: tx.raw().as_ref().to_hex(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Blockchain and storage contents in a format suitable for diffing.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ExportedState {
+    blocks: Vec<ExportedBlock>,
+    // Contents of indices with hex-encoded keys and values, sorted by index names and keys.
+    indices: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl ExportedState {
+    pub fn new(blocks: &[BlockWithTransactions], storage: &Patch) -> Self {
+        let indices = storage
+            .iter()
+            .map(|(name, changes)| {
+                let contents = changes
+                    .iter()
+                    .filter_map(|(key, change)| match *change {
+                        Change::Put(ref value) => Some((key.to_hex(), value.to_hex())),
+                        Change::Delete => None,
+                    })
+                    .collect();
+                (name.clone(), contents)
+            })
+            .collect();
+
+        ExportedState {
+            blocks: blocks.iter().map(ExportedBlock::from).collect(),
+            indices,
+        }
+    }
+
+    /// Reads the state from the file.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be read or parsed.
+    pub fn read(path: &Path) -> Self {
+        let file = File::open(path).unwrap_or_else(|e| {
+            panic!("Cannot open exported testkit state {}: {}", path.display(), e)
+        });
+        serde_json::from_reader(file).unwrap_or_else(|e| {
+            panic!("Cannot parse exported testkit state {}: {}", path.display(), e)
+        })
+    }
+
+    /// Writes the state to the file, overwriting it. The output is pretty-printed,
+    /// so that it can be diffed line by line.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be written.
+    pub fn write(&self, path: &Path) {
+        let file = File::create(path).unwrap_or_else(|e| {
+            panic!("Cannot create exported testkit state {}: {}", path.display(), e)
+        });
+        serde_json::to_writer_pretty(file, self).unwrap_or_else(|e| {
+            panic!("Cannot write exported testkit state {}: {}", path.display(), e)
+        });
+    }
+
+    /// Returns the contents of indices as a patch.
+    pub fn storage(&self) -> Patch {
+        self.indices
+            .iter()
+            .map(|(name, contents)| {
+                let changes = contents
+                    .iter()
+                    .map(|(key, value)| (decode_hex(key), Change::Put(decode_hex(value))))
+                    .collect();
+                (name.clone(), changes)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! External blockchains used by anchoring and bridge services, and their mock.
+//!
+//! Services interacting with an external blockchain (e.g., Bitcoin) can access it through
+//! the `ExternalChain` trait, so that the RPC client of a real node can be replaced
+//! with `ExternalChainMock` in tests.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use exonum::crypto::{self, Hash};
+
+/// Error returned by an external blockchain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalChainError {
+    /// The node of the external blockchain is unavailable.
+    Unavailable,
+    /// The transaction is already known to the external blockchain.
+    Duplicate,
+}
+
+/// Interface of an external blockchain, e.g., an RPC client of a Bitcoin node.
+///
+/// Transactions are identified by the hash of their payload.
+pub trait ExternalChain: Send + Sync + fmt::Debug {
+    /// Returns the height of the best chain.
+    fn height(&self) -> Result<u64, ExternalChainError>;
+
+    /// Returns the fee sufficient for a transaction to be included into the next block.
+    fn fee_estimate(&self) -> Result<u64, ExternalChainError>;
+
+    /// Broadcasts a transaction with the given payload and fee. Returns the identifier
+    /// of the transaction.
+    fn send_transaction(&self, payload: &[u8], fee: u64) -> Result<Hash, ExternalChainError>;
+
+    /// Returns the number of confirmations of the transaction: `Some(0)` for a transaction
+    /// waiting in the mempool, or `None` for an unknown transaction.
+    fn confirmations(&self, txid: &Hash) -> Result<Option<u64>, ExternalChainError>;
+}
+
+#[derive(Debug)]
+struct ChainState {
+    // Identifiers of transactions in each block; the block at height `h` has index `h - 1`.
+    blocks: Vec<Vec<Hash>>,
+    mempool: Vec<Hash>,
+    // Payloads and fees of all known transactions.
+    transactions: BTreeMap<Hash, (Vec<u8>, u64)>,
+    fee_estimate: u64,
+    available: bool,
+}
+
+/// Mock of an external blockchain for tests, which is controlled programmatically:
+/// blocks are mined with `mine_block()`, reorganizations are emulated with `reorg()`,
+/// and fee estimates and availability of the node are set explicitly.
+///
+/// A mined block includes the transactions from the mempool with fees not less than
+/// the current fee estimate; other transactions remain in the mempool. Clones
+/// of an `ExternalChainMock` share the same chain, so a clone can be passed to the service
+/// while the original is used to drive the chain.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use std::sync::Arc;
+/// # use exonum::blockchain::{Service, ServiceContext, Transaction};
+/// # use exonum::crypto::Hash;
+/// # use exonum::encoding;
+/// # use exonum::helpers::Height;
+/// # use exonum::messages::RawTransaction;
+/// # use exonum::storage::Snapshot;
+/// # use exonum_testkit::TestKitBuilder;
+/// # use exonum_testkit::external_chain::{ExternalChain, ExternalChainMock};
+/// #
+/// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+/// #
+/// # // Service anchoring every block to the external chain.
+/// # struct AnchoringService {
+/// #     chain: Arc<ExternalChain>,
+/// # }
+/// #
+/// # impl AnchoringService {
+/// #     fn new(chain: Arc<ExternalChain>) -> Self {
+/// #         AnchoringService { chain }
+/// #     }
+/// # }
+/// #
+/// # impl Service for AnchoringService {
+/// #     fn service_name(&self) -> &'static str {
+/// #         "anchoring"
+/// #     }
+/// #
+/// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+/// #         Vec::new()
+/// #     }
+/// #
+/// #     fn service_id(&self) -> u16 {
+/// #         1
+/// #     }
+/// #
+/// #     fn tx_from_raw(&self, _: RawTransaction) -> FromRawResult {
+/// #         unimplemented!();
+/// #     }
+/// #
+/// #     fn handle_commit(&self, context: &ServiceContext) {
+/// #         let payload = format!("anchor {}", context.height().0);
+/// #         self.chain.send_transaction(payload.as_bytes(), 0).unwrap();
+/// #     }
+/// # }
+/// #
+/// # fn main() {
+/// let chain = ExternalChainMock::new();
+/// let mut testkit = TestKitBuilder::validator()
+///     .with_service(AnchoringService::new(Arc::new(chain.clone())))
+///     .create();
+/// // Mine a block of the external chain for every block of the testkit.
+/// {
+///     let chain = chain.clone();
+///     testkit.on_after_commit(move |_, _| {
+///         chain.mine_block();
+///     });
+/// }
+/// testkit.create_blocks_until(Height(10));
+/// let anchor = chain.block(2).unwrap()[0];
+/// assert_eq!(chain.confirmations(&anchor), Ok(Some(9)));
+/// // Return the anchoring transaction to the mempool.
+/// chain.reorg(9);
+/// assert_eq!(chain.confirmations(&anchor), Ok(Some(0)));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExternalChainMock {
+    state: Arc<RwLock<ChainState>>,
+}
+
+impl ExternalChainMock {
+    /// Creates a chain without blocks and with zero fee estimate.
+    pub fn new() -> Self {
+        ExternalChainMock {
+            state: Arc::new(RwLock::new(ChainState {
+                blocks: Vec::new(),
+                mempool: Vec::new(),
+                transactions: BTreeMap::new(),
+                fee_estimate: 0,
+                available: true,
+            })),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<ChainState> {
+        self.state.read().expect(
+            "Cannot acquire read lock on external chain",
+        )
+    }
+
+    fn write(&self) -> RwLockWriteGuard<ChainState> {
+        self.state.write().expect(
+            "Cannot acquire write lock on external chain",
+        )
+    }
+
+    /// Mines a block with the transactions from the mempool, which pay sufficient fees.
+    /// Returns identifiers of the included transactions.
+    pub fn mine_block(&self) -> Vec<Hash> {
+        let mut state = self.write();
+        let fee_estimate = state.fee_estimate;
+        let (included, rest): (Vec<_>, Vec<_>) = {
+            let transactions = &state.transactions;
+            state.mempool.iter().partition(
+                |txid| transactions[txid].1 >= fee_estimate,
+            )
+        };
+        state.mempool = rest;
+        state.blocks.push(included.clone());
+        included
+    }
+
+    /// Mines the given number of blocks.
+    pub fn mine_blocks(&self, count: usize) {
+        for _ in 0..count {
+            self.mine_block();
+        }
+    }
+
+    /// Emulates a reorganization of the chain: the given number of latest blocks
+    /// are removed, and their transactions are returned to the mempool before
+    /// the transactions already waiting there. Blocks of the competing branch should
+    /// be mined afterwards.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `depth` exceeds the chain height.
+    pub fn reorg(&self, depth: usize) {
+        let mut state = self.write();
+        assert!(
+            depth <= state.blocks.len(),
+            "Cannot reorganize {} blocks of the chain with height {}",
+            depth,
+            state.blocks.len()
+        );
+        let new_len = state.blocks.len() - depth;
+        let mut mempool: Vec<_> = state.blocks.drain(new_len..).flat_map(|block| block).collect();
+        mempool.extend(state.mempool.drain(..));
+        state.mempool = mempool;
+    }
+
+    /// Sets the fee estimate, which is also the minimal fee of transactions included
+    /// into mined blocks.
+    pub fn set_fee_estimate(&self, fee: u64) {
+        self.write().fee_estimate = fee;
+    }
+
+    /// Sets whether the node of the chain is available. Requests to an unavailable node
+    /// fail with `ExternalChainError::Unavailable`.
+    pub fn set_available(&self, available: bool) {
+        self.write().available = available;
+    }
+
+    /// Returns the payload of a known transaction.
+    pub fn transaction(&self, txid: &Hash) -> Option<Vec<u8>> {
+        self.read().transactions.get(txid).map(
+            |&(ref payload, _)| payload.clone(),
+        )
+    }
+
+    /// Returns identifiers of the transactions in the block at the given height,
+    /// or `None` if there is no such block.
+    pub fn block(&self, height: u64) -> Option<Vec<Hash>> {
+        if height == 0 {
+            return None;
+        }
+        self.read().blocks.get(height as usize - 1).cloned()
+    }
+
+    /// Returns identifiers of the transactions in the mempool.
+    pub fn mempool(&self) -> Vec<Hash> {
+        self.read().mempool.clone()
+    }
+
+    fn check_available(state: &ChainState) -> Result<(), ExternalChainError> {
+        if state.available {
+            Ok(())
+        } else {
+            Err(ExternalChainError::Unavailable)
+        }
+    }
+}
+
+impl Default for ExternalChainMock {
+    fn default() -> Self {
+        ExternalChainMock::new()
+    }
+}
+
+impl ExternalChain for ExternalChainMock {
+    fn height(&self) -> Result<u64, ExternalChainError> {
+        let state = self.read();
+        ExternalChainMock::check_available(&state)?;
+        Ok(state.blocks.len() as u64)
+    }
+
+    fn fee_estimate(&self) -> Result<u64, ExternalChainError> {
+        let state = self.read();
+        ExternalChainMock::check_available(&state)?;
+        Ok(state.fee_estimate)
+    }
+
+    fn send_transaction(&self, payload: &[u8], fee: u64) -> Result<Hash, ExternalChainError> {
+        let mut state = self.write();
+        ExternalChainMock::check_available(&state)?;
+        let txid = crypto::hash(payload);
+        if state.transactions.contains_key(&txid) {
+            return Err(ExternalChainError::Duplicate);
+        }
+        state.transactions.insert(txid, (payload.to_vec(), fee));
+        state.mempool.push(txid);
+        Ok(txid)
+    }
+
+    fn confirmations(&self, txid: &Hash) -> Result<Option<u64>, ExternalChainError> {
+        let state = self.read();
+        ExternalChainMock::check_available(&state)?;
+        if state.mempool.contains(txid) {
+            return Ok(Some(0));
+        }
+        let height = state.blocks.len();
+        Ok(state.blocks.iter().position(|block| block.contains(txid)).map(
+            |index| (height - index) as u64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmations_and_reorg() {
+        let chain = ExternalChainMock::new();
+        let txid = chain.send_transaction(b"anchor", 10).unwrap();
+        assert_eq!(chain.confirmations(&txid), Ok(Some(0)));
+        assert_eq!(chain.send_transaction(b"anchor", 10), Err(ExternalChainError::Duplicate));
+
+        assert_eq!(chain.mine_block(), vec![txid]);
+        chain.mine_blocks(2);
+        assert_eq!(chain.height(), Ok(3));
+        assert_eq!(chain.confirmations(&txid), Ok(Some(3)));
+        assert_eq!(chain.block(1), Some(vec![txid]));
+
+        chain.reorg(3);
+        assert_eq!(chain.height(), Ok(0));
+        assert_eq!(chain.mempool(), vec![txid]);
+        assert_eq!(chain.confirmations(&txid), Ok(Some(0)));
+        assert_eq!(chain.confirmations(&crypto::hash(b"other")), Ok(None));
+    }
+
+    #[test]
+    fn test_fees_and_availability() {
+        let chain = ExternalChainMock::new();
+        chain.set_fee_estimate(100);
+        let cheap = chain.send_transaction(b"cheap", 50).unwrap();
+        let expensive = chain.send_transaction(b"expensive", 100).unwrap();
+        assert_eq!(chain.mine_block(), vec![expensive]);
+        assert_eq!(chain.mempool(), vec![cheap]);
+
+        chain.set_available(false);
+        assert_eq!(chain.fee_estimate(), Err(ExternalChainError::Unavailable));
+        assert_eq!(
+            chain.send_transaction(b"other", 100),
+            Err(ExternalChainError::Unavailable)
+        );
+        chain.set_available(true);
+        assert_eq!(chain.fee_estimate(), Ok(100));
+    }
+}
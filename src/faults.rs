@@ -0,0 +1,297 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fault injection for the transport of `TestNetworkRunner`.
+
+use std::collections::BTreeMap;
+
+/// Faults injected into the transport of transactions among the nodes of `TestNetworkRunner`.
+///
+/// Time in the transport is measured in discrete steps, which are advanced with
+/// `TestNetworkRunner::tick()`. A message with zero latency is delivered immediately
+/// after it is sent. Faults are pseudo-random, but reproducible for the same seed
+/// and sequence of operations.
+///
+/// The default policy does not inject any faults.
+///
+/// # Examples
+///
+/// ```
+/// # use exonum_testkit::FaultPolicy;
+/// let policy = FaultPolicy::new()
+///     .with_drop_rate(0.25)
+///     .with_latency(1)
+///     .with_link_latency(0, 3, 5)
+///     .with_reordering(2)
+///     .with_seed(42);
+/// # drop(policy);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FaultPolicy {
+    drop_rate: f64,
+    latency: u64,
+    link_latencies: BTreeMap<(usize, usize), u64>,
+    reordering_window: u64,
+    seed: u64,
+}
+
+impl FaultPolicy {
+    /// Creates a policy that does not inject any faults.
+    pub fn new() -> Self {
+        FaultPolicy::default()
+    }
+
+    /// Sets the probability of dropping a message.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `rate` is not in the `[0, 1]` range.
+    pub fn with_drop_rate(mut self, rate: f64) -> Self {
+        assert!(
+            rate >= 0.0 && rate <= 1.0,
+            "Drop rate should be in the [0, 1] range"
+        );
+        self.drop_rate = rate;
+        self
+    }
+
+    /// Sets the latency of delivering messages in steps for all links without
+    /// an explicitly specified latency.
+    pub fn with_latency(mut self, steps: u64) -> Self {
+        self.latency = steps;
+        self
+    }
+
+    /// Sets the latency of delivering messages in steps from the node `from` to the node `to`.
+    pub fn with_link_latency(mut self, from: usize, to: usize, steps: u64) -> Self {
+        self.link_latencies.insert((from, to), steps);
+        self
+    }
+
+    /// Sets the reordering window in steps. Each message is delayed by a random number of steps
+    /// not exceeding the window, so messages sent within the window may be delivered
+    /// out of order.
+    pub fn with_reordering(mut self, window: u64) -> Self {
+        self.reordering_window = window;
+        self
+    }
+
+    /// Sets the seed of the pseudo-random generator used to inject faults.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn latency(&self, from: usize, to: usize) -> u64 {
+        self.link_latencies.get(&(from, to)).cloned().unwrap_or(
+            self.latency,
+        )
+    }
+}
+
+/// Message in flight between two nodes.
+#[derive(Debug)]
+pub(crate) struct Envelope<M> {
+    pub from: usize,
+    pub to: usize,
+    pub message: M,
+    delivery_time: u64,
+    seq: u64,
+}
+
+/// Transport delivering messages among nodes according to a `FaultPolicy`.
+#[derive(Debug)]
+pub(crate) struct Transport<M> {
+    policy: FaultPolicy,
+    rng: Rng,
+    time: u64,
+    seq: u64,
+    in_flight: Vec<Envelope<M>>,
+    dropped: usize,
+}
+
+impl<M> Transport<M> {
+    pub fn new(policy: FaultPolicy) -> Self {
+        Transport {
+            rng: Rng::new(policy.seed),
+            policy,
+            time: 0,
+            seq: 0,
+            in_flight: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Replaces the fault policy. Messages already in flight are not affected.
+    pub fn set_policy(&mut self, policy: FaultPolicy) {
+        self.rng = Rng::new(policy.seed);
+        self.policy = policy;
+    }
+
+    /// Sends a message, which is either dropped or scheduled for delivery.
+    pub fn send(&mut self, from: usize, to: usize, message: M) {
+        if self.policy.drop_rate > 0.0 && self.rng.next_f64() < self.policy.drop_rate {
+            self.dropped += 1;
+            return;
+        }
+
+        let mut delay = self.policy.latency(from, to);
+        if self.policy.reordering_window > 0 {
+            delay += self.rng.next_u64() % (self.policy.reordering_window + 1);
+        }
+        self.in_flight.push(Envelope {
+            from,
+            to,
+            message,
+            delivery_time: self.time + delay,
+            seq: self.seq,
+        });
+        self.seq += 1;
+    }
+
+    /// Advances the time of the transport by one step.
+    pub fn tick(&mut self) {
+        self.time += 1;
+    }
+
+    /// Removes messages due for delivery at the current time, in the order of delivery.
+    pub fn take_due(&mut self) -> Vec<Envelope<M>> {
+        let time = self.time;
+        let (mut due, in_flight): (Vec<_>, Vec<_>) = self.in_flight.drain(..).partition(
+            |envelope| envelope.delivery_time <= time,
+        );
+        self.in_flight = in_flight;
+        due.sort_by_key(|envelope| (envelope.delivery_time, envelope.seq));
+        due
+    }
+
+    /// Returns the number of messages in flight.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Returns the number of messages dropped by the transport.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+/// Pseudo-random generator based on the SplitMix64 algorithm.
+#[derive(Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number uniformly distributed in the `[0, 1)` range.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Returns a number in the `[0, bound)` range.
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(envelopes: &[Envelope<u64>]) -> Vec<u64> {
+        envelopes.iter().map(|envelope| envelope.message).collect()
+    }
+
+    #[test]
+    fn test_latency() {
+        let policy = FaultPolicy::new().with_latency(2).with_link_latency(0, 1, 1);
+        let mut transport = Transport::new(policy);
+        transport.send(0, 1, 0);
+        transport.send(0, 2, 1);
+        assert!(transport.take_due().is_empty());
+
+        transport.tick();
+        let due = transport.take_due();
+        assert_eq!(messages(&due), vec![0]);
+        assert_eq!(due[0].to, 1);
+
+        transport.tick();
+        assert_eq!(messages(&transport.take_due()), vec![1]);
+        assert_eq!(transport.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_drops() {
+        let mut transport = Transport::new(FaultPolicy::new().with_drop_rate(1.0));
+        transport.send(0, 1, 0);
+        assert_eq!(transport.dropped(), 1);
+        assert_eq!(transport.in_flight(), 0);
+
+        let mut transport = Transport::new(FaultPolicy::new().with_drop_rate(0.5).with_seed(1));
+        for i in 0..100 {
+            transport.send(0, 1, i);
+        }
+        assert!(transport.dropped() > 0 && transport.dropped() < 100);
+        assert_eq!(transport.dropped() + transport.take_due().len(), 100);
+    }
+
+    #[test]
+    fn test_reordering() {
+        let policy = FaultPolicy::new().with_reordering(3).with_seed(7);
+        let mut transport = Transport::new(policy);
+        for i in 0..20 {
+            transport.send(0, 1, i);
+        }
+        let mut delivered = Vec::new();
+        for _ in 0..4 {
+            delivered.extend(messages(&transport.take_due()));
+            transport.tick();
+        }
+        assert_eq!(transport.in_flight(), 0);
+        assert_ne!(delivered, (0..20).collect::<Vec<_>>());
+
+        delivered.sort();
+        assert_eq!(delivered, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_reproducibility() {
+        let run = || {
+            let policy = FaultPolicy::new()
+                .with_drop_rate(0.3)
+                .with_reordering(2)
+                .with_seed(5);
+            let mut transport = Transport::new(policy);
+            for i in 0..50 {
+                transport.send(0, 1, i);
+            }
+            transport.tick();
+            transport.tick();
+            messages(&transport.take_due())
+        };
+        assert_eq!(run(), run());
+    }
+}
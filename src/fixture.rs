@@ -0,0 +1,182 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording and replaying API exchanges with golden files.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use serde_json::{self, Value};
+
+/// Mode of using an API fixture file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Requests are processed by the API handlers, and all requests together with responses
+    /// are written to the fixture file, overwriting it.
+    Record,
+    /// Requests are processed by the API handlers, and responses are compared with the ones
+    /// recorded in the fixture file.
+    Check,
+    /// Requests are not processed by the API handlers; responses recorded in the fixture file
+    /// are returned instead.
+    Replay,
+}
+
+/// API request together with the response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Exchange {
+    pub request: ExchangeRequest,
+    pub status: u16,
+    pub response_body: String,
+}
+
+/// API request recorded in a fixture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ExchangeRequest {
+    pub private: bool,
+    pub method: String,
+    pub endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+/// Sequence of API exchanges stored in a file.
+#[derive(Debug)]
+pub(crate) struct Fixture {
+    path: PathBuf,
+    mode: FixtureMode,
+    exchanges: Vec<Exchange>,
+    // Number of exchanges checked or replayed so far.
+    position: usize,
+}
+
+impl Fixture {
+    pub fn new(path: &Path, mode: FixtureMode) -> Self {
+        let exchanges = match mode {
+            FixtureMode::Record => Vec::new(),
+            FixtureMode::Check | FixtureMode::Replay => {
+                let file = File::open(path).unwrap_or_else(|e| {
+                    panic!("Cannot open API fixture {}: {}", path.display(), e)
+                });
+                serde_json::from_reader(file).unwrap_or_else(|e| {
+                    panic!("Cannot parse API fixture {}: {}", path.display(), e)
+                })
+            }
+        };
+
+        Fixture {
+            path: path.to_owned(),
+            mode,
+            exchanges,
+            position: 0,
+        }
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /// Returns the next recorded exchange, checking that it corresponds to `request`.
+    fn next_exchange(&mut self, request: &ExchangeRequest) -> &Exchange {
+        let position = self.position;
+        let exchange = self.exchanges.get(position).unwrap_or_else(|| {
+            panic!(
+                "Unexpected request `{} {}` (#{}) not present in API fixture {}",
+                request.method,
+                request.endpoint,
+                position,
+                self.path.display()
+            )
+        });
+        assert_eq!(
+            exchange.request,
+            *request,
+            "Request #{} differs from the one in API fixture {}",
+            position,
+            self.path.display()
+        );
+        self.position += 1;
+        exchange
+    }
+
+    /// Returns the recorded status and body of the response to `request`.
+    pub fn replay(&mut self, request: &ExchangeRequest) -> (u16, String) {
+        let exchange = self.next_exchange(request);
+        (exchange.status, exchange.response_body.clone())
+    }
+
+    /// Records the exchange or compares it with the recorded one, depending on the mode
+    /// of the fixture.
+    pub fn process(&mut self, exchange: Exchange) {
+        match self.mode {
+            FixtureMode::Record => self.exchanges.push(exchange),
+            FixtureMode::Check => {
+                let path = self.path.display().to_string();
+                let position = self.position;
+                let expected = self.next_exchange(&exchange.request);
+                let same_body = match (
+                    serde_json::from_str::<Value>(&expected.response_body),
+                    serde_json::from_str::<Value>(&exchange.response_body),
+                ) {
+                    // Compare JSON bodies semantically, so that formatting does not matter.
+                    (Ok(expected), Ok(actual)) => expected == actual,
+                    _ => expected.response_body == exchange.response_body,
+                };
+                if expected.status != exchange.status || !same_body {
+                    panic!(
+                        "Response to `{} {}` (#{}) differs from API fixture {}\n\
+                         expected: {} {}\n  actual: {} {}",
+                        exchange.request.method,
+                        exchange.request.endpoint,
+                        position,
+                        path,
+                        expected.status,
+                        expected.response_body,
+                        exchange.status,
+                        exchange.response_body
+                    );
+                }
+            }
+            FixtureMode::Replay => unreachable!("Replayed exchanges are not processed"),
+        }
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+
+        match self.mode {
+            FixtureMode::Record => {
+                let file = File::create(&self.path).unwrap_or_else(|e| {
+                    panic!("Cannot create API fixture {}: {}", self.path.display(), e)
+                });
+                serde_json::to_writer_pretty(file, &self.exchanges).unwrap_or_else(|e| {
+                    panic!("Cannot write API fixture {}: {}", self.path.display(), e)
+                });
+            }
+            FixtureMode::Check | FixtureMode::Replay => {
+                assert_eq!(
+                    self.position,
+                    self.exchanges.len(),
+                    "Not all requests from API fixture {} have been performed",
+                    self.path.display()
+                );
+            }
+        }
+    }
+}
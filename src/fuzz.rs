@@ -0,0 +1,331 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fuzzing of transaction deserialization and execution.
+//!
+//! `Fuzzer` structurally mutates raw transaction messages and feeds them into
+//! `Service::tx_from_raw()` of the testkit services. Parsed transactions that pass
+//! `verify()` are executed in a block, which is then rolled back. The fuzzer panics
+//! if a service panics on any of these steps.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # extern crate exonum_testkit;
+//! # use exonum::crypto;
+//! # use exonum::messages::Message;
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! # use exonum_testkit::fuzz::Fuzzer;
+//! #
+//! # fn main() {
+//! let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+//! let (pubkey, key) = crypto::gen_keypair();
+//! let seeds = vec![
+//!     MyTransaction::new(&pubkey, "Alice", &key).raw().clone(),
+//!     MyTransaction::new(&pubkey, "Bob", &key).raw().clone(),
+//! ];
+//! let report = Fuzzer::new(seeds)
+//!     .with_secret_key(key)
+//!     .with_iterations(1_000)
+//!     .run(&mut testkit);
+//! assert!(report.executed() > 0);
+//! # }
+//! ```
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use exonum::crypto::{self, SecretKey, SIGNATURE_LENGTH};
+use exonum::encoding::serialize::ToHex;
+use exonum::messages::{MessageBuffer, RawMessage};
+
+use TestKit;
+use faults::Rng;
+
+/// Length of the message header.
+const HEADER_LENGTH: usize = 10;
+/// Offset of the message type in the header.
+const MESSAGE_TYPE_OFFSET: usize = 2;
+/// Offset of the message length in the header.
+const PAYLOAD_LENGTH_OFFSET: usize = 6;
+
+/// Mutation applied to a raw message by `Fuzzer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    /// The message is truncated, but retains the header.
+    Truncate,
+    /// Random bytes are appended to the message.
+    Extend,
+    /// A random bit after the header is flipped.
+    FlipBit,
+    /// A 4-byte word after the header, such as a segment pointer or a segment length,
+    /// is replaced with a boundary or random value.
+    SegmentPointer,
+    /// The message type in the header is replaced with a random value.
+    MessageId,
+}
+
+const MUTATIONS: [Mutation; 5] = [
+    Mutation::Truncate,
+    Mutation::Extend,
+    Mutation::FlipBit,
+    Mutation::SegmentPointer,
+    Mutation::MessageId,
+];
+
+/// Fuzzer of transaction deserialization and execution. Mutations are pseudo-random,
+/// but reproducible for the same seed.
+#[derive(Debug)]
+pub struct Fuzzer {
+    messages: Vec<RawMessage>,
+    secret_key: Option<SecretKey>,
+    iterations: usize,
+    seed: Option<u64>,
+}
+
+impl Fuzzer {
+    /// Creates a fuzzer mutating the given messages. The messages should be valid
+    /// transactions of the testkit services, so that mutations explore the corner cases
+    /// of their deserialization.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `messages` is empty or any of messages is shorter than the message header.
+    pub fn new<I>(messages: I) -> Self
+    where
+        I: IntoIterator<Item = RawMessage>,
+    {
+        let messages: Vec<_> = messages.into_iter().collect();
+        assert!(!messages.is_empty(), "At least one message should be specified");
+        assert!(
+            messages.iter().all(|message| message.len() >= HEADER_LENGTH),
+            "Messages should contain the header"
+        );
+        Fuzzer {
+            messages,
+            secret_key: None,
+            iterations: 1_000,
+            seed: None,
+        }
+    }
+
+    /// Sets the number of mutated messages. The default value is 1000.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets the seed of the pseudo-random generator used to mutate messages. By default,
+    /// the seed of the testkit is used (see `TestKit::seed()`).
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the key, with which mutated messages are re-signed. Without the key, mutated
+    /// messages fail signature verification, so they are only parsed, but not executed.
+    pub fn with_secret_key(mut self, secret_key: SecretKey) -> Self {
+        self.secret_key = Some(secret_key);
+        self
+    }
+
+    /// Feeds mutated messages into the testkit services. Executed transactions are
+    /// rolled back, so the testkit state is not changed.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if a service panics on parsing, verifying or executing a mutated message.
+    ///   The panic message contains the hex-encoded message and the applied mutation.
+    pub fn run(&self, testkit: &mut TestKit) -> FuzzReport {
+        let seed = self.seed.unwrap_or_else(|| testkit.seed());
+        let mut rng = Rng::new(seed);
+        let mut report = FuzzReport::default();
+        for _ in 0..self.iterations {
+            let original = &self.messages[rng.next_below(self.messages.len())];
+            let mutation = MUTATIONS[rng.next_below(MUTATIONS.len())];
+            let bytes = self.mutate(original.as_ref().as_ref(), mutation, &mut rng);
+            let hex = bytes.to_hex();
+            let raw = RawMessage::new(MessageBuffer::from_vec(bytes));
+            report.messages += 1;
+
+            let tx = {
+                let blockchain = &testkit.blockchain;
+                match panic::catch_unwind(AssertUnwindSafe(|| blockchain.tx_from_raw(raw))) {
+                    Ok(Some(tx)) => tx,
+                    Ok(None) => continue,
+                    Err(payload) => fail("parsing", &hex, mutation, &*payload),
+                }
+            };
+            report.parsed += 1;
+
+            match panic::catch_unwind(AssertUnwindSafe(|| tx.verify())) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(payload) => fail("verifying", &hex, mutation, &*payload),
+            }
+            let execution = panic::catch_unwind(AssertUnwindSafe(|| testkit.probe_all(vec![tx])));
+            if let Err(payload) = execution {
+                fail("executing", &hex, mutation, &*payload);
+            }
+            report.executed += 1;
+        }
+        report
+    }
+
+    /// Applies the mutation to the message and re-signs it if the secret key is set.
+    fn mutate(&self, message: &[u8], mutation: Mutation, rng: &mut Rng) -> Vec<u8> {
+        let mut bytes = message.to_vec();
+        let body_len = bytes.len() - HEADER_LENGTH;
+        match mutation {
+            Mutation::Truncate => {
+                if body_len > 0 {
+                    bytes.truncate(HEADER_LENGTH + rng.next_below(body_len));
+                }
+            }
+            Mutation::Extend => {
+                let extra_len = 1 + rng.next_below(64);
+                bytes.extend((0..extra_len).map(|_| rng.next_u64() as u8));
+            }
+            Mutation::FlipBit => {
+                if body_len > 0 {
+                    let position = HEADER_LENGTH + rng.next_below(body_len);
+                    bytes[position] ^= 1 << rng.next_below(8);
+                }
+            }
+            Mutation::SegmentPointer => {
+                if body_len >= 4 {
+                    let position = HEADER_LENGTH + rng.next_below(body_len - 3);
+                    let len = bytes.len() as u32;
+                    let values = [
+                        0,
+                        1,
+                        len - 1,
+                        len,
+                        len + 1,
+                        u32::max_value(),
+                        rng.next_u64() as u32,
+                    ];
+                    let value = values[rng.next_below(values.len())];
+                    write_u32(&mut bytes[position..position + 4], value);
+                }
+            }
+            Mutation::MessageId => {
+                let message_type = rng.next_u64() as u16;
+                bytes[MESSAGE_TYPE_OFFSET] = message_type as u8;
+                bytes[MESSAGE_TYPE_OFFSET + 1] = (message_type >> 8) as u8;
+            }
+        }
+
+        // The network layer ensures that the length in the header is correct.
+        let len = bytes.len() as u32;
+        write_u32(&mut bytes[PAYLOAD_LENGTH_OFFSET..PAYLOAD_LENGTH_OFFSET + 4], len);
+        if let Some(ref secret_key) = self.secret_key {
+            if bytes.len() >= HEADER_LENGTH + SIGNATURE_LENGTH {
+                let signed_len = bytes.len() - SIGNATURE_LENGTH;
+                let signature = crypto::sign(&bytes[..signed_len], secret_key);
+                bytes[signed_len..].copy_from_slice(signature.as_ref());
+            }
+        }
+        bytes
+    }
+}
+
+/// Writes a little-endian `u32` into the buffer.
+fn write_u32(buffer: &mut [u8], value: u32) {
+    for (i, byte) in buffer.iter_mut().enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+}
+
+/// Panics with the description of the mutated message, on which a service has panicked.
+fn fail(stage: &str, hex: &str, mutation: Mutation, payload: &(Any + Send)) -> ! {
+    panic!(
+        "Service panicked on {} mutated message {} ({:?}): {}",
+        stage,
+        hex,
+        mutation,
+        panic_message(payload)
+    )
+}
+
+/// Extracts the message from the panic payload.
+pub(crate) fn panic_message(payload: &(Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(unknown panic)".to_owned()
+    }
+}
+
+/// Statistics of a fuzzing run.
+#[derive(Debug, Clone, Default)]
+pub struct FuzzReport {
+    messages: usize,
+    parsed: usize,
+    executed: usize,
+}
+
+impl FuzzReport {
+    /// Returns the number of mutated messages fed into the services.
+    pub fn messages(&self) -> usize {
+        self.messages
+    }
+
+    /// Returns the number of mutated messages successfully parsed by the services.
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// Returns the number of parsed transactions, which have passed `verify()`
+    /// and have been executed.
+    pub fn executed(&self) -> usize {
+        self.executed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::gen_keypair;
+    use super::*;
+
+    #[test]
+    fn test_mutations_keep_header() {
+        let (_, key) = gen_keypair();
+        let message = vec![0; HEADER_LENGTH + 16 + SIGNATURE_LENGTH];
+        let fuzzer = Fuzzer::new(vec![RawMessage::new(MessageBuffer::from_vec(message.clone()))])
+            .with_secret_key(key);
+        let mut rng = Rng::new(0);
+        for _ in 0..100 {
+            for &mutation in &MUTATIONS {
+                let bytes = fuzzer.mutate(&message, mutation, &mut rng);
+                assert!(bytes.len() >= HEADER_LENGTH);
+                let len = bytes.len();
+                let mut expected_len = vec![0; 4];
+                write_u32(&mut expected_len, len as u32);
+                assert_eq!(bytes[PAYLOAD_LENGTH_OFFSET..HEADER_LENGTH], expected_len[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_u32() {
+        let mut buffer = [0; 4];
+        write_u32(&mut buffer, 0x0102_0304);
+        assert_eq!(buffer, [4, 3, 2, 1]);
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exploration of transaction interleavings across blocks.
+//!
+//! `Interleavings` distributes a set of transactions across consecutive blocks in different
+//! ways: the transactions are ordered and the ordering is split into at most `max_blocks`
+//! blocks. Each such schedule is executed on a copy of the testkit made with `TestKit::fork()`,
+//! so invariants registered with `TestKit::add_invariant()` and other checks, such as
+//! the determinism check, are performed after every block. Schedules, for which block
+//! creation panics, are reported as failures.
+//!
+//! If the number of schedules does not exceed the budget, all of them are explored.
+//! Otherwise, schedules are sampled pseudo-randomly, preferring the ones covering new
+//! relations between pairs of transactions: whether a transaction precedes another one
+//! in the same block or in an earlier block.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # #[macro_use] extern crate exonum_testkit;
+//! # use exonum::crypto::{self, PublicKey};
+//! # use exonum::storage::MapIndex;
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! # use exonum_testkit::interleavings::Interleavings;
+//! #
+//! # fn main() {
+//! let mut testkit = TestKitBuilder::validator()
+//!     .with_service_factory(|| Box::new(MyService))
+//!     .create();
+//! testkit.add_invariant("messages are set", |snapshot| {
+//!     let messages: MapIndex<_, PublicKey, String> =
+//!         MapIndex::new("documentation.messages", snapshot);
+//!     messages.values().all(|message| !message.is_empty())
+//! });
+//! let (alice, alice_key) = crypto::gen_keypair();
+//! let (bob, bob_key) = crypto::gen_keypair();
+//! let txs = txvec![
+//!     MyTransaction::new(&alice, "foo", &alice_key),
+//!     MyTransaction::new(&alice, "bar", &alice_key),
+//!     MyTransaction::new(&bob, "baz", &bob_key),
+//! ];
+//! let report = Interleavings::new(txs)
+//!     .with_max_blocks(3)
+//!     .with_budget(200)
+//!     .run(&mut testkit);
+//! assert!(report.is_ok(), "{}", report.failures()[0]);
+//! # }
+//! ```
+
+use std::cmp;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use exonum::blockchain::Transaction;
+use exonum::crypto::{self, Hash};
+use exonum::messages::{Message, RawMessage};
+
+use TestKit;
+use faults::Rng;
+use fuzz::panic_message;
+use permutations::Permutations;
+
+/// Number of random schedules, among which the one covering most new relations is chosen.
+const CANDIDATES: usize = 8;
+
+/// Split of ordered transactions into blocks, with transactions referred to by their indexes.
+type Schedule = Vec<Vec<usize>>;
+
+/// Explorer of the ways to distribute transactions across blocks. See the module docs
+/// for an example.
+#[derive(Debug)]
+pub struct Interleavings {
+    transactions: Vec<RawMessage>,
+    max_blocks: Option<usize>,
+    budget: usize,
+    seed: Option<u64>,
+}
+
+impl Interleavings {
+    /// Creates an explorer of interleavings of the given transactions.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `txs` is empty.
+    pub fn new<I>(txs: I) -> Self
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let transactions: Vec<_> = txs.into_iter().map(|tx| tx.raw().clone()).collect();
+        assert!(!transactions.is_empty(), "At least one transaction should be specified");
+        Interleavings {
+            transactions,
+            max_blocks: None,
+            budget: 1_000,
+            seed: None,
+        }
+    }
+
+    /// Sets the maximum number of blocks, across which transactions are distributed.
+    /// By default, every transaction may be committed in a separate block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `max_blocks` is zero.
+    pub fn with_max_blocks(mut self, max_blocks: usize) -> Self {
+        assert!(max_blocks > 0, "Maximum number of blocks should be positive");
+        self.max_blocks = Some(max_blocks);
+        self
+    }
+
+    /// Sets the maximum number of explored schedules. The default value is 1000.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Sets the seed of the pseudo-random generator used to sample schedules if their number
+    /// exceeds the budget. By default, the seed of the testkit is used
+    /// (see `TestKit::seed()`).
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    fn max_blocks(&self) -> usize {
+        let len = self.transactions.len();
+        self.max_blocks.map_or(len, |max_blocks| cmp::min(max_blocks, len))
+    }
+
+    /// Returns the total number of schedules, saturating at `u64::max_value()`.
+    pub fn total_schedules(&self) -> u64 {
+        let len = self.transactions.len() as u64;
+        let orderings = (1..len + 1).fold(1_u64, |acc, i| acc.saturating_mul(i));
+        // The number of ways to split an ordering into `k` non-empty blocks
+        // is `C(len - 1, k - 1)`.
+        let mut splits = 0_u64;
+        let mut binomial = 1_u64;
+        for k in 1..self.max_blocks() as u64 + 1 {
+            splits = splits.saturating_add(binomial);
+            binomial = binomial.saturating_mul(len - k) / k;
+        }
+        orderings.saturating_mul(splits)
+    }
+
+    /// Executes schedules on copies of the testkit, so the testkit state is not changed.
+    /// Transactions are committed with `TestKit::create_block_with_transactions()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions cannot be parsed by the testkit services.
+    /// - Panics if the testkit cannot be copied (see `TestKit::fork()`).
+    pub fn run(&self, testkit: &mut TestKit) -> InterleavingReport {
+        let mut report = InterleavingReport {
+            total_schedules: self.total_schedules(),
+            total_relations: self.total_relations(),
+            ..InterleavingReport::default()
+        };
+        let hashes: Vec<_> = self.transactions
+            .iter()
+            .map(|raw| crypto::hash(raw.as_ref()))
+            .collect();
+        let mut covered = BTreeSet::new();
+        let mut states = BTreeSet::new();
+        for schedule in self.schedules(testkit) {
+            covered.extend(relations(&schedule));
+            report.schedules += 1;
+            match self.execute(testkit, &schedule) {
+                Ok(state_hash) => {
+                    states.insert(state_hash);
+                }
+                Err(message) => {
+                    report.failures.push(InterleavingFailure {
+                        blocks: schedule
+                            .iter()
+                            .map(|block| block.iter().map(|&i| hashes[i]).collect())
+                            .collect(),
+                        message,
+                    });
+                }
+            }
+        }
+        report.covered_relations = covered.len();
+        report.distinct_states = states.len();
+        report
+    }
+
+    /// Returns schedules to explore: all of them if their number fits into the budget,
+    /// or sampled ones otherwise.
+    fn schedules(&self, testkit: &TestKit) -> Vec<Schedule> {
+        if self.total_schedules() <= self.budget as u64 {
+            return self.all_schedules();
+        }
+
+        let seed = self.seed.unwrap_or_else(|| testkit.seed());
+        let mut rng = Rng::new(seed);
+        let mut explored = BTreeSet::new();
+        let mut covered = BTreeSet::new();
+        let mut schedules = Vec::new();
+        // Sampling gives up after too many duplicate schedules.
+        let mut attempts = 0;
+        while schedules.len() < self.budget && attempts < self.budget * CANDIDATES {
+            attempts += 1;
+            let best = (0..CANDIDATES)
+                .map(|_| self.random_schedule(&mut rng))
+                .filter(|schedule| !explored.contains(schedule))
+                .max_by_key(|schedule| {
+                    relations(schedule).difference(&covered).count()
+                });
+            if let Some(schedule) = best {
+                covered.extend(relations(&schedule));
+                explored.insert(schedule.clone());
+                schedules.push(schedule);
+            }
+        }
+        schedules
+    }
+
+    fn all_schedules(&self) -> Vec<Schedule> {
+        let len = self.transactions.len();
+        let indexes: Vec<_> = (0..len).collect();
+        let mut schedules = Vec::new();
+        for ordering in Permutations::new(&indexes) {
+            // Each bit of `cuts` marks a block boundary after the corresponding transaction.
+            for cuts in 0..1_u64 << (len - 1) {
+                if cuts.count_ones() as usize >= self.max_blocks() {
+                    continue;
+                }
+                let mut schedule = vec![Vec::new()];
+                for (position, &index) in ordering.iter().enumerate() {
+                    schedule.last_mut().unwrap().push(index);
+                    if cuts & (1 << position) != 0 {
+                        schedule.push(Vec::new());
+                    }
+                }
+                schedules.push(schedule);
+            }
+        }
+        schedules
+    }
+
+    fn random_schedule(&self, rng: &mut Rng) -> Schedule {
+        let len = self.transactions.len();
+        let mut ordering: Vec<_> = (0..len).collect();
+        for i in (1..len).rev() {
+            ordering.swap(i, rng.next_below(i + 1));
+        }
+
+        let blocks = rng.next_below(self.max_blocks()) + 1;
+        let mut gaps: Vec<_> = (1..len).collect();
+        for i in 0..blocks - 1 {
+            let other = i + rng.next_below(gaps.len() - i);
+            gaps.swap(i, other);
+        }
+        let mut cuts: Vec<_> = gaps[..blocks - 1].to_vec();
+        cuts.sort();
+        cuts.push(len);
+
+        let mut start = 0;
+        cuts.into_iter()
+            .map(|end| {
+                let block = ordering[start..end].to_vec();
+                start = end;
+                block
+            })
+            .collect()
+    }
+
+    fn total_relations(&self) -> usize {
+        let len = self.transactions.len();
+        let kinds = if self.max_blocks() > 1 { 2 } else { 1 };
+        len * (len - 1) * kinds
+    }
+
+    /// Executes the schedule on a copy of the testkit and returns the resulting state hash
+    /// or the panic message.
+    fn execute(&self, testkit: &mut TestKit, schedule: &[Vec<usize>]) -> Result<Hash, String> {
+        let mut testkit = testkit.fork();
+        for block in schedule {
+            let txs: Vec<_> = block
+                .iter()
+                .map(|&i| {
+                    testkit
+                        .blockchain
+                        .tx_from_raw(self.transactions[i].clone())
+                        .expect("Cannot parse transaction")
+                })
+                .collect();
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                testkit.create_block_with_transactions(txs);
+            })).map_err(|payload| panic_message(&*payload))?;
+        }
+        Ok(*testkit.last_block().state_hash())
+    }
+}
+
+/// Returns relations between pairs of transactions established by the schedule: whether
+/// the first transaction precedes the second one in the same block (`true`) or in an earlier
+/// block (`false`).
+fn relations(schedule: &[Vec<usize>]) -> BTreeSet<(usize, usize, bool)> {
+    let mut relations = BTreeSet::new();
+    for (block_index, block) in schedule.iter().enumerate() {
+        for (position, &first) in block.iter().enumerate() {
+            for &second in &block[position + 1..] {
+                relations.insert((first, second, true));
+            }
+            for later_block in &schedule[block_index + 1..] {
+                for &second in later_block {
+                    relations.insert((first, second, false));
+                }
+            }
+        }
+    }
+    relations
+}
+
+/// Schedule, for which block creation has panicked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterleavingFailure {
+    /// Hashes of transactions in the blocks of the schedule.
+    pub blocks: Vec<Vec<Hash>>,
+    /// Panic message.
+    pub message: String,
+}
+
+impl fmt::Display for InterleavingFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Schedule {:?} has failed: {}", self.blocks, self.message)
+    }
+}
+
+/// Results of exploring interleavings.
+#[derive(Debug, Clone, Default)]
+pub struct InterleavingReport {
+    schedules: usize,
+    total_schedules: u64,
+    covered_relations: usize,
+    total_relations: usize,
+    distinct_states: usize,
+    failures: Vec<InterleavingFailure>,
+}
+
+impl InterleavingReport {
+    /// Returns the number of explored schedules.
+    pub fn schedules(&self) -> usize {
+        self.schedules
+    }
+
+    /// Returns the total number of schedules, saturating at `u64::max_value()`.
+    pub fn total_schedules(&self) -> u64 {
+        self.total_schedules
+    }
+
+    /// Returns the fraction of relations between pairs of transactions covered
+    /// by explored schedules.
+    pub fn coverage(&self) -> f64 {
+        if self.total_relations == 0 {
+            1.0
+        } else {
+            self.covered_relations as f64 / self.total_relations as f64
+        }
+    }
+
+    /// Returns the number of distinct state hashes, to which successful schedules lead.
+    pub fn distinct_states(&self) -> usize {
+        self.distinct_states
+    }
+
+    /// Returns failed schedules in the order of exploration.
+    pub fn failures(&self) -> &[InterleavingFailure] {
+        &self.failures
+    }
+
+    /// Returns `true` if no schedule has failed.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::messages::MessageBuffer;
+
+    use super::*;
+
+    fn interleavings(len: usize, max_blocks: Option<usize>) -> Interleavings {
+        let transactions = (0..len)
+            .map(|i| RawMessage::new(MessageBuffer::from_vec(vec![i as u8; 10])))
+            .collect();
+        Interleavings {
+            transactions,
+            max_blocks,
+            budget: 1_000,
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn test_all_schedules() {
+        let schedules = interleavings(3, None).all_schedules();
+        assert_eq!(schedules.len(), 24);
+        assert_eq!(interleavings(3, None).total_schedules(), 24);
+        assert!(schedules.contains(&vec![vec![1], vec![2, 0]]));
+        let unique: BTreeSet<_> = schedules.into_iter().collect();
+        assert_eq!(unique.len(), 24);
+
+        let schedules = interleavings(4, Some(2)).all_schedules();
+        assert!(schedules.iter().all(|schedule| schedule.len() <= 2));
+        assert_eq!(schedules.len() as u64, interleavings(4, Some(2)).total_schedules());
+        assert_eq!(interleavings(30, None).total_schedules(), u64::max_value());
+    }
+
+    #[test]
+    fn test_random_schedule() {
+        let interleavings = interleavings(6, Some(3));
+        let mut rng = Rng::new(5);
+        for _ in 0..100 {
+            let schedule = interleavings.random_schedule(&mut rng);
+            assert!(!schedule.is_empty() && schedule.len() <= 3);
+            assert!(schedule.iter().all(|block| !block.is_empty()));
+            let mut indexes: Vec<_> = schedule.into_iter().flat_map(|block| block).collect();
+            indexes.sort();
+            assert_eq!(indexes, (0..6).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_relations() {
+        let relations = relations(&[vec![2, 0], vec![1]]);
+        let expected: BTreeSet<_> = vec![(2, 0, true), (2, 1, false), (0, 1, false)]
+            .into_iter()
+            .collect();
+        assert_eq!(relations, expected);
+    }
+}
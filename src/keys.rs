@@ -0,0 +1,144 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reproducible generation of keys for the testkit.
+
+use std::env;
+
+use exonum::crypto::{self, PublicKey, SecretKey, Seed};
+
+/// Environment variable setting the seed of testkits created without an explicit seed.
+pub(crate) const SEED_VAR: &str = "EXONUM_TESTKIT_SEED";
+
+/// Generator of keypairs, which are either random or derived from a fixed seed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KeyGenerator {
+    seed: Option<u64>,
+    counter: u64,
+    // Whether the seed is set explicitly rather than taken from the environment
+    // or chosen randomly.
+    explicit: bool,
+}
+
+impl KeyGenerator {
+    /// Creates a generator of random keypairs.
+    pub fn random() -> Self {
+        KeyGenerator::default()
+    }
+
+    /// Creates a generator of keypairs derived from the given seed.
+    pub fn seeded(seed: u64) -> Self {
+        KeyGenerator {
+            seed: Some(seed),
+            counter: 0,
+            explicit: true,
+        }
+    }
+
+    /// Creates a generator of keypairs derived from the seed in the `EXONUM_TESTKIT_SEED`
+    /// environment variable, or from a random seed if the variable is not set.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the variable cannot be parsed as `u64`.
+    pub fn from_env() -> Self {
+        let seed = match env::var(SEED_VAR) {
+            Ok(value) => {
+                value.trim().parse().unwrap_or_else(|_| {
+                    panic!("Cannot parse {} value {:?} as an integer", SEED_VAR, value)
+                })
+            }
+            Err(_) => decode(&crypto::gen_keypair().0.as_ref()[..8]),
+        };
+        KeyGenerator {
+            seed: Some(seed),
+            counter: 0,
+            explicit: false,
+        }
+    }
+
+    /// Returns the seed of the generator, or `None` if the generator is random.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Returns `true` if the seed is set with `seeded()`.
+    pub fn is_explicit(&self) -> bool {
+        self.explicit
+    }
+
+    /// Creates an independent generator, the keys of which are derived from the seed
+    /// of this generator and `label`. A random generator produces another random generator.
+    pub fn child(&self, label: u64) -> Self {
+        match self.seed {
+            Some(seed) => {
+                let hash = crypto::hash(&[encode(seed), encode(label)].concat());
+                KeyGenerator::seeded(decode(&hash.as_ref()[..8]))
+            }
+            None => KeyGenerator::random(),
+        }
+    }
+
+    /// Generates the next keypair.
+    pub fn gen_keypair(&mut self) -> (PublicKey, SecretKey) {
+        match self.seed {
+            Some(seed) => {
+                let hash = crypto::hash(&[encode(seed), encode(self.counter)].concat());
+                self.counter += 1;
+                crypto::gen_keypair_from_seed(&Seed::from_slice(hash.as_ref()).unwrap())
+            }
+            None => crypto::gen_keypair(),
+        }
+    }
+}
+
+fn encode(value: u64) -> Vec<u8> {
+    (0..8).map(|i| (value >> (8 * i)) as u8).collect()
+}
+
+fn decode(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |acc, &byte| (acc << 8) | u64::from(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_generator() {
+        let mut first = KeyGenerator::seeded(42);
+        let mut second = KeyGenerator::seeded(42);
+        let keys: Vec<_> = (0..3).map(|_| first.gen_keypair()).collect();
+        assert_eq!(keys, (0..3).map(|_| second.gen_keypair()).collect::<Vec<_>>());
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[0], KeyGenerator::seeded(43).gen_keypair());
+
+        assert_eq!(first.child(1).gen_keypair(), second.child(1).gen_keypair());
+        assert_ne!(first.child(1).gen_keypair(), first.child(2).gen_keypair());
+        assert_eq!(KeyGenerator::random().child(1).seed(), None);
+    }
+
+    #[test]
+    fn test_generator_from_env() {
+        let generator = KeyGenerator::from_env();
+        assert!(generator.seed().is_some());
+        assert!(!generator.is_explicit());
+        assert!(KeyGenerator::seeded(1).is_explicit());
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(decode(&encode(0x0123_4567_89ab_cdef)), 0x0123_4567_89ab_cdef);
+    }
+}
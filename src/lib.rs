@@ -123,52 +123,164 @@
 
 #![deny(missing_debug_implementations, missing_docs)]
 
+#[macro_use]
 extern crate exonum;
+#[cfg(feature = "macros")]
+extern crate exonum_testkit_macros;
 extern crate futures;
+extern crate hyper;
+#[cfg(feature = "tls")]
+extern crate hyper_native_tls;
 extern crate iron;
 extern crate iron_test;
+#[macro_use]
+extern crate log;
 extern crate mount;
+#[cfg(feature = "proptest")]
+extern crate proptest as proptest_lib;
 extern crate router;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+extern crate tempdir;
+extern crate valico;
 
 use futures::Stream;
 use futures::executor::{self, Spawn};
 use futures::sync::mpsc;
-use iron::IronError;
-use iron::headers::{ContentType, Headers};
-use iron::status::StatusClass;
+use iron::{Handler, IronResult, Response};
+use iron::headers::{ContentType, Cookie, Headers};
+use iron::status::{Status, StatusClass};
 use iron_test::{request, response};
 use mount::Mount;
 use router::Router;
 use serde::{Deserialize, Serialize};
 
-use std::collections::BTreeMap;
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::sync::mpsc as std_mpsc;
 use std::fmt;
-
-use exonum::blockchain::{Blockchain, ConsensusConfig, GenesisConfig, Schema as CoreSchema,
-                         Service, SharedNodeState, StoredConfiguration, Transaction, ValidatorKeys};
+use std::iter;
+use std::mem;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use exonum::blockchain::{Block, Blockchain, ConsensusConfig, GenesisConfig, Schema as CoreSchema,
+                         Service, SharedNodeState, StoredConfiguration, Transaction, TxLocation,
+                         ValidatorKeys};
 use exonum::crypto;
 use exonum::helpers::{Height, Round, ValidatorId};
-use exonum::messages::{Message, Precommit, Propose};
+use exonum::messages::{Message, Precommit, Propose, RawMessage};
 use exonum::node::{ApiSender, ExternalMessage, State as NodeState, TransactionSend, TxPool};
-use exonum::storage::{MemoryDB, Snapshot};
+use exonum::storage::{Database, Fork, Patch, Snapshot, StorageValue};
+use exonum::storage::proof_map_index::ProofMapKey;
 
 #[macro_use]
 mod macros;
+mod api_builder;
+mod api_error;
+mod async_api;
+mod backend;
+pub mod bench;
+mod block_proof;
+mod branch;
 mod checkpoint_db;
+mod client;
 pub mod compare;
+mod comparison;
+mod determinism;
+#[doc(hidden)]
+pub mod doc_helpers;
+mod events;
+mod execution;
+pub mod explorer;
+mod export;
+pub mod external_chain;
+mod faults;
+mod fixture;
+pub mod fuzz;
 mod greedy_fold;
+pub mod interleavings;
+mod keys;
+mod logs;
+mod malicious;
+mod mempool;
+mod metrics;
+mod multipart;
+mod permutations;
+mod profiler;
+pub mod proof;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+mod query;
+pub mod replay;
+pub mod scenario;
+mod runner;
+mod saved;
+mod schema;
+mod server;
+mod services_snapshot;
+mod shared_service;
+mod stats;
+mod stress;
+pub mod tamper;
+pub mod time;
+mod tx_factory;
+pub mod workload;
 
 #[doc(hidden)]
 pub use greedy_fold::GreedilyFoldable;
-pub use compare::ComparableSnapshot;
-
+pub use api_builder::TestKitApiBuilder;
+pub use api_error::{ApiMethod, BodyMatcher};
+pub use async_api::{AsyncTestKitApi, TestKitFuture};
+pub use block_proof::{BlockProof, ProofTampering};
+pub use branch::Branch;
+pub use client::ServiceApiClient;
+pub use compare::{assert_storage_diff, ComparableSnapshot};
+pub use comparison::{ComparisonTestKit, StateDivergence};
+pub use events::TestKitEvent;
+pub use execution::{BlockResult, ExecutionStatus, PanicPolicy, TxResult};
+pub use explorer::TestKitExplorer;
+pub use faults::FaultPolicy;
+pub use fixture::FixtureMode;
+pub use logs::LogRecord;
+pub use malicious::{Evidence, MaliciousNode};
+pub use mempool::{EvictionPolicy, PoolOrdering, PoolRetention};
+pub use multipart::MultipartForm;
+pub use profiler::{BlockProfile, ProfileReport, TxProfile, TxTypeProfile};
+pub use runner::{TestNetworkRunner, TestNetworkRunnerBuilder};
+pub use server::TestServer;
+pub use services_snapshot::ServicesSnapshot;
+pub use stats::{IndexStats, StorageStats};
+pub use stress::StressReport;
+pub use time::TimeProvider;
+pub use tx_factory::{Account, TxFactory};
+#[cfg(feature = "macros")]
+pub use exonum_testkit_macros::test;
+
+use backend::{StorageBackend, StorageKind};
 use checkpoint_db::{CheckpointDb, CheckpointDbHandler};
+use explorer::BlockWithTransactions;
+use export::ExportedState;
+use fixture::{Exchange, ExchangeRequest, Fixture};
+use events::EventBus;
+use keys::KeyGenerator;
+use metrics::Metrics;
+use logs::CapturedRecord;
+use mempool::MempoolLimiter;
+use permutations::Permutations;
+use proof::{ListStateProof, MapStateProof};
+use saved::SavedState;
+use shared_service::SharedService;
+use time::SystemTimeProvider;
 
 /// Emulated test network.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TestNetwork {
     us: TestNode,
     validators: Vec<TestNode>,
@@ -196,6 +308,36 @@ impl TestNetwork {
         &self.validators
     }
 
+    /// Returns nodes in the network, which are not validators. At the moment, the only
+    /// possible auditor is the node returned by `us()`.
+    pub fn auditors(&self) -> Vec<&TestNode> {
+        if self.us.validator_id().is_none() {
+            vec![&self.us]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns the validator proposing blocks at the given round. Unlike Exonum nodes,
+    /// the testkit does not rotate leaders between heights: the first validator is
+    /// the leader of the first round at every height, and the leader of each subsequent
+    /// round is the next validator.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `round` is zero; rounds are numbered starting from `Round::first()`.
+    pub fn leader_for(&self, round: Round) -> &TestNode {
+        assert!(round >= Round::first(), "Rounds are numbered starting from 1");
+        let id = (round.0 - 1) as usize % self.validators.len();
+        &self.validators[id]
+    }
+
+    /// Returns the number of validators sufficient for the Byzantine fault tolerant consensus,
+    /// i.e., the size of the quorum authorizing blocks.
+    pub fn majority_count(&self) -> usize {
+        NodeState::byzantine_majority_count(self.validators.len())
+    }
+
     /// Returns config encoding the network structure usable for creating the genesis block of
     /// a blockchain.
     pub fn genesis_config(&self) -> GenesisConfig {
@@ -248,30 +390,19 @@ pub struct TestNode {
 impl TestNode {
     /// Creates a new auditor.
     pub fn new_auditor() -> Self {
-        let (consensus_public_key, consensus_secret_key) = crypto::gen_keypair();
-        let (service_public_key, service_secret_key) = crypto::gen_keypair();
-
-        TestNode {
-            consensus_secret_key,
-            consensus_public_key,
-            service_secret_key,
-            service_public_key,
-            validator_id: None,
-        }
+        TestNode::generate(&mut KeyGenerator::random(), None)
     }
 
     /// Creates a new validator with the given id.
     pub fn new_validator(validator_id: ValidatorId) -> Self {
-        let (consensus_public_key, consensus_secret_key) = crypto::gen_keypair();
-        let (service_public_key, service_secret_key) = crypto::gen_keypair();
+        TestNode::generate(&mut KeyGenerator::random(), Some(validator_id))
+    }
 
-        TestNode {
-            consensus_secret_key,
-            consensus_public_key,
-            service_secret_key,
-            service_public_key,
-            validator_id: Some(validator_id),
-        }
+    /// Creates a new node with keys produced by the given generator.
+    fn generate(keys: &mut KeyGenerator, validator_id: Option<ValidatorId>) -> Self {
+        let consensus_keypair = keys.gen_keypair();
+        let service_keypair = keys.gen_keypair();
+        TestNode::from_parts(consensus_keypair, service_keypair, validator_id)
     }
 
     /// Constructs a new node from the given keypairs.
@@ -295,13 +426,24 @@ impl TestNode {
         height: Height,
         last_hash: &crypto::Hash,
         tx_hashes: &[crypto::Hash],
+    ) -> Propose {
+        self.create_propose_at_round(height, Round::first(), last_hash, tx_hashes)
+    }
+
+    /// Creates a `Propose` message signed by this validator for the specified round.
+    pub fn create_propose_at_round(
+        &self,
+        height: Height,
+        round: Round,
+        last_hash: &crypto::Hash,
+        tx_hashes: &[crypto::Hash],
     ) -> Propose {
         Propose::new(
             self.validator_id.expect(
                 "An attempt to create propose from a non-validator node.",
             ),
             height,
-            Round::first(),
+            round,
             last_hash,
             tx_hashes,
             &self.consensus_secret_key,
@@ -310,8 +452,16 @@ impl TestNode {
 
     /// Creates a `Precommit` message signed by this validator.
     pub fn create_precommit(&self, propose: &Propose, block_hash: &crypto::Hash) -> Precommit {
-        use std::time::SystemTime;
+        self.create_precommit_at_time(propose, block_hash, SystemTime::now())
+    }
 
+    /// Creates a `Precommit` message signed by this validator with the specified time.
+    pub fn create_precommit_at_time(
+        &self,
+        propose: &Propose,
+        block_hash: &crypto::Hash,
+        time: SystemTime,
+    ) -> Precommit {
         Precommit::new(
             self.validator_id.expect(
                 "An attempt to create propose from a non-validator node.",
@@ -320,7 +470,7 @@ impl TestNode {
             propose.round(),
             &propose.hash(),
             block_hash,
-            SystemTime::now(),
+            time,
             &self.consensus_secret_key,
         )
     }
@@ -347,6 +497,50 @@ impl TestNode {
     pub fn service_keypair(&self) -> (&crypto::PublicKey, &crypto::SecretKey) {
         (&self.service_public_key, &self.service_secret_key)
     }
+
+    /// Returns the consensus keypair.
+    pub fn consensus_keypair(&self) -> (&crypto::PublicKey, &crypto::SecretKey) {
+        (&self.consensus_public_key, &self.consensus_secret_key)
+    }
+
+    /// Creates a transaction signed with the service key of this node. The constructor
+    /// receives the service public key, which is usually specified as the transaction author,
+    /// and the secret key used to sign the transaction.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use] extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto::PublicKey;
+    /// # use exonum::helpers::ValidatorId;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// message! {
+    ///     struct TxVote {
+    ///         const TYPE = 1;
+    ///         const ID = 0;
+    ///
+    ///         from: &PublicKey,
+    ///         approve: bool,
+    ///     }
+    /// }
+    ///
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator().with_validators(3).create();
+    /// let validator = testkit.validator(ValidatorId(2));
+    /// let tx = validator.sign_tx(|pubkey, key| TxVote::new(pubkey, true, key));
+    /// assert_eq!(tx.from(), validator.service_keypair().0);
+    /// assert!(tx.verify_signature(tx.from()));
+    /// # }
+    /// ```
+    pub fn sign_tx<T, F>(&self, constructor: F) -> T
+    where
+        T: Message,
+        F: FnOnce(&crypto::PublicKey, &crypto::SecretKey) -> T,
+    {
+        constructor(&self.service_public_key, &self.service_secret_key)
+    }
 }
 
 impl From<TestNode> for ValidatorKeys {
@@ -394,6 +588,24 @@ pub struct TestKitBuilder {
     us: TestNode,
     validators: Vec<TestNode>,
     services: Vec<Box<Service>>,
+    service_factories: Vec<Arc<Fn() -> Box<Service> + Send + Sync>>,
+    service_configs: BTreeMap<String, serde_json::Value>,
+    time_provider: Box<TimeProvider>,
+    storage: StorageKind,
+    consensus_config: ConsensusConfig,
+    mempool_limit: Option<(usize, EvictionPolicy)>,
+    split_blocks: bool,
+    block_size_limit: Option<usize>,
+    rollback_retention: PoolRetention,
+    check_state_hash: bool,
+    check_determinism: bool,
+    panic_policy: PanicPolicy,
+    profiling: bool,
+    capture_logs: bool,
+    tx_factory: TxFactory,
+    key_generator: KeyGenerator,
+    saved: Option<SavedState>,
+    exported: Option<ExportedState>,
 }
 
 impl fmt::Debug for TestKitBuilder {
@@ -408,6 +620,24 @@ impl fmt::Debug for TestKitBuilder {
                     .map(|x| x.service_name())
                     .collect::<Vec<_>>(),
             )
+            .field("service_factories", &self.service_factories.len())
+            .field("service_configs", &self.service_configs)
+            .field("time_provider", &self.time_provider)
+            .field("storage", &self.storage)
+            .field("consensus_config", &self.consensus_config)
+            .field("mempool_limit", &self.mempool_limit)
+            .field("split_blocks", &self.split_blocks)
+            .field("block_size_limit", &self.block_size_limit)
+            .field("rollback_retention", &self.rollback_retention)
+            .field("check_state_hash", &self.check_state_hash)
+            .field("check_determinism", &self.check_determinism)
+            .field("panic_policy", &self.panic_policy)
+            .field("profiling", &self.profiling)
+            .field("capture_logs", &self.capture_logs)
+            .field("tx_factory", &self.tx_factory)
+            .field("seed", &self.key_generator.seed())
+            .field("from_saved", &self.saved.is_some())
+            .field("with_exported_state", &self.exported.is_some())
             .finish()
     }
 }
@@ -415,21 +645,86 @@ impl fmt::Debug for TestKitBuilder {
 impl TestKitBuilder {
     /// Creates testkit for the validator node.
     pub fn validator() -> Self {
-        let us = TestNode::new_validator(ValidatorId(0));
-        TestKitBuilder {
-            validators: vec![us.clone()],
-            services: Vec::new(),
-            us,
-        }
+        let mut key_generator = KeyGenerator::from_env();
+        let us = TestNode::generate(&mut key_generator, Some(ValidatorId(0)));
+        let validators = vec![us.clone()];
+        TestKitBuilder::new(us, validators, key_generator, None)
     }
 
     /// Creates testkit for the auditor node.
     pub fn auditor() -> Self {
-        let us = TestNode::new_auditor();
+        let mut key_generator = KeyGenerator::from_env();
+        let validator = TestNode::generate(&mut key_generator, Some(ValidatorId(0)));
+        let us = TestNode::generate(&mut key_generator, None);
+        TestKitBuilder::new(us, vec![validator], key_generator, None)
+    }
+
+    /// Creates testkit restoring the state saved with `TestKit::save()`, which is much faster
+    /// than re-creating the blocks. The network structure is taken from the saved state,
+    /// so it should not be changed with the builder. Services should be added to the builder
+    /// as usual; they should be the same as in the testkit, which state has been saved.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be read or parsed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate tempdir;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use tempdir::TempDir;
+    /// # fn main() {
+    /// # let dir = TempDir::new("exonum-testkit-doc").unwrap();
+    /// // In tests, the file is usually a fixture checked into the repository.
+    /// let path = dir.path().join("50_blocks.json");
+    /// if !path.exists() {
+    ///     let mut testkit = TestKitBuilder::validator().create();
+    ///     testkit.create_blocks_until(Height(50));
+    ///     testkit.save(&path);
+    /// }
+    /// let testkit = TestKitBuilder::from_saved(&path).create();
+    /// assert_eq!(testkit.height(), Height(50));
+    /// # }
+    /// ```
+    pub fn from_saved<P: AsRef<Path>>(path: P) -> Self {
+        let saved = SavedState::read(path.as_ref());
+        let us = TestNode::from(&saved.us);
+        let validators = saved.validators.iter().map(TestNode::from).collect();
+        TestKitBuilder::new(us, validators, KeyGenerator::from_env(), Some(saved))
+    }
+
+    fn new(
+        us: TestNode,
+        validators: Vec<TestNode>,
+        key_generator: KeyGenerator,
+        saved: Option<SavedState>,
+    ) -> Self {
         TestKitBuilder {
-            validators: vec![TestNode::new_validator(ValidatorId(0))],
-            services: Vec::new(),
             us,
+            validators,
+            services: Vec::new(),
+            service_factories: Vec::new(),
+            service_configs: BTreeMap::new(),
+            time_provider: Box::new(SystemTimeProvider),
+            storage: StorageKind::Memory,
+            consensus_config: ConsensusConfig::default(),
+            mempool_limit: None,
+            split_blocks: false,
+            block_size_limit: None,
+            rollback_retention: PoolRetention::Keep,
+            check_state_hash: false,
+            check_determinism: false,
+            panic_policy: PanicPolicy::Abort,
+            profiling: false,
+            capture_logs: false,
+            tx_factory: TxFactory::default(),
+            key_generator,
+            saved,
+            exported: None,
         }
     }
 
@@ -439,10 +734,110 @@ impl TestKitBuilder {
             validators_count > 0,
             "At least one validator should be present in the network."
         );
-        let additional_validators = (self.validators.len() as u16..validators_count)
-            .map(ValidatorId)
-            .map(TestNode::new_validator);
-        self.validators.extend(additional_validators);
+        for id in self.validators.len() as u16..validators_count {
+            let validator = TestNode::generate(&mut self.key_generator, Some(ValidatorId(id)));
+            self.validators.push(validator);
+        }
+        self
+    }
+
+    /// Makes keys generated by the testkit reproducible by deriving them from the given seed.
+    /// This concerns the keys of the nodes in the test network (including the nodes added
+    /// after this call), the keys returned by `TestKit::gen_keypair()`, and the keys
+    /// of validators added with `TestNetworkConfiguration::add_validator()`.
+    ///
+    /// The keys of the existing nodes are regenerated, so keys set
+    /// with `with_validator_keys()` before this call are overwritten.
+    ///
+    /// Without this call, the seed is taken from the `EXONUM_TESTKIT_SEED` environment
+    /// variable or chosen randomly. In any case, the seed is printed if the test panics
+    /// while the testkit is alive, so a failure can be reproduced by setting the variable.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_validators(2)
+    ///     .with_seed(42)
+    ///     .create();
+    /// let mut other_testkit = TestKitBuilder::validator()
+    ///     .with_seed(42)
+    ///     .with_validators(2)
+    ///     .create();
+    /// assert_eq!(testkit.network().validators(), other_testkit.network().validators());
+    /// assert_eq!(testkit.gen_keypair(), other_testkit.gen_keypair());
+    /// # }
+    /// ```
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.key_generator = KeyGenerator::seeded(seed);
+        let validators: Vec<_> = (0..self.validators.len() as u16)
+            .map(|id| TestNode::generate(&mut self.key_generator, Some(ValidatorId(id))))
+            .collect();
+        self.us = match self.us.validator_id() {
+            Some(id) => validators[id.0 as usize].clone(),
+            None => TestNode::generate(&mut self.key_generator, None),
+        };
+        self.validators = validators;
+        self
+    }
+
+    /// Sets the keys of validator nodes in the test network. Each item is a pair
+    /// of the consensus and service keypairs of a validator; the number of validators
+    /// is set to the number of items. If the testkit node is a validator, it uses the keys
+    /// of the first validator.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `keys` is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # extern crate exonum;
+    /// # use exonum::crypto::{gen_keypair_from_seed, Seed};
+    /// # use exonum::helpers::ValidatorId;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let keypair = |seed| gen_keypair_from_seed(&Seed::new([seed; 32]));
+    /// let testkit = TestKitBuilder::validator()
+    ///     .with_validator_keys(vec![(keypair(0), keypair(1)), (keypair(2), keypair(3))])
+    ///     .create();
+    /// assert_eq!(testkit.network().validators().len(), 2);
+    /// assert_eq!(testkit.network().us(), testkit.validator(ValidatorId(0)));
+    /// assert_eq!(*testkit.network().us().service_keypair().0, keypair(1).0);
+    /// # }
+    /// ```
+    pub fn with_validator_keys<I>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<
+            Item = (
+                (crypto::PublicKey, crypto::SecretKey),
+                (crypto::PublicKey, crypto::SecretKey),
+            ),
+        >,
+    {
+        let validators: Vec<_> = keys.into_iter()
+            .enumerate()
+            .map(|(id, (consensus_keypair, service_keypair))| {
+                TestNode::from_parts(
+                    consensus_keypair,
+                    service_keypair,
+                    Some(ValidatorId(id as u16)),
+                )
+            })
+            .collect();
+        assert!(
+            !validators.is_empty(),
+            "At least one validator should be present in the network."
+        );
+        if self.us.validator_id().is_some() {
+            self.us = validators[0].clone();
+        }
+        self.validators = validators;
         self
     }
 
@@ -455,429 +850,3522 @@ impl TestKitBuilder {
         self
     }
 
-    /// Creates the testkit.
-    pub fn create(self) -> TestKit {
-        crypto::init();
-        TestKit::assemble(
-            self.services,
-            TestNetwork {
-                us: self.us,
-                validators: self.validators,
-            },
-        )
-    }
-}
-
-/// Testkit for testing blockchain services. It offers simple network configuration emulation
-/// (with no real network setup).
-pub struct TestKit {
-    blockchain: Blockchain,
-    db_handler: CheckpointDbHandler<MemoryDB>,
-    events_stream: Spawn<Box<Stream<Item = (), Error = ()>>>,
-    network: TestNetwork,
-    api_sender: ApiSender,
-    mempool: TxPool,
-    cfg_proposal: Option<ConfigurationProposalState>,
-}
-
-impl fmt::Debug for TestKit {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        f.debug_struct("TestKit")
-            .field("blockchain", &self.blockchain)
-            .field("network", &self.network)
-            .field("mempool", &self.mempool)
-            .field("cfg_change_proposal", &self.cfg_proposal)
-            .finish()
+    /// Adds a service created by the given factory to the testkit. Unlike services added
+    /// with `with_service()`, such a service is re-created by `TestKit::restart()`, which
+    /// allows to test how the service restores its in-memory state after a node reboot.
+    pub fn with_service_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Box<Service> + Send + Sync + 'static,
+    {
+        self.service_factories.push(Arc::new(factory));
+        self
     }
-}
-
-impl TestKit {
-    fn assemble(services: Vec<Box<Service>>, network: TestNetwork) -> Self {
-        let api_channel = mpsc::channel(1_000);
-        let api_sender = ApiSender::new(api_channel.0.clone());
-
-        let db = CheckpointDb::new(MemoryDB::new());
-        let db_handler = db.handler();
-
-        let mut blockchain = Blockchain::new(
-            Box::new(db),
-            services,
-            *network.us().service_keypair().0,
-            network.us().service_keypair().1.clone(),
-            api_sender.clone(),
-        );
 
-        let genesis = network.genesis_config();
-        blockchain.create_genesis_block(genesis.clone()).unwrap();
-
-        let mempool = Arc::new(RwLock::new(BTreeMap::new()));
-        let event_stream: Box<Stream<Item = (), Error = ()>> = {
-            let blockchain = blockchain.clone();
-            let mempool = Arc::clone(&mempool);
-            Box::new(api_channel.1.greedy_fold((), move |_, event| {
-                let snapshot = blockchain.snapshot();
-                let schema = CoreSchema::new(&snapshot);
-                match event {
-                    ExternalMessage::Transaction(tx) => {
-                        let hash = tx.hash();
-                        if !schema.transactions().contains(&hash) {
-                            mempool
-                                .write()
-                                .expect("Cannot write transactions to mempool")
-                                .insert(tx.hash(), tx);
-                        }
-                    }
-                    ExternalMessage::PeerAdd(_) => { /* Ignored */ }
-                }
-            }))
-        };
-        let events_stream = executor::spawn(event_stream);
-
-        TestKit {
-            blockchain,
-            db_handler,
-            api_sender,
-            events_stream,
-            network,
-            mempool: Arc::clone(&mempool),
-            cfg_proposal: None,
-        }
+    /// Sets the configuration of the service with the given name recorded in the genesis block
+    /// instead of the configuration returned by `Service::initialize()`. The configuration
+    /// is available to the service as usual, e.g., in `ServiceContext::actual_configuration()`.
+    ///
+    /// The configuration is ignored if the testkit is restored with `from_saved()`.
+    ///
+    /// # Panics
+    ///
+    /// - `create()` panics if there is no service with the given name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # #[macro_use] extern crate serde_json;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::MyService;
+    /// # use serde_json::Value;
+    /// #
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .with_service_config("documentation", json!({ "initial_balance": 500 }))
+    ///     .create();
+    /// let config: Value = testkit.actual_configuration().services["documentation"].clone();
+    /// assert_eq!(config, json!({ "initial_balance": 500 }));
+    /// # }
+    /// ```
+    pub fn with_service_config<D>(mut self, name: &str, config: D) -> Self
+    where
+        D: Serialize,
+    {
+        let value = serde_json::to_value(config).unwrap();
+        self.service_configs.insert(name.into(), value);
+        self
     }
 
-    /// Creates a mounting point for public APIs used by the blockchain.
-    fn public_api_mount(&self) -> Mount {
-        self.blockchain.mount_public_api()
+    /// Sets the consensus configuration recorded in the genesis block, e.g., to change
+    /// the limit on the number of transactions in a block read by services.
+    /// The testkit does not run the consensus algorithm, so the configuration does not affect
+    /// how blocks are created by the testkit itself, unless block splitting is enabled
+    /// with `with_block_splitting()`.
+    ///
+    /// The configuration is ignored if the testkit is restored with `from_saved()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::ConsensusConfig;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let consensus_config = ConsensusConfig {
+    ///     txs_block_limit: 10,
+    ///     ..ConsensusConfig::default()
+    /// };
+    /// let testkit = TestKitBuilder::validator()
+    ///     .with_consensus_config(consensus_config.clone())
+    ///     .create();
+    /// assert_eq!(testkit.actual_configuration().consensus, consensus_config);
+    /// # }
+    /// ```
+    pub fn with_consensus_config(mut self, consensus_config: ConsensusConfig) -> Self {
+        self.consensus_config = consensus_config;
+        self
     }
 
-    /// Creates a mounting point for public APIs used by the blockchain.
-    fn private_api_mount(&self) -> Mount {
-        self.blockchain.mount_private_api()
+    /// Sets the source of time recorded in the precommits of created blocks.
+    /// By default, the system time is used.
+    pub fn with_time_provider<T>(mut self, time_provider: T) -> Self
+    where
+        T: TimeProvider + 'static,
+    {
+        self.time_provider = Box::new(time_provider);
+        self
     }
 
-    /// Creates an instance of `TestKitApi` to test the API provided by services.
-    pub fn api(&self) -> TestKitApi {
-        TestKitApi::new(self)
+    /// Stores the blockchain data in RocksDB in the given directory instead of the in-memory
+    /// storage, which is used by default. The directory is created if it does not exist;
+    /// it should not contain data of another blockchain. The directory is not removed
+    /// after the testkit is dropped.
+    ///
+    /// RocksDB storage is useful to reproduce issues related to the on-disk data layout
+    /// and iteration, and for load tests requiring realistic storage latencies.
+    ///
+    /// # Panics
+    ///
+    /// - `create()` panics if the database cannot be opened.
+    pub fn with_rocksdb<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.storage = StorageKind::RocksDb(Some(path.as_ref().to_owned()));
+        self
     }
 
-    /// Polls the *existing* events from the event loop until exhaustion. Does not wait
-    /// until new events arrive.
-    pub fn poll_events(&mut self) -> Option<Result<(), ()>> {
-        self.events_stream.wait_stream()
-    }
+    /// Bootstraps the testkit from the blockchain stored in RocksDB in the given directory,
+    /// e.g., a copy of the database of a real node, so that the testkit starts from
+    /// the latest block of the stored blockchain instead of the genesis block. If
+    /// the database is empty, the genesis block is created as usual.
+    ///
+    /// The database is opened read-only; changes made by the testkit are kept in memory.
+    /// The testkit cannot be rolled back past the latest stored block, and its state
+    /// cannot be saved with `TestKit::save()`.
+    ///
+    /// Blocks created by the testkit are signed by validators of the test network, which
+    /// do not necessarily correspond to the validators in the stored blockchain
+    /// configuration. Use `TestKit::commit_configuration_change()` to bring them in sync
+    /// if services depend on the validator keys.
+    ///
+    /// # Panics
+    ///
+    /// - `create()` panics if the database cannot be opened.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate tempdir;
+    /// # use exonum::crypto;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// # use tempdir::TempDir;
+    /// #
+    /// # fn main() {
+    /// # let dir = TempDir::new("exonum-testkit-doc").unwrap();
+    /// # {
+    /// #     let mut testkit = TestKitBuilder::validator().with_rocksdb(dir.path()).create();
+    /// #     testkit.create_blocks_until(Height(10));
+    /// # }
+    /// // In tests, the directory is usually a copy of the database of a real node.
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .with_preloaded_rocksdb(dir.path())
+    ///     .create();
+    /// assert_eq!(testkit.height(), Height(10));
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// testkit.create_block_with_transaction(MyTransaction::new(&pubkey, "foo", &key));
+    /// assert_eq!(testkit.height(), Height(11));
+    /// # }
+    /// ```
+    pub fn with_preloaded_rocksdb<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.storage = StorageKind::Preloaded(path.as_ref().to_owned());
+        self
+    }
+
+    /// Bootstraps the testkit from the blockchain exported with `TestKit::export()`, so that
+    /// the testkit starts from the latest exported block instead of the genesis block.
+    /// The testkit cannot be rolled back past the latest exported block.
+    ///
+    /// As with `with_preloaded_rocksdb()`, blocks created by the testkit are signed by
+    /// validators of the test network, which do not necessarily correspond to the validators
+    /// in the exported blockchain configuration.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be read or parsed.
+    pub fn with_exported_state<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.exported = Some(ExportedState::read(path.as_ref()));
+        self
+    }
+
+    /// Stores the blockchain data in RocksDB in a temporary directory, which is removed
+    /// after the testkit is dropped. See `with_rocksdb()` for details.
+    pub fn with_temp_rocksdb(mut self) -> Self {
+        self.storage = StorageKind::RocksDb(None);
+        self
+    }
+
+    /// Limits the number of transactions in the mempool. Once the limit is reached,
+    /// transactions received by the node (e.g., via API) are handled according to
+    /// the specified eviction policy. Hashes of dropped transactions can be retrieved with
+    /// `TestKit::evicted_transactions()`.
+    ///
+    /// Transactions explicitly passed to `create_block*()` methods are not subject to the limit.
+    pub fn with_mempool_limit(mut self, max_size: usize, policy: EvictionPolicy) -> Self {
+        self.mempool_limit = Some((max_size, policy));
+        self
+    }
+
+    /// Makes `TestKit::create_block()`, `create_block_with_transactions()` and related methods
+    /// split transactions across several blocks as a real node would: each block contains
+    /// at most `txs_block_limit` transactions according to the actual consensus configuration.
+    /// The transactions keep their order. By default, all transactions are put into one block.
+    ///
+    /// The returned `BlockResult` contains the results of transactions in all created blocks;
+    /// its `blocks()` method returns the number of created blocks.
+    ///
+    /// `create_block_with_proposer()`, `create_block_at_time()` and `create_block_fast()` always
+    /// create a single block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::{ConsensusConfig, Transaction};
+    /// # use exonum::crypto;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .with_consensus_config(ConsensusConfig {
+    ///         txs_block_limit: 10,
+    ///         ..ConsensusConfig::default()
+    ///     })
+    ///     .with_block_splitting()
+    ///     .create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let txs = (0..100).map(|i| {
+    ///     Box::new(MyTransaction::new(&pubkey, &i.to_string(), &key)) as Box<Transaction>
+    /// });
+    /// let result = testkit.create_block_with_transactions(txs);
+    /// assert_eq!(result.blocks(), 10);
+    /// assert_eq!(testkit.height(), Height(10));
+    /// # }
+    /// ```
+    pub fn with_block_splitting(mut self) -> Self {
+        self.split_blocks = true;
+        self
+    }
+
+    /// Sets the handling of the mempool by `TestKit::rollback()`, which is also used
+    /// by scenarios and the control API of `TestKit::serve_with_control()`. By default,
+    /// the mempool is kept intact (`PoolRetention::Keep`); with `PoolRetention::Restore`,
+    /// transactions from the rolled back blocks are put back into the mempool, so that
+    /// subsequent `create_block()` calls recommit them.
+    pub fn with_rollback_retention(mut self, retention: PoolRetention) -> Self {
+        self.rollback_retention = retention;
+        self
+    }
+
+    /// Enables block splitting (see `with_block_splitting()`) and additionally limits
+    /// the total size of transactions in a block, in bytes.
+    ///
+    /// # Panics
+    ///
+    /// - Block creation panics if a transaction is larger than the limit.
+    pub fn with_block_size_limit(mut self, max_bytes: usize) -> Self {
+        self.split_blocks = true;
+        self.block_size_limit = Some(max_bytes);
+        self
+    }
+
+    /// Enables the state hash check after each created block. The testkit panics if:
+    ///
+    /// - A service changes indexes, which names start with the service name followed
+    ///   by a dot (e.g., `cryptocurrency.wallets`), but none of the hashes returned
+    ///   by its `state_hash()` change in the block. This usually means that a new index
+    ///   has not been included into `state_hash()`.
+    /// - The state hash aggregator contains more tables of a service than the service
+    ///   returns from `state_hash()`, e.g., after an index has been removed from it.
+    /// - `Service::state_hash()` called once more for the committed blockchain state
+    ///   returns hashes that differ from the ones aggregated into the `state_hash`
+    ///   of the block, i.e., the implementation is non-deterministic or depends
+    ///   on uncommitted data.
+    ///
+    /// Services storing data intentionally excluded from the state hash in indexes
+    /// with the service prefix will fail the check.
+    pub fn with_state_hash_check(mut self) -> Self {
+        self.check_state_hash = true;
+        self
+    }
+
+    /// Enables the determinism check for created blocks. Each block is executed twice
+    /// on the same blockchain state, and the testkit panics if the executions produce
+    /// different storage changes or block hashes.
+    ///
+    /// The check is useful to catch transactions relying on randomness, the system time
+    /// or an unspecified iteration order of collections.
+    pub fn with_determinism_check(mut self) -> Self {
+        self.check_determinism = true;
+        self
+    }
+
+    /// Sets the policy of handling transactions, which panic during execution in created
+    /// blocks. By default, the testkit panics with a message describing the transaction
+    /// (`PanicPolicy::Abort`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::{ExecutionStatus, PanicPolicy, TestKitBuilder};
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .with_panic_policy(PanicPolicy::Record)
+    ///     .create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let tx_buggy = MyTransaction::new(&pubkey, "overflow", &key);
+    /// let result = testkit.create_block_with_transaction(tx_buggy.clone());
+    /// match *result.status(&tx_buggy.hash()).unwrap() {
+    ///     ExecutionStatus::Panicked(ref message) => assert!(message.contains("overflow")),
+    ///     ref status => panic!("Unexpected status: {:?}", status),
+    /// }
+    /// # }
+    /// ```
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Enables profiling of created blocks. The testkit records the wall time of executing
+    /// each transaction, computing state hashes of services and merging block changes
+    /// into the storage; the results are available via `TestKit::profile_report()`.
+    ///
+    /// Transactions are timed on a separate execution preceding the block creation,
+    /// and state hashes are timed by additional `Service::state_hash()` calls, so profiling
+    /// slows down the testkit.
+    pub fn with_profiling(mut self) -> Self {
+        self.profiling = true;
+        self
+    }
+
+    /// Enables capture of records emitted with the `log` crate during the creation of blocks.
+    /// Records emitted during the execution of transactions are attributed to
+    /// the transactions; other records (e.g., emitted by commit hooks) are attributed
+    /// to blocks only. See `TestKit::logs_for_tx()` and `TestKit::logs_for_block()`.
+    ///
+    /// The testkit installs its own logger for the process, and records emitted outside
+    /// of block creation are discarded. Only the `log` crate is supported; records emitted
+    /// with `slog` are not captured.
+    ///
+    /// # Panics
+    ///
+    /// - `create()` panics if another logger (e.g., `env_logger`) has been installed
+    ///   for the process before.
+    pub fn with_log_capture(mut self) -> Self {
+        self.capture_logs = true;
+        self
+    }
+
+    /// Sets the factory of transactions created with `TestKit::tx()`.
+    pub fn with_tx_factory(mut self, factory: TxFactory) -> Self {
+        self.tx_factory = factory;
+        self
+    }
+
+    /// Creates the testkit.
+    pub fn create(self) -> TestKit {
+        crypto::init();
+        TestKit::assemble(self)
+    }
+}
+
+/// Testkit for testing blockchain services. It offers simple network configuration emulation
+/// (with no real network setup).
+///
+/// The testkit and `TestKitApi` are `Send`, so blocks can be created by a background thread
+/// while the API is queried from the test thread. For this reason, hooks and factories passed
+/// to the testkit are required to be `Send`.
+pub struct TestKit {
+    blockchain: Blockchain,
+    services: Vec<Arc<Service>>,
+    // Factories of services, which are re-created on restart. Services created by them
+    // are the last ones in `services`.
+    service_factories: Vec<Arc<Fn() -> Box<Service> + Send + Sync>>,
+    db_handler: CheckpointDbHandler<StorageBackend>,
+    // Lengths of the database journal after committing each block, indexed by block height.
+    checkpoints: Vec<usize>,
+    // Proposes of the blocks created by the testkit, indexed by block height.
+    proposes: BTreeMap<Height, Propose>,
+    // Tampering of the precommits of the next created block.
+    proof_tampering: Option<ProofTampering>,
+    events_stream: Spawn<Box<Stream<Item = (), Error = ()> + Send>>,
+    network: TestNetwork,
+    api_sender: ApiSender,
+    mempool: TxPool,
+    // Metrics exposed when serving the testkit over HTTP.
+    metrics: Arc<Metrics>,
+    events: EventBus,
+    cfg_proposal: Option<ConfigurationProposalState>,
+    time_provider: Arc<TimeProvider>,
+    mempool_limiter: Arc<RwLock<MempoolLimiter>>,
+    // Transactions added to the mempool while committing the latest block.
+    broadcast_txs: Vec<RawMessage>,
+    pool_ordering: PoolOrdering,
+    split_blocks: bool,
+    block_size_limit: Option<usize>,
+    rollback_retention: PoolRetention,
+    sent_requests: Arc<RwLock<Vec<SentRequest>>>,
+    check_state_hash: bool,
+    check_determinism: bool,
+    panic_policy: PanicPolicy,
+    // Execution profile of created blocks, if profiling is enabled.
+    profile: Option<ProfileReport>,
+    // Log records captured during block creation, if log capture is enabled.
+    logs: Option<Vec<LogRecord>>,
+    tx_factory: TxFactory,
+    // Accounts created with `account()`, indexed by names.
+    accounts: BTreeMap<String, Account>,
+    key_generator: KeyGenerator,
+    // Validators marked as offline with `skip_leader()` together with the heights they are
+    // offline at.
+    skipped_leaders: Vec<(Height, ValidatorId)>,
+    // Number of empty rounds requested with `skip_rounds()` together with the height
+    // they are skipped at.
+    skipped_rounds: (Height, u32),
+    before_commit_hooks: Vec<Box<FnMut(&Block, &Fork) + Send>>,
+    after_commit_hooks: Vec<Box<FnMut(&Block, &Snapshot) + Send>>,
+    routing_hooks: Vec<Box<FnMut(&Block, &Snapshot) -> Vec<Box<Transaction>> + Send>>,
+    activation_hooks: Vec<Box<FnMut(&Block, &StoredConfiguration) + Send>>,
+    invariants: Vec<(String, Arc<Fn(&Snapshot) -> bool + Send + Sync>)>,
+}
+
+impl fmt::Debug for TestKit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TestKit")
+            .field("blockchain", &self.blockchain)
+            .field("network", &self.network)
+            .field("mempool", &self.mempool)
+            .field("cfg_change_proposal", &self.cfg_proposal)
+            .field("time_provider", &self.time_provider)
+            .field("seed", &self.key_generator.seed())
+            .finish()
+    }
+}
+
+impl Drop for TestKit {
+    fn drop(&mut self) {
+        if let Some(seed) = self.key_generator.seed() {
+            if !thread::panicking() {
+                return;
+            }
+            if self.key_generator.is_explicit() {
+                eprintln!("Testkit panicked with seed {}", seed);
+            } else {
+                eprintln!(
+                    "Testkit panicked with seed {}; set {}={} to reproduce",
+                    seed,
+                    keys::SEED_VAR,
+                    seed
+                );
+            }
+        }
+    }
+}
+
+impl TestKit {
+    fn assemble(builder: TestKitBuilder) -> Self {
+        let TestKitBuilder {
+            us,
+            validators,
+            mut services,
+            service_factories,
+            service_configs,
+            time_provider,
+            storage,
+            consensus_config,
+            mempool_limit,
+            split_blocks,
+            block_size_limit,
+            rollback_retention,
+            check_state_hash,
+            check_determinism,
+            panic_policy,
+            profiling,
+            capture_logs,
+            tx_factory,
+            key_generator,
+            saved,
+            exported,
+        } = builder;
+        let network = TestNetwork { us, validators };
+        if capture_logs {
+            assert!(
+                logs::install_logger(),
+                "Cannot capture logs: another logger has been installed for the process"
+            );
+        }
+
+        services.extend(service_factories.iter().map(|factory| factory()));
+        let services: Vec<Arc<Service>> = services.into_iter().map(Arc::from).collect();
+
+        let api_channel = mpsc::channel(1_000);
+        let api_sender = ApiSender::new(api_channel.0.clone());
+
+        let mut db = match saved {
+            Some(ref saved) => {
+                let mut db = storage.open();
+                db.merge(saved.storage()).expect(
+                    "Cannot restore the saved storage",
+                );
+                CheckpointDb::from_parts(db, saved.journal())
+            }
+            None => CheckpointDb::new(storage.open()),
+        };
+        if let Some(ref exported) = exported {
+            db.merge(exported.storage()).expect(
+                "Cannot load the exported state",
+            );
+        }
+        let db_handler = db.handler();
+
+        let mut blockchain = Blockchain::new(
+            Box::new(db),
+            SharedService::wrap_with_configs(&services, service_configs),
+            *network.us().service_keypair().0,
+            network.us().service_keypair().1.clone(),
+            api_sender.clone(),
+        );
+
+        let checkpoints = match saved {
+            Some(ref saved) => saved.checkpoints.clone(),
+            None => {
+                let bootstrapped = (exported.is_some() || db_handler.inner().is_preloaded()) && {
+                    let snapshot = blockchain.snapshot();
+                    CoreSchema::new(&snapshot).block_hashes_by_height().len() > 0
+                };
+                if !bootstrapped {
+                    let genesis = GenesisConfig::new_with_consensus(
+                        consensus_config,
+                        network.validators().iter().map(TestNode::public_keys),
+                    );
+                    blockchain.create_genesis_block(genesis).unwrap();
+                }
+                vec![db_handler.journal_len()]
+            }
+        };
+
+        let mempool = Arc::new(RwLock::new(BTreeMap::new()));
+        let mempool_limiter = Arc::new(RwLock::new(MempoolLimiter::new(mempool_limit)));
+        if let Some(ref saved) = saved {
+            let mut mempool = mempool.write().expect(
+                "Cannot write transactions to mempool",
+            );
+            let mut limiter = mempool_limiter.write().expect(
+                "Cannot acquire write lock on mempool limiter",
+            );
+            for raw in saved.mempool() {
+                let tx = blockchain.tx_from_raw(raw).expect(
+                    "Cannot parse transaction from the saved mempool",
+                );
+                limiter.insert(&mut mempool, tx);
+            }
+        }
+        let sent_requests = Arc::new(RwLock::new(Vec::new()));
+        let events = EventBus::default();
+        let events_stream = TestKit::spawn_events_stream(
+            api_channel.1,
+            &blockchain,
+            &mempool,
+            &mempool_limiter,
+            &sent_requests,
+            &events,
+        );
+
+        TestKit {
+            blockchain,
+            db_handler,
+            checkpoints,
+            proposes: BTreeMap::new(),
+            proof_tampering: None,
+            api_sender,
+            events_stream,
+            network,
+            mempool: Arc::clone(&mempool),
+            metrics: Arc::default(),
+            events,
+            cfg_proposal: None,
+            time_provider: Arc::from(time_provider),
+            mempool_limiter,
+            broadcast_txs: Vec::new(),
+            pool_ordering: PoolOrdering::default(),
+            split_blocks,
+            block_size_limit,
+            rollback_retention,
+            sent_requests,
+            check_state_hash,
+            check_determinism,
+            panic_policy,
+            profile: if profiling {
+                Some(ProfileReport::default())
+            } else {
+                None
+            },
+            logs: if capture_logs { Some(Vec::new()) } else { None },
+            tx_factory,
+            accounts: BTreeMap::new(),
+            key_generator,
+            skipped_leaders: Vec::new(),
+            skipped_rounds: (Height::zero(), 0),
+            before_commit_hooks: Vec::new(),
+            after_commit_hooks: Vec::new(),
+            routing_hooks: Vec::new(),
+            activation_hooks: Vec::new(),
+            invariants: Vec::new(),
+            services,
+            service_factories,
+        }
+    }
+
+    /// Spawns the stream processing requests sent to the node through `ApiSender`.
+    fn spawn_events_stream(
+        receiver: mpsc::Receiver<ExternalMessage>,
+        blockchain: &Blockchain,
+        mempool: &TxPool,
+        mempool_limiter: &Arc<RwLock<MempoolLimiter>>,
+        sent_requests: &Arc<RwLock<Vec<SentRequest>>>,
+        events: &EventBus,
+    ) -> Spawn<Box<Stream<Item = (), Error = ()> + Send>> {
+        let blockchain = blockchain.clone();
+        let mempool = Arc::clone(mempool);
+        let mempool_limiter = Arc::clone(mempool_limiter);
+        let sent_requests = Arc::clone(sent_requests);
+        let events = events.clone();
+        let stream: Box<Stream<Item = (), Error = ()> + Send> =
+            Box::new(receiver.greedy_fold((), move |_, event| {
+                sent_requests
+                    .write()
+                    .expect("Cannot write sent requests")
+                    .push(SentRequest::from_message(&event));
+
+                let snapshot = blockchain.snapshot();
+                let schema = CoreSchema::new(&snapshot);
+                match event {
+                    ExternalMessage::Transaction(tx) => {
+                        let hash = tx.hash();
+                        if !schema.transactions().contains(&hash) {
+                            let mut mempool = mempool.write().expect(
+                                "Cannot write transactions to mempool",
+                            );
+                            mempool_limiter
+                                .write()
+                                .expect("Cannot acquire write lock on mempool limiter")
+                                .insert(&mut mempool, tx);
+                            if mempool.contains_key(&hash) {
+                                events.emit(TestKitEvent::TransactionAdded { hash });
+                            }
+                        }
+                    }
+                    ExternalMessage::PeerAdd(_) => { /* Ignored */ }
+                }
+            }));
+        executor::spawn(stream)
+    }
+
+    /// Creates a mounting point for public APIs used by the blockchain.
+    fn public_api_mount(&self) -> Mount {
+        self.blockchain.mount_public_api()
+    }
+
+    /// Creates a mounting point for public APIs used by the blockchain.
+    fn private_api_mount(&self) -> Mount {
+        self.blockchain.mount_private_api()
+    }
+
+    /// Creates an instance of `TestKitApi` to test the API provided by services.
+    pub fn api(&self) -> TestKitApi {
+        TestKitApi::new(self)
+    }
+
+    /// Creates a builder of `TestKitApi`, which allows to wrap public service endpoints
+    /// with Iron middleware.
+    pub fn api_builder(&self) -> TestKitApiBuilder {
+        TestKitApiBuilder::new(self)
+    }
+
+    /// Serves public and private APIs of the testkit over HTTP on the given addresses,
+    /// e.g., to try out a service with a browser or an external HTTP client.
+    /// Port 0 can be used to let the OS assign a free port.
+    ///
+    /// Requests are processed in background threads. Transactions received by the server
+    /// get to the mempool and are committed by `create_block*()` methods
+    /// as usual, so the test is responsible for creating blocks while the server is running.
+    ///
+    /// The public API additionally exposes the `/metrics` endpoint in the Prometheus text
+    /// format with the blockchain height, the mempool size, request counts per endpoint
+    /// and the total time of executing transactions of created blocks.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the server cannot listen on any of the addresses.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate exonum_testkit;
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// let server = testkit.serve(
+    ///     "127.0.0.1:8080".parse().unwrap(),
+    ///     "127.0.0.1:8081".parse().unwrap(),
+    /// );
+    /// for _ in 0..60 {
+    ///     thread::sleep(Duration::from_secs(1));
+    ///     testkit.create_block();
+    /// }
+    /// server.shutdown();
+    /// # }
+    /// ```
+    pub fn serve(&self, public_addr: SocketAddr, private_addr: SocketAddr) -> TestServer {
+        let (public_mount, private_mount) = self.api().into_mounts();
+        let (public_mount, private_mount) = metrics::instrument(self, public_mount, private_mount);
+        TestServer::new(public_mount, public_addr, private_mount, private_addr)
+    }
+
+    /// Serves public and private APIs of the testkit over HTTPS on the given addresses.
+    /// The server uses the certificate and the private key from the PKCS #12 archive
+    /// at `identity`, which is protected with `password`.
+    ///
+    /// The method is available only with the `tls` feature enabled.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the identity cannot be loaded from the archive.
+    /// - Panics if the server cannot listen on any of the addresses.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate exonum_testkit;
+    /// # use std::path::Path;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator().create();
+    /// // The archive can be created from PEM files with
+    /// // `openssl pkcs12 -export -in cert.pem -inkey key.pem -out identity.p12`.
+    /// let server = testkit.serve_tls(
+    ///     "127.0.0.1:8443".parse().unwrap(),
+    ///     "127.0.0.1:8444".parse().unwrap(),
+    ///     Path::new("identity.p12"),
+    ///     "password",
+    /// );
+    /// server.shutdown();
+    /// # }
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn serve_tls(
+        &self,
+        public_addr: SocketAddr,
+        private_addr: SocketAddr,
+        identity: &Path,
+        password: &str,
+    ) -> TestServer {
+        let (public_mount, private_mount) = self.api().into_mounts();
+        let (public_mount, private_mount) = metrics::instrument(self, public_mount, private_mount);
+        TestServer::new_tls(
+            public_mount,
+            public_addr,
+            private_mount,
+            private_addr,
+            identity,
+            password,
+        )
+    }
+
+    /// Serves APIs of the testkit over HTTP like `serve()`, and additionally exposes
+    /// the control API on the private address, which allows to drive the testkit
+    /// from an external process (e.g., an end-to-end test suite written in JavaScript).
+    /// The control API has the following endpoints:
+    ///
+    /// - `GET api/testkit/v1/status` returns the current blockchain height
+    /// - `POST api/testkit/v1/blocks` creates a block with all transactions in the mempool
+    /// - `POST api/testkit/v1/rollback` with the body `{ "blocks": <number> }` rolls back
+    ///   the given number of blocks
+    /// - `POST api/testkit/v1/shutdown` shuts the server down
+    ///
+    /// All endpoints respond with `{ "height": <number> }` containing the blockchain height
+    /// after the request has been processed.
+    ///
+    /// The method blocks the current thread until the shutdown request is received.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the server cannot listen on any of the addresses.
+    pub fn serve_with_control(&mut self, public_addr: SocketAddr, private_addr: SocketAddr) {
+        server::serve_with_control(self, public_addr, private_addr);
+    }
+
+    /// Polls the *existing* events from the event loop until exhaustion. Does not wait
+    /// until new events arrive.
+    pub fn poll_events(&mut self) -> Option<Result<(), ()>> {
+        self.events_stream.wait_stream()
+    }
+
+    /// Subscribes to the events of the testkit: transactions added to the mempool, committed
+    /// blocks and rollbacks. The returned receiver gets all events happening after this call
+    /// in the order they happen; transactions sent through the API are reported when
+    /// the testkit processes them, e.g., on `poll_events()` or block creation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::helpers::Height;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::{TestKitBuilder, TestKitEvent};
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let tx = MyTransaction::new(&pubkey, "foo", &key);
+    /// let events = testkit.events();
+    /// testkit.create_block_with_transaction(tx.clone());
+    /// let committed: Vec<_> = events
+    ///     .try_iter()
+    ///     .filter_map(|event| match event {
+    ///         TestKitEvent::BlockCommitted { height, tx_hashes, .. } => Some((height, tx_hashes)),
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    /// assert_eq!(committed, vec![(Height(1), vec![tx.hash()])]);
+    /// # }
+    /// ```
+    pub fn events(&self) -> std_mpsc::Receiver<TestKitEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns a snapshot of the current blockchain state.
+    pub fn snapshot(&self) -> Box<Snapshot> {
+        self.blockchain.snapshot()
+    }
+
+    /// Returns an explorer of the blocks and transactions committed to the blockchain.
+    /// The explorer operates on the current state of the blockchain; blocks created
+    /// afterwards are not visible to it.
+    pub fn explorer(&self) -> TestKitExplorer {
+        TestKitExplorer::new(&self.blockchain)
+    }
+
+    /// Returns the header of the committed block at the given height together with
+    /// the propose and precommits generated for the block, or `None` if there is no such block.
+    /// This allows to test light clients and auditing code verifying blocks against
+    /// the validator set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    /// testkit.create_blocks_until(Height(3));
+    /// let proof = testkit.block_proof(Height(2)).unwrap();
+    /// assert!(proof.verify(&testkit.actual_configuration().validator_keys));
+    /// # }
+    /// ```
+    pub fn block_proof(&self, height: Height) -> Option<BlockProof> {
+        let block = match self.explorer().block(height) {
+            Some(block) => block,
+            None => return None,
+        };
+        let precommits: Vec<Precommit> = {
+            let snapshot = self.snapshot();
+            let schema = CoreSchema::new(&snapshot);
+            let precommits = schema.precommits(&block.hash());
+            precommits.iter().collect()
+        };
+        // The stored propose may belong to a block that has been rolled back and replaced
+        // by a block created without the consensus emulation.
+        let propose = self.proposes.get(&height).and_then(|propose| {
+            let matches = precommits.first().map_or(false, |precommit| {
+                precommit.propose_hash() == &propose.hash()
+            });
+            if matches { Some(propose.clone()) } else { None }
+        });
+        Some(BlockProof {
+            block,
+            propose,
+            precommits,
+        })
+    }
+
+    /// Returns a copy of the proof of the committed block at the given height
+    /// (see `block_proof()`) with the precommits tampered in the given way, or `None`
+    /// if there is no such block. The blockchain is not modified.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the tampering refers to a validator, which precommit is absent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::{Height, ValidatorId};
+    /// # use exonum_testkit::{ProofTampering, TestKitBuilder};
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    /// testkit.create_block();
+    /// let proof = testkit
+    ///     .tampered_block_proof(Height(1), ProofTampering::InvalidSignature(ValidatorId(2)))
+    ///     .unwrap();
+    /// assert!(!proof.verify(&testkit.actual_configuration().validator_keys));
+    /// # }
+    /// ```
+    pub fn tampered_block_proof(
+        &self,
+        height: Height,
+        tampering: ProofTampering,
+    ) -> Option<BlockProof> {
+        self.block_proof(height).map(|mut proof| {
+            proof.precommits = tampering.apply(proof.precommits, self.network().validators());
+            proof
+        })
+    }
+
+    /// Returns a proof of the value corresponding to `key` in the service `ProofMapIndex`
+    /// named `index_name`. The proof is rooted in the `state_hash` of the latest committed block.
+    ///
+    /// `service_id` and `table_idx` identify the root hash of the index among the hashes
+    /// returned by `Service::state_hash()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::{Service, Transaction};
+    /// # use exonum::crypto::{self, Hash, PublicKey};
+    /// # use exonum::encoding;
+    /// # use exonum::messages::RawTransaction;
+    /// # use exonum::storage::{ProofMapIndex, Snapshot};
+    /// # use exonum_testkit::TestKitBuilder;
+    /// #
+    /// # const SERVICE_ID: u16 = 1;
+    /// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+    /// #
+    /// # struct WalletService;
+    /// #
+    /// # impl Service for WalletService {
+    /// #     fn service_name(&self) -> &'static str {
+    /// #         "wallets"
+    /// #     }
+    /// #
+    /// #     fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+    /// #         let balances: ProofMapIndex<_, PublicKey, u64> =
+    /// #             ProofMapIndex::new("wallets.balances", snapshot);
+    /// #         vec![balances.root_hash()]
+    /// #     }
+    /// #
+    /// #     fn service_id(&self) -> u16 {
+    /// #         SERVICE_ID
+    /// #     }
+    /// #
+    /// #     fn tx_from_raw(&self, raw: RawTransaction) -> FromRawResult {
+    /// #         Err(encoding::Error::IncorrectMessageType { message_type: raw.message_type() })
+    /// #     }
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(WalletService)
+    ///     .create();
+    /// testkit.create_block();
+    ///
+    /// let (alice, _) = crypto::gen_keypair();
+    /// let proof = testkit.map_proof::<PublicKey, u64>(
+    ///     SERVICE_ID,
+    ///     0, // Index of the balances table in `state_hash()`
+    ///     "wallets.balances",
+    ///     alice,
+    /// );
+    /// // The proof asserts that Alice has no balance.
+    /// assert_eq!(proof.assert_valid(), None);
+    /// # }
+    /// ```
+    pub fn map_proof<K, V>(
+        &self,
+        service_id: u16,
+        table_idx: usize,
+        index_name: &str,
+        key: K,
+    ) -> MapStateProof<K, V>
+    where
+        K: ProofMapKey,
+        V: StorageValue,
+    {
+        let snapshot = self.snapshot();
+        MapStateProof::new(&*snapshot, service_id, table_idx, index_name, key)
+    }
+
+    /// Returns a proof of the element with the given `index` in the service `ProofListIndex`
+    /// named `index_name`. The proof is rooted in the `state_hash` of the latest committed block.
+    ///
+    /// `service_id` and `table_idx` identify the root hash of the index among the hashes
+    /// returned by `Service::state_hash()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `index` is out of range of the list.
+    pub fn list_proof<V>(
+        &self,
+        service_id: u16,
+        table_idx: usize,
+        index_name: &str,
+        index: u64,
+    ) -> ListStateProof<V>
+    where
+        V: StorageValue,
+    {
+        let snapshot = self.snapshot();
+        ListStateProof::new(&*snapshot, service_id, table_idx, index_name, index)
+    }
+
+    /// Returns a blockchain instance for low level manipulations with storage.
+    pub fn blockchain_mut(&mut self) -> &mut Blockchain {
+        &mut self.blockchain
+    }
+
+    /// Rolls the blockchain back for a certain number of blocks. The mempool is handled
+    /// according to the policy set with `TestKitBuilder::with_rollback_retention()`;
+    /// by default, it is kept intact. With a policy other than `PoolRetention::Keep`,
+    /// the method behaves as `rollback_blocks()` and polls events before rolling back.
+    ///
+    /// # Examples
+    ///
+    /// Rollbacks are useful in testing alternative scenarios (e.g., transactions executed
+    /// in different order and/or in different blocks) that require an expensive setup:
+    ///
+    /// ```
+    /// # #[macro_use] extern crate exonum;
+    /// # #[macro_use] extern crate exonum_testkit;
+    /// # use exonum::blockchain::{Service, Transaction};
+    /// # use exonum::messages::RawTransaction;
+    /// # use exonum::encoding;
+    /// # use exonum_testkit::{TestKit, TestKitBuilder};
+    /// #
+    /// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+    /// # pub struct MyService;
+    /// # impl Service for MyService {
+    /// #    fn service_name(&self) -> &'static str {
+    /// #        "documentation"
+    /// #    }
+    /// #    fn state_hash(&self, _: &exonum::storage::Snapshot) -> Vec<exonum::crypto::Hash> {
+    /// #        Vec::new()
+    /// #    }
+    /// #    fn service_id(&self) -> u16 {
+    /// #        0
+    /// #    }
+    /// #    fn tx_from_raw(&self, _raw: RawTransaction) -> FromRawResult {
+    /// #        unimplemented!();
+    /// #    }
+    /// # }
+    /// #
+    /// # message! {
+    /// #     struct MyTransaction {
+    /// #         const TYPE = 0;
+    /// #         const ID = 0;
+    /// #         from: &exonum::crypto::PublicKey,
+    /// #         msg: &str,
+    /// #     }
+    /// # }
+    /// # impl Transaction for MyTransaction {
+    /// #     fn verify(&self) -> bool { true }
+    /// #     fn execute(&self, _: &mut exonum::storage::Fork) {}
+    /// # }
+    /// #
+    /// # fn expensive_setup(_: &mut TestKit) {}
+    /// # fn assert_something_about(_: &TestKit) {}
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .create();
+    /// expensive_setup(&mut testkit);
+    /// let (pubkey, key) = exonum::crypto::gen_keypair();
+    /// let tx_a = MyTransaction::new(&pubkey, "foo", &key);
+    /// let tx_b = MyTransaction::new(&pubkey, "bar", &key);
+    /// testkit.create_block_with_transactions(txvec![tx_a.clone(), tx_b.clone()]);
+    /// assert_something_about(&testkit);
+    /// testkit.rollback(1);
+    /// testkit.create_block_with_transactions(txvec![tx_a.clone()]);
+    /// testkit.create_block_with_transactions(txvec![tx_b.clone()]);
+    /// assert_something_about(&testkit);
+    /// testkit.rollback(2);
+    /// # }
+    /// ```
+    pub fn rollback(&mut self, blocks: usize) {
+        match self.rollback_retention {
+            PoolRetention::Keep => self.rollback_storage(blocks),
+            retention => self.rollback_blocks(blocks, retention),
+        }
+    }
+
+    /// Rolls the storage back for a certain number of blocks without touching the mempool.
+    fn rollback_storage(&mut self, blocks: usize) {
+        assert!(
+            (blocks as u64) <= self.height().0,
+            "Cannot rollback past genesis block"
+        );
+        assert!(
+            blocks < self.checkpoints.len(),
+            "Cannot rollback past block {}, from which the testkit has been bootstrapped",
+            self.initial_height().0
+        );
+        let new_len = self.checkpoints.len() - blocks;
+        self.checkpoints.truncate(new_len);
+        let checkpoint = *self.checkpoints.last().unwrap();
+        let journal_len = self.db_handler.journal_len();
+        self.db_handler.rollback(journal_len - checkpoint);
+
+        let height = self.height();
+        self.proposes.split_off(&height.next());
+        if let Some(ref mut logs) = self.logs {
+            logs.retain(|record| record.height <= height);
+        }
+        self.events.emit(TestKitEvent::RolledBack { height });
+    }
+
+    /// Rolls the blockchain back for a certain number of blocks, handling the mempool
+    /// according to the given policy rather than the one set with
+    /// `TestKitBuilder::with_rollback_retention()`. With `PoolRetention::Keep`, transactions
+    /// pending before the rollback remain in the mempool, so they can be retried on top
+    /// of a different chain prefix. Unlike `rollback()`, the method polls events before rolling
+    /// back, so that transactions sent through the API are handled according to the policy
+    /// as well.
+    ///
+    /// # Panics
+    ///
+    /// - Panics in the same cases as `rollback()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # #[macro_use] extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::{PoolRetention, TestKitBuilder};
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let pending_tx = MyTransaction::new(&pubkey, "pending", &key);
+    /// let setup_a = MyTransaction::new(&pubkey, "setup A", &key);
+    /// let setup_b = MyTransaction::new(&pubkey, "setup B", &key);
+    /// testkit.api().send(pending_tx.clone());
+    /// testkit.create_block_with_transactions(txvec![setup_a.clone()]);
+    /// assert!(testkit.mempool().contains_key(&pending_tx.hash()));
+    /// // Retry the pending transaction on top of a different prefix.
+    /// testkit.rollback_blocks(1, PoolRetention::Keep);
+    /// testkit.create_block_with_transactions(txvec![setup_b.clone()]);
+    /// testkit.create_block();
+    /// assert!(!testkit.mempool().contains_key(&pending_tx.hash()));
+    /// # }
+    /// ```
+    pub fn rollback_blocks(&mut self, blocks: usize, retention: PoolRetention) {
+        self.poll_events();
+
+        let rolled_back: Vec<RawMessage> = if retention == PoolRetention::Restore {
+            let height = self.height().0;
+            let snapshot = self.snapshot();
+            let schema = CoreSchema::new(&snapshot);
+            (height.saturating_sub(blocks as u64) + 1..height + 1)
+                .flat_map(|height| schema.block_txs(Height(height)).iter().collect::<Vec<_>>())
+                .map(|tx_hash| {
+                    schema.transactions().get(&tx_hash).expect(
+                        "Cannot find transaction in the blockchain",
+                    )
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.rollback_storage(blocks);
+
+        match retention {
+            PoolRetention::Keep => {}
+            PoolRetention::Restore => self.restore_to_mempool(rolled_back),
+            PoolRetention::Clear => {
+                self.mempool
+                    .write()
+                    .expect("Cannot write transactions to mempool")
+                    .clear();
+            }
+        }
+    }
+
+    /// Detaches the blocks above the given height from the blockchain and returns them
+    /// as a `Branch`. The blockchain is rolled back to the block at the given height, which
+    /// becomes the common ancestor for a competing branch built afterwards. Transactions
+    /// from the detached blocks are not returned to the mempool.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `ancestor` is greater than the current blockchain height.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.create_blocks_until(Height(5));
+    /// let first_branch = testkit.detach_branch(Height(3));
+    /// assert_eq!(testkit.height(), Height(3));
+    ///
+    /// // Build a competing branch.
+    /// testkit.create_block();
+    /// // Switch to the longer branch.
+    /// let second_branch = testkit.switch_to_branch(first_branch);
+    /// assert_eq!(testkit.height(), Height(5));
+    /// assert_eq!(second_branch.height(), Height(4));
+    /// # }
+    /// ```
+    pub fn detach_branch(&mut self, ancestor: Height) -> Branch {
+        let height = self.height();
+        assert!(
+            ancestor <= height,
+            "Cannot detach branch from height {}; the blockchain height is {}",
+            ancestor.0,
+            height.0
+        );
+
+        let explorer = self.explorer();
+        let ancestor_hash = explorer.block(ancestor).unwrap().hash();
+        let blocks = (ancestor.0 + 1..height.0 + 1)
+            .map(|height| explorer.block_with_txs(Height(height)).unwrap())
+            .collect();
+        self.rollback_storage((height.0 - ancestor.0) as usize);
+        Branch::new(ancestor, ancestor_hash, blocks)
+    }
+
+    /// Switches the blockchain to the given branch. Blocks committed by Exonum consensus
+    /// are final, so a node never switches branches; the method is a testing tool allowing
+    /// to check code that processes the blockchain state against several alternative histories.
+    /// The blocks above the ancestor block of the branch are rolled back, and the blocks
+    /// of the branch are re-executed with the same transactions, proposers and precommit times,
+    /// so that `handle_commit()` hooks of services and commit hooks of the testkit are invoked
+    /// for every reapplied block. Transactions from the rolled back blocks, which are not
+    /// included into the branch, are returned to the mempool, subject to the mempool size
+    /// limit set with `TestKitBuilder::with_mempool_limit()`.
+    ///
+    /// Returns the branch consisting of the rolled back blocks, which can be used to switch
+    /// the blockchain back.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the ancestor block of the branch is absent in the blockchain.
+    /// - Panics if re-execution of a block of the branch leads to a different block.
+    pub fn switch_to_branch(&mut self, branch: Branch) -> Branch {
+        let ancestor_hash = self.explorer().block(branch.ancestor()).map(|block| block.hash());
+        assert_eq!(
+            ancestor_hash.as_ref(),
+            Some(branch.ancestor_hash()),
+            "The ancestor block of the branch is absent in the blockchain"
+        );
+
+        let detached = self.detach_branch(branch.ancestor());
+        for block in branch.into_blocks() {
+            let height = block.height();
+            assert!(
+                self.reapply_block(block),
+                "Re-execution of block {} has led to a different block",
+                height.0
+            );
+        }
+
+        let transactions: Vec<_> = detached
+            .blocks()
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .map(|tx| tx.raw().clone())
+            .collect();
+        self.restore_to_mempool(transactions);
+        detached
+    }
+
+    /// Returns transactions from rolled back blocks to the mempool, respecting the mempool
+    /// size limit. Transactions committed to the blockchain are skipped.
+    fn restore_to_mempool(&self, transactions: Vec<RawMessage>) {
+        let snapshot = self.snapshot();
+        let schema = CoreSchema::new(&snapshot);
+        let mut mempool = self.mempool.write().expect(
+            "Cannot write transactions to mempool",
+        );
+        let mut limiter = self.mempool_limiter.write().expect(
+            "Cannot acquire write lock on mempool limiter",
+        );
+        for raw in transactions {
+            let tx = self.blockchain.tx_from_raw(raw).expect(
+                "Cannot parse committed transaction",
+            );
+            if !schema.transactions().contains(&tx.hash()) {
+                limiter.insert(&mut mempool, tx);
+            }
+        }
+    }
+
+    /// Re-executes a block committed on another node or on another branch of the blockchain
+    /// with the same transactions, proposer, round and precommit time. Returns `true` if
+    /// the resulting block is the same as the original one.
+    fn reapply_block(&mut self, block: BlockWithTransactions) -> bool {
+        let BlockWithTransactions {
+            header,
+            transactions,
+            precommits,
+        } = block;
+        let time = precommits.first().map(|precommit| precommit.time());
+        let round = precommits.first().map_or(Round::first(), |precommit| {
+            precommit.round()
+        });
+
+        let tx_hashes = self.add_to_mempool(transactions);
+        self.do_create_block(Some((header.proposer_id(), round)), time, &tx_hashes);
+        self.last_block_hash() == header.hash()
+    }
+
+    /// Creates an independent copy of the testkit, which shares the blockchain history,
+    /// the mempool and the network configuration with this testkit at the moment of the call,
+    /// but diverges from it afterwards. This allows to run several scenarios starting from
+    /// the same state and compare the outcomes.
+    ///
+    /// The copy has its own service instances created by the factories passed to
+    /// `TestKitBuilder::with_service_factory()`, and its own time provider
+    /// (see `TimeProvider::fork()`), so that the copy and the original testkit do not
+    /// affect each other. If the testkit uses RocksDB, the copy is stored in a temporary
+    /// directory. Commit hooks registered with `on_before_commit()` and `on_after_commit()`
+    /// are not copied; invariants registered with `add_invariant()` are.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the testkit has services added with `TestKitBuilder::with_service()`,
+    ///   since such services cannot be re-created for the copy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service_factory(|| Box::new(MyService))
+    ///     .create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let mut other = testkit.fork();
+    /// testkit.create_block_with_transaction(MyTransaction::new(&pubkey, "foo", &key));
+    /// other.create_block_with_transaction(MyTransaction::new(&pubkey, "bar", &key));
+    /// assert_ne!(testkit.last_block_hash(), other.last_block_hash());
+    /// # }
+    /// ```
+    pub fn fork(&mut self) -> TestKit {
+        assert_eq!(
+            self.services.len(),
+            self.service_factories.len(),
+            "Cannot fork a testkit with services added by `TestKitBuilder::with_service()`; \
+             add services with `with_service_factory()` instead"
+        );
+        self.poll_events();
+
+        let services: Vec<Arc<Service>> = self.service_factories
+            .iter()
+            .map(|factory| Arc::from(factory()))
+            .collect();
+
+        let api_channel = mpsc::channel(1_000);
+        let api_sender = ApiSender::new(api_channel.0.clone());
+
+        let db = self.db_handler.copy_to(self.db_handler.inner().empty_copy());
+        let db_handler = db.handler();
+
+        let blockchain = Blockchain::new(
+            Box::new(db),
+            SharedService::wrap(&services),
+            *self.network.us().service_keypair().0,
+            self.network.us().service_keypair().1.clone(),
+            api_sender.clone(),
+        );
+
+        let mempool: BTreeMap<_, _> = self.mempool()
+            .values()
+            .map(|tx| {
+                let tx = blockchain.tx_from_raw(tx.raw().clone()).expect(
+                    "Cannot parse transaction from the mempool",
+                );
+                (tx.hash(), tx)
+            })
+            .collect();
+        let mempool = Arc::new(RwLock::new(mempool));
+        let mempool_limiter = Arc::new(RwLock::new(
+            self.mempool_limiter
+                .read()
+                .expect("Cannot acquire read lock on mempool limiter")
+                .clone(),
+        ));
+        let sent_requests = Arc::new(RwLock::new(self.sent_requests()));
+        let events = EventBus::default();
+        let events_stream = TestKit::spawn_events_stream(
+            api_channel.1,
+            &blockchain,
+            &mempool,
+            &mempool_limiter,
+            &sent_requests,
+            &events,
+        );
+
+        TestKit {
+            blockchain,
+            db_handler,
+            checkpoints: self.checkpoints.clone(),
+            proposes: self.proposes.clone(),
+            proof_tampering: None,
+            api_sender,
+            events_stream,
+            network: self.network.clone(),
+            mempool,
+            metrics: Arc::default(),
+            events,
+            cfg_proposal: self.cfg_proposal.clone(),
+            time_provider: Arc::from(self.time_provider.fork()),
+            mempool_limiter,
+            broadcast_txs: self.broadcast_txs.clone(),
+            pool_ordering: self.pool_ordering.clone(),
+            split_blocks: self.split_blocks,
+            block_size_limit: self.block_size_limit,
+            rollback_retention: self.rollback_retention,
+            sent_requests,
+            check_state_hash: self.check_state_hash,
+            check_determinism: self.check_determinism,
+            panic_policy: self.panic_policy,
+            profile: self.profile.as_ref().map(|_| ProfileReport::default()),
+            logs: self.logs.clone(),
+            tx_factory: self.tx_factory.clone(),
+            accounts: self.accounts.clone(),
+            key_generator: self.key_generator.clone(),
+            skipped_leaders: self.skipped_leaders.clone(),
+            skipped_rounds: self.skipped_rounds,
+            before_commit_hooks: Vec::new(),
+            after_commit_hooks: Vec::new(),
+            routing_hooks: Vec::new(),
+            activation_hooks: Vec::new(),
+            invariants: self.invariants.clone(),
+            services,
+            service_factories: self.service_factories.clone(),
+        }
+    }
+
+    /// Emulates a reboot of the node: services added with `TestKitBuilder::with_service_factory()`
+    /// are re-created, and the blockchain together with API handlers is reconstructed from
+    /// the persisted storage. The mempool, which is not persisted by a node, is cleared.
+    /// Services added with `TestKitBuilder::with_service()` keep their in-memory state.
+    ///
+    /// Instances of `TestKitApi` created before the restart continue to use the old API
+    /// handlers, so the API should be obtained anew with `api()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::MyService;
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service_factory(|| Box::new(MyService))
+    ///     .create();
+    /// testkit.create_blocks_until(Height(5));
+    /// testkit.restart();
+    /// // The services are re-created, but the blockchain state is kept.
+    /// assert_eq!(testkit.height(), Height(5));
+    /// # }
+    /// ```
+    pub fn restart(&mut self) {
+        self.poll_events();
+
+        let fixed_services = self.services.len() - self.service_factories.len();
+        self.services.truncate(fixed_services);
+        let services = self.service_factories
+            .iter()
+            .map(|factory| Arc::from(factory()))
+            .collect::<Vec<_>>();
+        self.services.extend(services);
+
+        let api_channel = mpsc::channel(1_000);
+        self.api_sender = ApiSender::new(api_channel.0.clone());
+        self.blockchain = Blockchain::new(
+            Box::new(self.db_handler.database()),
+            SharedService::wrap(&self.services),
+            *self.network.us().service_keypair().0,
+            self.network.us().service_keypair().1.clone(),
+            self.api_sender.clone(),
+        );
+
+        self.mempool
+            .write()
+            .expect("Cannot write transactions to mempool")
+            .clear();
+        self.events_stream = TestKit::spawn_events_stream(
+            api_channel.1,
+            &self.blockchain,
+            &self.mempool,
+            &self.mempool_limiter,
+            &self.sent_requests,
+            &self.events,
+        );
+    }
+
+    /// Exports blocks, transactions and contents of all indices of the blockchain storage
+    /// to the given file in the pretty-printed JSON format, which is suitable for golden-file
+    /// diffing. Keys and values of indices are hex-encoded. The exported blockchain can be
+    /// used to bootstrap another testkit with `TestKitBuilder::with_exported_state()`.
+    ///
+    /// The output is reproducible if the testkit is created with `TestKitBuilder::with_seed()`
+    /// and a time provider returning the same times, since validator keys and the time
+    /// of precommits are recorded in the storage.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the testkit has been bootstrapped with
+    ///   `TestKitBuilder::with_preloaded_rocksdb()`.
+    /// - Panics if the file cannot be written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate tempdir;
+    /// # use exonum::crypto::{self, Seed};
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// # use exonum_testkit::time::MockTimeProvider;
+    /// # use std::fs::File;
+    /// # use std::io::Read;
+    /// # use std::path::Path;
+    /// # use tempdir::TempDir;
+    /// #
+    /// # fn main() {
+    /// # let dir = TempDir::new("exonum-testkit-doc").unwrap();
+    /// let (pubkey, key) = crypto::gen_keypair_from_seed(&Seed::new([1; 32]));
+    /// let export = |path: &Path| {
+    ///     let mut testkit = TestKitBuilder::validator()
+    ///         .with_seed(0)
+    ///         .with_time_provider(MockTimeProvider::default())
+    ///         .with_service(MyService)
+    ///         .create();
+    ///     testkit.create_block_with_transaction(MyTransaction::new(&pubkey, "foo", &key));
+    ///     testkit.export(path);
+    /// };
+    /// // In tests, the output is usually compared with a golden file.
+    /// export(&dir.path().join("first.json"));
+    /// export(&dir.path().join("second.json"));
+    /// let read = |name: &str| {
+    ///     let mut contents = String::new();
+    ///     File::open(dir.path().join(name)).unwrap().read_to_string(&mut contents).unwrap();
+    ///     contents
+    /// };
+    /// assert_eq!(read("first.json"), read("second.json"));
+    /// # }
+    /// ```
+    pub fn export<P: AsRef<Path>>(&self, path: P) {
+        assert!(
+            !self.db_handler.inner().is_preloaded(),
+            "Cannot export the state of the testkit bootstrapped from a preloaded database"
+        );
+        let explorer = self.explorer();
+        let blocks = (0..self.height().0 + 1)
+            .map(|height| explorer.block_with_txs(Height(height)).unwrap())
+            .collect::<Vec<_>>();
+        let state = ExportedState::new(&blocks, &self.db_handler.state());
+        state.write(path.as_ref());
+    }
+
+    /// Saves the state of the testkit to the given file: the blockchain data together with
+    /// the history of changes required for `rollback()`, the mempool and keys of the test
+    /// network. The state can be restored with `TestKitBuilder::from_saved()`.
+    ///
+    /// Pending configuration change proposals, validators marked as offline, sent requests
+    /// and commit hooks are not saved.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the testkit has been bootstrapped with
+    ///   `TestKitBuilder::with_preloaded_rocksdb()`.
+    /// - Panics if the file cannot be written.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) {
+        assert!(
+            !self.db_handler.inner().is_preloaded(),
+            "Cannot save the state of the testkit bootstrapped from a preloaded database"
+        );
+        self.poll_events();
+        let mempool = self.mempool()
+            .values()
+            .map(|tx| tx.raw().clone())
+            .collect::<Vec<_>>();
+        let state = SavedState::new(
+            &self.network.us,
+            &self.network.validators,
+            &self.db_handler.state(),
+            &self.db_handler.journal(),
+            self.checkpoints.clone(),
+            &mempool,
+        );
+        state.write(path.as_ref());
+    }
+
+    /// Returns a snapshot of the blockchain state as of the block at the given height.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `height` is greater than the current blockchain height.
+    /// - Panics if `height` is less than the height, at which the testkit has been
+    ///   bootstrapped with `TestKitBuilder::with_preloaded_rocksdb()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::Schema;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.create_blocks_until(Height(5));
+    ///
+    /// let old_snapshot = testkit.snapshot_at(Height(3));
+    /// assert_eq!(Schema::new(&old_snapshot).block_hashes_by_height().len(), 4);
+    /// let snapshot = testkit.snapshot();
+    /// assert_eq!(Schema::new(&snapshot).block_hashes_by_height().len(), 6);
+    /// # }
+    /// ```
+    pub fn snapshot_at(&self, height: Height) -> Box<Snapshot> {
+        assert!(
+            height <= self.height(),
+            "Cannot get snapshot at height {}; the blockchain height is {}",
+            height.0,
+            self.height().0
+        );
+        let initial_height = self.initial_height();
+        assert!(
+            height >= initial_height,
+            "Cannot get snapshot at height {}; the testkit has been bootstrapped at height {}",
+            height.0,
+            initial_height.0
+        );
+        self.db_handler.snapshot_at(
+            self.checkpoints[(height.0 - initial_height.0) as usize],
+        )
+    }
+
+    /// Returns the execution profile of the blocks created by the testkit since its creation.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if profiling is not enabled with `TestKitBuilder::with_profiling()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::Transaction;
+    /// # use exonum::crypto;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .with_profiling()
+    ///     .create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let txs = (0..3).map(|i| {
+    ///     Box::new(MyTransaction::new(&pubkey, &i.to_string(), &key)) as Box<Transaction>
+    /// });
+    /// testkit.create_block_with_transactions(txs);
+    /// let slowest = &testkit.profile_report().by_transaction_type()[0];
+    /// assert_eq!((slowest.service_id, slowest.message_type, slowest.count), (0, 0, 3));
+    /// println!("{}", testkit.profile_report());
+    /// # }
+    /// ```
+    pub fn profile_report(&self) -> &ProfileReport {
+        self.profile.as_ref().expect(
+            "Profiling is not enabled; use `TestKitBuilder::with_profiling()`",
+        )
+    }
+
+    /// Returns log records emitted during the execution of the transaction with the given hash,
+    /// in the order of emission. Records are captured when the outcome of the transaction
+    /// is determined during the creation of the block with it; an empty vector is returned
+    /// for unknown transactions.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if log capture is not enabled with `TestKitBuilder::with_log_capture()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .with_log_capture()
+    ///     .create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let tx = MyTransaction::new(&pubkey, "ignored", &key);
+    /// testkit.create_block_with_transaction(tx.clone());
+    /// let logs = testkit.logs_for_tx(&tx.hash());
+    /// assert!(logs.iter().any(|record| record.message.contains("Ignored message")));
+    /// # }
+    /// ```
+    pub fn logs_for_tx(&self, tx_hash: &crypto::Hash) -> Vec<&LogRecord> {
+        self.captured_logs()
+            .iter()
+            .filter(|record| record.tx_hash.as_ref() == Some(tx_hash))
+            .collect()
+    }
+
+    /// Returns log records emitted during the creation of the block at the given height,
+    /// including the records attributed to transactions in the block, in the order
+    /// of emission.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if log capture is not enabled with `TestKitBuilder::with_log_capture()`.
+    pub fn logs_for_block(&self, height: Height) -> Vec<&LogRecord> {
+        self.captured_logs()
+            .iter()
+            .filter(|record| record.height == height)
+            .collect()
+    }
+
+    fn captured_logs(&self) -> &[LogRecord] {
+        self.logs.as_ref().expect(
+            "Log capture is not enabled; use `TestKitBuilder::with_log_capture()`",
+        )
+    }
+
+    /// Returns the approximate storage footprint of indices in the current blockchain state.
+    ///
+    /// Only entries written through the testkit are taken into account; in particular,
+    /// entries of a preloaded RocksDB database that have not been modified are not counted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::Transaction;
+    /// # use exonum::crypto;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let messages = (0..10).map(|i| {
+    ///     let (pubkey, key) = crypto::gen_keypair();
+    ///     Box::new(MyTransaction::new(&pubkey, &i.to_string(), &key)) as Box<Transaction>
+    /// });
+    /// let before = testkit.storage_stats();
+    /// testkit.create_block_with_transactions(messages);
+    /// let after = testkit.storage_stats();
+    /// let index = "documentation.messages";
+    /// assert!(after.index(index).total_bytes() - before.index(index).total_bytes() < 1024);
+    /// # }
+    /// ```
+    pub fn storage_stats(&self) -> StorageStats {
+        StorageStats::collect(&*self.snapshot(), &self.db_handler.journal())
+    }
+
+    /// Returns the approximate storage footprint of indices after the block at the given
+    /// height. See `storage_stats()` for details.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `height` is greater than the current blockchain height.
+    /// - Panics if `height` is less than the height, at which the testkit has been
+    ///   bootstrapped with `TestKitBuilder::with_preloaded_rocksdb()`.
+    pub fn storage_stats_at(&self, height: Height) -> StorageStats {
+        StorageStats::collect(&*self.snapshot_at(height), &self.db_handler.journal())
+    }
+
+    /// Returns the index entries modified by the latest committed block, sorted by index names
+    /// and keys. Entries written with the same value as before the block are not included.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the latest block is the genesis block or the block, from which the testkit
+    ///   has been bootstrapped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// testkit.create_block_with_transaction(MyTransaction::new(&pubkey, "foo", &key));
+    /// let changed_indices: Vec<_> = testkit
+    ///     .last_block_changes()
+    ///     .into_iter()
+    ///     .map(|change| change.index)
+    ///     .filter(|index| !index.starts_with("core."))
+    ///     .collect();
+    /// assert_eq!(changed_indices, vec!["documentation.messages"]);
+    /// # }
+    /// ```
+    pub fn last_block_changes(&self) -> Vec<StorageChange> {
+        assert!(
+            self.checkpoints.len() > 1,
+            "Cannot get changes of block {}; the testkit has been created at this height",
+            self.height().0
+        );
+        let checkpoint = self.checkpoints[self.checkpoints.len() - 2];
+        let old_snapshot = self.db_handler.snapshot_at(checkpoint);
+        let new_snapshot = self.snapshot();
+
+        // Each modified entry is mentioned in the journal.
+        let journal = self.db_handler.journal();
+        let keys: BTreeSet<(&String, &Vec<u8>)> = journal[checkpoint..]
+            .iter()
+            .flat_map(|patch| {
+                patch.iter().flat_map(|(index, changes)| {
+                    changes.keys().map(move |key| (index, key))
+                })
+            })
+            .collect();
+        keys.into_iter()
+            .filter_map(|(index, key)| {
+                let old = old_snapshot.get(index, key);
+                let new = new_snapshot.get(index, key);
+                if old == new {
+                    None
+                } else {
+                    Some(StorageChange {
+                        index: index.clone(),
+                        key: key.clone(),
+                        old,
+                        new,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the height of the earliest block, to which the testkit can be rolled back.
+    fn initial_height(&self) -> Height {
+        Height(self.height().0 + 1 - self.checkpoints.len() as u64)
+    }
+
+    /// Executes a list of transactions given the current state of the blockchain, but does not
+    /// commit execution results to the blockchain. The execution result is the same
+    /// as if transactions were included into a new block; for example,
+    /// transactions included into one of previous blocks do not lead to any state changes.
+    ///
+    /// Transactions are applied in the order of iteration, so the returned snapshot can be used
+    /// to check invariants of multi-transaction workflows (e.g., creating a wallet and transferring
+    /// funds from it within the same hypothetical block). The mempool is left intact: probed
+    /// transactions that were in the mempool before the call remain there afterwards.
+    pub fn probe_all<I>(&mut self, transactions: I) -> Box<Snapshot>
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        self.poll_events();
+
+        // Filter out already committed transactions; otherwise,
+        // `create_block_with_transactions()` will panic.
+        let schema = CoreSchema::new(self.snapshot());
+        let uncommitted_txs: Vec<_> = transactions
+            .into_iter()
+            .filter(|tx| !schema.transactions().contains(&tx.hash()))
+            .collect();
+
+        // Stash probed transactions that are already in the mempool, so that they are not lost
+        // after the block is rolled back.
+        let stashed_txs: Vec<_> = {
+            let mut mempool = self.mempool.write().expect(
+                "Cannot modify transactions in mempool",
+            );
+            uncommitted_txs
+                .iter()
+                .filter_map(|tx| mempool.remove(&tx.hash()))
+                .collect()
+        };
+
+        self.create_block_with_transactions(uncommitted_txs);
+        let snapshot = self.snapshot();
+        self.rollback_storage(1);
+
+        let mut mempool = self.mempool.write().expect(
+            "Cannot write transactions to mempool",
+        );
+        for tx in stashed_txs {
+            mempool.insert(tx.hash(), tx);
+        }
+        snapshot
+    }
+
+    /// Executes a transaction given the current state of the blockchain but does not
+    /// commit execution results to the blockchain. The execution result is the same
+    /// as if a transaction was included into a new block; for example,
+    /// a transaction included into one of previous blocks does not lead to any state changes.
+    pub fn probe<T: Transaction>(&mut self, transaction: T) -> Box<Snapshot> {
+        self.probe_all(vec![Box::new(transaction) as Box<Transaction>])
+    }
+
+    fn do_create_block(
+        &mut self,
+        proposer: Option<(ValidatorId, Round)>,
+        time: Option<SystemTime>,
+        tx_hashes: &[crypto::Hash],
+    ) -> BlockResult {
+        let new_block_height = self.height().next();
+        let last_hash = self.last_block_hash();
+
+        self.update_configuration(new_block_height);
+        let (proposer, round) = match proposer {
+            Some((id, round)) => {
+                let proposer = self.network()
+                    .validators()
+                    .get(id.0 as usize)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        panic!("Validator with id {:?} is absent in test network", id)
+                    });
+                (proposer, round)
+            }
+            None => self.scheduled_proposer(new_block_height),
+        };
+        let (result, tx_types) = {
+            let transactions = self.mempool();
+            let txs: Vec<&Transaction> = tx_hashes
+                .iter()
+                .map(|hash| &*transactions[hash])
+                .collect();
+            let tx_types: Vec<_> = txs.iter()
+                .map(|tx| (tx.raw().service_id(), tx.raw().message_type()))
+                .collect();
+            // Transactions are executed on throwaway forks to determine their outcomes.
+            let result = execution::execute_block(
+                &self.blockchain,
+                new_block_height,
+                &txs,
+                self.logs.is_some(),
+            );
+            if self.panic_policy == PanicPolicy::Abort {
+                if let Some((hash, message)) = result.first_panicked() {
+                    panic!(
+                        "Transaction {:?} has panicked during execution in block {}: {}\n{:?}",
+                        hash,
+                        new_block_height.0,
+                        message,
+                        transactions[hash]
+                    );
+                }
+            }
+            (result, tx_types)
+        };
+        // Transactions that have panicked are recorded in `result`, but are not included
+        // into the block.
+        let included_hashes = result.included_hashes();
+
+        let start = Instant::now();
+        let (block_hash, patch) = {
+            let validator_id = proposer.validator_id().unwrap();
+            let transactions = self.mempool();
+            let execution = self.blockchain.create_patch(
+                validator_id,
+                new_block_height,
+                &included_hashes,
+                &transactions,
+            );
+            if self.check_determinism {
+                let other_execution = self.blockchain.create_patch(
+                    validator_id,
+                    new_block_height,
+                    &included_hashes,
+                    &transactions,
+                );
+                determinism::check_determinism(new_block_height, &execution, &other_execution);
+            }
+            execution
+        };
+        let execution_time = start.elapsed();
+
+        // Remove txs from mempool
+        {
+            let mut transactions = self.mempool.write().expect(
+                "Cannot modify transactions in mempool",
+            );
+            for hash in tx_hashes {
+                transactions.remove(hash);
+            }
+        }
+
+        let propose = proposer.create_propose_at_round(
+            new_block_height,
+            round,
+            &last_hash,
+            &included_hashes,
+        );
+        let time = time.unwrap_or_else(|| self.time_provider.current_time());
+        let precommits: Vec<_> = self.network()
+            .validators()
+            .iter()
+            .map(|v| v.create_precommit_at_time(&propose, &block_hash, time))
+            .collect();
+        let precommits = match self.proof_tampering.take() {
+            Some(tampering) => tampering.apply(precommits, self.network().validators()),
+            None => precommits,
+        };
+        self.proposes.insert(new_block_height, propose);
+
+        let (patch, mut block_logs) =
+            self.capture_logs(|testkit| testkit.before_commit(&block_hash, patch));
+        // Transactions sent before the commit are processed, so that transactions broadcast
+        // by `handle_commit()` hooks can be told apart.
+        self.poll_events();
+        let pool_before: Vec<_> = self.mempool().keys().cloned().collect();
+        let state_hash_time = self.profile.as_ref().map(|_| self.state_hash_time(&patch));
+        let start = Instant::now();
+        let ((), commit_logs) = self.capture_logs(|testkit| {
+            testkit
+                .blockchain
+                .commit(&patch, block_hash, precommits.iter())
+                .unwrap();
+        });
+        let merge_time = start.elapsed();
+        block_logs.extend(commit_logs);
+        self.metrics.record_block(execution_time);
+        if let Some(ref mut profile) = self.profile {
+            let transactions = result
+                .transactions()
+                .iter()
+                .zip(tx_types)
+                .map(|(tx, (service_id, message_type))| {
+                    TxProfile {
+                        hash: *tx.hash(),
+                        service_id,
+                        message_type,
+                        execution: tx.execution_time(),
+                    }
+                })
+                .collect();
+            profile.push(BlockProfile {
+                height: new_block_height,
+                transactions,
+                execution: execution_time,
+                state_hash: state_hash_time.unwrap(),
+                merge: merge_time,
+            });
+        }
+        let ((), after_commit_logs) = self.capture_logs(|testkit| {
+            testkit.after_commit();
+            testkit.poll_events();
+        });
+        block_logs.extend(after_commit_logs);
+
+        self.broadcast_txs = self.mempool()
+            .iter()
+            .filter(|&(hash, _)| !pool_before.contains(hash))
+            .map(|(_, tx)| tx.raw().clone())
+            .collect();
+
+        if let Some(ref mut logs) = self.logs {
+            for tx in result.transactions() {
+                logs.extend(tx.logs().iter().cloned());
+            }
+            logs.extend(block_logs.into_iter().map(|record| {
+                record.into_record(new_block_height, None)
+            }));
+        }
+        result
+    }
+
+    /// Calls the function and returns log records emitted during the call if log capture
+    /// is enabled.
+    fn capture_logs<F, R>(&mut self, f: F) -> (R, Vec<CapturedRecord>)
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        if self.logs.is_some() {
+            logs::capture(|| f(self))
+        } else {
+            (f(self), Vec::new())
+        }
+    }
+
+    /// Measures the wall time of `Service::state_hash()` calls for all services on the state
+    /// resulting from the block with the given changes.
+    fn state_hash_time(&self, patch: &Patch) -> Duration {
+        let mut fork = self.blockchain.fork();
+        fork.merge(patch.clone());
+        let start = Instant::now();
+        for service in self.blockchain.service_map().values() {
+            service.state_hash(&fork);
+        }
+        start.elapsed()
+    }
+
+    /// Runs hooks registered with `on_before_commit` on the block that is about to be committed.
+    fn before_commit(&mut self, block_hash: &crypto::Hash, patch: Patch) -> Patch {
+        if self.before_commit_hooks.is_empty() {
+            return patch;
+        }
+
+        let mut fork = self.blockchain.fork();
+        fork.merge(patch);
+        let block = CoreSchema::new(&fork).blocks().get(block_hash).unwrap();
+        for hook in &mut self.before_commit_hooks {
+            hook(&block, &fork);
+        }
+        fork.into_patch()
+    }
+
+    /// Returns the validator proposing the block at the given height together with the round
+    /// of the proposal. Validators take turns starting from the leader of the first round
+    /// after those skipped with `skip_rounds()`, skipping validators marked as offline
+    /// with `skip_leader()`; each skipped validator increments the round.
+    fn scheduled_proposer(&self, height: Height) -> (TestNode, Round) {
+        let network = self.network();
+        let first_round = if self.skipped_rounds.0 == height {
+            self.skipped_rounds.1 + 1
+        } else {
+            1
+        };
+        (first_round..first_round + network.validators().len() as u32)
+            .map(Round)
+            .map(|round| (network.leader_for(round), round))
+            .find(|&(leader, _)| {
+                let id = leader.validator_id().unwrap();
+                !self.skipped_leaders.contains(&(height, id))
+            })
+            .map(|(leader, round)| (leader.clone(), round))
+            .expect("All validators are offline")
+    }
+
+    /// Performs bookkeeping after a block has been committed to the blockchain.
+    fn after_commit(&mut self) {
+        self.checkpoints.push(self.db_handler.journal_len());
+        if self.check_state_hash {
+            self.assert_state_hash();
+        }
+        if !self.invariants.is_empty() {
+            self.check_invariants();
+        }
+
+        {
+            let snapshot = self.snapshot();
+            let schema = CoreSchema::new(&snapshot);
+            let block = schema.last_block().unwrap();
+            self.events.emit(TestKitEvent::BlockCommitted {
+                height: block.height(),
+                block_hash: block.hash(),
+                tx_hashes: schema.block_txs(block.height()).iter().collect(),
+            });
+        }
+
+        if !self.after_commit_hooks.is_empty() {
+            let snapshot = self.snapshot();
+            let block = CoreSchema::new(&snapshot).last_block().unwrap();
+            for hook in &mut self.after_commit_hooks {
+                hook(&block, &*snapshot);
+            }
+        }
+
+        if !self.routing_hooks.is_empty() {
+            let snapshot = self.snapshot();
+            let block = CoreSchema::new(&snapshot).last_block().unwrap();
+            let mut txs = Vec::new();
+            for hook in &mut self.routing_hooks {
+                txs.extend(hook(&block, &*snapshot));
+            }
+            self.add_to_mempool(txs);
+        }
+
+        if !self.activation_hooks.is_empty() {
+            let snapshot = self.snapshot();
+            let schema = CoreSchema::new(&snapshot);
+            let block = schema.last_block().unwrap();
+            let configuration = schema.actual_configuration();
+            if configuration.actual_from == block.height().next() {
+                for hook in &mut self.activation_hooks {
+                    hook(&block, &configuration);
+                }
+            }
+        }
+    }
+
+    /// Checks that invariants registered with `add_invariant()` hold after the latest block.
+    fn check_invariants(&self) {
+        let snapshot = self.snapshot();
+        for &(ref name, ref invariant) in &self.invariants {
+            if !invariant(&*snapshot) {
+                let schema = CoreSchema::new(&snapshot);
+                let height = self.height();
+                let tx_hashes: Vec<_> = schema.block_txs(height).iter().collect();
+                panic!(
+                    "Invariant `{}` is violated after block {} with transactions {:?}",
+                    name,
+                    height.0,
+                    tx_hashes
+                );
+            }
+        }
+    }
+
+    /// Checks that the `state_hash` of the latest block aggregates the state hashes
+    /// returned by `Service::state_hash()` for the current blockchain state, and that
+    /// the state hashes of services cover the indexes changed by the block.
+    /// See `TestKitBuilder::with_state_hash_check()` for the kinds of errors caught
+    /// by the check.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the state hash aggregator is inconsistent with the block, or if the state
+    ///   hash of a service table differs from the aggregated one.
+    /// - Panics if the aggregator contains more tables of a service than returned
+    ///   by its `state_hash()`.
+    /// - Panics if a service has changed indexes with its prefix in the latest block,
+    ///   but its state hash has not changed.
+    pub fn assert_state_hash(&self) {
+        // The changes are unknown for the block, at which the testkit has been created.
+        let previous = if self.checkpoints.len() > 1 {
+            let checkpoint = self.checkpoints[self.checkpoints.len() - 2];
+            let changed_indices: BTreeSet<String> = self.last_block_changes()
+                .into_iter()
+                .map(|change| change.index)
+                .collect();
+            Some((self.db_handler.snapshot_at(checkpoint), changed_indices))
+        } else {
+            None
+        };
+
+        let snapshot = self.snapshot();
+        let schema = CoreSchema::new(&snapshot);
+        let block = schema.last_block().unwrap();
+        let aggregator = schema.state_hash_aggregator();
+        assert_eq!(
+            aggregator.root_hash(),
+            *block.state_hash(),
+            "State hash aggregator is inconsistent with the state hash of block {}",
+            block.height().0
+        );
+
+        for service in self.blockchain.service_map().values() {
+            let hashes = service.state_hash(&*snapshot);
+            let len = hashes.len();
+            for (table_idx, hash) in hashes.iter().enumerate() {
+                let key = Blockchain::service_table_unique_key(service.service_id(), table_idx);
+                assert_eq!(
+                    aggregator.get(&key),
+                    Some(*hash),
+                    "State hash of table #{} of service `{}` is inconsistent with block {}",
+                    table_idx,
+                    service.service_name(),
+                    block.height().0
+                );
+            }
+
+            let extra_key = Blockchain::service_table_unique_key(service.service_id(), len);
+            assert!(
+                aggregator.get(&extra_key).is_none(),
+                "Service `{}` returns {} state hashes, but the state hash aggregator contains \
+                 more tables of the service after block {}",
+                service.service_name(),
+                len,
+                block.height().0
+            );
+
+            if let Some((ref old_snapshot, ref changed_indices)) = previous {
+                let prefix = format!("{}.", service.service_name());
+                let changed: Vec<_> = changed_indices
+                    .iter()
+                    .filter(|index| index.starts_with(&prefix))
+                    .collect();
+                if !changed.is_empty() && service.state_hash(&**old_snapshot) == hashes {
+                    panic!(
+                        "Service `{}` has changed indexes {:?} in block {}, but its state hash \
+                         has not changed; are the indexes included into `state_hash()`?",
+                        service.service_name(),
+                        changed,
+                        block.height().0
+                    );
+                }
+            }
+        }
+    }
+
+    /// Update test network configuration if such an update has been scheduled
+    /// with `commit_configuration_change`.
+    fn update_configuration(&mut self, new_block_height: Height) {
+        use ConfigurationProposalState::*;
+
+        let actual_from = new_block_height.next();
+        if let Some(cfg_proposal) = self.cfg_proposal.take() {
+            match cfg_proposal {
+                Uncommitted(cfg_proposal) => {
+                    // Commit configuration proposal
+                    let stored = cfg_proposal.stored_configuration().clone();
+                    let mut fork = self.blockchain.fork();
+                    CoreSchema::new(&mut fork).commit_configuration(stored);
+                    let changes = fork.into_patch();
+                    self.blockchain.merge(changes).unwrap();
+                    self.cfg_proposal = Some(Committed(cfg_proposal));
+                }
+                Committed(cfg_proposal) => {
+                    if cfg_proposal.actual_from() == actual_from {
+                        // Modify the self configuration
+                        self.network_mut().update(
+                            cfg_proposal.us,
+                            cfg_proposal.validators,
+                        );
+                    } else {
+                        self.cfg_proposal = Some(Committed(cfg_proposal));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Creates a block with the given transactions.
+    /// Transactions that are in the mempool will be ignored.
+    /// Returns the execution results of the transactions in the block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions has been already committed to the blockchain.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # #[macro_use] extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum_testkit::{ExecutionStatus, TestKitBuilder};
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let result = testkit.create_block_with_transactions(txvec![
+    ///     MyTransaction::new(&pubkey, "foo", &key),
+    ///     MyTransaction::new(&pubkey, "ignored", &key),
+    /// ]);
+    /// // The second transaction is ignored by the service and does not change the state.
+    /// assert_eq!(result.transactions()[0].status(), &ExecutionStatus::Applied);
+    /// assert_eq!(result.transactions()[1].status(), &ExecutionStatus::NoOp);
+    /// # }
+    /// ```
+    pub fn create_block_with_transactions<I>(&mut self, txs: I) -> BlockResult
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let tx_hashes = self.add_to_mempool(txs);
+        self.create_block_with_tx_hashes(&tx_hashes)
+    }
+
+    /// Creates a block with the given transactions after checking that the resulting
+    /// blockchain state does not depend on the order of transactions. Every permutation
+    /// of transactions is executed without committing, and the state hashes of the resulting
+    /// blocks are compared. The block is then created with transactions in the order
+    /// of iteration; the execution results of the transactions in the block are returned.
+    ///
+    /// The number of permutations grows factorially with the number of transactions,
+    /// so the method is suited for small transaction sets only.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if two orderings of transactions lead to different state hashes.
+    ///   The panic message lists transaction hashes in both orderings.
+    /// - Panics if any of transactions has been already committed to the blockchain.
+    pub fn create_block_with_permutations<I>(&mut self, txs: I) -> BlockResult
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let tx_hashes = self.add_to_mempool(txs);
+        self.poll_events();
+
+        let reference_hash = self.execution_state_hash(&tx_hashes);
+        for permutation in Permutations::new(&tx_hashes).skip(1) {
+            let state_hash = self.execution_state_hash(&permutation);
+            assert_eq!(
+                reference_hash,
+                state_hash,
+                "Execution of transactions depends on their order: {:?} and {:?} lead \
+                 to different state hashes",
+                tx_hashes,
+                permutation
+            );
+        }
+
+        self.do_create_block(None, None, &tx_hashes)
+    }
+
+    /// Executes transactions with the given hashes from the mempool on top of the current
+    /// blockchain state and returns the `state_hash` of the resulting block. Execution results
+    /// are not committed.
+    fn execution_state_hash(&self, tx_hashes: &[crypto::Hash]) -> crypto::Hash {
+        let validator_id = self.leader().validator_id().unwrap();
+        let (block_hash, patch) = self.blockchain.create_patch(
+            validator_id,
+            self.height().next(),
+            tx_hashes,
+            &self.mempool(),
+        );
+
+        let mut fork = self.blockchain.fork();
+        fork.merge(patch);
+        let block = CoreSchema::new(&fork).blocks().get(&block_hash).unwrap();
+        *block.state_hash()
+    }
+
+    /// Creates a block with the given transactions, which is proposed by the validator
+    /// with the specified identifier instead of the current leader.
+    /// Transactions that are in the mempool will be ignored.
+    /// Returns the execution results of the transactions in the block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if there is no validator with the given id in the test network.
+    /// - Panics if any of transactions has been already committed to the blockchain.
+    pub fn create_block_with_proposer<I>(&mut self, proposer_id: ValidatorId, txs: I) -> BlockResult
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let tx_hashes = self.add_to_mempool(txs);
+        self.poll_events();
+        self.do_create_block(Some((proposer_id, Round::first())), None, &tx_hashes)
+    }
+
+    /// Creates a block with the given transactions, the precommits for which are marked with
+    /// the specified time instead of the time returned by the testkit time provider.
+    /// Transactions that are in the mempool will be ignored.
+    /// Returns the execution results of the transactions in the block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if any of transactions has been already committed to the blockchain.
+    pub fn create_block_at_time<I>(&mut self, time: SystemTime, txs: I) -> BlockResult
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let tx_hashes = self.add_to_mempool(txs);
+        self.poll_events();
+        self.do_create_block(None, Some(time), &tx_hashes)
+    }
+
+    /// Adds a transaction to the mempool, as if it were received by the node.
+    /// Returns the hash of the transaction if it has been added.
+    ///
+    /// Unlike transactions received via API, the transaction is not subject to the mempool
+    /// size limit.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `AddTxError::Invalid` if the transaction does not pass `verify()`.
+    /// - Returns `AddTxError::AlreadyCommitted` if the transaction has been already committed
+    ///   to the blockchain.
+    /// - Returns `AddTxError::Duplicate` if the transaction is already in the mempool.
+    pub fn add_tx<T: Transaction>(&mut self, tx: T) -> Result<crypto::Hash, AddTxError> {
+        self.poll_events();
+
+        if !tx.verify() {
+            return Err(AddTxError::Invalid);
+        }
+        self.insert_into_mempool(Box::new(tx))
+    }
+
+    /// Adds a transaction parsed from the raw message to the mempool without checking it
+    /// with `verify()`, as if a buggy or malicious peer has included it into a block proposal.
+    /// This allows to test that the execution of transactions and service APIs behave sanely
+    /// for invalid transactions, e.g., transactions with an invalid signature.
+    /// Returns the hash of the transaction if it has been added.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `AddTxError::Malformed` if the message cannot be parsed by any of
+    ///   the testkit services.
+    /// - Returns `AddTxError::AlreadyCommitted` if the transaction has been already committed
+    ///   to the blockchain.
+    /// - Returns `AddTxError::Duplicate` if the transaction is already in the mempool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto::{self, PublicKey};
+    /// # use exonum::messages::Message;
+    /// # use exonum::storage::MapIndex;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, _) = crypto::gen_keypair();
+    /// let (_, other_key) = crypto::gen_keypair();
+    /// // The transaction is signed with a wrong key.
+    /// let tx = MyTransaction::new(&pubkey, "forged", &other_key);
+    /// testkit.add_tx_unchecked(tx.raw().clone()).unwrap();
+    /// testkit.create_block();
+    /// let snapshot = testkit.snapshot();
+    /// let messages: MapIndex<_, PublicKey, String> =
+    ///     MapIndex::new("documentation.messages", &snapshot);
+    /// assert_eq!(messages.get(&pubkey), Some("forged".to_owned()));
+    /// # }
+    /// ```
+    pub fn add_tx_unchecked(&mut self, raw: RawMessage) -> Result<crypto::Hash, AddTxError> {
+        self.poll_events();
 
-    /// Returns a snapshot of the current blockchain state.
-    pub fn snapshot(&self) -> Box<Snapshot> {
-        self.blockchain.snapshot()
+        let tx = self.blockchain.tx_from_raw(raw).ok_or(AddTxError::Malformed)?;
+        self.insert_into_mempool(tx)
+    }
+
+    /// Puts the transaction into the mempool unless it is already committed or known.
+    fn insert_into_mempool(&self, tx: Box<Transaction>) -> Result<crypto::Hash, AddTxError> {
+        let hash = tx.hash();
+        if CoreSchema::new(&self.snapshot()).transactions().contains(&hash) {
+            return Err(AddTxError::AlreadyCommitted);
+        }
+
+        let mut mempool = self.mempool.write().expect(
+            "Cannot write transactions to mempool",
+        );
+        if mempool.contains_key(&hash) {
+            return Err(AddTxError::Duplicate);
+        }
+        mempool.insert(hash, tx);
+        self.events.emit(TestKitEvent::TransactionAdded { hash });
+        Ok(hash)
+    }
+
+    /// Verifies the given transactions and puts them into the mempool, returning the hashes
+    /// of the transactions that have passed verification.
+    fn add_to_mempool<I>(&self, txs: I) -> Vec<crypto::Hash>
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let mut mempool = self.mempool.write().expect(
+            "Cannot write transactions to mempool",
+        );
+
+        let snapshot = self.snapshot();
+        let schema = CoreSchema::new(&snapshot);
+        txs.into_iter()
+            .filter(|tx| tx.verify())
+            .map(|tx| {
+                let txid = tx.hash();
+                assert!(
+                    !schema.transactions().contains(&txid),
+                    "Transaction is already committed: {:?}",
+                    tx
+                );
+                mempool.insert(txid, tx);
+                self.events.emit(TestKitEvent::TransactionAdded { hash: txid });
+                txid
+            })
+            .collect()
+    }
+
+    /// Creates a block with the given transaction.
+    /// Transactions that are in the mempool will be ignored.
+    /// Returns the execution result of the transaction.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if given transaction has been already committed to the blockchain.
+    pub fn create_block_with_transaction<T: Transaction>(&mut self, tx: T) -> BlockResult {
+        self.create_block_with_transactions(txvec![tx])
+    }
+
+    /// Creates block with the specified transactions. The transactions must be previously
+    /// sent to the node via API or directly put into the `channel()`.
+    /// Returns the execution results of the transactions in the block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics in the case any of transaction hashes are not in the mempool.
+    pub fn create_block_with_tx_hashes(&mut self, tx_hashes: &[crypto::Hash]) -> BlockResult {
+        self.poll_events();
+
+        {
+            let txs = self.mempool();
+            for hash in tx_hashes {
+                assert!(txs.contains_key(hash));
+            }
+        }
+
+        self.create_blocks(tx_hashes)
+    }
+
+    /// Creates block with all transactions in the mempool.
+    /// Transactions are ordered according to the ordering set with `set_pool_ordering()`,
+    /// by their hashes by default.
+    /// Returns the execution results of the transactions in the block.
+    pub fn create_block(&mut self) -> BlockResult {
+        self.poll_events();
+
+        let tx_hashes = self.pool_ordering.arrange(&self.mempool());
+
+        self.create_blocks(&tx_hashes)
+    }
+
+    /// Creates one or more blocks with the given transactions from the mempool, depending
+    /// on whether block splitting is enabled.
+    fn create_blocks(&mut self, tx_hashes: &[crypto::Hash]) -> BlockResult {
+        if !self.split_blocks {
+            return self.do_create_block(None, None, tx_hashes);
+        }
+
+        let mut results = Vec::new();
+        let mut rest = tx_hashes;
+        loop {
+            let (block, tail) = rest.split_at(self.next_block_len(rest));
+            results.push(self.do_create_block(None, None, block));
+            rest = tail;
+            if rest.is_empty() {
+                break;
+            }
+        }
+        BlockResult::merge(results)
+    }
+
+    /// Returns the number of the leading transactions fitting into the next block.
+    fn next_block_len(&self, tx_hashes: &[crypto::Hash]) -> usize {
+        let txs_limit = self.actual_configuration().consensus.txs_block_limit as usize;
+        assert!(
+            txs_limit > 0,
+            "Cannot split transactions into blocks with zero `txs_block_limit`"
+        );
+
+        let mempool = self.mempool();
+        let mut size = 0;
+        let mut len = 0;
+        for hash in tx_hashes.iter().take(txs_limit) {
+            if let Some(max_bytes) = self.block_size_limit {
+                let tx_size = mempool[hash].raw().len();
+                assert!(
+                    tx_size <= max_bytes,
+                    "Transaction {:?} of {} bytes exceeds the block size limit of {} bytes",
+                    hash,
+                    tx_size,
+                    max_bytes
+                );
+                if size + tx_size > max_bytes {
+                    break;
+                }
+                size += tx_size;
+            }
+            len += 1;
+        }
+        len
+    }
+
+    /// Creates a block with all transactions in the mempool in a dedicated thread.
+    /// The returned future resolves to the testkit together with the execution results
+    /// of the transactions in the block; polling the future does not block, so the testkit
+    /// can be driven from a reactor, e.g., in tests of services spawning asynchronous work.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate futures;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use futures::Future;
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator().create();
+    /// let (testkit, block) = testkit.create_block_async().wait().unwrap();
+    /// assert_eq!(testkit.height(), Height(1));
+    /// assert_eq!(block.height(), Height(1));
+    /// # }
+    /// ```
+    pub fn create_block_async(self) -> TestKitFuture<(TestKit, BlockResult)> {
+        let mut testkit = self;
+        TestKitFuture::spawn(move || {
+            let block = testkit.create_block();
+            (testkit, block)
+        })
+    }
+
+    /// Creates a block with the given transactions in a dedicated thread.
+    /// See `create_block_async()` and `create_block_with_transactions()`.
+    pub fn create_block_with_transactions_async<I>(
+        self,
+        txs: I,
+    ) -> TestKitFuture<(TestKit, BlockResult)>
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let mut testkit = self;
+        let txs: Vec<_> = txs.into_iter().collect();
+        TestKitFuture::spawn(move || {
+            let block = testkit.create_block_with_transactions(txs);
+            (testkit, block)
+        })
+    }
+
+    /// Creates a block with all transactions in the mempool, storing it in the blockchain
+    /// with the precommits tampered in the given way. This allows to test services and tools,
+    /// which validate block proofs (e.g., via the explorer API), against adversarial input.
+    /// Unlike `create_block()`, the method always creates a single block.
+    /// Returns the execution results of the transactions in the block.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the tampering refers to a validator, which precommit is absent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # use exonum_testkit::{ProofTampering, TestKitBuilder};
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    /// testkit.create_block_with_tampered_proof(ProofTampering::TruncatedPrecommits(1));
+    /// let proof = testkit.block_proof(testkit.height()).unwrap();
+    /// assert!(!proof.verify(&testkit.actual_configuration().validator_keys));
+    /// # }
+    /// ```
+    pub fn create_block_with_tampered_proof(&mut self, tampering: ProofTampering) -> BlockResult {
+        self.poll_events();
+
+        let tx_hashes = self.pool_ordering.arrange(&self.mempool());
+        self.proof_tampering = Some(tampering);
+        self.do_create_block(None, None, &tx_hashes)
+    }
+
+    /// Creates a block with the given transactions, skipping the work that is not needed
+    /// for load tests of state transitions. Compared to `create_block_with_transactions()`:
+    ///
+    /// - Transactions are not checked with `verify()` and are not put into the mempool.
+    ///   The caller is responsible for passing valid transactions that have not been
+    ///   committed before.
+    /// - The propose and precommits for the block are not created, so the block is stored
+    ///   without precommits. `block_proof()` returns a proof without precommits
+    ///   for the block, which does not verify.
+    /// - Execution results of transactions are not determined (see `BlockResult`).
+    ///   The panic policy does not apply: a panicking transaction always aborts the block
+    ///   creation with the original panic.
+    /// - The determinism check, profiling and log capture are not performed for the block,
+    ///   and the block is not counted in the block execution metrics served at `/metrics`.
+    /// - Requests sent to the node, e.g., via API, are not processed.
+    ///
+    /// The resulting blockchain state and block hash are the same as with the regular
+    /// block creation. Invariants and commit hooks are run as usual.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::blockchain::Transaction;
+    /// # use exonum::crypto;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// for height in 0..10 {
+    ///     let txs = (0..100).map(|i| {
+    ///         let msg = format!("{}", height * 100 + i);
+    ///         Box::new(MyTransaction::new(&pubkey, &msg, &key)) as Box<Transaction>
+    ///     });
+    ///     testkit.create_block_fast(txs);
+    /// }
+    /// assert_eq!(testkit.height(), Height(10));
+    /// # }
+    /// ```
+    pub fn create_block_fast<I>(&mut self, txs: I)
+    where
+        I: IntoIterator<Item = Box<Transaction>>,
+    {
+        let new_block_height = self.height().next();
+        self.update_configuration(new_block_height);
+
+        let mut pool = BTreeMap::new();
+        let tx_hashes: Vec<_> = txs.into_iter()
+            .map(|tx| {
+                let hash = tx.hash();
+                pool.insert(hash, tx);
+                hash
+            })
+            .collect();
+        let (proposer, _) = self.scheduled_proposer(new_block_height);
+        let (block_hash, patch) = self.blockchain.create_patch(
+            proposer.validator_id().unwrap(),
+            new_block_height,
+            &tx_hashes,
+            &pool,
+        );
+
+        {
+            let mut mempool = self.mempool.write().expect(
+                "Cannot modify transactions in mempool",
+            );
+            if !mempool.is_empty() {
+                for hash in &tx_hashes {
+                    mempool.remove(hash);
+                }
+            }
+        }
+
+        let patch = self.before_commit(&block_hash, patch);
+        self.blockchain
+            .commit(&patch, block_hash, iter::empty())
+            .unwrap();
+        self.after_commit();
+    }
+
+    /// Creates a chain of blocks until a given height.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # extern crate exonum;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.create_blocks_until(Height(5));
+    /// assert_eq!(Height(5), testkit.height());
+    /// # }
+    pub fn create_blocks_until(&mut self, height: Height) {
+        while self.height() < height {
+            self.create_block();
+        }
+    }
+
+    /// Creates blocks with all transactions in the mempool until the given condition
+    /// on the blockchain state holds or the given maximum height is reached.
+    /// The condition is checked before creating each block.
+    ///
+    /// Returns `true` if the condition holds for the resulting blockchain state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # extern crate exonum;
+    /// # use exonum::blockchain::Schema;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// let reached = testkit.create_blocks_until_condition(Height(10), |snapshot| {
+    ///     Schema::new(snapshot).height() == Height(3)
+    /// });
+    /// assert!(reached);
+    /// assert_eq!(Height(3), testkit.height());
+    /// # }
+    /// ```
+    pub fn create_blocks_until_condition<F>(&mut self, max_height: Height, mut condition: F) -> bool
+    where
+        F: FnMut(&Snapshot) -> bool,
+    {
+        loop {
+            if condition(&*self.snapshot()) {
+                return true;
+            }
+            if self.height() >= max_height {
+                return false;
+            }
+            self.create_block();
+        }
+    }
+
+    /// Advances the blockchain to a given height by creating empty blocks. Unlike
+    /// `create_blocks_until()`, this method does not construct and sign consensus messages
+    /// for the created blocks, which makes it much faster when many blocks need to be created.
+    ///
+    /// Transactions in the mempool are not included into the created blocks, although
+    /// `handle_commit()` hooks of services are still invoked for every block.
+    ///
+    /// **Note:** Blocks created by this method have no precommits stored in the blockchain.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # extern crate exonum;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.fast_forward_to(Height(10_000));
+    /// assert_eq!(Height(10_000), testkit.height());
+    /// # }
+    /// ```
+    pub fn fast_forward_to(&mut self, height: Height) {
+        while self.height() < height {
+            let new_block_height = self.height().next();
+            self.update_configuration(new_block_height);
+            let (block_hash, patch) = {
+                let validator_id = self.leader().validator_id().unwrap();
+                let transactions = self.mempool();
+                self.blockchain.create_patch(
+                    validator_id,
+                    new_block_height,
+                    &[],
+                    &transactions,
+                )
+            };
+            let patch = self.before_commit(&block_hash, patch);
+            self.blockchain
+                .commit(&patch, block_hash, iter::empty())
+                .unwrap();
+            self.after_commit();
+            self.poll_events();
+        }
+    }
+
+    /// Registers a hook called for every block created by the testkit before the block
+    /// is committed. The hook receives the block header and a fork with the changes
+    /// introduced by the block applied on top of the current blockchain state.
+    ///
+    /// Hooks are called in the order of registration for blocks created by any method,
+    /// including `fast_forward_to()` and `probe()`. A panicking hook aborts the block creation
+    /// before any changes are committed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # extern crate exonum;
+    /// # use exonum::blockchain::Schema;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.on_before_commit(|block, fork| {
+    ///     // The block is not yet committed, but is visible in the fork.
+    ///     assert_eq!(Schema::new(fork).block_hashes_by_height().len(), block.height().0 + 1);
+    /// });
+    /// testkit.create_blocks_until(exonum::helpers::Height(5));
+    /// # }
+    /// ```
+    pub fn on_before_commit<F>(&mut self, hook: F)
+    where
+        F: FnMut(&Block, &Fork) + Send + 'static,
+    {
+        self.before_commit_hooks.push(Box::new(hook));
+    }
+
+    /// Registers a hook called for every block created by the testkit after the block
+    /// is committed. The hook receives the block header and a snapshot of the blockchain state
+    /// after the commit.
+    ///
+    /// Hooks are called in the order of registration for blocks created by any method,
+    /// including `fast_forward_to()` and `probe()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # extern crate exonum;
+    /// # use std::sync::{Arc, Mutex};
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// let heights = Arc::new(Mutex::new(Vec::new()));
+    /// {
+    ///     let heights = Arc::clone(&heights);
+    ///     testkit.on_after_commit(move |block, _| heights.lock().unwrap().push(block.height()));
+    /// }
+    /// testkit.create_blocks_until(Height(3));
+    /// assert_eq!(*heights.lock().unwrap(), vec![Height(1), Height(2), Height(3)]);
+    /// # }
+    /// ```
+    pub fn on_after_commit<F>(&mut self, hook: F)
+    where
+        F: FnMut(&Block, &Snapshot) + Send + 'static,
+    {
+        self.after_commit_hooks.push(Box::new(hook));
+    }
+
+    /// Registers a hook routing transactions produced in response to committed blocks
+    /// into the mempool, e.g., to emulate a service issuing transactions of another service
+    /// when the state of a third one changes. The hook receives the same arguments
+    /// as hooks registered with `on_after_commit()` and is called after them; transactions
+    /// that do not pass `verify()` are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto::{self, PublicKey};
+    /// # use exonum::storage::{MapIndex, Snapshot};
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// # fn last_message(snapshot: &Snapshot, pubkey: &PublicKey) -> Option<String> {
+    /// #     MapIndex::new("documentation.messages", snapshot).get(pubkey)
+    /// # }
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let (payer, payer_key) = (pubkey, key.clone());
+    /// testkit.route_after_commit(move |_, snapshot| {
+    ///     match last_message(snapshot, &payer) {
+    ///         Some(ref message) if message == "order" => {
+    ///             vec![Box::new(MyTransaction::new(&payer, "payment", &payer_key)) as Box<_>]
+    ///         }
+    ///         _ => Vec::new(),
+    ///     }
+    /// });
+    /// testkit.create_block_with_transaction(MyTransaction::new(&pubkey, "order", &key));
+    /// // The payment is created in response to the order.
+    /// testkit.create_block();
+    /// let message = last_message(&testkit.snapshot(), &pubkey);
+    /// assert_eq!(message, Some("payment".to_owned()));
+    /// # }
+    /// ```
+    pub fn route_after_commit<F>(&mut self, hook: F)
+    where
+        F: FnMut(&Block, &Snapshot) -> Vec<Box<Transaction>> + Send + 'static,
+    {
+        self.routing_hooks.push(Box::new(hook));
     }
 
-    /// Returns a blockchain instance for low level manipulations with storage.
-    pub fn blockchain_mut(&mut self) -> &mut Blockchain {
-        &mut self.blockchain
+    /// Returns the state hashes of all services together with the blockchain state,
+    /// so that schemas of several services can be inspected at once.
+    pub fn services_snapshot(&self) -> ServicesSnapshot {
+        ServicesSnapshot::new(&self.blockchain)
     }
 
-    /// Rolls the blockchain back for a certain number of blocks.
+    /// Registers a hook called when a configuration committed to the blockchain (e.g., with
+    /// `commit_configuration_change()`) becomes actual. The hook receives the header
+    /// of the activation block and the activated configuration; the configuration
+    /// is actual starting from the height following the activation block, i.e.,
+    /// `block.height().next() == configuration.actual_from`.
     ///
-    /// # Examples
+    /// Hooks are called after the hooks registered with `on_after_commit()`.
     ///
-    /// Rollbacks are useful in testing alternative scenarios (e.g., transactions executed
-    /// in different order and/or in different blocks) that require an expensive setup:
+    /// # Example
     ///
     /// ```
-    /// # #[macro_use] extern crate exonum;
-    /// # #[macro_use] extern crate exonum_testkit;
-    /// # use exonum::blockchain::{Service, Transaction};
-    /// # use exonum::messages::RawTransaction;
-    /// # use exonum::encoding;
-    /// # use exonum_testkit::{TestKit, TestKitBuilder};
-    /// #
-    /// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
-    /// # pub struct MyService;
-    /// # impl Service for MyService {
-    /// #    fn service_name(&self) -> &'static str {
-    /// #        "documentation"
-    /// #    }
-    /// #    fn state_hash(&self, _: &exonum::storage::Snapshot) -> Vec<exonum::crypto::Hash> {
-    /// #        Vec::new()
-    /// #    }
-    /// #    fn service_id(&self) -> u16 {
-    /// #        0
-    /// #    }
-    /// #    fn tx_from_raw(&self, _raw: RawTransaction) -> FromRawResult {
-    /// #        unimplemented!();
-    /// #    }
-    /// # }
-    /// #
-    /// # message! {
-    /// #     struct MyTransaction {
-    /// #         const TYPE = 0;
-    /// #         const ID = 0;
-    /// #         from: &exonum::crypto::PublicKey,
-    /// #         msg: &str,
-    /// #     }
-    /// # }
-    /// # impl Transaction for MyTransaction {
-    /// #     fn verify(&self) -> bool { true }
-    /// #     fn execute(&self, _: &mut exonum::storage::Fork) {}
-    /// # }
-    /// #
-    /// # fn expensive_setup(_: &mut TestKit) {}
-    /// # fn assert_something_about(_: &TestKit) {}
-    /// #
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use std::sync::{Arc, Mutex};
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestKitBuilder;
     /// # fn main() {
-    /// let mut testkit = TestKitBuilder::validator()
-    ///     .with_service(MyService)
-    ///     .create();
-    /// expensive_setup(&mut testkit);
-    /// let (pubkey, key) = exonum::crypto::gen_keypair();
-    /// let tx_a = MyTransaction::new(&pubkey, "foo", &key);
-    /// let tx_b = MyTransaction::new(&pubkey, "bar", &key);
-    /// testkit.create_block_with_transactions(txvec![tx_a.clone(), tx_b.clone()]);
-    /// assert_something_about(&testkit);
-    /// testkit.rollback(1);
-    /// testkit.create_block_with_transactions(txvec![tx_a.clone()]);
-    /// testkit.create_block_with_transactions(txvec![tx_b.clone()]);
-    /// assert_something_about(&testkit);
-    /// testkit.rollback(2);
+    /// let mut testkit = TestKitBuilder::auditor().with_validators(3).create();
+    /// let activated = Arc::new(Mutex::new(Vec::new()));
+    /// {
+    ///     let activated = Arc::clone(&activated);
+    ///     testkit.on_configuration_activated(move |block, configuration| {
+    ///         assert_eq!(configuration.validator_keys.len(), 4);
+    ///         activated.lock().unwrap().push(block.height());
+    ///     });
+    /// }
+    /// let mut proposal = testkit.configuration_change_proposal();
+    /// proposal.set_actual_from(Height(5));
+    /// // Add us to validators.
+    /// let mut validators = proposal.validators().to_vec();
+    /// validators.push(testkit.network().us().clone());
+    /// proposal.set_validators(validators);
+    /// testkit.commit_configuration_change(proposal);
+    /// testkit.activate_following_configuration();
+    /// assert_eq!(*activated.lock().unwrap(), vec![Height(4)]);
     /// # }
     /// ```
-    pub fn rollback(&mut self, blocks: usize) {
-        assert!(
-            (blocks as u64) <= self.height().0,
-            "Cannot rollback past genesis block"
-        );
-        self.db_handler.rollback(blocks);
+    pub fn on_configuration_activated<F>(&mut self, hook: F)
+    where
+        F: FnMut(&Block, &StoredConfiguration) + Send + 'static,
+    {
+        self.activation_hooks.push(Box::new(hook));
     }
 
-    /// Executes a list of transactions given the current state of the blockchain, but does not
-    /// commit execution results to the blockchain. The execution result is the same
-    /// as if transactions were included into a new block; for example,
-    /// transactions included into one of previous blocks do not lead to any state changes.
-    pub fn probe_all<I>(&mut self, transactions: I) -> Box<Snapshot>
+    /// Registers a named invariant evaluated on the blockchain state after every block created
+    /// by the testkit, including blocks created by `fast_forward_to()` and `probe()`.
+    /// Invariants are evaluated in the order of registration before hooks registered with
+    /// `on_after_commit()`.
+    ///
+    /// # Panics
+    ///
+    /// - Block creation panics if an invariant does not hold. The panic message contains
+    ///   the name of the invariant, the height of the block and the hashes of transactions
+    ///   in the block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # #[macro_use] extern crate exonum_testkit;
+    /// # use exonum::crypto::{self, PublicKey};
+    /// # use exonum::storage::MapIndex;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// testkit.add_invariant("messages are not empty", |snapshot| {
+    ///     let messages: MapIndex<_, PublicKey, String> =
+    ///         MapIndex::new("documentation.messages", snapshot);
+    ///     messages.values().all(|message| !message.is_empty())
+    /// });
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let (other_pubkey, other_key) = crypto::gen_keypair();
+    /// testkit.create_block_with_transactions(txvec![
+    ///     MyTransaction::new(&pubkey, "foo", &key),
+    ///     MyTransaction::new(&other_pubkey, "bar", &other_key),
+    /// ]);
+    /// # }
+    /// ```
+    pub fn add_invariant<F>(&mut self, name: &str, invariant: F)
     where
-        I: IntoIterator<Item = Box<Transaction>>,
+        F: Fn(&Snapshot) -> bool + Send + Sync + 'static,
     {
-        // Filter out already committed transactions; otherwise,
-        // `create_block_with_transactions()` will panic.
-        let schema = CoreSchema::new(self.snapshot());
-        let uncommitted_txs = transactions.into_iter().filter(|tx| {
-            !schema.transactions().contains(&tx.hash())
-        });
-
-        self.create_block_with_transactions(uncommitted_txs);
-        let snapshot = self.snapshot();
-        self.rollback(1);
-        snapshot
+        self.invariants.push((name.to_owned(), Arc::new(invariant)));
     }
 
-    /// Executes a transaction given the current state of the blockchain but does not
-    /// commit execution results to the blockchain. The execution result is the same
-    /// as if a transaction was included into a new block; for example,
-    /// a transaction included into one of previous blocks does not lead to any state changes.
-    pub fn probe<T: Transaction>(&mut self, transaction: T) -> Box<Snapshot> {
-        self.probe_all(vec![Box::new(transaction) as Box<Transaction>])
+    /// Returns the hash of latest committed block.
+    pub fn last_block_hash(&self) -> crypto::Hash {
+        self.blockchain.last_hash()
     }
 
-    fn do_create_block(&mut self, tx_hashes: &[crypto::Hash]) {
-        let new_block_height = self.height().next();
-        let last_hash = self.last_block_hash();
+    /// Returns the height of latest committed block.
+    pub fn height(&self) -> Height {
+        self.blockchain.last_block().height()
+    }
 
-        self.update_configuration(new_block_height);
-        let (block_hash, patch) = {
-            let validator_id = self.leader().validator_id().unwrap();
-            let transactions = self.mempool();
-            self.blockchain.create_patch(
-                validator_id,
-                new_block_height,
-                tx_hashes,
-                &transactions,
-            )
-        };
+    /// Returns the header of the latest committed block.
+    pub fn last_block(&self) -> Block {
+        self.blockchain.last_block()
+    }
 
-        // Remove txs from mempool
-        {
-            let mut transactions = self.mempool.write().expect(
-                "Cannot modify transactions in mempool",
-            );
-            for hash in tx_hashes {
-                transactions.remove(hash);
-            }
-        }
+    /// Returns the location of the committed transaction with the given hash, or `None`
+    /// if the transaction is not committed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # #[macro_use] extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let tx1 = MyTransaction::new(&pubkey, "foo", &key);
+    /// let tx2 = MyTransaction::new(&pubkey, "bar", &key);
+    /// testkit.create_block_with_transactions(txvec![tx1.clone(), tx2.clone()]);
+    /// let location = testkit.tx_location(&tx2.hash()).unwrap();
+    /// assert_eq!(location.block_height(), testkit.height());
+    /// assert_eq!(location.position_in_block(), 1);
+    /// assert_eq!(testkit.block_of_tx(&tx2.hash()), Some(testkit.last_block()));
+    /// # }
+    /// ```
+    pub fn tx_location(&self, tx_hash: &crypto::Hash) -> Option<TxLocation> {
+        CoreSchema::new(&self.snapshot())
+            .tx_location_by_tx_hash()
+            .get(tx_hash)
+    }
 
-        let propose = self.leader().create_propose(
-            new_block_height,
-            &last_hash,
-            tx_hashes,
-        );
-        let precommits: Vec<_> = self.network()
-            .validators()
-            .iter()
-            .map(|v| v.create_precommit(&propose, &block_hash))
-            .collect();
+    /// Returns the header of the block containing the committed transaction with the given
+    /// hash, or `None` if the transaction is not committed.
+    pub fn block_of_tx(&self, tx_hash: &crypto::Hash) -> Option<Block> {
+        let snapshot = self.snapshot();
+        let schema = CoreSchema::new(&snapshot);
+        let location = match schema.tx_location_by_tx_hash().get(tx_hash) {
+            Some(location) => location,
+            None => return None,
+        };
+        schema
+            .block_hashes_by_height()
+            .get(location.block_height().0)
+            .and_then(|block_hash| schema.blocks().get(&block_hash))
+    }
 
-        self.blockchain
-            .commit(&patch, block_hash, precommits.iter())
-            .unwrap();
+    /// Returns the actual blockchain configuration.
+    pub fn actual_configuration(&self) -> StoredConfiguration {
+        CoreSchema::new(&self.snapshot()).actual_configuration()
+    }
 
-        self.poll_events();
+    /// Returns the configuration committed to the blockchain, which becomes actual
+    /// at a future height, or `None` if there is no such configuration.
+    ///
+    /// A proposal passed to `commit_configuration_change()` is committed to the blockchain
+    /// with the next created block, so it is not returned until the block is created.
+    pub fn following_configuration(&self) -> Option<StoredConfiguration> {
+        CoreSchema::new(&self.snapshot()).following_configuration()
     }
 
-    /// Update test network configuration if such an update has been scheduled
-    /// with `commit_configuration_change`.
-    fn update_configuration(&mut self, new_block_height: Height) {
+    /// Creates blocks with all transactions in the mempool until the configuration
+    /// scheduled with `commit_configuration_change()` or committed to the blockchain becomes
+    /// actual, i.e., until the height preceding its `actual_from` height. Returns
+    /// the activated configuration.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if no configuration is scheduled for activation.
+    pub fn activate_following_configuration(&mut self) -> StoredConfiguration {
         use ConfigurationProposalState::*;
 
-        let actual_from = new_block_height.next();
-        if let Some(cfg_proposal) = self.cfg_proposal.take() {
-            match cfg_proposal {
-                Uncommitted(cfg_proposal) => {
-                    // Commit configuration proposal
-                    let stored = cfg_proposal.stored_configuration().clone();
-                    let mut fork = self.blockchain.fork();
-                    CoreSchema::new(&mut fork).commit_configuration(stored);
-                    let changes = fork.into_patch();
-                    self.blockchain.merge(changes).unwrap();
-                    self.cfg_proposal = Some(Committed(cfg_proposal));
-                }
-                Committed(cfg_proposal) => {
-                    if cfg_proposal.actual_from() == actual_from {
-                        // Modify the self configuration
-                        self.network_mut().update(
-                            cfg_proposal.us,
-                            cfg_proposal.validators,
-                        );
-                    } else {
-                        self.cfg_proposal = Some(Committed(cfg_proposal));
-                    }
-                }
+        let actual_from = match self.cfg_proposal {
+            Some(Uncommitted(ref proposal)) |
+            Some(Committed(ref proposal)) => proposal.actual_from(),
+            None => {
+                self.following_configuration()
+                    .expect("No configuration is scheduled for activation")
+                    .actual_from
             }
-        }
+        };
+        self.create_blocks_until(actual_from.previous());
+        self.actual_configuration()
     }
 
-    /// Creates a block with the given transactions.
-    /// Transactions that are in the mempool will be ignored.
+    /// Returns reference to validator with the given identifier.
     ///
     /// # Panics
+    /// - Panics if validator with the given id is absent in test network.
+    pub fn validator(&self, id: ValidatorId) -> &TestNode {
+        &self.network.validators[id.0 as usize]
+    }
+
+    /// Returns a handle to the validator with the given identifier, which produces Byzantine
+    /// consensus messages for the next height of the blockchain. The messages are timestamped
+    /// with the current time of the testkit time provider.
     ///
-    /// - Panics if any of transactions has been already committed to the blockchain.
-    pub fn create_block_with_transactions<I>(&mut self, txs: I)
-    where
-        I: IntoIterator<Item = Box<Transaction>>,
-    {
-        let tx_hashes: Vec<_> = {
-            let mut mempool = self.mempool.write().expect(
-                "Cannot write transactions to mempool",
-            );
+    /// See `MaliciousNode` for details.
+    ///
+    /// # Panics
+    /// - Panics if validator with the given id is absent in test network.
+    pub fn malicious_node(&self, id: ValidatorId) -> MaliciousNode {
+        MaliciousNode::new(
+            self.validator(id).clone(),
+            self.height().next(),
+            self.last_block_hash(),
+            self.time_provider.current_time(),
+        )
+    }
 
-            let snapshot = self.snapshot();
-            let schema = CoreSchema::new(&snapshot);
-            txs.into_iter()
-                .filter(|tx| tx.verify())
-                .map(|tx| {
-                    let txid = tx.hash();
-                    assert!(
-                        !schema.transactions().contains(&txid),
-                        "Transaction is already committed: {:?}",
-                        tx
-                    );
-                    mempool.insert(txid, tx);
-                    txid
-                })
-                .collect()
-        };
-        self.create_block_with_tx_hashes(&tx_hashes);
+    /// Returns sufficient number of validators for the Byzantine Fault Toulerance consensus.
+    pub fn majority_count(&self) -> usize {
+        self.network().majority_count()
     }
 
-    /// Creates a block with the given transaction.
-    /// Transactions that are in the mempool will be ignored.
+    /// Returns the test node memory pool handle.
+    pub fn mempool(&self) -> RwLockReadGuard<BTreeMap<crypto::Hash, Box<Transaction>>> {
+        self.mempool.read().expect(
+            "Can't read transactions from the mempool.",
+        )
+    }
+
+    /// Returns transactions added to the mempool while committing the latest block, most notably,
+    /// transactions broadcast by `handle_commit()` hooks of services on that block.
+    /// Such transactions are committed with the next block created by `create_block()`.
     ///
-    /// # Panics
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.create_block();
+    /// // None of the services broadcasts transactions after commit.
+    /// assert!(testkit.broadcast_txs_since_last_block().is_empty());
+    /// # }
+    /// ```
+    pub fn broadcast_txs_since_last_block(&self) -> Vec<Box<Transaction>> {
+        self.broadcast_txs
+            .iter()
+            .filter_map(|raw| self.blockchain.tx_from_raw(raw.clone()))
+            .collect()
+    }
+
+    /// Sets the order in which transactions from the mempool are included into blocks
+    /// created with `create_block()`. This allows to reproduce a specific interleaving
+    /// of transactions, e.g., one observed in production.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::messages::Message;
+    /// # use exonum_testkit::{PoolOrdering, TestKit, TestKitBuilder};
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let (first, second) = (
+    ///     MyTransaction::new(&pubkey, "first", &key),
+    ///     MyTransaction::new(&pubkey, "second", &key),
+    /// );
+    /// let position = |testkit: &TestKit, tx: &MyTransaction| {
+    ///     testkit.tx_location(&tx.hash()).unwrap().position_in_block()
+    /// };
+    ///
+    /// // Transactions are ordered by decreasing hashes.
+    /// testkit.set_pool_ordering(PoolOrdering::comparator(|a, b| b.hash().cmp(&a.hash())));
+    /// testkit.add_tx(first.clone()).unwrap();
+    /// testkit.add_tx(second.clone()).unwrap();
+    /// testkit.create_block();
+    /// let first_is_greater = first.hash() > second.hash();
+    /// assert_eq!(position(&testkit, &first) == 0, first_is_greater);
     ///
-    /// - Panics if given transaction has been already committed to the blockchain.
-    pub fn create_block_with_transaction<T: Transaction>(&mut self, tx: T) {
-        self.create_block_with_transactions(txvec![tx]);
+    /// // Transactions are included in the given order.
+    /// let (third, fourth) = (
+    ///     MyTransaction::new(&pubkey, "third", &key),
+    ///     MyTransaction::new(&pubkey, "fourth", &key),
+    /// );
+    /// testkit.set_pool_ordering(PoolOrdering::Explicit(vec![fourth.hash(), third.hash()]));
+    /// testkit.add_tx(third.clone()).unwrap();
+    /// testkit.add_tx(fourth.clone()).unwrap();
+    /// testkit.create_block();
+    /// assert_eq!(position(&testkit, &fourth), 0);
+    /// assert_eq!(position(&testkit, &third), 1);
+    /// # }
+    /// ```
+    pub fn set_pool_ordering(&mut self, ordering: PoolOrdering) {
+        self.pool_ordering = ordering;
     }
 
-    /// Creates block with the specified transactions. The transactions must be previously
-    /// sent to the node via API or directly put into the `channel()`.
-    ///
-    /// # Panics
+    /// Returns the status of the transaction with the given hash.
     ///
-    /// - Panics in the case any of transaction hashes are not in the mempool.
-    pub fn create_block_with_tx_hashes(&mut self, tx_hashes: &[crypto::Hash]) {
-        self.poll_events();
-
-        {
-            let txs = self.mempool();
-            for hash in tx_hashes {
-                assert!(txs.contains_key(hash));
+    /// Transactions sent to the node via API are not visible until the events
+    /// are polled with `poll_events()` or a block is created.
+    pub fn tx_status(&self, tx_hash: &crypto::Hash) -> TxStatus {
+        let snapshot = self.snapshot();
+        let schema = CoreSchema::new(&snapshot);
+        if let Some(location) = schema.tx_location_by_tx_hash().get(tx_hash) {
+            TxStatus::Committed {
+                height: location.block_height(),
+                position_in_block: location.position_in_block(),
             }
+        } else if self.mempool().contains_key(tx_hash) {
+            TxStatus::InPool
+        } else {
+            TxStatus::Unknown
         }
-
-        self.do_create_block(tx_hashes);
     }
 
-    /// Creates block with all transactions in the mempool.
-    pub fn create_block(&mut self) {
-        self.poll_events();
-
-        let tx_hashes: Vec<_> = self.mempool().keys().cloned().collect();
-
-        self.do_create_block(&tx_hashes);
+    /// Returns hashes of the transactions dropped from the mempool because of its size limit,
+    /// in the order of eviction.
+    ///
+    /// See `TestKitBuilder::with_mempool_limit()` for details.
+    pub fn evicted_transactions(&self) -> Vec<crypto::Hash> {
+        self.mempool_limiter
+            .read()
+            .expect("Cannot acquire read lock on mempool limiter")
+            .evicted()
+            .to_vec()
     }
 
-    /// Creates a chain of blocks until a given height.
+    /// Returns all requests sent to the node through `ApiSender`, e.g., by service API handlers
+    /// or `handle_commit()` hooks, in the order of arrival. Requests are recorded regardless
+    /// of whether they have been processed successfully; for example, the list includes
+    /// transactions already committed to the blockchain and transactions rejected because
+    /// of the mempool size limit.
+    ///
+    /// Requests are not visible until the events are polled with `poll_events()`
+    /// or a block is created.
     ///
     /// # Example
     ///
     /// ```
-    /// # extern crate exonum_testkit;
     /// # extern crate exonum;
-    /// # use exonum::helpers::Height;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::crypto;
+    /// # use exonum::messages::Message;
     /// # use exonum_testkit::TestKitBuilder;
+    /// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+    /// #
     /// # fn main() {
-    /// let mut testkit = TestKitBuilder::validator().create();
-    /// testkit.create_blocks_until(Height(5));
-    /// assert_eq!(Height(5), testkit.height());
+    /// let mut testkit = TestKitBuilder::validator()
+    ///     .with_service(MyService)
+    ///     .create();
+    /// let api = testkit.api();
+    /// let (pubkey, key) = crypto::gen_keypair();
+    /// let tx = MyTransaction::new(&pubkey, "foo", &key);
+    /// api.send(tx.clone());
+    /// testkit.poll_events();
+    /// let sent: Vec<_> = testkit.sent_requests().iter().filter_map(|r| r.tx_hash()).collect();
+    /// assert_eq!(sent, vec![tx.hash()]);
     /// # }
-    pub fn create_blocks_until(&mut self, height: Height) {
-        while self.height() < height {
-            self.create_block();
-        }
-    }
-
-    /// Returns the hash of latest committed block.
-    pub fn last_block_hash(&self) -> crypto::Hash {
-        self.blockchain.last_hash()
-    }
-
-    /// Returns the height of latest committed block.
-    pub fn height(&self) -> Height {
-        self.blockchain.last_block().height()
-    }
-
-    /// Returns the actual blockchain configuration.
-    pub fn actual_configuration(&self) -> StoredConfiguration {
-        CoreSchema::new(&self.snapshot()).actual_configuration()
+    /// ```
+    pub fn sent_requests(&self) -> Vec<SentRequest> {
+        self.sent_requests
+            .read()
+            .expect("Cannot read sent requests")
+            .clone()
     }
 
-    /// Returns reference to validator with the given identifier.
+    /// Marks the validator with the given id as offline at the next height, so that it does not
+    /// propose the next block. If the validator is scheduled to propose the block, the block
+    /// is proposed by the next validator in a later round. Validators take turns
+    /// in the order of their identifiers (see `TestNetwork::leader_for()`).
+    ///
+    /// The mark only concerns blocks created without an explicitly specified proposer
+    /// and applies to the next height only.
     ///
     /// # Panics
+    ///
     /// - Panics if validator with the given id is absent in test network.
-    pub fn validator(&self, id: ValidatorId) -> &TestNode {
-        &self.network.validators[id.0 as usize]
-    }
+    /// - Panics if all validators are marked as offline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::{Height, Round, ValidatorId};
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    /// testkit.skip_leader(ValidatorId(0));
+    /// testkit.skip_leader(ValidatorId(1));
+    /// testkit.create_block();
+    ///
+    /// let block = testkit.explorer().block_with_txs(Height(1)).unwrap();
+    /// assert_eq!(block.header.proposer_id(), ValidatorId(2));
+    /// assert_eq!(block.precommits[0].round(), Round(3));
+    /// # }
+    /// ```
+    pub fn skip_leader(&mut self, id: ValidatorId) {
+        let validators_count = self.network().validators().len();
+        assert!(
+            (id.0 as usize) < validators_count,
+            "Validator with id {:?} is absent in test network",
+            id
+        );
 
-    /// Returns sufficient number of validators for the Byzantine Fault Toulerance consensus.
-    pub fn majority_count(&self) -> usize {
-        NodeState::byzantine_majority_count(self.network().validators().len())
+        let next_height = self.height().next();
+        self.skipped_leaders.retain(|&(height, _)| height >= next_height);
+        if !self.skipped_leaders.contains(&(next_height, id)) {
+            self.skipped_leaders.push((next_height, id));
+        }
+        let skipped_count = self.skipped_leaders
+            .iter()
+            .filter(|&&(height, _)| height == next_height)
+            .count();
+        assert!(
+            skipped_count < validators_count,
+            "Cannot mark all validators as offline"
+        );
     }
 
-    /// Returns the test node memory pool handle.
-    pub fn mempool(&self) -> RwLockReadGuard<BTreeMap<crypto::Hash, Box<Transaction>>> {
-        self.mempool.read().expect(
-            "Can't read transactions from the mempool.",
-        )
+    /// Advances consensus at the next height by `rounds` rounds without committing a block,
+    /// as if the rounds have ended with timeouts. The next block created without an explicitly
+    /// specified proposer is proposed at a later round by the leader of that round
+    /// (see `TestNetwork::leader_for()`). Calls at the same height accumulate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::{Height, Round, ValidatorId};
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().with_validators(4).create();
+    /// testkit.skip_rounds(2);
+    /// testkit.create_block();
+    ///
+    /// let block = testkit.explorer().block_with_txs(Height(1)).unwrap();
+    /// assert_eq!(block.header.proposer_id(), ValidatorId(2));
+    /// assert_eq!(block.precommits[0].round(), Round(3));
+    /// # }
+    /// ```
+    pub fn skip_rounds(&mut self, rounds: u32) {
+        let next_height = self.height().next();
+        if self.skipped_rounds.0 == next_height {
+            self.skipped_rounds.1 += rounds;
+        } else {
+            self.skipped_rounds = (next_height, rounds);
+        }
     }
 
     /// Returns the leader on the current height. At the moment first validator.
@@ -895,6 +4383,43 @@ impl TestKit {
         &mut self.network
     }
 
+    /// Returns the seed, from which the keys of the testkit are derived. The seed is set
+    /// with `TestKitBuilder::with_seed()` or the `EXONUM_TESTKIT_SEED` environment variable;
+    /// otherwise, it is chosen randomly. Randomized tools, such as `Fuzzer` and `Workload`,
+    /// use this seed unless another one is specified.
+    pub fn seed(&self) -> u64 {
+        self.key_generator.seed().expect(
+            "Keys of the testkit are always derived from a seed",
+        )
+    }
+
+    /// Generates a keypair, e.g., for signing transactions. The sequence of generated
+    /// keypairs is reproducible for the same `seed()`.
+    pub fn gen_keypair(&mut self) -> (crypto::PublicKey, crypto::SecretKey) {
+        self.key_generator.gen_keypair()
+    }
+
+    /// Returns the account with the given name. The keypair of the account is generated
+    /// with `gen_keypair()` when the account is requested for the first time.
+    pub fn account(&mut self, name: &str) -> Account {
+        if let Some(account) = self.accounts.get(name) {
+            return account.clone();
+        }
+        let account = Account::new(name, self.gen_keypair());
+        self.accounts.insert(name.to_owned(), account.clone());
+        account
+    }
+
+    /// Creates a transaction with the constructor registered under the given name
+    /// in the factory set with `TestKitBuilder::with_tx_factory()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if no transaction with the given name is registered.
+    pub fn tx(&self, name: &str, author: &Account, params: serde_json::Value) -> Box<Transaction> {
+        self.tx_factory.create(name, author, &params)
+    }
+
     /// Returns a copy of the actual configuration of the testkit.
     /// The returned configuration could be modified for use with
     /// `commit_configuration_change` method.
@@ -904,6 +4429,7 @@ impl TestKit {
             self.network().us().clone(),
             self.network().validators().into(),
             stored_configuration,
+            self.key_generator.child(self.height().0),
         )
     }
 
@@ -977,16 +4503,93 @@ impl TestKit {
     }
 }
 
+/// Error returned by `TestKit::add_tx()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddTxError {
+    /// The transaction does not pass `verify()`.
+    Invalid,
+    /// The transaction has been already committed to the blockchain.
+    AlreadyCommitted,
+    /// The transaction is already in the mempool.
+    Duplicate,
+    /// The raw message cannot be parsed into a transaction by any of the testkit services.
+    Malformed,
+}
+
+/// Status of a transaction from the point of view of the testkit node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// The transaction is unknown to the node.
+    Unknown,
+    /// The transaction is in the mempool.
+    InPool,
+    /// The transaction is committed to the blockchain.
+    Committed {
+        /// Height of the block containing the transaction.
+        height: Height,
+        /// Zero-based position of the transaction in the block.
+        position_in_block: u64,
+    },
+}
+
+/// Modification of an index entry made by a block. See `TestKit::last_block_changes()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageChange {
+    /// Name of the index.
+    pub index: String,
+    /// Key of the entry.
+    pub key: Vec<u8>,
+    /// Value of the entry before the block, or `None` if the entry has been created.
+    pub old: Option<Vec<u8>>,
+    /// Value of the entry after the block, or `None` if the entry has been removed.
+    pub new: Option<Vec<u8>>,
+}
+
+/// Request sent to the testkit node through `ApiSender`.
+#[derive(Debug, Clone)]
+pub enum SentRequest {
+    /// Transaction sent to the node.
+    Transaction {
+        /// Hash of the transaction.
+        hash: crypto::Hash,
+        /// Raw transaction message.
+        raw: RawMessage,
+    },
+    /// Request to connect to the peer with the given address.
+    PeerAdd(SocketAddr),
+}
+
+impl SentRequest {
+    fn from_message(message: &ExternalMessage) -> Self {
+        match *message {
+            ExternalMessage::Transaction(ref tx) => SentRequest::Transaction {
+                hash: tx.hash(),
+                raw: tx.raw().clone(),
+            },
+            ExternalMessage::PeerAdd(addr) => SentRequest::PeerAdd(addr),
+        }
+    }
+
+    /// Returns the hash of the sent transaction, or `None` if the request is not a transaction.
+    pub fn tx_hash(&self) -> Option<crypto::Hash> {
+        match *self {
+            SentRequest::Transaction { hash, .. } => Some(hash),
+            SentRequest::PeerAdd(_) => None,
+        }
+    }
+}
+
 /// A configuration of the test network.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TestNetworkConfiguration {
     us: TestNode,
     validators: Vec<TestNode>,
     stored_configuration: StoredConfiguration,
+    key_generator: KeyGenerator,
 }
 
 // A new configuration proposal state.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum ConfigurationProposalState {
     Uncommitted(TestNetworkConfiguration),
     Committed(TestNetworkConfiguration),
@@ -997,6 +4600,7 @@ impl TestNetworkConfiguration {
         us: TestNode,
         validators: Vec<TestNode>,
         mut stored_configuration: StoredConfiguration,
+        key_generator: KeyGenerator,
     ) -> Self {
         let prev_hash = exonum::storage::StorageValue::hash(&stored_configuration);
         stored_configuration.previous_cfg_hash = prev_hash;
@@ -1004,6 +4608,7 @@ impl TestNetworkConfiguration {
             us,
             validators,
             stored_configuration,
+            key_generator,
         }
     }
 
@@ -1064,6 +4669,39 @@ impl TestNetworkConfiguration {
         self.update_our_role();
     }
 
+    /// Adds a new validator with freshly generated keys to the end of the validators list.
+    /// Returns the added node.
+    pub fn add_validator(&mut self) -> &TestNode {
+        let mut validators = self.validators.clone();
+        validators.push(TestNode::generate(&mut self.key_generator, None));
+        self.set_validators(validators);
+        self.validators.last().unwrap()
+    }
+
+    /// Removes the validator with the given id from the validators list. Validators following
+    /// the removed one are renumbered. Returns the removed node.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if there is no validator with the given id.
+    /// - Panics if the validator is the only one in the list.
+    pub fn remove_validator(&mut self, id: ValidatorId) -> TestNode {
+        assert!(
+            (id.0 as usize) < self.validators.len(),
+            "Validator with id {:?} is absent in test network configuration",
+            id
+        );
+        assert!(
+            self.validators.len() > 1,
+            "Cannot remove the only validator from test network configuration"
+        );
+        let mut validators = self.validators.clone();
+        let mut removed = validators.remove(id.0 as usize);
+        removed.change_role(None);
+        self.set_validators(validators);
+        removed
+    }
+
     /// Returns the configuration for service with the given identifier.
     pub fn service_config<D>(&self, id: &str) -> D
     where
@@ -1127,10 +4765,16 @@ impl ApiKind {
 
 /// API encapsulation for the testkit. Allows to execute and synchronously retrieve results
 /// for REST-ful endpoints of services.
+///
+/// WebSocket endpoints are not supported: services expose only Iron HTTP handlers via
+/// `Service::public_api_handler()` and `Service::private_api_handler()`, so there is no
+/// in-process WebSocket handler to connect to. Subscription-style code can be tested
+/// by polling HTTP endpoints after creating blocks.
 pub struct TestKitApi {
     public_mount: Mount,
     private_mount: Mount,
     api_sender: ApiSender,
+    fixture: Option<Mutex<Fixture>>,
 }
 
 impl fmt::Debug for TestKitApi {
@@ -1142,6 +4786,11 @@ impl fmt::Debug for TestKitApi {
 impl TestKitApi {
     /// Creates a new instance of API.
     fn new(testkit: &TestKit) -> Self {
+        TestKitApi::with_service_handler(testkit, Box::new(testkit.public_api_mount()))
+    }
+
+    /// Creates a new instance of API with the given handler of public service endpoints.
+    fn with_service_handler(testkit: &TestKit, service_handler: Box<Handler>) -> Self {
         use std::sync::Arc;
         use exonum::api::{public, Api};
 
@@ -1150,9 +4799,7 @@ impl TestKitApi {
         TestKitApi {
             public_mount: {
                 let mut mount = Mount::new();
-
-                let service_mount = testkit.public_api_mount();
-                mount.mount("api/services", service_mount);
+                mount.mount("api/services", service_handler);
 
                 let mut router = Router::new();
                 let pool = Arc::clone(&testkit.mempool);
@@ -1179,9 +4826,56 @@ impl TestKitApi {
             },
 
             api_sender: testkit.api_sender.clone(),
+            fixture: None,
         }
     }
 
+    /// Attaches a fixture file to the API, which allows to record requests to the API
+    /// together with responses, and to check or replay the responses in subsequent runs.
+    /// This is useful for contract tests catching unintended changes in service APIs.
+    ///
+    /// Requests are matched with the fixture in the order they are performed. Request and
+    /// response headers are not recorded. In the `Record` mode, the fixture is written when
+    /// the API instance is dropped.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the fixture cannot be read in the `Check` or `Replay` mode.
+    /// - In the `Check` and `Replay` modes, a request panics if it differs from the recorded
+    ///   one; in the `Check` mode, it also panics if the response differs from the recorded
+    ///   one. Additionally, dropping the API panics if not all recorded requests have been
+    ///   performed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate tempdir;
+    /// # use exonum::blockchain::Block;
+    /// # use exonum_testkit::{ApiKind, FixtureMode, TestKitBuilder};
+    /// # use tempdir::TempDir;
+    /// # fn main() {
+    /// # let dir = TempDir::new("exonum-testkit-doc").unwrap();
+    /// # let path = dir.path().join("blocks.json");
+    /// let testkit = TestKitBuilder::validator().create();
+    /// {
+    ///     // In tests, the mode is usually selected with an environment variable.
+    ///     let api = testkit.api().with_fixture(&path, FixtureMode::Record);
+    ///     let blocks: Vec<Block> = api.get(ApiKind::Explorer, "v1/blocks?count=10");
+    ///     assert_eq!(blocks.len(), 1);
+    /// }
+    /// // Subsequent runs check that responses have not changed.
+    /// let api = testkit.api().with_fixture(&path, FixtureMode::Check);
+    /// let blocks: Vec<Block> = api.get(ApiKind::Explorer, "v1/blocks?count=10");
+    /// assert_eq!(blocks.len(), 1);
+    /// # }
+    /// ```
+    pub fn with_fixture<P: AsRef<Path>>(mut self, path: P, mode: FixtureMode) -> Self {
+        self.fixture = Some(Mutex::new(Fixture::new(path.as_ref(), mode)));
+        self
+    }
+
     /// Returns the mounting point for public APIs. Useful for intricate testing not covered
     /// by `get*` and `post*` functions.
     pub fn public_mount(&self) -> &Mount {
@@ -1194,6 +4888,90 @@ impl TestKitApi {
         &self.private_mount
     }
 
+    /// Returns a typed client for the API of a service. See `service_api_client!`.
+    pub fn client<'a, C: ServiceApiClient<'a>>(&'a self) -> C {
+        C::from_api(self)
+    }
+
+    /// Sends `iterations` requests to the public API from each of `concurrency` threads
+    /// simultaneously and aggregates the responses. This allows to catch bugs in handlers
+    /// manifesting under parallel load, such as races on interior mutability or reads
+    /// from inconsistent snapshots.
+    ///
+    /// `method` is either `"GET"` or `"POST"`; POST requests are sent with an empty body
+    /// (use `stress_post()` to specify the body). `path` is relative to the API root,
+    /// e.g., `api/services/cryptocurrency/v1/wallets`. Panics in handlers are recorded
+    /// in the report. Requests are not recorded in the fixture attached to the API.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `method` is neither `"GET"` nor `"POST"`.
+    /// - Panics if `concurrency` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # use exonum_testkit::TestKitBuilder;
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.create_block();
+    /// let mut api = testkit.api();
+    /// let report = api.stress("GET", "api/explorer/v1/blocks?count=10", 8, 100);
+    /// assert!(report.is_successful(), "{:?}", report.panics());
+    /// assert!(report.is_consistent(), "{:?}", report.bodies());
+    /// # }
+    /// ```
+    pub fn stress(
+        &mut self,
+        method: &str,
+        path: &str,
+        concurrency: usize,
+        iterations: usize,
+    ) -> StressReport {
+        let body = match method {
+            "GET" => None,
+            "POST" => Some(String::new()),
+            _ => panic!("Unsupported HTTP method for stress requests: {}", method),
+        };
+        self.stress_internal(path, body, concurrency, iterations)
+    }
+
+    /// Sends concurrent POST requests with the given body to the public API.
+    /// See `stress()` for details.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `concurrency` is zero.
+    pub fn stress_post<T: Serialize>(
+        &mut self,
+        path: &str,
+        body: &T,
+        concurrency: usize,
+        iterations: usize,
+    ) -> StressReport {
+        let body = serde_json::to_string(body).expect("Cannot serialize request body");
+        self.stress_internal(path, Some(body), concurrency, iterations)
+    }
+
+    fn stress_internal(
+        &mut self,
+        path: &str,
+        body: Option<String>,
+        concurrency: usize,
+        iterations: usize,
+    ) -> StressReport {
+        assert!(concurrency > 0, "At least one thread should send requests");
+        // Handlers are shared among threads for the duration of the requests.
+        let mount = Arc::new(mem::replace(&mut self.public_mount, Mount::new()));
+        let url = format!("http://localhost:3000/{}", path);
+        let report = stress::run(Arc::clone(&mount), &url, body, concurrency, iterations);
+        self.public_mount = Arc::try_unwrap(mount).unwrap_or_else(|_| {
+            panic!("API handlers are still used by stress threads")
+        });
+        report
+    }
+
     /// Sends a transaction to the node via `ApiSender`.
     pub fn send<T: Transaction>(&self, transaction: T) {
         self.api_sender.send(Box::new(transaction)).expect(
@@ -1201,7 +4979,72 @@ impl TestKitApi {
         );
     }
 
-    fn get_internal<D>(mount: &Mount, url: &str, expect_error: bool) -> D
+    /// Sends a request to the API handlers, which is a GET request if `body` is `None`
+    /// and a POST request otherwise.
+    fn dispatch(
+        &self,
+        private: bool,
+        endpoint: &str,
+        headers: Headers,
+        body: Option<&[u8]>,
+    ) -> IronResult<Response> {
+        let mount = if private {
+            &self.private_mount
+        } else {
+            &self.public_mount
+        };
+        let url = format!("http://localhost:3000/{}", endpoint);
+        match body {
+            Some(body) => match ::std::str::from_utf8(body) {
+                Ok(body) => request::post(&url, headers, body, mount),
+                // `iron_test` only supports textual bodies.
+                Err(_) => multipart::post_bytes(&url, headers, body, mount),
+            },
+            None => request::get(&url, headers, mount),
+        }
+    }
+
+    /// Sends a request to the API like `dispatch()`, recording it in the fixture
+    /// or replaying it from the fixture if one is attached to the API.
+    fn send_request(
+        &self,
+        private: bool,
+        endpoint: &str,
+        headers: Headers,
+        body: Option<&[u8]>,
+    ) -> TestResponse {
+        let mut fixture = match self.fixture {
+            Some(ref fixture) => fixture.lock().expect("Cannot acquire lock on API fixture"),
+            None => return TestResponse::new(self.dispatch(private, endpoint, headers, body)),
+        };
+
+        let request = ExchangeRequest {
+            private,
+            method: if body.is_some() { "POST" } else { "GET" }.to_owned(),
+            endpoint: endpoint.to_owned(),
+            body: body.map(|body| String::from_utf8_lossy(body).into_owned()),
+        };
+        if fixture.mode() == FixtureMode::Replay {
+            let (status, body) = fixture.replay(&request);
+            return TestResponse::from_parts(status, body.into_bytes());
+        }
+
+        let resp = TestResponse::new(self.dispatch(private, endpoint, headers, body));
+        fixture.process(Exchange {
+            request,
+            status: resp.status().to_u16(),
+            response_body: String::from_utf8_lossy(resp.body()).into_owned(),
+        });
+        resp
+    }
+
+    fn get_internal<D>(
+        &self,
+        private: bool,
+        endpoint: &str,
+        headers: Headers,
+        expect_error: bool,
+    ) -> D
     where
         for<'de> D: Deserialize<'de>,
     {
@@ -1211,29 +5054,18 @@ impl TestKitApi {
             StatusClass::Success
         };
 
-        let url = format!("http://localhost:3000/{}", url);
-        let resp = request::get(&url, Headers::new(), mount);
-        let resp = if expect_error {
-            // Support either "normal" or erroneous responses.
-            // For example, `Api.not_found_response()` returns the response as `Ok(..)`.
-            match resp {
-                Ok(resp) => resp,
-                Err(IronError { response, .. }) => response,
-            }
-        } else {
-            resp.expect("Got unexpected `Err(..)` response")
-        };
-
-        if let Some(ref status) = resp.status {
-            if status.class() != status_class {
-                panic!("Unexpected response status: {:?}", status);
+        let resp = self.send_request(private, endpoint, headers, None);
+        // Support either "normal" or erroneous responses if an error is expected.
+        // For example, `Api.not_found_response()` returns the response as `Ok(..)`.
+        if let Some(ref error) = resp.error {
+            if !expect_error {
+                panic!("Got unexpected `Err(..)` response: {}", error);
             }
-        } else {
-            panic!("Response status not set");
         }
-
-        let resp = response::extract_body_to_string(resp);
-        serde_json::from_str(&resp).unwrap()
+        if resp.status().class() != status_class {
+            panic!("Unexpected response status: {:?}", resp.status());
+        }
+        serde_json::from_slice(resp.body()).unwrap()
     }
 
     /// Gets information from a public endpoint of the node.
@@ -1246,65 +5078,305 @@ impl TestKitApi {
     where
         for<'de> D: Deserialize<'de>,
     {
-        TestKitApi::get_internal(
-            &self.public_mount,
-            &format!("{}/{}", kind.into_prefix(), endpoint),
-            false,
-        )
+        self.get_internal(
+            false,
+            &format!("{}/{}", kind.into_prefix(), endpoint),
+            Headers::new(),
+            false,
+        )
+    }
+
+    /// Gets information from a private endpoint of the node.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if an error occurs during request processing (e.g., the requested endpoint is
+    ///  unknown), or if the response has a non-20x response status.
+    pub fn get_private<D>(&self, kind: ApiKind, endpoint: &str) -> D
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        self.get_internal(
+            true,
+            &format!("{}/{}", kind.into_prefix(), endpoint),
+            Headers::new(),
+            false,
+        )
+    }
+
+    /// Gets information from a public endpoint of the node, passing the given query
+    /// parameters. `query` should serialize into a JSON object with scalar or array
+    /// fields; the fields are sorted by name and percent-encoded into the query string
+    /// of the request.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if an error occurs during request processing (e.g., the requested endpoint is
+    ///  unknown), or if the response has a non-20x response status.
+    /// - Panics if `query` cannot be serialized into a query string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # #[macro_use] extern crate serde_derive;
+    /// # use exonum::blockchain::Block;
+    /// # use exonum_testkit::{ApiKind, TestKitBuilder};
+    /// #[derive(Serialize)]
+    /// struct BlocksQuery {
+    ///     count: u64,
+    ///     skip_empty_blocks: bool,
+    /// }
+    ///
+    /// # fn main() {
+    /// let mut testkit = TestKitBuilder::validator().create();
+    /// testkit.create_block();
+    /// let api = testkit.api();
+    ///
+    /// let query = BlocksQuery { count: 10, skip_empty_blocks: false };
+    /// // Requests `v1/blocks?count=10&skip_empty_blocks=false`.
+    /// let blocks: Vec<Block> = api.get_with_query(ApiKind::Explorer, "v1/blocks", &query);
+    /// assert_eq!(blocks.len(), 2);
+    /// # }
+    /// ```
+    pub fn get_with_query<D, Q>(&self, kind: ApiKind, endpoint: &str, query: &Q) -> D
+    where
+        for<'de> D: Deserialize<'de>,
+        Q: Serialize,
+    {
+        self.request(kind, endpoint).query(query).get()
     }
 
-    /// Gets information from a private endpoint of the node.
+    /// Gets information from a public endpoint of the node and checks that the response
+    /// conforms to the given JSON schema (draft 4).
     ///
     /// # Panics
     ///
     /// - Panics if an error occurs during request processing (e.g., the requested endpoint is
     ///  unknown), or if the response has a non-20x response status.
-    pub fn get_private<D>(&self, kind: ApiKind, endpoint: &str) -> D
+    /// - Panics if the response does not conform to the schema. The panic message lists
+    ///   all found violations.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum_testkit;
+    /// # #[macro_use] extern crate serde_json;
+    /// # use exonum_testkit::{ApiKind, TestKitBuilder};
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator().create();
+    /// let api = testkit.api();
+    /// // Schemas are usually read from files, e.g., with `include_str!()`.
+    /// let schema = json!({
+    ///     "type": "array",
+    ///     "items": {
+    ///         "type": "object",
+    ///         "required": ["height", "prev_hash", "state_hash"]
+    ///     }
+    /// });
+    /// api.assert_schema(ApiKind::Explorer, "v1/blocks?count=10", &schema);
+    /// # }
+    /// ```
+    pub fn assert_schema(&self, kind: ApiKind, endpoint: &str, schema: &serde_json::Value) {
+        let endpoint = format!("{}/{}", kind.into_prefix(), endpoint);
+        let resp = self.send_request(false, &endpoint, Headers::new(), None);
+        if resp.status().class() != StatusClass::Success {
+            panic!("Unexpected response status: {:?}", resp.status());
+        }
+        resp.assert_schema(schema);
+    }
+
+    /// Gets an error from a public endpoint of the node.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the response has a non-40x response status.
+    pub fn get_err<D>(&self, kind: ApiKind, endpoint: &str) -> D
     where
         for<'de> D: Deserialize<'de>,
     {
-        TestKitApi::get_internal(
-            &self.private_mount,
-            &format!("{}/{}", kind.into_prefix(), endpoint),
+        self.get_internal(
             false,
+            &format!("{}/{}", kind.into_prefix(), endpoint),
+            Headers::new(),
+            true,
         )
     }
 
-    /// Gets an error from a public endpoint of the node.
+    /// Gets an error from a private endpoint of the node.
     ///
     /// # Panics
     ///
     /// - Panics if the response has a non-40x response status.
-    pub fn get_err<D>(&self, kind: ApiKind, endpoint: &str) -> D
+    pub fn get_private_err<D>(&self, kind: ApiKind, endpoint: &str) -> D
     where
         for<'de> D: Deserialize<'de>,
     {
-        TestKitApi::get_internal(
-            &self.public_mount,
+        self.get_internal(
+            true,
             &format!("{}/{}", kind.into_prefix(), endpoint),
+            Headers::new(),
             true,
         )
     }
 
-    fn post_internal<T, D>(mount: &Mount, endpoint: &str, data: &T) -> D
+    /// Sends a request to a public endpoint of the node and checks that the response
+    /// has the expected status and body. Returns the response for further checks.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the response status differs from `expected_status`, or the body
+    ///   does not match `expected_body`. The panic message contains the response.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate iron;
+    /// # extern crate router;
+    /// # use exonum::blockchain::{ApiContext, Service, Transaction};
+    /// # use exonum::crypto::{Hash, PublicKey};
+    /// # use exonum::encoding;
+    /// # use exonum::encoding::serialize::FromHex;
+    /// # use exonum::messages::RawTransaction;
+    /// # use exonum::storage::Snapshot;
+    /// # use exonum_testkit::{ApiKind, ApiMethod, BodyMatcher, TestKitBuilder};
+    /// # use iron::{Handler, IronResult, Request, Response};
+    /// # use iron::status::Status;
+    /// # use router::Router;
+    /// #
+    /// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+    /// #
+    /// # // Service with an endpoint validating the public key of a wallet.
+    /// # struct CurrencyService;
+    /// #
+    /// # impl Service for CurrencyService {
+    /// #     fn service_name(&self) -> &'static str {
+    /// #         "cryptocurrency"
+    /// #     }
+    /// #
+    /// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+    /// #         Vec::new()
+    /// #     }
+    /// #
+    /// #     fn service_id(&self) -> u16 {
+    /// #         1
+    /// #     }
+    /// #
+    /// #     fn tx_from_raw(&self, _: RawTransaction) -> FromRawResult {
+    /// #         unimplemented!();
+    /// #     }
+    /// #
+    /// #     fn public_api_handler(&self, _: &ApiContext) -> Option<Box<Handler>> {
+    /// #         let wallet = |req: &mut Request| -> IronResult<Response> {
+    /// #             let param = req.extensions.get::<Router>().unwrap().find("pub_key");
+    /// #             match PublicKey::from_hex(param.unwrap()) {
+    /// #                 Ok(_) => Ok(Response::with((Status::NotFound, "Wallet not found"))),
+    /// #                 Err(_) => {
+    /// #                     let body = "Invalid request param: `pub_key`";
+    /// #                     Ok(Response::with((Status::BadRequest, body)))
+    /// #                 }
+    /// #             }
+    /// #         };
+    /// #         let mut router = Router::new();
+    /// #         router.get("/v1/wallet/:pub_key", wallet, "wallet");
+    /// #         Some(Box::new(router))
+    /// #     }
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator()
+    ///     .with_service(CurrencyService)
+    ///     .create();
+    /// let api = testkit.api();
+    /// api.assert_error(
+    ///     ApiKind::Service("cryptocurrency"),
+    ///     ApiMethod::Get,
+    ///     "v1/wallet/c0ffee",
+    ///     Status::BadRequest,
+    ///     BodyMatcher::contains("Invalid request param"),
+    /// );
+    /// # }
+    /// ```
+    pub fn assert_error(
+        &self,
+        kind: ApiKind,
+        method: ApiMethod,
+        endpoint: &str,
+        expected_status: Status,
+        expected_body: BodyMatcher,
+    ) -> TestResponse {
+        self.assert_error_internal(false, kind, method, endpoint, expected_status, expected_body)
+    }
+
+    /// Sends a request to a private endpoint of the node and checks that the response
+    /// has the expected status and body. See `assert_error()` for details.
+    pub fn assert_private_error(
+        &self,
+        kind: ApiKind,
+        method: ApiMethod,
+        endpoint: &str,
+        expected_status: Status,
+        expected_body: BodyMatcher,
+    ) -> TestResponse {
+        self.assert_error_internal(true, kind, method, endpoint, expected_status, expected_body)
+    }
+
+    fn assert_error_internal(
+        &self,
+        private: bool,
+        kind: ApiKind,
+        method: ApiMethod,
+        endpoint: &str,
+        expected_status: Status,
+        expected_body: BodyMatcher,
+    ) -> TestResponse {
+        let builder = RequestBuilder::new(self, private, kind, endpoint);
+        let (method_name, resp) = match method {
+            ApiMethod::Get => ("GET", builder.get_response()),
+            ApiMethod::Post(ref data) => ("POST", builder.post_response(data)),
+        };
+        let body = String::from_utf8_lossy(resp.body()).into_owned();
+        assert!(
+            resp.status() == expected_status,
+            "Unexpected response status for {} {}: expected {:?}, got {:?} with body {}",
+            method_name,
+            endpoint,
+            expected_status,
+            resp.status(),
+            body
+        );
+        assert!(
+            expected_body.matches(resp.body()),
+            "Unexpected response body for {} {}: expected {:?}, got {}",
+            method_name,
+            endpoint,
+            expected_body,
+            body
+        );
+        resp
+    }
+
+    fn post_internal<T, D>(
+        &self,
+        private: bool,
+        endpoint: &str,
+        mut headers: Headers,
+        data: &T,
+    ) -> D
     where
         T: Serialize,
         for<'de> D: Deserialize<'de>,
     {
-        let url = format!("http://localhost:3000/{}", endpoint);
-        let resp = request::post(
-            &url,
-            {
-                let mut headers = Headers::new();
-                headers.set(ContentType::json());
-                headers
-            },
-            &serde_json::to_string(&data).expect("Cannot serialize data to JSON"),
-            mount,
-        ).expect("Cannot send data");
-
-        let resp = response::extract_body_to_string(resp);
-        serde_json::from_str(&resp).expect("Cannot parse result")
+        headers.set(ContentType::json());
+        let body = serde_json::to_string(&data).expect("Cannot serialize data to JSON");
+        let resp = self.send_request(private, endpoint, headers, Some(body.as_bytes()));
+        if let Some(ref error) = resp.error {
+            panic!("Cannot send data: {}", error);
+        }
+        serde_json::from_slice(resp.body()).expect("Cannot parse result")
     }
 
     /// Posts a transaction to the service using the public API. The returned value is the result
@@ -1321,9 +5393,10 @@ impl TestKitApi {
         T: Serialize,
         for<'de> D: Deserialize<'de>,
     {
-        TestKitApi::post_internal(
-            &self.public_mount,
+        self.post_internal(
+            false,
             &format!("{}/{}", kind.into_prefix(), endpoint),
+            Headers::new(),
             transaction,
         )
     }
@@ -1342,12 +5415,449 @@ impl TestKitApi {
         T: Serialize,
         for<'de> D: Deserialize<'de>,
     {
-        TestKitApi::post_internal(
-            &self.private_mount,
+        self.post_internal(
+            true,
             &format!("{}/{}", kind.into_prefix(), endpoint),
+            Headers::new(),
             transaction,
         )
     }
+
+    /// Converts the API into a futures-based one, which processes requests
+    /// in a dedicated thread. See `AsyncTestKitApi`.
+    pub fn into_async(self) -> AsyncTestKitApi {
+        AsyncTestKitApi::new(self)
+    }
+
+    fn into_mounts(self) -> (Mount, Mount) {
+        (self.public_mount, self.private_mount)
+    }
+
+    /// Starts building a request to a public endpoint of the node. Unlike `get()` and `post()`,
+    /// the builder allows to set HTTP headers and cookies of the request.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::api::public::HealthCheckInfo;
+    /// # use exonum_testkit::{ApiKind, TestKitBuilder};
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator().create();
+    /// let api = testkit.api();
+    /// let info: HealthCheckInfo = api.request(ApiKind::System, "v1/healthcheck")
+    ///     .header("Authorization", "Bearer c0ffee")
+    ///     .cookie("session", "deadbeef")
+    ///     .get();
+    /// assert!(!info.connectivity);
+    /// # }
+    /// ```
+    pub fn request(&self, kind: ApiKind, endpoint: &str) -> RequestBuilder {
+        RequestBuilder::new(self, false, kind, endpoint)
+    }
+
+    /// Posts the given body with the specified content type to a public endpoint of the node
+    /// and returns the raw response. The body is sent as is and may contain arbitrary
+    /// binary data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate iron;
+    /// # extern crate router;
+    /// # use std::io::Read;
+    /// # use exonum::blockchain::{ApiContext, Service, Transaction};
+    /// # use exonum::crypto::Hash;
+    /// # use exonum::encoding;
+    /// # use exonum::messages::RawTransaction;
+    /// # use exonum::storage::Snapshot;
+    /// # use exonum_testkit::{ApiKind, TestKitBuilder};
+    /// # use iron::{Handler, IronResult, Request, Response};
+    /// # use iron::status::Status;
+    /// # use router::Router;
+    /// #
+    /// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+    /// #
+    /// # // Service with an endpoint returning the length of the request body.
+    /// # struct UploadService;
+    /// #
+    /// # impl Service for UploadService {
+    /// #     fn service_name(&self) -> &'static str {
+    /// #         "uploads"
+    /// #     }
+    /// #
+    /// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+    /// #         Vec::new()
+    /// #     }
+    /// #
+    /// #     fn service_id(&self) -> u16 {
+    /// #         1
+    /// #     }
+    /// #
+    /// #     fn tx_from_raw(&self, _: RawTransaction) -> FromRawResult {
+    /// #         unimplemented!();
+    /// #     }
+    /// #
+    /// #     fn public_api_handler(&self, _: &ApiContext) -> Option<Box<Handler>> {
+    /// #         let upload = |req: &mut Request| -> IronResult<Response> {
+    /// #             let mut body = Vec::new();
+    /// #             req.body.read_to_end(&mut body).unwrap();
+    /// #             Ok(Response::with((Status::Ok, body.len().to_string())))
+    /// #         };
+    /// #         let mut router = Router::new();
+    /// #         router.post("/v1/upload", upload, "upload");
+    /// #         Some(Box::new(router))
+    /// #     }
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator()
+    ///     .with_service(UploadService)
+    ///     .create();
+    /// let api = testkit.api();
+    /// let resp = api.post_raw(
+    ///     ApiKind::Service("uploads"),
+    ///     "v1/upload",
+    ///     "application/octet-stream",
+    ///     &[0xff, 0x00, 0x01],
+    /// );
+    /// assert_eq!(resp.status(), Status::Ok);
+    /// assert_eq!(resp.body(), b"3");
+    /// # }
+    /// ```
+    pub fn post_raw(
+        &self,
+        kind: ApiKind,
+        endpoint: &str,
+        content_type: &str,
+        body: &[u8],
+    ) -> TestResponse {
+        self.request(kind, endpoint).post_raw(content_type, body)
+    }
+
+    /// Posts the given `multipart/form-data` form to a public endpoint of the node
+    /// and returns the raw response. File parts may contain arbitrary binary data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # extern crate iron;
+    /// # extern crate router;
+    /// # use exonum::blockchain::{ApiContext, Service, Transaction};
+    /// # use exonum::crypto::Hash;
+    /// # use exonum::encoding;
+    /// # use exonum::messages::RawTransaction;
+    /// # use exonum::storage::Snapshot;
+    /// # use exonum_testkit::{ApiKind, MultipartForm, TestKitBuilder};
+    /// # use iron::{Handler, IronResult, Request, Response};
+    /// # use iron::status::Status;
+    /// # use router::Router;
+    /// #
+    /// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+    /// #
+    /// # // Service with an endpoint returning the content type of the request.
+    /// # struct TimestampingService;
+    /// #
+    /// # impl Service for TimestampingService {
+    /// #     fn service_name(&self) -> &'static str {
+    /// #         "timestamping"
+    /// #     }
+    /// #
+    /// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+    /// #         Vec::new()
+    /// #     }
+    /// #
+    /// #     fn service_id(&self) -> u16 {
+    /// #         1
+    /// #     }
+    /// #
+    /// #     fn tx_from_raw(&self, _: RawTransaction) -> FromRawResult {
+    /// #         unimplemented!();
+    /// #     }
+    /// #
+    /// #     fn public_api_handler(&self, _: &ApiContext) -> Option<Box<Handler>> {
+    /// #         let upload = |req: &mut Request| -> IronResult<Response> {
+    /// #             let content_type = req.headers.get_raw("Content-Type").unwrap()[0].clone();
+    /// #             Ok(Response::with((Status::Ok, content_type)))
+    /// #         };
+    /// #         let mut router = Router::new();
+    /// #         router.post("/v1/upload", upload, "upload");
+    /// #         Some(Box::new(router))
+    /// #     }
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let testkit = TestKitBuilder::validator()
+    ///     .with_service(TimestampingService)
+    ///     .create();
+    /// let api = testkit.api();
+    /// let contents = vec![0x25, 0x50, 0x44, 0x46, 0xff];
+    /// let form = MultipartForm::new()
+    ///     .field("description", "Contract")
+    ///     .file("document", "contract.pdf", "application/pdf", &contents);
+    /// let resp = api.post_multipart(ApiKind::Service("timestamping"), "v1/upload", form);
+    /// assert_eq!(resp.status(), Status::Ok);
+    /// assert!(resp.body().starts_with(b"multipart/form-data; boundary="));
+    /// # }
+    /// ```
+    pub fn post_multipart(
+        &self,
+        kind: ApiKind,
+        endpoint: &str,
+        form: MultipartForm,
+    ) -> TestResponse {
+        self.request(kind, endpoint).post_multipart(form)
+    }
+
+    /// Starts building a request to a private endpoint of the node.
+    pub fn private_request(&self, kind: ApiKind, endpoint: &str) -> RequestBuilder {
+        RequestBuilder::new(self, true, kind, endpoint)
+    }
+}
+
+/// Builder of a request to the testkit API, which allows to set HTTP headers and cookies.
+///
+/// See `TestKitApi::request()` for details.
+pub struct RequestBuilder<'a> {
+    api: &'a TestKitApi,
+    private: bool,
+    endpoint: String,
+    headers: Headers,
+    cookies: Vec<String>,
+}
+
+impl<'a> fmt::Debug for RequestBuilder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("RequestBuilder")
+            .field("private", &self.private)
+            .field("endpoint", &self.endpoint)
+            .field("headers", &self.headers)
+            .field("cookies", &self.cookies)
+            .finish()
+    }
+}
+
+impl<'a> RequestBuilder<'a> {
+    fn new(api: &'a TestKitApi, private: bool, kind: ApiKind, endpoint: &str) -> Self {
+        RequestBuilder {
+            api,
+            private,
+            endpoint: format!("{}/{}", kind.into_prefix(), endpoint),
+            headers: Headers::new(),
+            cookies: Vec::new(),
+        }
+    }
+
+    /// Adds an HTTP header to the request. Repeated headers with the same name
+    /// are sent as multiple values.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let mut values = self.headers
+            .get_raw(name)
+            .map(|values| values.to_vec())
+            .unwrap_or_default();
+        values.push(value.as_bytes().to_vec());
+        self.headers.set_raw(name.to_owned(), values);
+        self
+    }
+
+    /// Appends query parameters to the request URL. `query` should serialize into a JSON
+    /// object with scalar or array fields; the fields are percent-encoded.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `query` cannot be serialized into a query string.
+    pub fn query<Q: Serialize>(mut self, query: &Q) -> Self {
+        let query = query::encode_query(query);
+        if !query.is_empty() {
+            let separator = if self.endpoint.contains('?') { '&' } else { '?' };
+            self.endpoint.push(separator);
+            self.endpoint.push_str(&query);
+        }
+        self
+    }
+
+    /// Adds a cookie to the request.
+    pub fn cookie(mut self, name: &str, value: &str) -> Self {
+        self.cookies.push(format!("{}={}", name, value));
+        self
+    }
+
+    fn into_parts(self) -> (&'a TestKitApi, bool, String, Headers) {
+        let RequestBuilder {
+            api,
+            private,
+            endpoint,
+            mut headers,
+            cookies,
+        } = self;
+        if !cookies.is_empty() {
+            headers.set(Cookie(cookies));
+        }
+        (api, private, endpoint, headers)
+    }
+
+    /// Sends a GET request.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if an error occurs during request processing (e.g., the requested endpoint is
+    ///  unknown), or if the response has a non-20x response status.
+    pub fn get<D>(self) -> D
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        let (api, private, endpoint, headers) = self.into_parts();
+        api.get_internal(private, &endpoint, headers, false)
+    }
+
+    /// Sends a GET request expecting an error.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the response has a non-40x response status.
+    pub fn get_err<D>(self) -> D
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        let (api, private, endpoint, headers) = self.into_parts();
+        api.get_internal(private, &endpoint, headers, true)
+    }
+
+    /// Sends a POST request with the given data serialized to JSON.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if an error occurs during request processing (e.g., the requested endpoint is
+    ///  unknown).
+    pub fn post<T, D>(self, data: &T) -> D
+    where
+        T: Serialize,
+        for<'de> D: Deserialize<'de>,
+    {
+        let (api, private, endpoint, headers) = self.into_parts();
+        api.post_internal(private, &endpoint, headers, data)
+    }
+
+    /// Sends a GET request and returns the raw response, regardless of its status.
+    pub fn get_response(self) -> TestResponse {
+        let (api, private, endpoint, headers) = self.into_parts();
+        api.send_request(private, &endpoint, headers, None)
+    }
+
+    /// Sends a POST request with the given data serialized to JSON and returns the raw
+    /// response, regardless of its status.
+    pub fn post_response<T: Serialize>(self, data: &T) -> TestResponse {
+        let body = serde_json::to_string(&data).expect("Cannot serialize data to JSON");
+        self.post_raw("application/json", body.as_bytes())
+    }
+
+    /// Sends a POST request with the given body and content type and returns the raw
+    /// response, regardless of its status. The body is sent as is and may contain
+    /// arbitrary binary data.
+    pub fn post_raw(self, content_type: &str, body: &[u8]) -> TestResponse {
+        let (api, private, endpoint, mut headers) = self.into_parts();
+        headers.set_raw("Content-Type", vec![content_type.as_bytes().to_vec()]);
+        api.send_request(private, &endpoint, headers, Some(body))
+    }
+
+    /// Sends a `multipart/form-data` POST request with the given form and returns the raw
+    /// response, regardless of its status.
+    pub fn post_multipart(self, form: MultipartForm) -> TestResponse {
+        let (api, private, endpoint, mut headers) = self.into_parts();
+        let (content_type, body) = form.into_request();
+        headers.set_raw("Content-Type", vec![content_type.into_bytes()]);
+        api.send_request(private, &endpoint, headers, Some(&body))
+    }
+}
+
+/// Raw response of the testkit API, which allows to check the status, headers and body
+/// of the response.
+#[derive(Debug)]
+pub struct TestResponse {
+    status: Status,
+    headers: Headers,
+    body: Vec<u8>,
+    // Description of the error if the response has been returned as `Err(..)`.
+    error: Option<String>,
+}
+
+impl TestResponse {
+    fn new(resp: IronResult<Response>) -> Self {
+        // Erroneous responses are returned as `Err(..)` by Iron handlers.
+        let (resp, error) = match resp {
+            Ok(resp) => (resp, None),
+            Err(e) => {
+                let error = format!("{:?}", e);
+                (e.response, Some(error))
+            }
+        };
+        let status = resp.status.expect("Response status not set");
+        let headers = resp.headers.clone();
+        TestResponse {
+            status,
+            headers,
+            body: response::extract_body_to_bytes(resp),
+            error,
+        }
+    }
+
+    fn from_parts(status: u16, body: Vec<u8>) -> Self {
+        TestResponse {
+            status: Status::from_u16(status),
+            headers: Headers::new(),
+            body,
+            error: None,
+        }
+    }
+
+    /// Returns the status of the response.
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Returns the headers of the response.
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Checks that the body of the response conforms to the given JSON schema (draft 4).
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the body is not valid JSON or does not conform to the schema.
+    ///   The panic message lists all found violations.
+    pub fn assert_schema(&self, schema: &serde_json::Value) {
+        let value: serde_json::Value = self.json();
+        if let Err(errors) = schema::validate(&value, schema) {
+            panic!(
+                "Response does not conform to JSON schema:\n{}\nResponse: {}",
+                errors.join("\n"),
+                value
+            );
+        }
+    }
+
+    /// Deserializes the body of the response from JSON.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the body cannot be deserialized into the specified type.
+    pub fn json<D>(&self) -> D
+    where
+        for<'de> D: Deserialize<'de>,
+    {
+        serde_json::from_slice(&self.body).expect("Cannot parse response body")
+    }
 }
 
 #[test]
@@ -0,0 +1,158 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Capture of log records emitted during block execution.
+//!
+//! The testkit installs a logger, which records messages only on threads with an active
+//! capture scope; messages emitted outside of capture scopes are discarded.
+
+use std::cell::RefCell;
+use std::mem;
+use std::sync::{Once, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
+
+thread_local! {
+    static SCOPE: RefCell<Option<Vec<CapturedRecord>>> = RefCell::new(None);
+}
+
+static INSTALL_LOGGER: Once = ONCE_INIT;
+static LOGGER_INSTALLED: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// Log record emitted during the creation of a block. See `TestKit::logs_for_tx()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Height of the block, during creation of which the record has been emitted.
+    pub height: Height,
+    /// Hash of the transaction, during execution of which the record has been emitted,
+    /// or `None` if the record has been emitted outside of transaction execution
+    /// (e.g., by commit hooks).
+    pub tx_hash: Option<Hash>,
+    /// Level of the record.
+    pub level: Level,
+    /// Target of the record, usually the module path of the emitting code.
+    pub target: String,
+    /// Formatted message.
+    pub message: String,
+}
+
+/// Log record captured in a scope, which is not yet attributed to a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CapturedRecord {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+impl CapturedRecord {
+    pub fn into_record(self, height: Height, tx_hash: Option<Hash>) -> LogRecord {
+        LogRecord {
+            height,
+            tx_hash,
+            level: self.level,
+            target: self.target,
+            message: self.message,
+        }
+    }
+}
+
+/// Logger recording messages into the capture scope of the current thread.
+struct CapturingLogger;
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        SCOPE.with(|scope| scope.borrow().is_some())
+    }
+
+    fn log(&self, record: &Record) {
+        let message = record.args().to_string();
+        SCOPE.with(|scope| if let Some(ref mut records) = *scope.borrow_mut() {
+            records.push(CapturedRecord {
+                level: record.level(),
+                target: record.target().to_owned(),
+                message,
+            });
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the capturing logger. Returns `false` if another logger has been installed
+/// for the process, in which case log records cannot be captured.
+pub(crate) fn install_logger() -> bool {
+    INSTALL_LOGGER.call_once(|| {
+        if log::set_boxed_logger(Box::new(CapturingLogger)).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+            LOGGER_INSTALLED.store(true, Ordering::SeqCst);
+        }
+    });
+    LOGGER_INSTALLED.load(Ordering::SeqCst)
+}
+
+/// Restores the previous capture scope of the thread when dropped.
+struct ScopeGuard {
+    previous: Option<Vec<CapturedRecord>>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        SCOPE.with(|scope| *scope.borrow_mut() = previous);
+    }
+}
+
+/// Calls the function and returns its result together with log records emitted
+/// by the current thread during the call.
+pub(crate) fn capture<F, R>(f: F) -> (R, Vec<CapturedRecord>)
+where
+    F: FnOnce() -> R,
+{
+    let previous = SCOPE.with(|scope| {
+        mem::replace(&mut *scope.borrow_mut(), Some(Vec::new()))
+    });
+    let _guard = ScopeGuard { previous };
+    let result = f();
+    let records = SCOPE.with(|scope| scope.borrow_mut().take().unwrap_or_default());
+    (result, records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        assert!(install_logger());
+        info!("Not captured");
+        let ((), records) = capture(|| {
+            info!("Outer {}", 1);
+            let ((), inner_records) = capture(|| warn!(target: "inner", "Inner"));
+            assert_eq!(inner_records.len(), 1);
+            assert_eq!(inner_records[0].level, Level::Warn);
+            assert_eq!(inner_records[0].target, "inner");
+            debug!("Outer {}", 2);
+        });
+
+        let messages: Vec<_> = records.iter().map(|record| record.message.as_str()).collect();
+        assert_eq!(messages, vec!["Outer 1", "Outer 2"]);
+        let record = records[0].clone().into_record(Height(1), None);
+        assert_eq!(record.level, Level::Info);
+        assert_eq!(record.height, Height(1));
+        SCOPE.with(|scope| assert!(scope.borrow().is_none()));
+    }
+}
@@ -23,3 +23,173 @@ macro_rules! txvec {
         vec![$($x.into()),*]
     )
 }
+
+/// Declares a typed client for the API of a service together with an extension trait
+/// for `TestKitApi`, which returns the client.
+///
+/// Each endpoint is declared as `<kind> fn <name>(<args>) -> <response> = "<path>";`,
+/// where the kind is one of:
+///
+/// - `get` / `get_private`: GET request to the public / private API; the path is
+///   a format string, which placeholders are filled with the arguments
+/// - `post` / `post_private`: POST request to the public / private API with the single
+///   argument serialized as the request body
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # #[macro_use] extern crate exonum_testkit;
+/// # extern crate iron;
+/// # extern crate router;
+/// # use std::io::Read;
+/// # use exonum::blockchain::{ApiContext, Service, Transaction};
+/// # use exonum::crypto::Hash;
+/// # use exonum::encoding;
+/// # use exonum::messages::RawTransaction;
+/// # use exonum::storage::Snapshot;
+/// # use exonum_testkit::TestKitBuilder;
+/// # use iron::{Handler, IronResult, Request, Response};
+/// # use iron::status::Status;
+/// # use router::Router;
+/// #
+/// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+/// #
+/// # struct MessagesService;
+/// #
+/// # impl Service for MessagesService {
+/// #     fn service_name(&self) -> &'static str {
+/// #         "messages"
+/// #     }
+/// #
+/// #     fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+/// #         Vec::new()
+/// #     }
+/// #
+/// #     fn service_id(&self) -> u16 {
+/// #         1
+/// #     }
+/// #
+/// #     fn tx_from_raw(&self, _: RawTransaction) -> FromRawResult {
+/// #         unimplemented!();
+/// #     }
+/// #
+/// #     fn public_api_handler(&self, _: &ApiContext) -> Option<Box<Handler>> {
+/// #         let message = |req: &mut Request| -> IronResult<Response> {
+/// #             let id = req.extensions.get::<Router>().unwrap().find("id").unwrap();
+/// #             Ok(Response::with((Status::Ok, format!("\"Message #{}\"", id))))
+/// #         };
+/// #         let echo = |req: &mut Request| -> IronResult<Response> {
+/// #             let mut body = Vec::new();
+/// #             req.body.read_to_end(&mut body).unwrap();
+/// #             Ok(Response::with((Status::Ok, body)))
+/// #         };
+/// #         let mut router = Router::new();
+/// #         router.get("/v1/messages/:id", message, "message");
+/// #         router.post("/v1/echo", echo, "echo");
+/// #         Some(Box::new(router))
+/// #     }
+/// # }
+/// #
+/// service_api_client! {
+///     /// Client for the messages service.
+///     pub struct MessagesClient("messages");
+///     /// Provides access to `MessagesClient`.
+///     pub trait MessagesApi { fn messages(); }
+///
+///     /// Returns the message with the given identifier.
+///     get fn message(id: u64) -> String = "v1/messages/{}";
+///     /// Returns the posted value.
+///     post fn echo(value: &u64) -> u64 = "v1/echo";
+/// }
+///
+/// # fn main() {
+/// let testkit = TestKitBuilder::validator()
+///     .with_service(MessagesService)
+///     .create();
+/// let api = testkit.api();
+/// assert_eq!(api.messages().message(1), "Message #1");
+/// assert_eq!(api.messages().echo(&42), 42);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! service_api_client {
+    (@method $service:tt $(#[$attr:meta])*
+        get fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty = $path:tt) => {
+        $(#[$attr])*
+        pub fn $name(&self, $($arg: $ty),*) -> $ret {
+            self.api.get($crate::ApiKind::Service($service), &format!($path, $($arg),*))
+        }
+    };
+    (@method $service:tt $(#[$attr:meta])*
+        get_private fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty = $path:tt) => {
+        $(#[$attr])*
+        pub fn $name(&self, $($arg: $ty),*) -> $ret {
+            self.api.get_private(
+                $crate::ApiKind::Service($service),
+                &format!($path, $($arg),*),
+            )
+        }
+    };
+    (@method $service:tt $(#[$attr:meta])*
+        post fn $name:ident($arg:ident: $ty:ty) -> $ret:ty = $path:tt) => {
+        $(#[$attr])*
+        pub fn $name(&self, $arg: $ty) -> $ret {
+            self.api.post($crate::ApiKind::Service($service), $path, $arg)
+        }
+    };
+    (@method $service:tt $(#[$attr:meta])*
+        post_private fn $name:ident($arg:ident: $ty:ty) -> $ret:ty = $path:tt) => {
+        $(#[$attr])*
+        pub fn $name(&self, $arg: $ty) -> $ret {
+            self.api.post_private($crate::ApiKind::Service($service), $path, $arg)
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        pub struct $client:ident($service:tt);
+        $(#[$ext_attr:meta])*
+        pub trait $ext:ident { fn $accessor:ident(); }
+        $(
+            $(#[$method_attr:meta])*
+            $kind:ident fn $name:ident($($args:tt)*) -> $ret:ty = $path:tt;
+        )*
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $client<'a> {
+            api: &'a $crate::TestKitApi,
+        }
+
+        impl<'a> $crate::ServiceApiClient<'a> for $client<'a> {
+            const SERVICE_NAME: &'static str = $service;
+
+            fn from_api(api: &'a $crate::TestKitApi) -> Self {
+                $client { api }
+            }
+
+            fn api(&self) -> &'a $crate::TestKitApi {
+                self.api
+            }
+        }
+
+        impl<'a> $client<'a> {
+            $(
+                service_api_client!(@method $service $(#[$method_attr])*
+                    $kind fn $name($($args)*) -> $ret = $path);
+            )*
+        }
+
+        $(#[$ext_attr])*
+        pub trait $ext {
+            /// Returns the typed client for the service API.
+            fn $accessor(&self) -> $client;
+        }
+
+        impl $ext for $crate::TestKitApi {
+            fn $accessor(&self) -> $client {
+                $crate::TestKitApi::client(self)
+            }
+        }
+    };
+}
@@ -0,0 +1,175 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simulation of Byzantine behavior of validators.
+
+use std::time::SystemTime;
+
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::helpers::Height;
+use exonum::messages::{Message, Precommit, Propose, RawMessage};
+
+use TestNode;
+
+/// Evidence of misbehavior of a validator, consisting of consensus messages signed by it.
+#[derive(Debug, Clone)]
+pub enum Evidence {
+    /// Two different proposes for the same height and round.
+    DoubleSign(Propose, Propose),
+    /// Precommits for two different blocks at the same height and round.
+    ConflictingPrecommits(Precommit, Precommit),
+    /// Propose referencing a block, which is not the latest block of the blockchain.
+    InvalidPropose(Propose),
+}
+
+impl Evidence {
+    /// Returns the consensus messages constituting the evidence.
+    pub fn messages(&self) -> Vec<RawMessage> {
+        match *self {
+            Evidence::DoubleSign(ref first, ref second) => {
+                vec![first.raw().clone(), second.raw().clone()]
+            }
+            Evidence::ConflictingPrecommits(ref first, ref second) => {
+                vec![first.raw().clone(), second.raw().clone()]
+            }
+            Evidence::InvalidPropose(ref propose) => vec![propose.raw().clone()],
+        }
+    }
+
+    /// Checks that the messages are signed with the given consensus key and, for evidence
+    /// consisting of two messages, that the messages conflict with each other. The validity
+    /// of the block referenced by `InvalidPropose` is not checked.
+    pub fn verify(&self, consensus_key: &PublicKey) -> bool {
+        match *self {
+            Evidence::DoubleSign(ref first, ref second) => {
+                first.verify_signature(consensus_key) && second.verify_signature(consensus_key) &&
+                    first.validator() == second.validator() &&
+                    first.height() == second.height() &&
+                    first.round() == second.round() && first.hash() != second.hash()
+            }
+            Evidence::ConflictingPrecommits(ref first, ref second) => {
+                first.verify_signature(consensus_key) && second.verify_signature(consensus_key) &&
+                    first.validator() == second.validator() &&
+                    first.height() == second.height() &&
+                    first.round() == second.round() &&
+                    first.block_hash() != second.block_hash()
+            }
+            Evidence::InvalidPropose(ref propose) => propose.verify_signature(consensus_key),
+        }
+    }
+}
+
+/// Validator of the test network producing Byzantine consensus messages for the next height
+/// of the testkit blockchain.
+///
+/// The testkit does not run a consensus algorithm, so the produced messages are not processed
+/// by the testkit itself; instead, they can be used to test services and monitoring tools
+/// reacting to evidence of misbehavior, e.g., by wrapping them into transactions. Messages
+/// are deterministic for the same validator keys, blockchain state and testkit time.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::crypto;
+/// # use exonum::helpers::ValidatorId;
+/// # use exonum_testkit::{Evidence, TestKitBuilder};
+/// # fn main() {
+/// let testkit = TestKitBuilder::validator().with_validators(4).create();
+/// let malicious = testkit.malicious_node(ValidatorId(2));
+/// let evidence = malicious.conflicting_precommits(&crypto::hash(&[1]), &crypto::hash(&[2]));
+///
+/// let consensus_key = testkit.network().consensus_public_key_of(ValidatorId(2)).unwrap();
+/// assert!(evidence.verify(consensus_key));
+/// match evidence {
+///     Evidence::ConflictingPrecommits(first, second) => {
+///         assert_eq!(first.height(), second.height());
+///     }
+///     _ => unreachable!(),
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MaliciousNode {
+    node: TestNode,
+    height: Height,
+    last_hash: Hash,
+    time: SystemTime,
+}
+
+impl MaliciousNode {
+    pub(crate) fn new(node: TestNode, height: Height, last_hash: Hash, time: SystemTime) -> Self {
+        MaliciousNode {
+            node,
+            height,
+            last_hash,
+            time,
+        }
+    }
+
+    /// Returns the misbehaving validator.
+    pub fn node(&self) -> &TestNode {
+        &self.node
+    }
+
+    /// Signs two proposes with different sets of transactions for the same height and round.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the sets of transactions are equal.
+    pub fn double_sign(&self, first_txs: &[Hash], second_txs: &[Hash]) -> Evidence {
+        assert_ne!(
+            first_txs,
+            second_txs,
+            "Proposes with the same transactions do not conflict"
+        );
+        Evidence::DoubleSign(
+            self.node.create_propose(self.height, &self.last_hash, first_txs),
+            self.node.create_propose(self.height, &self.last_hash, second_txs),
+        )
+    }
+
+    /// Signs precommits for two different blocks at the same height and round.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the block hashes are equal.
+    pub fn conflicting_precommits(&self, first_block: &Hash, second_block: &Hash) -> Evidence {
+        assert_ne!(
+            first_block,
+            second_block,
+            "Precommits for the same block do not conflict"
+        );
+        let propose = self.node.create_propose(self.height, &self.last_hash, &[]);
+        Evidence::ConflictingPrecommits(
+            self.node.create_precommit_at_time(
+                &propose,
+                first_block,
+                self.time,
+            ),
+            self.node.create_precommit_at_time(
+                &propose,
+                second_block,
+                self.time,
+            ),
+        )
+    }
+
+    /// Signs a propose for the next height referencing a non-existing previous block.
+    pub fn invalid_propose(&self, tx_hashes: &[Hash]) -> Evidence {
+        let fake_hash = crypto::hash(self.last_hash.as_ref());
+        Evidence::InvalidPropose(self.node.create_propose(self.height, &fake_hash, tx_hashes))
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Size limits and ordering for the testkit mempool.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+use exonum::blockchain::Transaction;
+use exonum::crypto::Hash;
+
+/// Policy of handling incoming transactions once the mempool size limit is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Incoming transactions are dropped.
+    RejectNew,
+    /// The oldest transactions in the mempool are evicted to make room for incoming ones.
+    EvictOldest,
+}
+
+/// Handling of the mempool when blocks are rolled back. See `TestKit::rollback_blocks()`
+/// and `TestKitBuilder::with_rollback_retention()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolRetention {
+    /// The mempool is kept intact; transactions from the rolled back blocks are discarded.
+    /// This is the default behavior of `TestKit::rollback()`.
+    Keep,
+    /// Transactions from the rolled back blocks are put back into the mempool, so they are
+    /// committed again by subsequent blocks.
+    Restore,
+    /// The mempool is cleared.
+    Clear,
+}
+
+/// Tracker of transactions received by the mempool, which enforces the mempool size limit.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MempoolLimiter {
+    limit: Option<(usize, EvictionPolicy)>,
+    order: VecDeque<Hash>,
+    evicted: Vec<Hash>,
+}
+
+impl MempoolLimiter {
+    pub fn new(limit: Option<(usize, EvictionPolicy)>) -> Self {
+        MempoolLimiter {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// Inserts a transaction into the mempool, evicting transactions according to the policy
+    /// if the mempool is full.
+    pub fn insert(&mut self, mempool: &mut BTreeMap<Hash, Box<Transaction>>, tx: Box<Transaction>) {
+        let hash = tx.hash();
+        if mempool.contains_key(&hash) {
+            return;
+        }
+
+        if let Some((limit, policy)) = self.limit {
+            // Forget transactions removed from the mempool by other means (e.g., committed ones).
+            self.order.retain(|h| mempool.contains_key(h));
+
+            while mempool.len() >= limit {
+                let oldest = match policy {
+                    EvictionPolicy::RejectNew => None,
+                    EvictionPolicy::EvictOldest => self.order.pop_front(),
+                };
+                match oldest {
+                    Some(oldest) => {
+                        mempool.remove(&oldest);
+                        self.evicted.push(oldest);
+                    }
+                    None => {
+                        self.evicted.push(hash);
+                        return;
+                    }
+                }
+            }
+            self.order.push_back(hash);
+        }
+
+        mempool.insert(hash, tx);
+    }
+
+    /// Returns hashes of the transactions dropped from the mempool, in the order of eviction.
+    pub fn evicted(&self) -> &[Hash] {
+        &self.evicted
+    }
+}
+
+/// Order in which transactions from the mempool are included into blocks created
+/// with `TestKit::create_block()`. See `TestKit::set_pool_ordering()`.
+#[derive(Clone)]
+pub enum PoolOrdering {
+    /// Transactions are ordered by their hashes. This is the default ordering.
+    ByHash,
+    /// Transactions are ordered with the comparator; transactions comparing equal
+    /// are ordered by their hashes.
+    Comparator(Arc<Fn(&Transaction, &Transaction) -> Ordering + Send + Sync>),
+    /// Transactions with the given hashes are included first, in the given order;
+    /// hashes of transactions absent from the mempool are skipped. The remaining transactions
+    /// are ordered by their hashes.
+    Explicit(Vec<Hash>),
+}
+
+impl fmt::Debug for PoolOrdering {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PoolOrdering::ByHash => f.write_str("ByHash"),
+            PoolOrdering::Comparator(_) => f.write_str("Comparator(..)"),
+            PoolOrdering::Explicit(ref hashes) => f.debug_tuple("Explicit").field(hashes).finish(),
+        }
+    }
+}
+
+impl Default for PoolOrdering {
+    fn default() -> Self {
+        PoolOrdering::ByHash
+    }
+}
+
+impl PoolOrdering {
+    /// Creates an ordering with the given comparator of transactions.
+    pub fn comparator<F>(comparator: F) -> Self
+    where
+        F: Fn(&Transaction, &Transaction) -> Ordering + Send + Sync + 'static,
+    {
+        PoolOrdering::Comparator(Arc::new(comparator))
+    }
+
+    /// Returns hashes of all transactions in the mempool in this order.
+    pub(crate) fn arrange(&self, mempool: &BTreeMap<Hash, Box<Transaction>>) -> Vec<Hash> {
+        match *self {
+            PoolOrdering::ByHash => mempool.keys().cloned().collect(),
+            PoolOrdering::Comparator(ref comparator) => {
+                let mut txs: Vec<_> = mempool.iter().collect();
+                // The sort is stable, so equal transactions remain ordered by hashes.
+                txs.sort_by(|&(_, a), &(_, b)| comparator(&**a, &**b));
+                txs.into_iter().map(|(hash, _)| *hash).collect()
+            }
+            PoolOrdering::Explicit(ref hashes) => {
+                let mut included = BTreeSet::new();
+                hashes
+                    .iter()
+                    .chain(mempool.keys())
+                    .filter(|&hash| mempool.contains_key(hash) && included.insert(*hash))
+                    .cloned()
+                    .collect()
+            }
+        }
+    }
+}
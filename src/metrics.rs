@@ -0,0 +1,209 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics of the testkit exposed in the Prometheus text format when serving
+//! the testkit over HTTP.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use exonum::blockchain::{Blockchain, Schema as CoreSchema};
+use exonum::node::TxPool;
+use iron::{Handler, IronResult, Request, Response};
+use iron::headers::ContentType;
+use iron::modifiers::Header;
+use iron::status::Status;
+use mount::Mount;
+
+use TestKit;
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    // Request counts indexed by the API (public or private) and the request path.
+    requests: BTreeMap<(&'static str, String), u64>,
+    blocks: u64,
+    block_execution: Duration,
+}
+
+/// Metrics collected by the testkit and the HTTP server.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    state: Mutex<MetricsState>,
+}
+
+impl Metrics {
+    fn state(&self) -> MutexGuard<MetricsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the time of executing transactions of a created block.
+    pub fn record_block(&self, execution: Duration) {
+        let mut state = self.state();
+        state.blocks += 1;
+        state.block_execution += execution;
+    }
+
+    fn record_request(&self, api: &'static str, path: String) {
+        *self.state().requests.entry((api, path)).or_insert(0) += 1;
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    fn render(&self, height: u64, mempool_size: usize) -> String {
+        let state = self.state();
+        let mut output = String::new();
+        write_header(&mut output, "height", "gauge", "Height of the latest committed block.");
+        writeln!(output, "exonum_testkit_height {}", height).unwrap();
+        write_header(
+            &mut output,
+            "mempool_size",
+            "gauge",
+            "Number of transactions in the mempool.",
+        );
+        writeln!(output, "exonum_testkit_mempool_size {}", mempool_size).unwrap();
+
+        write_header(
+            &mut output,
+            "requests_total",
+            "counter",
+            "Number of API requests per endpoint.",
+        );
+        for (&(api, ref path), count) in &state.requests {
+            writeln!(
+                output,
+                "exonum_testkit_requests_total{{api=\"{}\",endpoint=\"{}\"}} {}",
+                api,
+                escape_label(path),
+                count
+            ).unwrap();
+        }
+
+        write_header(
+            &mut output,
+            "block_execution_seconds",
+            "summary",
+            "Time of executing transactions of created blocks.",
+        );
+        let execution = state.block_execution;
+        let seconds = execution.as_secs() as f64 + f64::from(execution.subsec_nanos()) * 1e-9;
+        writeln!(output, "exonum_testkit_block_execution_seconds_sum {}", seconds).unwrap();
+        writeln!(output, "exonum_testkit_block_execution_seconds_count {}", state.blocks).unwrap();
+        output
+    }
+}
+
+fn write_header(output: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(output, "# HELP exonum_testkit_{} {}", name, help).unwrap();
+    writeln!(output, "# TYPE exonum_testkit_{} {}", name, kind).unwrap();
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Handler counting requests to the wrapped API.
+struct RequestCounter {
+    handler: Mount,
+    api: &'static str,
+    metrics: Arc<Metrics>,
+}
+
+impl Handler for RequestCounter {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let path = format!("/{}", req.url.path().join("/"));
+        self.metrics.record_request(self.api, path);
+        self.handler.handle(req)
+    }
+}
+
+/// Handler of the `/metrics` endpoint.
+struct MetricsHandler {
+    blockchain: Blockchain,
+    mempool: TxPool,
+    metrics: Arc<Metrics>,
+}
+
+impl Handler for MetricsHandler {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let height = CoreSchema::new(&self.blockchain.snapshot()).height().0;
+        let mempool_size = self.mempool
+            .read()
+            .expect("Cannot read transactions from mempool")
+            .len();
+        let body = self.metrics.render(height, mempool_size);
+        Ok(Response::with(
+            (Status::Ok, body, Header(ContentType::plaintext())),
+        ))
+    }
+}
+
+/// Wraps the public and private API mounts to count requests, and exposes the metrics
+/// of the testkit at `/metrics` of the public API.
+pub(crate) fn instrument(testkit: &TestKit, public: Mount, private: Mount) -> (Mount, Mount) {
+    let wrap = |handler: Mount, api: &'static str| {
+        let mut mount = Mount::new();
+        mount.mount(
+            "/",
+            RequestCounter {
+                handler,
+                api,
+                metrics: Arc::clone(&testkit.metrics),
+            },
+        );
+        mount
+    };
+
+    let mut public = wrap(public, "public");
+    public.mount(
+        "metrics",
+        MetricsHandler {
+            blockchain: testkit.blockchain.clone(),
+            mempool: Arc::clone(&testkit.mempool),
+            metrics: Arc::clone(&testkit.metrics),
+        },
+    );
+    (public, wrap(private, "private"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.record_block(Duration::from_millis(500));
+        metrics.record_block(Duration::from_millis(250));
+        metrics.record_request("public", "/api/services/wallets".to_owned());
+        metrics.record_request("public", "/api/services/wallets".to_owned());
+        metrics.record_request("private", "/api/\"quoted\"".to_owned());
+
+        let output = metrics.render(2, 3);
+        assert!(output.contains("\nexonum_testkit_height 2\n"));
+        assert!(output.contains("\nexonum_testkit_mempool_size 3\n"));
+        assert!(output.contains(
+            "exonum_testkit_requests_total{api=\"public\",endpoint=\"/api/services/wallets\"} 2\n",
+        ));
+        assert!(output.contains(
+            "\nexonum_testkit_requests_total{api=\"private\",endpoint=\"/api/\\\"quoted\\\"\"} 1\n",
+        ));
+        assert!(output.contains("\nexonum_testkit_block_execution_seconds_sum 0.75\n"));
+        assert!(output.contains("\nexonum_testkit_block_execution_seconds_count 2\n"));
+        assert!(output.contains("# TYPE exonum_testkit_requests_total counter\n"));
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multipart form requests and binary request bodies.
+
+use std::io::{self, Cursor, Read, Write};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use exonum::crypto;
+use exonum::encoding::serialize::ToHex;
+use hyper::buffer::BufReader;
+use hyper::net::NetworkStream;
+use hyper::server::Request as HttpRequest;
+use iron::{Handler, IronResult, Protocol, Request, Response, Url};
+use iron::headers::Headers;
+
+#[derive(Debug, Clone)]
+enum Part {
+    Field { name: String, value: String },
+    File {
+        name: String,
+        file_name: String,
+        content_type: String,
+        content: Vec<u8>,
+    },
+}
+
+/// Body of a `multipart/form-data` request, consisting of text fields and file parts.
+///
+/// See `TestKitApi::post_multipart()` for an example.
+#[derive(Debug, Clone, Default)]
+pub struct MultipartForm {
+    parts: Vec<Part>,
+}
+
+impl MultipartForm {
+    /// Creates an empty form.
+    pub fn new() -> Self {
+        MultipartForm::default()
+    }
+
+    /// Adds a text field to the form.
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        self.parts.push(Part::Field {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        });
+        self
+    }
+
+    /// Adds a file part with the given file name, content type and binary contents
+    /// to the form.
+    pub fn file(mut self, name: &str, file_name: &str, content_type: &str, content: &[u8]) -> Self {
+        self.parts.push(Part::File {
+            name: name.to_owned(),
+            file_name: file_name.to_owned(),
+            content_type: content_type.to_owned(),
+            content: content.to_vec(),
+        });
+        self
+    }
+
+    /// Returns the boundary separating parts of the form. The boundary is derived
+    /// from the hash of the parts, so that it does not occur in them.
+    fn boundary(&self) -> String {
+        let mut contents = Vec::new();
+        for part in &self.parts {
+            match *part {
+                Part::Field { ref value, .. } => contents.extend_from_slice(value.as_bytes()),
+                Part::File { ref content, .. } => contents.extend_from_slice(content),
+            }
+        }
+        let hash = crypto::hash(&contents);
+        let prefix: &[u8] = &hash.as_ref()[..16];
+        format!("exonum-testkit-{}", prefix.to_hex())
+    }
+
+    /// Returns the value of the `Content-Type` header and the body of the request.
+    pub(crate) fn into_request(self) -> (String, Vec<u8>) {
+        let boundary = self.boundary();
+        let mut body = Vec::new();
+        for part in self.parts {
+            write!(body, "--{}\r\n", boundary).unwrap();
+            match part {
+                Part::Field { name, value } => {
+                    write!(
+                        body,
+                        "Content-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                        escape(&name),
+                        value
+                    ).unwrap();
+                }
+                Part::File {
+                    name,
+                    file_name,
+                    content_type,
+                    content,
+                } => {
+                    write!(
+                        body,
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                         Content-Type: {}\r\n\r\n",
+                        escape(&name),
+                        escape(&file_name),
+                        content_type
+                    ).unwrap();
+                    body.extend_from_slice(&content);
+                    body.extend_from_slice(b"\r\n");
+                }
+            }
+        }
+        write!(body, "--{}--\r\n", boundary).unwrap();
+        (format!("multipart/form-data; boundary={}", boundary), body)
+    }
+}
+
+/// Escapes quotes in parameters of the `Content-Disposition` header.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// In-memory stream with the serialized HTTP request.
+struct RequestStream {
+    data: Cursor<Vec<u8>>,
+}
+
+impl Read for RequestStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.data.read(buf)
+    }
+}
+
+impl Write for RequestStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl NetworkStream for RequestStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(local_addr())
+    }
+
+    fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_write_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn local_addr() -> SocketAddr {
+    "127.0.0.1:3000".parse().unwrap()
+}
+
+/// Sends a POST request with a binary body to the handler. Unlike `iron_test::request::post()`,
+/// the body does not need to be valid UTF-8.
+pub(crate) fn post_bytes<H: Handler>(
+    path: &str,
+    headers: Headers,
+    body: &[u8],
+    handler: &H,
+) -> IronResult<Response> {
+    let url = Url::parse(path).expect("Invalid request URL");
+    let mut buffer = Vec::new();
+    write!(buffer, "POST {} HTTP/1.1\r\n", url).unwrap();
+    write!(buffer, "Content-Length: {}\r\n", body.len()).unwrap();
+    for header in headers.iter() {
+        write!(buffer, "{}: {}\r\n", header.name(), header.value_string()).unwrap();
+    }
+    buffer.extend_from_slice(b"\r\n");
+    buffer.extend_from_slice(body);
+
+    let mut stream = RequestStream { data: Cursor::new(buffer) };
+    let mut reader = BufReader::new(&mut stream as &mut NetworkStream);
+    let http_request = HttpRequest::new(&mut reader, local_addr()).expect("Invalid HTTP request");
+    let mut request = Request::from_http(http_request, local_addr(), &Protocol::http())
+        .expect("Invalid HTTP request");
+    handler.handle(&mut request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_body() {
+        let (content_type, body) = MultipartForm::new()
+            .field("title", "Report")
+            .file("document", "report \"v1\".bin", "application/octet-stream", &[0xff, 0, 1])
+            .into_request();
+        let boundary = content_type
+            .trim_left_matches("multipart/form-data; boundary=")
+            .to_owned();
+        assert!(boundary.starts_with("exonum-testkit-"));
+
+        let mut expected = format!(
+            "--{0}\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             Report\r\n\
+             --{0}\r\n\
+             Content-Disposition: form-data; name=\"document\"; \
+             filename=\"report \\\"v1\\\".bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            boundary
+        ).into_bytes();
+        expected.extend_from_slice(&[0xff, 0, 1]);
+        expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn test_empty_multipart_body() {
+        let (content_type, body) = MultipartForm::new().into_request();
+        let boundary = content_type.trim_left_matches("multipart/form-data; boundary=");
+        assert_eq!(body, format!("--{}--\r\n", boundary).into_bytes());
+    }
+}
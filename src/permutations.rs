@@ -0,0 +1,102 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Iterator over all permutations of a slice in the lexicographic order of item positions.
+#[derive(Debug)]
+pub struct Permutations<'a, T: 'a> {
+    items: &'a [T],
+    indexes: Vec<usize>,
+    exhausted: bool,
+}
+
+impl<'a, T: 'a + Clone> Permutations<'a, T> {
+    pub fn new(items: &'a [T]) -> Self {
+        Permutations {
+            items,
+            indexes: (0..items.len()).collect(),
+            exhausted: false,
+        }
+    }
+
+    /// Advances indexes to the next permutation. Returns `false` if the current permutation
+    /// is the last one.
+    fn advance(&mut self) -> bool {
+        let len = self.indexes.len();
+        if len < 2 {
+            return false;
+        }
+
+        // Find the rightmost index that is smaller than its successor.
+        let pivot = match (0..len - 1).rev().find(|&i| self.indexes[i] < self.indexes[i + 1]) {
+            Some(pivot) => pivot,
+            None => return false,
+        };
+        // Find the rightmost index greater than the pivot, and swap them.
+        let successor = (pivot + 1..len)
+            .rev()
+            .find(|&i| self.indexes[i] > self.indexes[pivot])
+            .unwrap();
+        self.indexes.swap(pivot, successor);
+        self.indexes[pivot + 1..].reverse();
+        true
+    }
+}
+
+impl<'a, T: 'a + Clone> Iterator for Permutations<'a, T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        if self.exhausted {
+            return None;
+        }
+
+        let permutation = self.indexes.iter().map(|&i| self.items[i].clone()).collect();
+        self.exhausted = !self.advance();
+        Some(permutation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutations() {
+        let permutations: Vec<_> = Permutations::new(&[1, 2, 3]).collect();
+        assert_eq!(
+            permutations,
+            vec![
+                vec![1, 2, 3],
+                vec![1, 3, 2],
+                vec![2, 1, 3],
+                vec![2, 3, 1],
+                vec![3, 1, 2],
+                vec![3, 2, 1],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_permutations_trivial() {
+        let empty: [u8; 0] = [];
+        assert_eq!(Permutations::new(&empty).collect::<Vec<_>>(), vec![vec![]]);
+        assert_eq!(Permutations::new(&[1]).collect::<Vec<_>>(), vec![vec![1]]);
+    }
+
+    #[test]
+    fn test_permutations_count() {
+        let items: Vec<_> = (0..6).collect();
+        assert_eq!(Permutations::new(&items).count(), 720);
+    }
+}
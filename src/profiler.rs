@@ -0,0 +1,219 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Profiling of block execution.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+
+/// Execution time of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxProfile {
+    /// Hash of the transaction.
+    pub hash: Hash,
+    /// Identifier of the service, to which the transaction belongs.
+    pub service_id: u16,
+    /// Message type of the transaction.
+    pub message_type: u16,
+    /// Wall time of `Transaction::execute()`.
+    pub execution: Duration,
+}
+
+/// Execution profile of a block created by the testkit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockProfile {
+    /// Height of the block.
+    pub height: Height,
+    /// Profiles of transactions in the order of their execution.
+    pub transactions: Vec<TxProfile>,
+    /// Wall time of the block execution, including the execution of transactions
+    /// and the computation of the state hash.
+    pub execution: Duration,
+    /// Wall time of `Service::state_hash()` calls for all services on the resulting state.
+    pub state_hash: Duration,
+    /// Wall time of merging the changes made by the block into the storage.
+    pub merge: Duration,
+}
+
+/// Aggregated execution time of transactions of the same type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxTypeProfile {
+    /// Identifier of the service, to which the transactions belong.
+    pub service_id: u16,
+    /// Message type of the transactions.
+    pub message_type: u16,
+    /// Number of executed transactions.
+    pub count: usize,
+    /// Total wall time of `Transaction::execute()` for the transactions.
+    pub total: Duration,
+}
+
+impl TxTypeProfile {
+    /// Returns the mean execution time of a transaction.
+    pub fn mean(&self) -> Duration {
+        self.total / self.count as u32
+    }
+}
+
+/// Execution profile of the blocks created by the testkit. See `TestKit::profile_report()`.
+///
+/// The report can be printed with `Display`, which lists transaction types in the order
+/// of decreasing total execution time and the totals for the block execution phases.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    blocks: Vec<BlockProfile>,
+}
+
+impl ProfileReport {
+    /// Returns profiles of the created blocks.
+    pub fn blocks(&self) -> &[BlockProfile] {
+        &self.blocks
+    }
+
+    /// Returns the execution time aggregated by transaction types, in the order of
+    /// decreasing total time.
+    pub fn by_transaction_type(&self) -> Vec<TxTypeProfile> {
+        let mut types = BTreeMap::new();
+        for tx in self.blocks.iter().flat_map(|block| &block.transactions) {
+            let entry = types
+                .entry((tx.service_id, tx.message_type))
+                .or_insert((0, Duration::default()));
+            entry.0 += 1;
+            entry.1 += tx.execution;
+        }
+
+        let mut types: Vec<_> = types
+            .into_iter()
+            .map(|((service_id, message_type), (count, total))| {
+                TxTypeProfile {
+                    service_id,
+                    message_type,
+                    count,
+                    total,
+                }
+            })
+            .collect();
+        types.sort_by(|a, b| b.total.cmp(&a.total));
+        types
+    }
+
+    pub(crate) fn push(&mut self, block: BlockProfile) {
+        self.blocks.push(block);
+    }
+}
+
+/// Converts the duration to microseconds.
+fn micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + u64::from(duration.subsec_nanos() / 1_000)
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Transactions:")?;
+        for tx_type in self.by_transaction_type() {
+            writeln!(
+                f,
+                "  service {}, type {}: {} tx, {} us total, {} us mean",
+                tx_type.service_id,
+                tx_type.message_type,
+                tx_type.count,
+                micros(tx_type.total),
+                micros(tx_type.mean())
+            )?;
+        }
+
+        let total = |phase: fn(&BlockProfile) -> Duration| {
+            micros(self.blocks.iter().map(phase).fold(
+                Duration::default(),
+                |acc, d| acc + d,
+            ))
+        };
+        writeln!(f, "Blocks: {}", self.blocks.len())?;
+        writeln!(f, "  execution: {} us", total(|block| block.execution))?;
+        writeln!(f, "  state hash: {} us", total(|block| block.state_hash))?;
+        write!(f, "  merge: {} us", total(|block| block.merge))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::hash;
+    use super::*;
+
+    fn us(micros: u32) -> Duration {
+        Duration::new(0, micros * 1_000)
+    }
+
+    fn tx(service_id: u16, message_type: u16, micros: u32) -> TxProfile {
+        TxProfile {
+            hash: hash(&[service_id as u8, message_type as u8]),
+            service_id,
+            message_type,
+            execution: us(micros),
+        }
+    }
+
+    fn block(height: u64, transactions: Vec<TxProfile>) -> BlockProfile {
+        BlockProfile {
+            height: Height(height),
+            transactions,
+            execution: us(100),
+            state_hash: us(10),
+            merge: us(1),
+        }
+    }
+
+    #[test]
+    fn test_by_transaction_type() {
+        let mut report = ProfileReport::default();
+        report.push(block(1, vec![tx(1, 0, 5), tx(1, 1, 30), tx(1, 0, 15)]));
+        report.push(block(2, vec![]));
+        report.push(block(3, vec![tx(2, 0, 1), tx(1, 0, 10)]));
+
+        let types = report.by_transaction_type();
+        assert_eq!(
+            types,
+            vec![
+                TxTypeProfile {
+                    service_id: 1,
+                    message_type: 0,
+                    count: 3,
+                    total: us(30),
+                },
+                TxTypeProfile {
+                    service_id: 1,
+                    message_type: 1,
+                    count: 1,
+                    total: us(30),
+                },
+                TxTypeProfile {
+                    service_id: 2,
+                    message_type: 0,
+                    count: 1,
+                    total: us(1),
+                },
+            ]
+        );
+        assert_eq!(types[0].mean(), us(10));
+
+        let display = report.to_string();
+        assert!(display.contains("service 1, type 0: 3 tx, 30 us total, 10 us mean"));
+        assert!(display.contains("Blocks: 3"));
+        assert!(display.contains("execution: 300 us"));
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proofs of service data rooted in the `state_hash` of a block.
+//!
+//! Light clients verify service data in two steps: first, the root hash of a service table
+//! is proven to be a part of the `state_hash` of a block; second, the value is proven to be
+//! a part of the service table. Proofs in this module combine both steps.
+
+use std::fmt;
+
+use exonum::blockchain::{Block, Blockchain, Schema as CoreSchema};
+use exonum::crypto::Hash;
+use exonum::storage::{ListProof, MapProof, ProofListIndex, ProofMapIndex, Snapshot, StorageValue};
+use exonum::storage::proof_map_index::ProofMapKey;
+
+/// Error occurring during validation of a proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// The proof of the service table to the block `state_hash` is invalid.
+    InvalidTableProof(String),
+    /// The service table is not a part of the block `state_hash`.
+    MissingTable,
+    /// The root hash of the service table does not match the hash in the block `state_hash`.
+    TableHashMismatch,
+    /// The proof of the value to the service table is invalid.
+    InvalidValueProof(String),
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ProofError::InvalidTableProof(ref e) => write!(f, "Invalid proof to table: {}", e),
+            ProofError::MissingTable => write!(f, "Service table is absent in state hash"),
+            ProofError::TableHashMismatch => write!(f, "Service table root hash mismatch"),
+            ProofError::InvalidValueProof(ref e) => write!(f, "Invalid proof to value: {}", e),
+        }
+    }
+}
+
+/// Proof of the root hash of a service table to the `state_hash` of a block.
+#[derive(Debug)]
+pub struct TableProof {
+    block: Block,
+    table_key: Hash,
+    proof: MapProof<Hash>,
+}
+
+impl TableProof {
+    fn new(snapshot: &Snapshot, service_id: u16, table_idx: usize) -> Self {
+        let schema = CoreSchema::new(snapshot);
+        TableProof {
+            block: schema.last_block().expect("Blockchain has no blocks"),
+            table_key: Blockchain::service_table_unique_key(service_id, table_idx),
+            proof: schema.get_proof_to_service_table(service_id, table_idx),
+        }
+    }
+
+    /// Returns the block, to the `state_hash` of which the proof is rooted.
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
+    /// Returns the underlying proof.
+    pub fn proof(&self) -> &MapProof<Hash> {
+        &self.proof
+    }
+
+    /// Validates the proof, checking that the service table with the given root hash
+    /// is a part of the `state_hash` of the block.
+    pub fn validate(&self, table_root_hash: &Hash) -> Result<(), ProofError> {
+        let table_hash = self.proof
+            .validate(&self.table_key, *self.block.state_hash())
+            .map_err(|e| ProofError::InvalidTableProof(format!("{:?}", e)))?;
+        match table_hash {
+            Some(hash) if hash == table_root_hash => Ok(()),
+            Some(_) => Err(ProofError::TableHashMismatch),
+            None => Err(ProofError::MissingTable),
+        }
+    }
+}
+
+/// Proof of a value in a service `ProofMapIndex` rooted in the `state_hash` of a block.
+#[derive(Debug)]
+pub struct MapStateProof<K, V> {
+    key: K,
+    to_table: TableProof,
+    to_value: MapProof<V>,
+}
+
+impl<K, V> MapStateProof<K, V>
+where
+    K: ProofMapKey,
+    V: StorageValue,
+{
+    pub(crate) fn new(
+        snapshot: &Snapshot,
+        service_id: u16,
+        table_idx: usize,
+        index_name: &str,
+        key: K,
+    ) -> Self {
+        let to_table = TableProof::new(snapshot, service_id, table_idx);
+        let to_value = ProofMapIndex::<_, K, V>::new(index_name, snapshot).get_proof(&key);
+        MapStateProof {
+            key,
+            to_table,
+            to_value,
+        }
+    }
+
+    /// Returns the proof of the service table to the block `state_hash`.
+    pub fn to_table(&self) -> &TableProof {
+        &self.to_table
+    }
+
+    /// Returns the proof of the value to the service table.
+    pub fn to_value(&self) -> &MapProof<V> {
+        &self.to_value
+    }
+
+    /// Validates the proof, returning the proven value, or `None` if the proof shows that
+    /// there is no value corresponding to the key.
+    pub fn validate(&self) -> Result<Option<&V>, ProofError> {
+        let table_root_hash = self.to_value.root_hash();
+        self.to_table.validate(&table_root_hash)?;
+        self.to_value
+            .validate(&self.key, table_root_hash)
+            .map_err(|e| ProofError::InvalidValueProof(format!("{:?}", e)))
+    }
+
+    /// Asserts that the proof is valid and returns the proven value.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the proof is invalid.
+    pub fn assert_valid(&self) -> Option<&V> {
+        match self.validate() {
+            Ok(value) => value,
+            Err(e) => panic!("Map proof is invalid: {}", e),
+        }
+    }
+}
+
+/// Proof of an element in a service `ProofListIndex` rooted in the `state_hash` of a block.
+#[derive(Debug)]
+pub struct ListStateProof<V> {
+    index: u64,
+    len: u64,
+    root_hash: Hash,
+    to_table: TableProof,
+    to_value: ListProof<V>,
+}
+
+impl<V: StorageValue> ListStateProof<V> {
+    pub(crate) fn new(
+        snapshot: &Snapshot,
+        service_id: u16,
+        table_idx: usize,
+        index_name: &str,
+        index: u64,
+    ) -> Self {
+        let to_table = TableProof::new(snapshot, service_id, table_idx);
+        let list = ProofListIndex::<_, V>::new(index_name, snapshot);
+        assert!(
+            index < list.len(),
+            "Index {} is out of range of list `{}` with length {}",
+            index,
+            index_name,
+            list.len()
+        );
+        ListStateProof {
+            index,
+            len: list.len(),
+            root_hash: list.root_hash(),
+            to_table,
+            to_value: list.get_proof(index),
+        }
+    }
+
+    /// Returns the proof of the service table to the block `state_hash`.
+    pub fn to_table(&self) -> &TableProof {
+        &self.to_table
+    }
+
+    /// Returns the proof of the element to the service table.
+    pub fn to_value(&self) -> &ListProof<V> {
+        &self.to_value
+    }
+
+    /// Validates the proof, returning the proven element.
+    pub fn validate(&self) -> Result<&V, ProofError> {
+        self.to_table.validate(&self.root_hash)?;
+        let values = self.to_value
+            .validate(self.root_hash, self.len)
+            .map_err(|e| ProofError::InvalidValueProof(format!("{:?}", e)))?;
+        match values.into_iter().find(|&(index, _)| index == self.index) {
+            Some((_, value)) => Ok(value),
+            None => Err(ProofError::InvalidValueProof(
+                format!("No element with index {}", self.index),
+            )),
+        }
+    }
+
+    /// Asserts that the proof is valid and returns the proven element.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the proof is invalid.
+    pub fn assert_valid(&self) -> &V {
+        match self.validate() {
+            Ok(value) => value,
+            Err(e) => panic!("List proof is invalid: {}", e),
+        }
+    }
+}
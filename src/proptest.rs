@@ -0,0 +1,224 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Property-based testing of services with [`proptest`].
+//!
+//! The module provides strategies generating keypairs, transactions and blocks,
+//! and the `check()` function running a property against a fresh testkit for each
+//! generated input. The module is available with the `proptest` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # extern crate exonum_testkit;
+//! # use exonum::blockchain::Transaction;
+//! # use exonum::crypto::{self, PublicKey, SecretKey};
+//! # use exonum::helpers::Height;
+//! # use exonum::messages::Message;
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! # use exonum_testkit::proptest::{self, Config};
+//! #
+//! # fn main() {
+//! let keypairs = (0..3).map(|_| crypto::gen_keypair()).collect();
+//! let factory = |keypair: &(PublicKey, SecretKey), seed: u64| -> Box<Transaction> {
+//!     Box::new(MyTransaction::new(&keypair.0, &seed.to_string(), &keypair.1))
+//! };
+//! proptest::check(
+//!     Config::default(),
+//!     &proptest::blocks(factory, keypairs, 0..20),
+//!     || TestKitBuilder::validator().with_service(MyService).create(),
+//!     |testkit, blocks| {
+//!         proptest::create_blocks(testkit, blocks);
+//!         assert_eq!(testkit.height(), Height(blocks.len() as u64));
+//!         for tx in blocks.iter().flat_map(|block| block.iter()) {
+//!             assert!(testkit.tx_location(&tx.hash()).is_some());
+//!         }
+//!     },
+//! );
+//! # }
+//! ```
+//!
+//! [`proptest`]: https://crates.io/crates/proptest
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+use exonum::blockchain::Transaction;
+use exonum::crypto::{gen_keypair_from_seed, PublicKey, SecretKey, Seed, SEED_LENGTH};
+use exonum::messages::Message;
+use proptest_lib::{collection, num};
+use proptest_lib::bool as boolean;
+use proptest_lib::strategy::{BoxedStrategy, Strategy, ValueFor};
+use proptest_lib::test_runner::TestRunner;
+
+use TestKit;
+
+pub use proptest_lib::test_runner::Config;
+
+/// Factory of valid service transactions used by the `transactions()` and `blocks()`
+/// strategies.
+///
+/// The trait is implemented for functions with the signature of `create_transaction()`.
+pub trait TransactionFactory {
+    /// Creates a transaction signed with the given keypair. The contents of the transaction
+    /// should be derived from the `seed`; the transaction should pass `verify()`.
+    fn create_transaction(&self, keypair: &(PublicKey, SecretKey), seed: u64) -> Box<Transaction>;
+}
+
+impl<F> TransactionFactory for F
+where
+    F: Fn(&(PublicKey, SecretKey), u64) -> Box<Transaction>,
+{
+    fn create_transaction(&self, keypair: &(PublicKey, SecretKey), seed: u64) -> Box<Transaction> {
+        self(keypair, seed)
+    }
+}
+
+/// Returns a strategy generating keypairs.
+pub fn keypair() -> BoxedStrategy<(PublicKey, SecretKey)> {
+    collection::vec(num::u8::ANY, SEED_LENGTH..SEED_LENGTH + 1)
+        .prop_map(|bytes| gen_keypair_from_seed(&Seed::from_slice(&bytes).unwrap()))
+        .boxed()
+}
+
+/// Returns a strategy generating sequences of transactions created by the `factory`
+/// and signed with randomly chosen `keypairs`. The number of transactions is within
+/// the `size` range; duplicate transactions are removed.
+///
+/// # Panics
+///
+/// - Panics if `keypairs` is empty.
+pub fn transactions<F>(
+    factory: F,
+    keypairs: Vec<(PublicKey, SecretKey)>,
+    size: Range<usize>,
+) -> BoxedStrategy<Vec<Box<Transaction>>>
+where
+    F: TransactionFactory + 'static,
+{
+    assert!(!keypairs.is_empty(), "At least one keypair should be specified");
+    let factory = Arc::new(factory);
+    collection::vec((0..keypairs.len(), num::u64::ANY), size)
+        .prop_map(move |seeds| {
+            let mut hashes = BTreeSet::new();
+            seeds
+                .into_iter()
+                .map(|(index, seed)| factory.create_transaction(&keypairs[index], seed))
+                .filter(|tx| hashes.insert(tx.hash()))
+                .collect()
+        })
+        .boxed()
+}
+
+/// Returns a strategy generating sequences of transactions as in `transactions()`,
+/// which are randomly partitioned into blocks. Some of the blocks may be empty.
+///
+/// # Panics
+///
+/// - Panics if `keypairs` is empty.
+pub fn blocks<F>(
+    factory: F,
+    keypairs: Vec<(PublicKey, SecretKey)>,
+    size: Range<usize>,
+) -> BoxedStrategy<Vec<Vec<Box<Transaction>>>>
+where
+    F: TransactionFactory + 'static,
+{
+    let cuts = collection::vec(boolean::ANY, size.end..size.end + 1);
+    (transactions(factory, keypairs, size), cuts)
+        .prop_map(|(txs, cuts)| partition(txs, &cuts))
+        .boxed()
+}
+
+/// Splits items into blocks, starting a new block after each item with the set cut flag.
+fn partition<T>(items: Vec<T>, cuts: &[bool]) -> Vec<Vec<T>> {
+    let mut blocks = vec![Vec::new()];
+    for (item, &cut) in items.into_iter().zip(cuts) {
+        blocks.last_mut().unwrap().push(item);
+        if cut {
+            blocks.push(Vec::new());
+        }
+    }
+    blocks
+}
+
+/// Creates a block for each of the given blocks of transactions, e.g., generated
+/// by the `blocks()` strategy. Transactions are copied, so that the generated value
+/// could be used in assertions afterwards.
+///
+/// # Panics
+///
+/// - Panics if any of transactions cannot be parsed by the testkit services.
+/// - Panics if any of transactions has been already committed to the blockchain.
+pub fn create_blocks(testkit: &mut TestKit, blocks: &[Vec<Box<Transaction>>]) {
+    for block in blocks {
+        let txs: Vec<_> = block
+            .iter()
+            .map(|tx| {
+                testkit.blockchain.tx_from_raw(tx.raw().clone()).unwrap_or_else(|| {
+                    panic!("Transaction {:?} cannot be parsed by the services", tx.hash())
+                })
+            })
+            .collect();
+        testkit.create_block_with_transactions(txs);
+    }
+}
+
+/// Checks a property for the values generated by the `strategy`. For each value,
+/// a fresh testkit is created with `create_testkit`; the property should panic
+/// if it does not hold.
+///
+/// # Panics
+///
+/// - Panics if the property does not hold. The panic message contains the minimal
+///   failing input found by shrinking.
+pub fn check<S, B, P>(config: Config, strategy: &S, create_testkit: B, property: P)
+where
+    S: Strategy,
+    B: Fn() -> TestKit,
+    P: Fn(&mut TestKit, &ValueFor<S>),
+{
+    let mut runner = TestRunner::new(config);
+    let result = runner.run(strategy, |value| {
+        let mut testkit = create_testkit();
+        property(&mut testkit, value);
+        Ok(())
+    });
+    if let Err(e) = result {
+        panic!("Property does not hold: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition() {
+        let items = vec![1, 2, 3, 4];
+        assert_eq!(partition(items.clone(), &[false; 4]), vec![vec![1, 2, 3, 4]]);
+        assert_eq!(
+            partition(items.clone(), &[true, false, true, false]),
+            vec![vec![1], vec![2, 3], vec![4]]
+        );
+        assert_eq!(
+            partition(items, &[false, false, false, true]),
+            vec![vec![1, 2, 3, 4], vec![]]
+        );
+        assert_eq!(partition(Vec::<u8>::new(), &[true]), vec![vec![]]);
+    }
+}
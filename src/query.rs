@@ -0,0 +1,94 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serialization of query strings for API requests.
+
+use serde::Serialize;
+use serde_json::{self, Value};
+
+/// Serializes a structure into a query string (without the leading `?`).
+///
+/// Parameters are sorted by name to make the query string deterministic. `null` fields
+/// are skipped, and arrays are serialized as repeated parameters.
+///
+/// # Panics
+///
+/// - Panics if `query` is not serialized into a JSON object, or if some of its fields
+///   are objects or nested arrays.
+pub fn encode_query<Q: Serialize>(query: &Q) -> String {
+    let value = serde_json::to_value(query).expect("Cannot serialize query");
+    let mut fields: Vec<_> = match value {
+        Value::Object(fields) => fields.into_iter().collect(),
+        other => panic!("Query should be serialized into an object, got {}", other),
+    };
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut params = Vec::new();
+    for (name, value) in fields {
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                Value::Null => continue,
+                Value::String(s) => s,
+                Value::Bool(_) | Value::Number(_) => value.to_string(),
+                _ => panic!("Unsupported value of query parameter `{}`: {}", name, value),
+            };
+            params.push(format!("{}={}", percent_encode(&name), percent_encode(&value)));
+        }
+    }
+    params.join("&")
+}
+
+/// Percent-encodes all characters except for unreserved ones (RFC 3986, section 2.3).
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &byte in s.as_bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+
+    #[test]
+    fn test_encode_query() {
+        let mut query = BTreeMap::new();
+        query.insert("count", Value::from(10));
+        query.insert("flag", Value::from(true));
+        query.insert("missing", Value::Null);
+        query.insert("name", Value::from("Alice & Bob/?=é"));
+        query.insert("tags", Value::from(vec!["a b", "c"]));
+        assert_eq!(
+            encode_query(&query),
+            "count=10&flag=true&name=Alice%20%26%20Bob%2F%3F%3D%C3%A9&tags=a%20b&tags=c"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Query should be serialized into an object")]
+    fn test_encode_query_non_object() {
+        encode_query(&[1, 2, 3]);
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Replaying transaction logs exported from Exonum nodes.
+//!
+//! A transaction log is a text file in the JSON lines format, each line of which describes
+//! a block committed to the blockchain:
+//!
+//! ```text
+//! {"height": 1, "transactions": ["<hex>", "<hex>"], "state_hash": "<hex>"}
+//! {"height": 2, "transactions": [], "state_hash": "<hex>"}
+//! ```
+//!
+//! Transactions are hex-encoded raw messages. The `height` and `state_hash` fields
+//! are optional; if they are specified, they are compared with the height and the state hash
+//! of the block created by the testkit. Empty lines are ignored.
+//!
+//! For state hashes to match, the testkit should start from the same blockchain state
+//! as the node, e.g., by bootstrapping it with `TestKitBuilder::with_preloaded_rocksdb()`.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # extern crate exonum_testkit;
+//! # use exonum::crypto;
+//! # use exonum::encoding::serialize::ToHex;
+//! # use exonum::messages::Message;
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! # use exonum_testkit::replay::TransactionLog;
+//! #
+//! # fn main() {
+//! let (pubkey, key) = crypto::gen_keypair();
+//! let tx = MyTransaction::new(&pubkey, "foo", &key);
+//! // In tests, the log is usually exported from a node and read with `TransactionLog::read()`.
+//! let log = {
+//!     let mut node = TestKitBuilder::validator().with_seed(0).with_service(MyService).create();
+//!     node.create_block_with_transaction(tx.clone());
+//!     format!(
+//!         r#"{{"height": 1, "transactions": ["{}"], "state_hash": "{}"}}"#,
+//!         tx.raw().as_ref().to_hex(),
+//!         node.last_block().state_hash().to_hex()
+//!     )
+//! };
+//!
+//! let mut testkit = TestKitBuilder::validator().with_seed(0).with_service(MyService).create();
+//! let report = TransactionLog::from_reader(log.as_bytes()).replay(&mut testkit);
+//! assert!(report.is_ok(), "{:?}", report.first_divergence());
+//! assert_eq!(report.transactions(), 1);
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use exonum::blockchain::{Schema as CoreSchema, Transaction};
+use exonum::crypto::Hash;
+use exonum::encoding::serialize::ToHex;
+use exonum::helpers::Height;
+use exonum::messages::{Message, MessageBuffer, RawMessage};
+use serde_json;
+
+use TestKit;
+use saved::decode_hex;
+
+/// Line of a transaction log.
+#[derive(Debug, Serialize, Deserialize)]
+struct LogLine {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<u64>,
+    transactions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_hash: Option<Hash>,
+}
+
+/// Block of transactions from a transaction log.
+#[derive(Debug, Clone)]
+pub struct LoggedBlock {
+    /// Height of the block, if specified in the log.
+    pub height: Option<Height>,
+    /// Raw transactions in the order of their appearance in the block.
+    pub transactions: Vec<RawMessage>,
+    /// State hash of the block, if specified in the log.
+    pub state_hash: Option<Hash>,
+}
+
+/// Sequence of blocks to replay against the testkit.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionLog {
+    blocks: Vec<LoggedBlock>,
+}
+
+impl TransactionLog {
+    /// Creates a log from the given blocks.
+    pub fn new(blocks: Vec<LoggedBlock>) -> Self {
+        TransactionLog { blocks }
+    }
+
+    /// Reads the log from the file.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be read or parsed.
+    pub fn read<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let file = File::open(path).unwrap_or_else(|e| {
+            panic!("Cannot open transaction log {}: {}", path.display(), e)
+        });
+        TransactionLog::from_reader(BufReader::new(file))
+    }
+
+    /// Reads the log from the given reader.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the log cannot be read or parsed.
+    pub fn from_reader<R: BufRead>(reader: R) -> Self {
+        let mut blocks = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.unwrap_or_else(|e| {
+                panic!("Cannot read line {} of transaction log: {}", i + 1, e)
+            });
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let line: LogLine = serde_json::from_str(&line).unwrap_or_else(|e| {
+                panic!("Cannot parse line {} of transaction log: {}", i + 1, e)
+            });
+            blocks.push(LoggedBlock {
+                height: line.height.map(Height),
+                transactions: line.transactions
+                    .iter()
+                    .map(|tx| RawMessage::new(MessageBuffer::from_vec(decode_hex(tx))))
+                    .collect(),
+                state_hash: line.state_hash,
+            });
+        }
+        TransactionLog { blocks }
+    }
+
+    /// Returns blocks of the log.
+    pub fn blocks(&self) -> &[LoggedBlock] {
+        &self.blocks
+    }
+
+    /// Replays the log against the testkit, creating a block for each block of the log.
+    /// Transactions, which cannot be committed, are skipped. Replaying continues after
+    /// a divergence is found, so the report lists all divergences.
+    pub fn replay(&self, testkit: &mut TestKit) -> ReplayReport {
+        let mut report = ReplayReport::default();
+        for block in &self.blocks {
+            let height = testkit.height().next();
+            if let Some(logged_height) = block.height {
+                if logged_height != height {
+                    report.divergences.push(Divergence::Height {
+                        expected: logged_height,
+                        actual: height,
+                    });
+                }
+            }
+
+            let txs = parse_transactions(testkit, block, height, &mut report);
+            report.transactions += txs.len();
+            testkit.create_block_with_transactions(txs);
+            report.blocks += 1;
+
+            if let Some(expected) = block.state_hash {
+                let actual = *testkit.explorer().block(height).unwrap().state_hash();
+                if actual != expected {
+                    report.divergences.push(Divergence::StateHash {
+                        height,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Formats the log as JSON lines, which can be read back with `TransactionLog::from_reader()`.
+impl fmt::Display for TransactionLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for block in &self.blocks {
+            let line = LogLine {
+                height: block.height.map(|height| height.0),
+                transactions: block
+                    .transactions
+                    .iter()
+                    .map(|raw| raw.as_ref().to_hex())
+                    .collect(),
+                state_hash: block.state_hash,
+            };
+            writeln!(f, "{}", serde_json::to_string(&line).map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses transactions of the block, reporting the ones, which cannot be committed.
+fn parse_transactions(
+    testkit: &TestKit,
+    block: &LoggedBlock,
+    height: Height,
+    report: &mut ReplayReport,
+) -> Vec<Box<Transaction>> {
+    let snapshot = testkit.snapshot();
+    let schema = CoreSchema::new(&snapshot);
+    let mut txs = Vec::new();
+    for (index, raw) in block.transactions.iter().enumerate() {
+        match testkit.blockchain.tx_from_raw(raw.clone()) {
+            Some(tx) => {
+                if !tx.verify() || schema.transactions().contains(&tx.hash()) {
+                    report.divergences.push(Divergence::RejectedTransaction {
+                        height,
+                        hash: tx.hash(),
+                    });
+                } else {
+                    txs.push(tx);
+                }
+            }
+            None => {
+                report.divergences.push(
+                    Divergence::UnknownTransaction { height, index },
+                );
+            }
+        }
+    }
+    txs
+}
+
+/// Difference between the replayed log and the testkit blockchain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// The block is replayed at a height different from the one in the log.
+    Height {
+        /// Height in the log.
+        expected: Height,
+        /// Height of the block created by the testkit.
+        actual: Height,
+    },
+    /// The transaction at the given position in the block cannot be parsed by the testkit
+    /// services.
+    UnknownTransaction {
+        /// Height of the block.
+        height: Height,
+        /// Zero-based position of the transaction in the logged block.
+        index: usize,
+    },
+    /// The transaction is not committed, because it is not valid or has already been
+    /// committed.
+    RejectedTransaction {
+        /// Height of the block.
+        height: Height,
+        /// Hash of the transaction.
+        hash: Hash,
+    },
+    /// The state hash of the block created by the testkit differs from the one in the log.
+    StateHash {
+        /// Height of the block.
+        height: Height,
+        /// State hash in the log.
+        expected: Hash,
+        /// State hash of the block created by the testkit.
+        actual: Hash,
+    },
+}
+
+/// Results of replaying a transaction log.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    blocks: usize,
+    transactions: usize,
+    divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// Returns the number of replayed blocks.
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Returns the number of committed transactions.
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    /// Returns divergences found while replaying, in the order of their discovery.
+    pub fn divergences(&self) -> &[Divergence] {
+        &self.divergences
+    }
+
+    /// Returns the first divergence, after which the testkit state is likely to differ
+    /// from the logged one.
+    pub fn first_divergence(&self) -> Option<&Divergence> {
+        self.divergences.first()
+    }
+
+    /// Returns `true` if no divergences have been found.
+    pub fn is_ok(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
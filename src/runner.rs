@@ -0,0 +1,437 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simulation of a network of several testkit nodes.
+
+use std::fmt;
+
+use exonum::blockchain::{Schema as CoreSchema, Service};
+use exonum::helpers::{Height, Round, ValidatorId};
+use exonum::messages::{Message, RawMessage};
+use exonum::storage::StorageValue;
+
+use explorer::BlockWithTransactions;
+use faults::{FaultPolicy, Transport};
+use {TestKit, TestKitApi, TestKitBuilder};
+
+/// Builder for `TestNetworkRunner`.
+pub struct TestNetworkRunnerBuilder {
+    nodes: u16,
+    service_factories: Vec<Box<Fn() -> Box<Service>>>,
+    fault_policy: FaultPolicy,
+}
+
+impl fmt::Debug for TestNetworkRunnerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TestNetworkRunnerBuilder")
+            .field("nodes", &self.nodes)
+            .field("services", &self.service_factories.len())
+            .field("fault_policy", &self.fault_policy)
+            .finish()
+    }
+}
+
+impl TestNetworkRunnerBuilder {
+    /// Adds a service to every node of the network. As services cannot be shared among nodes,
+    /// the service is instantiated for each node with the provided factory.
+    pub fn with_service<F, S>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> S + 'static,
+        S: Into<Box<Service>>,
+    {
+        self.service_factories.push(
+            Box::new(move || factory().into()),
+        );
+        self
+    }
+
+    /// Sets the policy of injecting faults into the transport of transactions among nodes.
+    /// By default, no faults are injected.
+    pub fn with_fault_policy(mut self, policy: FaultPolicy) -> Self {
+        self.fault_policy = policy;
+        self
+    }
+
+    /// Creates the network.
+    pub fn create(self) -> TestNetworkRunner {
+        let validators = TestKitBuilder::validator().with_validators(self.nodes).validators;
+        let nodes = validators
+            .iter()
+            .map(|us| {
+                let mut builder = TestKitBuilder::validator();
+                builder.us = us.clone();
+                builder.validators = validators.clone();
+                for factory in &self.service_factories {
+                    builder = builder.with_service(factory());
+                }
+                builder.create()
+            })
+            .collect();
+        let groups = vec![0; nodes.len()];
+        TestNetworkRunner {
+            nodes,
+            groups,
+            transport: Transport::new(self.fault_policy),
+        }
+    }
+}
+
+/// Network of several testkit nodes, one per validator, which run the same services
+/// over separate databases.
+///
+/// The nodes exchange transactions and blocks via an in-memory transport controlled
+/// explicitly by the test: transactions sent to a node (e.g., via its API) are not seen by other
+/// nodes until delivered with `send_transactions()` or `broadcast_transactions()`, and a block
+/// created by a node is not seen by other nodes until they are synchronized with `sync()`
+/// or `sync_all()`. Thus, nodes may see transactions in different orders and serve API
+/// requests from a stale blockchain state.
+///
+/// Faults in the transport of transactions, such as message drops, latency and reordering,
+/// can be injected with a `FaultPolicy`.
+///
+/// The network can be split with `partition()`, in which case the nodes from different parts
+/// do not exchange transactions and blocks until the partition is healed with `heal()`.
+/// Only the part containing a majority of validators can create blocks.
+///
+/// Configuration changes are not propagated among nodes.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::crypto;
+/// # use exonum::helpers::Height;
+/// # use exonum_testkit::TestNetworkRunner;
+/// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+/// #
+/// # fn main() {
+/// let mut network = TestNetworkRunner::with_nodes(4)
+///     .with_service(|| MyService)
+///     .create();
+/// let (pubkey, key) = crypto::gen_keypair();
+/// network.api(0).send(MyTransaction::new(&pubkey, "foo", &key));
+/// network.create_block(0);
+/// // Node #1 serves the stale state.
+/// assert_eq!(network.node(1).height(), Height(0));
+/// network.sync_all();
+/// assert_eq!(network.node(1).height(), Height(1));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TestNetworkRunner {
+    nodes: Vec<TestKit>,
+    // Indexes of the connected components of the network, one per node.
+    groups: Vec<usize>,
+    transport: Transport<RawMessage>,
+}
+
+impl TestNetworkRunner {
+    /// Starts building a network with the given number of validator nodes.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `nodes` is zero.
+    pub fn with_nodes(nodes: u16) -> TestNetworkRunnerBuilder {
+        assert!(nodes > 0, "At least one node should be present in the network.");
+        TestNetworkRunnerBuilder {
+            nodes,
+            service_factories: Vec::new(),
+            fault_policy: FaultPolicy::default(),
+        }
+    }
+
+    /// Returns the number of nodes in the network.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the network has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the node with the given index. The index of a node is equal to the identifier
+    /// of its validator.
+    pub fn node(&self, index: usize) -> &TestKit {
+        &self.nodes[index]
+    }
+
+    /// Returns a mutable reference to the node with the given index.
+    ///
+    /// Blocks created directly with the node methods are treated the same way as blocks
+    /// created with `create_block()`; however, the block proposer is the validator #0
+    /// regardless of the node.
+    pub fn node_mut(&mut self, index: usize) -> &mut TestKit {
+        &mut self.nodes[index]
+    }
+
+    /// Returns the API of the node with the given index.
+    pub fn api(&self, index: usize) -> TestKitApi {
+        self.nodes[index].api()
+    }
+
+    /// Splits the network into two parts, so that nodes from different parts cannot
+    /// communicate with each other. Nodes not listed in either part are isolated from all
+    /// other nodes. The previous partition, if any, is replaced.
+    ///
+    /// While the network is partitioned, blocks can be created only by nodes from the part
+    /// containing a majority of validators (see `TestKit::majority_count()`), as the BFT
+    /// consensus requires. Nodes from other parts cannot make progress and catch up with
+    /// the majority once the partition is healed with `heal()`.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if a node is absent in the network or is listed more than once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate exonum;
+    /// # extern crate exonum_testkit;
+    /// # use exonum::helpers::Height;
+    /// # use exonum_testkit::TestNetworkRunner;
+    /// # fn main() {
+    /// let mut network = TestNetworkRunner::with_nodes(4).create();
+    /// network.partition(&[0, 1, 2], &[3]);
+    /// network.create_block(0);
+    /// network.sync_all();
+    /// assert_eq!(network.node(3).height(), Height(0));
+    ///
+    /// network.heal();
+    /// network.sync_all();
+    /// assert_eq!(network.node(3).height(), Height(1));
+    /// # }
+    /// ```
+    pub fn partition(&mut self, first: &[usize], second: &[usize]) {
+        let len = self.nodes.len();
+        // Unlisted nodes are placed into separate groups.
+        let mut groups: Vec<_> = (0..len).map(|index| index + 2).collect();
+        for (group, part) in [first, second].iter().enumerate() {
+            for &index in part.iter() {
+                assert!(index < len, "Node {} is absent in the network", index);
+                assert!(
+                    groups[index] >= 2,
+                    "Node {} is listed more than once in the partition",
+                    index
+                );
+                groups[index] = group;
+            }
+        }
+        self.groups = groups;
+    }
+
+    /// Restores the connectivity between all nodes of the network. The nodes do not catch up
+    /// with each other until synchronized.
+    pub fn heal(&mut self) {
+        self.groups = vec![0; self.nodes.len()];
+    }
+
+    /// Returns `true` if the nodes with the given indexes can communicate with each other.
+    pub fn is_connected(&self, first: usize, second: usize) -> bool {
+        self.groups[first] == self.groups[second]
+    }
+
+    /// Sends all transactions from the mempool of the node `from` to the node `to`.
+    /// Transactions already committed on the receiving node are ignored.
+    /// Does nothing if the nodes are not connected.
+    ///
+    /// Transactions are delivered according to the fault policy of the network; with
+    /// the default policy, they are delivered immediately.
+    pub fn send_transactions(&mut self, from: usize, to: usize) {
+        if !self.is_connected(from, to) {
+            return;
+        }
+        for transaction in self.mempool_messages(from) {
+            self.transport.send(from, to, transaction);
+        }
+        self.deliver_messages();
+    }
+
+    /// Sends all transactions from the mempool of the node `from` to all other nodes
+    /// connected to it.
+    pub fn broadcast_transactions(&mut self, from: usize) {
+        let transactions = self.mempool_messages(from);
+        for to in 0..self.nodes.len() {
+            if to != from && self.is_connected(from, to) {
+                for transaction in &transactions {
+                    self.transport.send(from, to, transaction.clone());
+                }
+            }
+        }
+        self.deliver_messages();
+    }
+
+    /// Replaces the policy of injecting faults into the transport of transactions.
+    /// Transactions already in flight are delivered according to the previous policy.
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.transport.set_policy(policy);
+    }
+
+    /// Advances the time of the transport by one step and delivers the transactions
+    /// due at the new time.
+    pub fn tick(&mut self) {
+        self.transport.tick();
+        self.deliver_messages();
+    }
+
+    /// Returns the number of transactions sent among nodes, but not yet delivered.
+    pub fn in_flight_messages(&self) -> usize {
+        self.transport.in_flight()
+    }
+
+    /// Returns the number of transactions dropped by the transport.
+    pub fn dropped_messages(&self) -> usize {
+        self.transport.dropped()
+    }
+
+    /// Delivers transactions due at the current time. Transactions between nodes that have
+    /// been disconnected since the transactions were sent are lost.
+    fn deliver_messages(&mut self) {
+        for envelope in self.transport.take_due() {
+            if self.is_connected(envelope.from, envelope.to) {
+                receive_transactions(&mut self.nodes[envelope.to], vec![envelope.message]);
+            }
+        }
+    }
+
+    fn mempool_messages(&mut self, index: usize) -> Vec<RawMessage> {
+        self.nodes[index].poll_events();
+        self.nodes[index]
+            .mempool()
+            .values()
+            .map(|tx| tx.raw().clone())
+            .collect()
+    }
+
+    /// Creates a block with all transactions in the mempool of the node with the given index,
+    /// which acts as the block proposer. Other nodes do not receive the block until they are
+    /// synchronized.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the proposer is connected to fewer nodes (including itself) than required
+    ///   for the consensus, i.e., if it is in a minority part of a partitioned network.
+    pub fn create_block(&mut self, proposer: usize) {
+        let connected = (0..self.nodes.len())
+            .filter(|&index| self.is_connected(proposer, index))
+            .count();
+        let majority_count = self.nodes[proposer].majority_count();
+        assert!(
+            connected >= majority_count,
+            "Node {} cannot create a block: it is connected to {} validators, while {} \
+             are required for the consensus",
+            proposer,
+            connected,
+            majority_count
+        );
+
+        let node = &mut self.nodes[proposer];
+        node.poll_events();
+        let tx_hashes: Vec<_> = node.mempool().keys().cloned().collect();
+        node.do_create_block(
+            Some((ValidatorId(proposer as u16), Round::first())),
+            None,
+            &tx_hashes,
+        );
+    }
+
+    /// Delivers blocks missing on the node `to` from the node `from`. Blocks are re-executed
+    /// on the receiving node with the same transactions, proposer and precommit time.
+    /// Does nothing if the node `to` is not behind the node `from`, or if the nodes
+    /// are not connected.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the node `to` has committed a block different from the block
+    ///   at the same height on the node `from`, or if re-execution of a block on the node `to`
+    ///   leads to a different block.
+    pub fn sync(&mut self, to: usize, from: usize) {
+        if !self.is_connected(to, from) {
+            return;
+        }
+        let explorer = self.nodes[from].explorer();
+        let source_height = self.nodes[from].height();
+        let target = &mut self.nodes[to];
+        let target_height = target.height();
+        if target_height > source_height {
+            return;
+        }
+
+        assert_eq!(
+            target.last_block_hash(),
+            explorer.block(target_height).unwrap().hash(),
+            "Blockchains of nodes {} and {} have diverged at height {}",
+            to,
+            from,
+            target_height.0
+        );
+        for height in target_height.next().0..source_height.0 + 1 {
+            let block = explorer.block_with_txs(Height(height)).unwrap();
+            apply_block(target, block, to);
+        }
+    }
+
+    /// Synchronizes every node with the node having the greatest height among the nodes
+    /// connected to it.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if blockchains of any connected nodes have diverged.
+    pub fn sync_all(&mut self) {
+        for index in 0..self.nodes.len() {
+            let leader = (0..self.nodes.len())
+                .filter(|&other| self.is_connected(index, other))
+                .max_by_key(|&other| (self.nodes[other].height().0, -(other as isize)))
+                .unwrap();
+            if index != leader {
+                self.sync(index, leader);
+            }
+        }
+    }
+}
+
+/// Adds transactions to the mempool of the node as if they were received from the network.
+fn receive_transactions(node: &mut TestKit, transactions: Vec<RawMessage>) {
+    node.poll_events();
+
+    let snapshot = node.snapshot();
+    let schema = CoreSchema::new(&snapshot);
+    let mut mempool = node.mempool.write().expect(
+        "Cannot write transactions to mempool",
+    );
+    let mut limiter = node.mempool_limiter.write().expect(
+        "Cannot acquire write lock on mempool limiter",
+    );
+    for raw in transactions {
+        let tx = node.blockchain.tx_from_raw(raw).expect(
+            "Cannot parse transaction received from the network",
+        );
+        if !schema.transactions().contains(&tx.hash()) {
+            limiter.insert(&mut mempool, tx);
+        }
+    }
+}
+
+/// Re-executes a block received from another node and checks that the resulting block
+/// is the same.
+fn apply_block(node: &mut TestKit, block: BlockWithTransactions, index: usize) {
+    let height = block.height();
+    assert!(
+        node.reapply_block(block),
+        "Re-execution of block {} on node {} has led to a different block",
+        height.0,
+        index
+    );
+}
@@ -0,0 +1,212 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saving the testkit state to a file and loading it back.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+use exonum::crypto::{PublicKey, SecretKey};
+use exonum::encoding::serialize::{FromHex, ToHex};
+use exonum::helpers::ValidatorId;
+use exonum::messages::{MessageBuffer, RawMessage};
+use exonum::storage::{Change, Patch};
+use serde_json;
+
+use TestNode;
+
+/// Patch with hex-encoded keys and values. Removed keys have `None` values.
+type SavedPatch = BTreeMap<String, BTreeMap<String, Option<String>>>;
+
+/// Keys and the role of a node of the test network.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SavedNode {
+    consensus_public_key: PublicKey,
+    consensus_secret_key: SecretKey,
+    service_public_key: PublicKey,
+    service_secret_key: SecretKey,
+    validator_id: Option<u16>,
+}
+
+impl<'a> From<&'a TestNode> for SavedNode {
+    fn from(node: &'a TestNode) -> Self {
+        let (consensus_public_key, consensus_secret_key) = node.consensus_keypair();
+        let (service_public_key, service_secret_key) = node.service_keypair();
+        SavedNode {
+            consensus_public_key: *consensus_public_key,
+            consensus_secret_key: consensus_secret_key.clone(),
+            service_public_key: *service_public_key,
+            service_secret_key: service_secret_key.clone(),
+            validator_id: node.validator_id().map(|id| id.0),
+        }
+    }
+}
+
+impl<'a> From<&'a SavedNode> for TestNode {
+    fn from(node: &'a SavedNode) -> Self {
+        TestNode::from_parts(
+            (node.consensus_public_key, node.consensus_secret_key.clone()),
+            (node.service_public_key, node.service_secret_key.clone()),
+            node.validator_id.map(ValidatorId),
+        )
+    }
+}
+
+/// State of the testkit stored in a file.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SavedState {
+    pub us: SavedNode,
+    pub validators: Vec<SavedNode>,
+    storage: SavedPatch,
+    journal: Vec<SavedPatch>,
+    pub checkpoints: Vec<usize>,
+    mempool: Vec<String>,
+}
+
+impl SavedState {
+    pub fn new(
+        us: &TestNode,
+        validators: &[TestNode],
+        storage: &Patch,
+        journal: &[Patch],
+        checkpoints: Vec<usize>,
+        mempool: &[RawMessage],
+    ) -> Self {
+        SavedState {
+            us: SavedNode::from(us),
+            validators: validators.iter().map(SavedNode::from).collect(),
+            storage: encode_patch(storage),
+            journal: journal.iter().map(encode_patch).collect(),
+            checkpoints,
+            mempool: mempool.iter().map(|raw| raw.as_ref().to_hex()).collect(),
+        }
+    }
+
+    /// Reads the state from the file.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be read or parsed.
+    pub fn read(path: &Path) -> Self {
+        let file = File::open(path).unwrap_or_else(|e| {
+            panic!("Cannot open saved testkit state {}: {}", path.display(), e)
+        });
+        serde_json::from_reader(file).unwrap_or_else(|e| {
+            panic!("Cannot parse saved testkit state {}: {}", path.display(), e)
+        })
+    }
+
+    /// Writes the state to the file, overwriting it.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be written.
+    pub fn write(&self, path: &Path) {
+        let file = File::create(path).unwrap_or_else(|e| {
+            panic!("Cannot create saved testkit state {}: {}", path.display(), e)
+        });
+        serde_json::to_writer(file, self).unwrap_or_else(|e| {
+            panic!("Cannot write saved testkit state {}: {}", path.display(), e)
+        });
+    }
+
+    /// Returns the contents of the storage as a patch.
+    pub fn storage(&self) -> Patch {
+        decode_patch(&self.storage)
+    }
+
+    /// Returns the journal of the storage.
+    pub fn journal(&self) -> Vec<Patch> {
+        self.journal.iter().map(decode_patch).collect()
+    }
+
+    /// Returns raw transactions from the mempool.
+    pub fn mempool(&self) -> Vec<RawMessage> {
+        self.mempool
+            .iter()
+            .map(|hex| {
+                RawMessage::new(MessageBuffer::from_vec(decode_hex(hex)))
+            })
+            .collect()
+    }
+}
+
+fn encode_patch(patch: &Patch) -> SavedPatch {
+    patch
+        .iter()
+        .map(|(name, changes)| {
+            let changes = changes
+                .iter()
+                .map(|(key, change)| {
+                    let value = match *change {
+                        Change::Put(ref value) => Some(value.to_hex()),
+                        Change::Delete => None,
+                    };
+                    (key.to_hex(), value)
+                })
+                .collect();
+            (name.clone(), changes)
+        })
+        .collect()
+}
+
+fn decode_patch(patch: &SavedPatch) -> Patch {
+    patch
+        .iter()
+        .map(|(name, changes)| {
+            let changes = changes
+                .iter()
+                .map(|(key, value)| {
+                    let change = match *value {
+                        Some(ref value) => Change::Put(decode_hex(value)),
+                        None => Change::Delete,
+                    };
+                    (decode_hex(key), change)
+                })
+                .collect();
+            (name.clone(), changes)
+        })
+        .collect()
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Vec<u8> {
+    Vec::<u8>::from_hex(hex).unwrap_or_else(|e| {
+        panic!("Invalid hex string in testkit state: {}", e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::storage::{Database, Fork, MemoryDB};
+
+    use super::*;
+
+    #[test]
+    fn test_patch_roundtrip() {
+        let db = MemoryDB::new();
+        let mut fork: Fork = db.fork();
+        fork.put("foo", vec![1, 2], vec![3]);
+        fork.put("foo", vec![], vec![]);
+        fork.remove("bar", vec![4]);
+        let patch = fork.into_patch();
+
+        let saved = encode_patch(&patch);
+        assert_eq!(saved["foo"]["0102"], Some("03".to_owned()));
+        assert_eq!(saved["bar"]["04"], None);
+
+        let decoded = decode_patch(&saved);
+        assert_eq!(encode_patch(&decoded), saved);
+    }
+}
@@ -0,0 +1,330 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative scenarios driving the testkit.
+//!
+//! A scenario is a list of steps, each of which creates a block, rolls the blockchain back
+//! or checks its state. Transactions are referred to by names and parameters; the names
+//! are mapped to transaction factories registered in a `ScenarioRunner`.
+//!
+//! Scenarios are written in JSON, or in YAML with the `yaml` feature:
+//!
+//! ```text
+//! - create_block:
+//!     - tx: increment
+//!       params: { by: 5 }
+//!     - tx: increment
+//!       params: { by: 3 }
+//! - assert_height: 1
+//! - assert_api:
+//!     path: services/counter/count
+//!     equals: 8
+//! - rollback: 1
+//! - assert_api:
+//!     path: services/counter/count
+//!     equals: 0
+//! ```
+//!
+//! `assert_api` steps send a GET request to the given path relative to the `api` prefix
+//! and compare the JSON response with the expected value; `"private": true` sends
+//! the request to the private API.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # extern crate exonum_testkit;
+//! # use exonum::crypto;
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! # use exonum_testkit::scenario::{Scenario, ScenarioRunner};
+//! #
+//! # fn main() {
+//! let runner = ScenarioRunner::new().with_transaction("post", |params| {
+//!     let (pubkey, key) = crypto::gen_keypair();
+//!     Box::new(MyTransaction::new(&pubkey, params["msg"].as_str().unwrap(), &key))
+//! });
+//! let mut testkit = TestKitBuilder::validator()
+//!     .with_service(MyService)
+//!     .create();
+//! // In tests, scenarios are usually read from files with `Scenario::read()`.
+//! let scenario = Scenario::from_json(r#"[
+//!     {"create_block": [{"tx": "post", "params": {"msg": "foo"}}]},
+//!     {"assert_height": 1},
+//!     {"rollback": 1},
+//!     {"assert_height": 0}
+//! ]"#);
+//! runner.run(&scenario, &mut testkit);
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use exonum::blockchain::Transaction;
+use exonum::helpers::Height;
+use iron::headers::Headers;
+use serde_json::{self, Value};
+#[cfg(feature = "yaml")]
+use serde_yaml;
+
+use TestKit;
+
+/// Step of a scenario.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Creates a block with the given transactions.
+    CreateBlock(Vec<TxSpec>),
+    /// Rolls back the given number of blocks. See `TestKit::rollback()`.
+    Rollback(usize),
+    /// Checks that the blockchain has the given height.
+    AssertHeight(u64),
+    /// Checks the response of an API endpoint.
+    AssertApi(ApiAssertion),
+}
+
+/// Transaction referred to in a scenario.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TxSpec {
+    /// Name of the transaction factory registered in the `ScenarioRunner`.
+    pub tx: String,
+    /// Parameters passed to the factory; `null` if not specified.
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// Expected response of an API endpoint.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ApiAssertion {
+    /// Path of the endpoint relative to the `api` prefix, e.g., `services/counter/count`.
+    pub path: String,
+    /// Whether the request is sent to the private API.
+    #[serde(default)]
+    pub private: bool,
+    /// Expected JSON response.
+    pub equals: Value,
+}
+
+/// Declarative scenario, which can be run against a testkit with a `ScenarioRunner`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Scenario {
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Creates a scenario from the given steps.
+    pub fn new(steps: Vec<Step>) -> Self {
+        Scenario { steps }
+    }
+
+    /// Parses a scenario in the JSON format.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the scenario cannot be parsed.
+    pub fn from_json(script: &str) -> Self {
+        let steps = serde_json::from_str(script).unwrap_or_else(|e| {
+            panic!("Cannot parse scenario: {}", e)
+        });
+        Scenario { steps }
+    }
+
+    /// Parses a scenario in the YAML format.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the scenario cannot be parsed.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(script: &str) -> Self {
+        let steps = serde_yaml::from_str(script).unwrap_or_else(|e| {
+            panic!("Cannot parse scenario: {}", e)
+        });
+        Scenario { steps }
+    }
+
+    /// Reads the scenario from the file. Files with the `yaml` or `yml` extension
+    /// are parsed as YAML (available with the `yaml` feature), other files as JSON.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the file cannot be read or parsed.
+    pub fn read<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let mut script = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut script))
+            .unwrap_or_else(|e| panic!("Cannot read scenario {}: {}", path.display(), e));
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Scenario::parse_yaml(&script, path),
+            _ => Scenario::from_json(&script),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    fn parse_yaml(script: &str, _: &Path) -> Self {
+        Scenario::from_yaml(script)
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    fn parse_yaml(_: &str, path: &Path) -> Self {
+        panic!(
+            "Cannot read scenario {}: YAML scenarios require the `yaml` feature",
+            path.display()
+        )
+    }
+
+    /// Returns steps of the scenario.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+type TxFactory = Box<Fn(&Value) -> Box<Transaction>>;
+
+/// Runner of scenarios, which maps transaction names to transaction factories.
+#[derive(Default)]
+pub struct ScenarioRunner {
+    factories: BTreeMap<String, TxFactory>,
+}
+
+impl fmt::Debug for ScenarioRunner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScenarioRunner")
+            .field("transactions", &self.factories.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ScenarioRunner {
+    /// Creates a runner without registered transactions.
+    pub fn new() -> Self {
+        ScenarioRunner::default()
+    }
+
+    /// Registers a factory creating transactions with the given name from their parameters.
+    /// The factory may panic if the parameters are invalid.
+    pub fn with_transaction<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: Fn(&Value) -> Box<Transaction> + 'static,
+    {
+        self.factories.insert(name.to_owned(), Box::new(factory));
+        self
+    }
+
+    /// Runs the scenario against the testkit.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if a step of the scenario refers to an unregistered transaction.
+    /// - Panics if a check of the scenario fails.
+    pub fn run(&self, scenario: &Scenario, testkit: &mut TestKit) {
+        for (i, step) in scenario.steps.iter().enumerate() {
+            let step_number = i + 1;
+            match *step {
+                Step::CreateBlock(ref txs) => {
+                    let txs: Vec<_> = txs.iter()
+                        .map(|spec| self.create_transaction(spec, step_number))
+                        .collect();
+                    testkit.create_block_with_transactions(txs);
+                }
+                Step::Rollback(blocks) => testkit.rollback(blocks),
+                Step::AssertHeight(height) => {
+                    assert_eq!(
+                        testkit.height(),
+                        Height(height),
+                        "Step {} of scenario failed: unexpected blockchain height",
+                        step_number
+                    );
+                }
+                Step::AssertApi(ref assertion) => {
+                    let response: Value = testkit.api().get_internal(
+                        assertion.private,
+                        &format!("api/{}", assertion.path),
+                        Headers::new(),
+                        false,
+                    );
+                    assert_eq!(
+                        response,
+                        assertion.equals,
+                        "Step {} of scenario failed: unexpected response of {}",
+                        step_number,
+                        assertion.path
+                    );
+                }
+            }
+        }
+    }
+
+    fn create_transaction(&self, spec: &TxSpec, step_number: usize) -> Box<Transaction> {
+        let factory = self.factories.get(&spec.tx).unwrap_or_else(|| {
+            panic!(
+                "Step {} of scenario refers to unknown transaction `{}`",
+                step_number,
+                spec.tx
+            )
+        });
+        factory(&spec.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let scenario = Scenario::from_json(
+            r#"[
+                {"create_block": [{"tx": "inc", "params": {"by": 5}}, {"tx": "reset"}]},
+                {"rollback": 1},
+                {"assert_height": 0},
+                {"assert_api": {"path": "services/counter/count", "equals": 0}}
+            ]"#,
+        );
+
+        assert_eq!(
+            scenario.steps(),
+            &[
+                Step::CreateBlock(vec![
+                    TxSpec {
+                        tx: "inc".to_owned(),
+                        params: serde_json::from_str(r#"{"by": 5}"#).unwrap(),
+                    },
+                    TxSpec {
+                        tx: "reset".to_owned(),
+                        params: Value::Null,
+                    },
+                ]),
+                Step::Rollback(1),
+                Step::AssertHeight(0),
+                Step::AssertApi(ApiAssertion {
+                    path: "services/counter/count".to_owned(),
+                    private: false,
+                    equals: Value::from(0),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot parse scenario")]
+    fn test_parse_unknown_step() {
+        Scenario::from_json(r#"[{"create_blocks": 5}]"#);
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of API responses against JSON schemas.
+
+use serde_json::Value;
+use valico::json_schema::Scope;
+
+/// Validates `value` against the JSON schema (draft 4), returning descriptions of found
+/// errors if the validation fails.
+///
+/// # Panics
+///
+/// - Panics if `schema` is not a valid JSON schema.
+pub fn validate(value: &Value, schema: &Value) -> Result<(), Vec<String>> {
+    let mut scope = Scope::new();
+    let schema = scope
+        .compile_and_return(schema.clone(), false)
+        .unwrap_or_else(|e| panic!("Invalid JSON schema: {:?}", e));
+    let state = schema.validate(value);
+    if state.is_valid() {
+        Ok(())
+    } else {
+        Err(
+            state
+                .errors
+                .iter()
+                .map(|error| {
+                    let path = match error.get_path() {
+                        "" => "/",
+                        path => path,
+                    };
+                    format!("{}: {}", path, error.get_title())
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let schema: Value = serde_json::from_str(
+            r#"{
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "balance": { "type": "integer", "minimum": 0 }
+                },
+                "required": ["name", "balance"]
+            }"#,
+        ).unwrap();
+
+        let value: Value = serde_json::from_str(r#"{ "name": "Alice", "balance": 10 }"#).unwrap();
+        assert_eq!(validate(&value, &schema), Ok(()));
+
+        let value: Value = serde_json::from_str(r#"{ "name": 1, "balance": -1 }"#).unwrap();
+        let errors = validate(&value, &schema).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.starts_with("/name")));
+        assert!(errors.iter().any(|e| e.starts_with("/balance")));
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serving testkit APIs over HTTP.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+#[cfg(feature = "tls")]
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use hyper::buffer::BufReader;
+use hyper::net::{HttpStream, NetworkStream};
+use hyper::server::Request as HttpRequest;
+use iron::{Handler, IronResult, Protocol, Request, Response};
+use iron::error::HttpResult;
+use iron::headers::{Connection, ContentLength, ContentType};
+use iron::modifiers::Header;
+use iron::status::Status;
+use iron_test::response::extract_body_to_bytes;
+use mount::Mount;
+use router::Router;
+use serde_json::{self, Value};
+#[cfg(feature = "tls")]
+use hyper::net::SslServer;
+#[cfg(feature = "tls")]
+use hyper_native_tls::NativeTlsServer;
+
+use TestKit;
+use metrics;
+
+/// Timeout for reading a request from an accepted connection.
+const READ_TIMEOUT_SECS: u64 = 10;
+
+/// Function wrapping accepted connections, e.g., into TLS streams.
+type WrapStream = Arc<Fn(HttpStream) -> HttpResult<Box<NetworkStream + Send>> + Send + Sync>;
+
+/// Socket listening on a single address. Connections are accepted on a separate thread,
+/// and each connection is served on its own thread.
+struct Listener {
+    addr: SocketAddr,
+    is_shut_down: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    fn start<H: Handler>(
+        handler: H,
+        addr: SocketAddr,
+        protocol: Protocol,
+        wrap: WrapStream,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let is_shut_down = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let is_shut_down = Arc::clone(&is_shut_down);
+            let handler = Arc::new(handler);
+            thread::spawn(move || {
+                let mut connections = Vec::new();
+                for stream in listener.incoming() {
+                    if is_shut_down.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    // Errors of individual connections do not affect the server.
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+                    let timeout = Duration::from_secs(READ_TIMEOUT_SECS);
+                    let _ = stream.set_read_timeout(Some(timeout));
+                    let handler = Arc::clone(&handler);
+                    let protocol = protocol.clone();
+                    let wrap = Arc::clone(&wrap);
+                    connections.push(thread::spawn(move || {
+                        if let Ok(mut stream) = wrap(HttpStream(stream)) {
+                            serve_connection(&mut *stream, addr, &protocol, &*handler);
+                        }
+                    }));
+                }
+                for connection in connections {
+                    let _ = connection.join();
+                }
+            })
+        };
+
+        Ok(Listener {
+            addr,
+            is_shut_down,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stops accepting connections and waits until the accepted ones are served.
+    /// The socket is closed once the method returns.
+    fn shutdown(&mut self) {
+        self.is_shut_down.store(true, Ordering::SeqCst);
+        // The accepting thread is woken up with a dummy connection.
+        let mut addr = self.addr;
+        if addr.ip().is_unspecified() {
+            let localhost: IpAddr = match addr {
+                SocketAddr::V4(_) => Ipv4Addr::new(127, 0, 0, 1).into(),
+                SocketAddr::V6(_) => Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1).into(),
+            };
+            addr.set_ip(localhost);
+        }
+        if TcpStream::connect(addr).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Reads a single request from the connection and writes back the response of the handler.
+/// The connection is closed afterwards.
+fn serve_connection<H: Handler>(
+    stream: &mut NetworkStream,
+    local_addr: SocketAddr,
+    protocol: &Protocol,
+    handler: &H,
+) {
+    let response = {
+        let mut reader = BufReader::new(&mut *stream);
+        let http_request = match HttpRequest::new(&mut reader, local_addr) {
+            Ok(request) => request,
+            Err(_) => return,
+        };
+        match Request::from_http(http_request, local_addr, protocol) {
+            Ok(mut request) => handler.handle(&mut request).unwrap_or_else(|e| e.response),
+            Err(_) => Response::with(Status::BadRequest),
+        }
+    };
+    // The client may have disconnected already, so errors are ignored.
+    let _ = write_response(stream, response);
+}
+
+fn write_response(stream: &mut NetworkStream, response: Response) -> io::Result<()> {
+    let status = response.status.unwrap_or(Status::NotFound);
+    let mut headers = response.headers.clone();
+    let body = extract_body_to_bytes(response);
+    headers.set(ContentLength(body.len() as u64));
+    headers.set(Connection::close());
+    write!(stream, "HTTP/1.1 {}\r\n{}\r\n", status, headers)?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+/// HTTP server exposing public and private APIs of the testkit.
+///
+/// The server is created with `TestKit::serve()` and is shut down when dropped.
+/// The shutdown waits until requests being processed are served and closes the sockets,
+/// so that the addresses can be reused right away.
+pub struct TestServer {
+    public_listener: Listener,
+    private_listener: Listener,
+}
+
+impl fmt::Debug for TestServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TestServer")
+            .field("public_addr", &self.public_addr())
+            .field("private_addr", &self.private_addr())
+            .finish()
+    }
+}
+
+impl TestServer {
+    pub(crate) fn new(
+        public_mount: Mount,
+        public_addr: SocketAddr,
+        private_mount: Mount,
+        private_addr: SocketAddr,
+    ) -> Self {
+        TestServer::start(
+            public_mount,
+            public_addr,
+            private_mount,
+            private_addr,
+            &Protocol::http(),
+            Arc::new(|stream: HttpStream| Ok(Box::new(stream) as Box<NetworkStream + Send>)),
+        )
+    }
+
+    /// Creates a server accepting HTTPS connections with the identity from the given
+    /// PKCS #12 archive.
+    #[cfg(feature = "tls")]
+    pub(crate) fn new_tls(
+        public_mount: Mount,
+        public_addr: SocketAddr,
+        private_mount: Mount,
+        private_addr: SocketAddr,
+        identity: &Path,
+        password: &str,
+    ) -> Self {
+        let ssl = NativeTlsServer::new(identity, password).unwrap_or_else(|e| {
+            panic!("Cannot load TLS identity from {}: {}", identity.display(), e)
+        });
+        TestServer::start(
+            public_mount,
+            public_addr,
+            private_mount,
+            private_addr,
+            &Protocol::https(),
+            Arc::new(move |stream: HttpStream| {
+                ssl.wrap_server(stream).map(|stream| {
+                    Box::new(stream) as Box<NetworkStream + Send>
+                })
+            }),
+        )
+    }
+
+    fn start(
+        public_mount: Mount,
+        public_addr: SocketAddr,
+        private_mount: Mount,
+        private_addr: SocketAddr,
+        protocol: &Protocol,
+        wrap: WrapStream,
+    ) -> Self {
+        let listen = |handler: Mount, addr: SocketAddr| {
+            Listener::start(handler, addr, protocol.clone(), Arc::clone(&wrap))
+                .unwrap_or_else(|e| panic!("Cannot start API server on {}: {}", addr, e))
+        };
+        TestServer {
+            public_listener: listen(public_mount, public_addr),
+            private_listener: listen(private_mount, private_addr),
+        }
+    }
+
+    /// Returns the address of the public API server. If the server has been started
+    /// on port 0, the address contains the port assigned by the OS.
+    pub fn public_addr(&self) -> SocketAddr {
+        self.public_listener.addr
+    }
+
+    /// Returns the address of the private API server.
+    pub fn private_addr(&self) -> SocketAddr {
+        self.private_listener.addr
+    }
+
+    /// Shuts the server down. The server stops accepting connections, serves requests
+    /// that are being processed and releases its addresses.
+    pub fn shutdown(self) {
+        // The actual work is performed in `Drop`.
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.public_listener.shutdown();
+        self.private_listener.shutdown();
+    }
+}
+
+/// Request to the testkit received via the control API.
+#[derive(Debug)]
+enum ControlRequest {
+    CreateBlock,
+    Status,
+    Rollback { blocks: u64 },
+    Shutdown,
+}
+
+/// Control request together with the channel for the reply. The reply is either
+/// the blockchain height after processing the request or an error message.
+type ControlMessage = (ControlRequest, mpsc::Sender<Result<u64, String>>);
+
+/// Control API forwarding requests to the thread owning the testkit.
+#[derive(Clone)]
+struct ControlApi {
+    sender: Arc<Mutex<mpsc::Sender<ControlMessage>>>,
+}
+
+impl ControlApi {
+    fn router(self) -> Router {
+        let mut router = Router::new();
+
+        let self_ = self.clone();
+        let status = move |_: &mut Request| self_.send(ControlRequest::Status);
+        router.get("/v1/status", status, "status");
+
+        let self_ = self.clone();
+        let create_block = move |_: &mut Request| self_.send(ControlRequest::CreateBlock);
+        router.post("/v1/blocks", create_block, "create_block");
+
+        let self_ = self.clone();
+        let rollback = move |req: &mut Request| self_.rollback(req);
+        router.post("/v1/rollback", rollback, "rollback");
+
+        let shutdown = move |_: &mut Request| self.send(ControlRequest::Shutdown);
+        router.post("/v1/shutdown", shutdown, "shutdown");
+
+        router
+    }
+
+    fn rollback(&self, req: &mut Request) -> IronResult<Response> {
+        let mut body = String::new();
+        let blocks = req.body
+            .read_to_string(&mut body)
+            .ok()
+            .and_then(|_| serde_json::from_str::<Value>(&body).ok())
+            .and_then(|value| value.get("blocks").and_then(Value::as_u64));
+        match blocks {
+            Some(blocks) => self.send(ControlRequest::Rollback { blocks }),
+            None => json_response(
+                Status::BadRequest,
+                &Value::from("Expected request body of the form `{\"blocks\": <number>}`"),
+            ),
+        }
+    }
+
+    fn send(&self, request: ControlRequest) -> IronResult<Response> {
+        let (reply_sender, reply_receiver) = mpsc::channel();
+        let sent = self.sender
+            .lock()
+            .expect("Cannot acquire lock on control channel")
+            .send((request, reply_sender));
+
+        match sent.ok().and_then(|_| reply_receiver.recv().ok()) {
+            Some(Ok(height)) => {
+                let mut status = serde_json::Map::new();
+                status.insert("height".to_owned(), Value::from(height));
+                json_response(Status::Ok, &Value::Object(status))
+            }
+            Some(Err(message)) => json_response(Status::BadRequest, &Value::from(message)),
+            None => json_response(
+                Status::ServiceUnavailable,
+                &Value::from("Testkit is shut down"),
+            ),
+        }
+    }
+}
+
+fn json_response(status: Status, body: &Value) -> IronResult<Response> {
+    Ok(Response::with(
+        (status, body.to_string(), Header(ContentType::json())),
+    ))
+}
+
+/// Serves testkit APIs together with the control API until a shutdown request is received.
+pub(crate) fn serve_with_control(
+    testkit: &mut TestKit,
+    public_addr: SocketAddr,
+    private_addr: SocketAddr,
+) {
+    let (sender, receiver) = mpsc::channel();
+    let control_api = ControlApi { sender: Arc::new(Mutex::new(sender)) };
+
+    let (public_mount, mut private_mount) = testkit.api().into_mounts();
+    private_mount.mount("api/testkit", control_api.router());
+    let (public_mount, private_mount) = metrics::instrument(testkit, public_mount, private_mount);
+    let server = TestServer::new(public_mount, public_addr, private_mount, private_addr);
+
+    for (request, reply) in receiver.iter() {
+        let result = match request {
+            ControlRequest::CreateBlock => {
+                testkit.create_block();
+                Ok(testkit.height().0)
+            }
+            ControlRequest::Status => Ok(testkit.height().0),
+            ControlRequest::Rollback { blocks } => {
+                if blocks > testkit.height().0 {
+                    Err(format!(
+                        "Cannot rollback {} blocks; the blockchain height is {}",
+                        blocks,
+                        testkit.height().0
+                    ))
+                } else {
+                    testkit.rollback(blocks as usize);
+                    Ok(testkit.height().0)
+                }
+            }
+            ControlRequest::Shutdown => {
+                // The client may have disconnected already, so errors are ignored.
+                let _ = reply.send(Ok(testkit.height().0));
+                break;
+            }
+        };
+        let _ = reply.send(result);
+    }
+
+    server.shutdown();
+}
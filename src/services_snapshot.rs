@@ -0,0 +1,182 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! State of all testkit services captured at once.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use exonum::blockchain::{Blockchain, Schema as CoreSchema};
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use exonum::storage::Snapshot;
+
+/// Blockchain state together with the state hashes of all services, captured
+/// with `TestKit::services_snapshot()`. Schemas of different services can be instantiated
+/// over the same `snapshot()`, so that the state of interacting services
+/// is inspected consistently.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::blockchain::{Service, Transaction};
+/// # use exonum::crypto::{self, Hash, PublicKey};
+/// # use exonum::encoding;
+/// # use exonum::messages::{Message, RawTransaction};
+/// # use exonum::storage::{Fork, ProofMapIndex, Snapshot};
+/// # use exonum_testkit::TestKitBuilder;
+/// #
+/// # type FromRawResult = Result<Box<Transaction>, encoding::Error>;
+/// #
+/// # // Unlike `doc_helpers::MyService`, the service includes messages into its state hash.
+/// # pub struct MyService;
+/// #
+/// # impl Service for MyService {
+/// #     fn service_name(&self) -> &'static str {
+/// #         "documentation"
+/// #     }
+/// #
+/// #     fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+/// #         let messages: ProofMapIndex<_, PublicKey, String> =
+/// #             ProofMapIndex::new("documentation.messages", snapshot);
+/// #         vec![messages.root_hash()]
+/// #     }
+/// #
+/// #     fn service_id(&self) -> u16 {
+/// #         0
+/// #     }
+/// #
+/// #     fn tx_from_raw(&self, raw: RawTransaction) -> FromRawResult {
+/// #         Ok(Box::new(MyTransaction::from_raw(raw)?))
+/// #     }
+/// # }
+/// #
+/// # message! {
+/// #     struct MyTransaction {
+/// #         const TYPE = 0;
+/// #         const ID = 0;
+/// #
+/// #         from: &PublicKey,
+/// #         msg: &str,
+/// #     }
+/// # }
+/// #
+/// # impl Transaction for MyTransaction {
+/// #     fn verify(&self) -> bool {
+/// #         self.verify_signature(self.from())
+/// #     }
+/// #
+/// #     fn execute(&self, fork: &mut Fork) {
+/// #         let mut messages = ProofMapIndex::new("documentation.messages", fork);
+/// #         messages.put(self.from(), self.msg().to_owned());
+/// #     }
+/// # }
+/// #
+/// # fn main() {
+/// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+/// let (pubkey, key) = crypto::gen_keypair();
+/// let before = testkit.services_snapshot();
+/// testkit.create_block_with_transaction(MyTransaction::new(&pubkey, "foo", &key));
+/// let after = testkit.services_snapshot();
+/// assert_eq!(after.changed_services(&before), vec!["documentation"]);
+/// let messages: ProofMapIndex<_, PublicKey, String> =
+///     ProofMapIndex::new("documentation.messages", after.snapshot());
+/// assert_eq!(messages.get(&pubkey), Some("foo".to_owned()));
+/// # }
+/// ```
+pub struct ServicesSnapshot {
+    snapshot: Box<Snapshot>,
+    height: Height,
+    state_hash: Hash,
+    service_hashes: BTreeMap<String, Vec<Hash>>,
+}
+
+impl fmt::Debug for ServicesSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServicesSnapshot")
+            .field("height", &self.height)
+            .field("state_hash", &self.state_hash)
+            .field("service_hashes", &self.service_hashes)
+            .finish()
+    }
+}
+
+impl ServicesSnapshot {
+    /// Captures the state hashes of all services of the blockchain.
+    pub(crate) fn new(blockchain: &Blockchain) -> Self {
+        let snapshot = blockchain.snapshot();
+        let block = CoreSchema::new(&snapshot).last_block().unwrap();
+        let service_hashes = blockchain
+            .service_map()
+            .values()
+            .map(|service| {
+                (service.service_name().to_owned(), service.state_hash(&*snapshot))
+            })
+            .collect();
+        ServicesSnapshot {
+            height: block.height(),
+            state_hash: *block.state_hash(),
+            service_hashes,
+            snapshot,
+        }
+    }
+
+    /// Returns the blockchain state, over which schemas of services can be instantiated.
+    pub fn snapshot(&self) -> &Snapshot {
+        &*self.snapshot
+    }
+
+    /// Returns the height of the latest committed block.
+    pub fn height(&self) -> Height {
+        self.height
+    }
+
+    /// Returns the state hash of the latest committed block, which aggregates the state
+    /// hashes of all services and of the core tables.
+    pub fn state_hash(&self) -> &Hash {
+        &self.state_hash
+    }
+
+    /// Returns the names of all services in alphabetical order.
+    pub fn service_names(&self) -> Vec<&str> {
+        self.service_hashes.keys().map(String::as_str).collect()
+    }
+
+    /// Returns the state hashes of tables of the service with the given name,
+    /// or `None` if there is no such service.
+    pub fn state_hashes(&self, service_name: &str) -> Option<&[Hash]> {
+        self.service_hashes.get(service_name).map(Vec::as_slice)
+    }
+
+    /// Returns the names of services, which state hashes differ from the `other` snapshot.
+    /// Services present only in one of the snapshots are considered changed.
+    pub fn changed_services(&self, other: &ServicesSnapshot) -> Vec<String> {
+        let mut names: Vec<_> = self.service_hashes
+            .iter()
+            .filter(|&(name, hashes)| other.service_hashes.get(name) != Some(hashes))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.extend(
+            other
+                .service_hashes
+                .keys()
+                .filter(|name| !self.service_hashes.contains_key(*name))
+                .cloned(),
+        );
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Services shared among several testkit instances.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use exonum::blockchain::{ApiContext, Service, ServiceContext, Transaction};
+use exonum::crypto::Hash;
+use exonum::encoding::Error as EncodingError;
+use exonum::messages::RawTransaction;
+use exonum::storage::{Fork, Snapshot};
+use iron::Handler;
+use serde_json::Value;
+
+/// Service delegating all calls to a service, which may be shared among several blockchains.
+/// Services cannot be cloned, so this allows to fork the testkit.
+pub(crate) struct SharedService {
+    service: Arc<Service>,
+    // Configuration returned from `initialize()` instead of the one provided by the service.
+    config: Option<Value>,
+}
+
+impl SharedService {
+    /// Wraps the given services.
+    pub fn wrap(services: &[Arc<Service>]) -> Vec<Box<Service>> {
+        SharedService::wrap_with_configs(services, BTreeMap::new())
+    }
+
+    /// Wraps the given services, replacing genesis configurations of services with
+    /// the given names.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if there is no service with one of the names.
+    pub fn wrap_with_configs(
+        services: &[Arc<Service>],
+        mut configs: BTreeMap<String, Value>,
+    ) -> Vec<Box<Service>> {
+        let wrapped = services
+            .iter()
+            .map(|service| {
+                Box::new(SharedService {
+                    service: Arc::clone(service),
+                    config: configs.remove(service.service_name()),
+                }) as Box<Service>
+            })
+            .collect();
+        if let Some(name) = configs.keys().next() {
+            panic!("Cannot set configuration for absent service {}", name);
+        }
+        wrapped
+    }
+}
+
+impl Service for SharedService {
+    fn service_id(&self) -> u16 {
+        self.service.service_id()
+    }
+
+    fn service_name(&self) -> &'static str {
+        self.service.service_name()
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        self.service.state_hash(snapshot)
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, EncodingError> {
+        self.service.tx_from_raw(raw)
+    }
+
+    fn initialize(&self, fork: &mut Fork) -> Value {
+        let config = self.service.initialize(fork);
+        self.config.clone().unwrap_or(config)
+    }
+
+    fn handle_commit(&self, context: &ServiceContext) {
+        self.service.handle_commit(context)
+    }
+
+    fn public_api_handler(&self, context: &ApiContext) -> Option<Box<Handler>> {
+        self.service.public_api_handler(context)
+    }
+
+    fn private_api_handler(&self, context: &ApiContext) -> Option<Box<Handler>> {
+        self.service.private_api_handler(context)
+    }
+}
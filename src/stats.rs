@@ -0,0 +1,172 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage footprint statistics.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use exonum::storage::{Patch, Snapshot};
+
+/// Approximate storage footprint of an index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    /// Number of entries in the index.
+    pub entries: usize,
+    /// Total size of entry keys in bytes.
+    pub key_bytes: usize,
+    /// Total size of entry values in bytes.
+    pub value_bytes: usize,
+}
+
+impl IndexStats {
+    /// Returns the total size of keys and values in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.key_bytes + self.value_bytes
+    }
+}
+
+/// Approximate storage footprint of indices, measured as the size of keys and values
+/// of their entries. The overhead of the database backend is not taken into account.
+/// See `TestKit::storage_stats()`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::blockchain::Transaction;
+/// # use exonum::crypto;
+/// # use exonum_testkit::TestKitBuilder;
+/// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+/// #
+/// # fn main() {
+/// let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+/// let messages = (0..10).map(|i| {
+///     let (pubkey, key) = crypto::gen_keypair();
+///     Box::new(MyTransaction::new(&pubkey, &i.to_string(), &key)) as Box<Transaction>
+/// });
+/// let before = testkit.storage_stats();
+/// testkit.create_block_with_transactions(messages);
+/// let growth = testkit.storage_stats().growth_since(&before);
+/// assert!(growth["documentation.messages"] < 10 * 1024);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    indices: BTreeMap<String, IndexStats>,
+}
+
+impl StorageStats {
+    /// Collects statistics for the entries in the snapshot, which keys are mentioned
+    /// in the given patches.
+    pub(crate) fn collect(snapshot: &Snapshot, patches: &[Patch]) -> Self {
+        let keys: BTreeSet<(&String, &Vec<u8>)> = patches
+            .iter()
+            .flat_map(|patch| {
+                patch.iter().flat_map(|(index, changes)| {
+                    changes.keys().map(move |key| (index, key))
+                })
+            })
+            .collect();
+
+        let mut indices = BTreeMap::new();
+        for (index, key) in keys {
+            if let Some(value) = snapshot.get(index, key) {
+                let stats = indices.entry(index.clone()).or_insert_with(
+                    IndexStats::default,
+                );
+                stats.entries += 1;
+                stats.key_bytes += key.len();
+                stats.value_bytes += value.len();
+            }
+        }
+        StorageStats { indices }
+    }
+
+    /// Returns statistics for all non-empty indices, sorted by index names.
+    pub fn indices(&self) -> &BTreeMap<String, IndexStats> {
+        &self.indices
+    }
+
+    /// Returns statistics for the index with the given name. Empty statistics are returned
+    /// for empty or non-existing indices.
+    pub fn index(&self, name: &str) -> IndexStats {
+        self.indices.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the total size of keys and values in all indices in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.indices.values().map(IndexStats::total_bytes).sum()
+    }
+
+    /// Returns the change of the total size of each index in bytes compared to the `earlier`
+    /// statistics. Indices, which size has not changed, are not included.
+    pub fn growth_since(&self, earlier: &StorageStats) -> BTreeMap<String, isize> {
+        let names: BTreeSet<&String> = self.indices.keys().chain(earlier.indices.keys()).collect();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let growth = self.index(name).total_bytes() as isize -
+                    earlier.index(name).total_bytes() as isize;
+                if growth == 0 {
+                    None
+                } else {
+                    Some((name.clone(), growth))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::storage::{Database, MemoryDB};
+    use super::*;
+
+    #[test]
+    fn test_storage_stats() {
+        let mut db = MemoryDB::new();
+        let mut fork = db.fork();
+        fork.put("foo", vec![1], vec![1, 2, 3]);
+        fork.put("foo", vec![2, 2], vec![4]);
+        fork.put("bar", vec![1], vec![5]);
+        let first_patch = fork.into_patch();
+        db.merge(first_patch.clone()).unwrap();
+        let first = StorageStats::collect(&*db.snapshot(), &[first_patch.clone()]);
+
+        assert_eq!(
+            first.index("foo"),
+            IndexStats {
+                entries: 2,
+                key_bytes: 3,
+                value_bytes: 4,
+            }
+        );
+        assert_eq!(first.index("baz"), IndexStats::default());
+        assert_eq!(first.total_bytes(), 9);
+
+        let mut fork = db.fork();
+        fork.remove("bar", vec![1]);
+        fork.put("foo", vec![3], vec![6, 7]);
+        let second_patch = fork.into_patch();
+        db.merge(second_patch.clone()).unwrap();
+        let second = StorageStats::collect(&*db.snapshot(), &[first_patch, second_patch]);
+
+        assert_eq!(second.indices().keys().collect::<Vec<_>>(), vec!["foo"]);
+        let growth = second.growth_since(&first);
+        assert_eq!(growth.len(), 2);
+        assert_eq!(growth["foo"], 3);
+        assert_eq!(growth["bar"], -2);
+    }
+}
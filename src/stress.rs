@@ -0,0 +1,133 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Concurrent requests to API handlers.
+
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use iron::headers::Headers;
+use iron_test::request;
+use mount::Mount;
+
+use TestResponse;
+use fuzz::panic_message;
+
+/// Aggregated results of concurrent requests to an API endpoint.
+/// See `TestKitApi::stress()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StressReport {
+    requests: usize,
+    statuses: BTreeMap<u16, usize>,
+    bodies: BTreeMap<String, usize>,
+    panics: Vec<String>,
+    elapsed: Duration,
+}
+
+impl StressReport {
+    /// Returns the total number of sent requests.
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// Returns the number of responses with each status code.
+    pub fn statuses(&self) -> &BTreeMap<u16, usize> {
+        &self.statuses
+    }
+
+    /// Returns distinct response bodies together with the number of responses with each body.
+    /// For endpoints, which do not change the state, responses with different bodies
+    /// usually indicate inconsistent reads.
+    pub fn bodies(&self) -> &BTreeMap<String, usize> {
+        &self.bodies
+    }
+
+    /// Returns messages of panics in the handlers.
+    pub fn panics(&self) -> &[String] {
+        &self.panics
+    }
+
+    /// Returns the wall time of sending all requests.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns `true` if all requests have succeeded, i.e., no handler has panicked
+    /// and all responses have a `2xx` status.
+    pub fn is_successful(&self) -> bool {
+        self.panics.is_empty() && self.statuses.keys().all(|&status| status / 100 == 2)
+    }
+
+    /// Returns `true` if all responses have the same body and no handler has panicked.
+    pub fn is_consistent(&self) -> bool {
+        self.panics.is_empty() && self.bodies.len() <= 1
+    }
+
+    fn record(&mut self, response: thread::Result<TestResponse>) {
+        self.requests += 1;
+        match response {
+            Ok(response) => {
+                *self.statuses.entry(response.status().to_u16()).or_insert(0) += 1;
+                let body = String::from_utf8_lossy(response.body()).into_owned();
+                *self.bodies.entry(body).or_insert(0) += 1;
+            }
+            Err(payload) => self.panics.push(panic_message(&*payload)),
+        }
+    }
+}
+
+/// Sends `iterations` requests from each of `concurrency` threads to the mount.
+pub(crate) fn run(
+    mount: Arc<Mount>,
+    url: &str,
+    body: Option<String>,
+    concurrency: usize,
+    iterations: usize,
+) -> StressReport {
+    let barrier = Arc::new(Barrier::new(concurrency));
+    let start = Instant::now();
+    let threads: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let mount = Arc::clone(&mount);
+            let barrier = Arc::clone(&barrier);
+            let url = url.to_owned();
+            let body = body.clone();
+            thread::spawn(move || {
+                let send = || {
+                    TestResponse::new(match body {
+                        Some(ref body) => request::post(&url, Headers::new(), body, &*mount),
+                        None => request::get(&url, Headers::new(), &*mount),
+                    })
+                };
+                barrier.wait();
+                (0..iterations)
+                    .map(|_| panic::catch_unwind(AssertUnwindSafe(&send)))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let mut report = StressReport::default();
+    for thread in threads {
+        let responses = thread.join().expect("Stress thread has panicked");
+        for response in responses {
+            report.record(response);
+        }
+    }
+    report.elapsed = start.elapsed();
+    report
+}
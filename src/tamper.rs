@@ -0,0 +1,135 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that transactions with tampered signatures or contents are rejected.
+
+use exonum::blockchain::Transaction;
+use exonum::crypto::SIGNATURE_LENGTH;
+use exonum::encoding::Error as EncodingError;
+use exonum::encoding::serialize::ToHex;
+use exonum::messages::{MessageBuffer, RawMessage};
+
+/// Way, in which a signed message is tampered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tampering {
+    /// The lowest bit of the byte at the given position is flipped.
+    FlippedBit(usize),
+    /// All bytes of the signature are set to zero.
+    ZeroSignature,
+    /// The signature is removed from the message.
+    NoSignature,
+}
+
+/// Returns copies of the signed message tampered in every supported way: with each byte
+/// of the message modified, with a zeroed signature and without a signature.
+pub fn tampered_messages(raw: &RawMessage) -> Vec<(Tampering, RawMessage)> {
+    let bytes: &[u8] = raw.as_ref().as_ref();
+    let tamper = |tampering: Tampering| {
+        let mut bytes = bytes.to_vec();
+        match tampering {
+            Tampering::FlippedBit(position) => bytes[position] ^= 1,
+            Tampering::ZeroSignature => {
+                let signature_start = bytes.len().saturating_sub(SIGNATURE_LENGTH);
+                for byte in &mut bytes[signature_start..] {
+                    *byte = 0;
+                }
+            }
+            Tampering::NoSignature => {
+                let signature_start = bytes.len().saturating_sub(SIGNATURE_LENGTH);
+                bytes.truncate(signature_start);
+            }
+        }
+        (tampering, RawMessage::new(MessageBuffer::from_vec(bytes)))
+    };
+
+    (0..bytes.len())
+        .map(Tampering::FlippedBit)
+        .chain(vec![Tampering::ZeroSignature, Tampering::NoSignature])
+        .map(tamper)
+        .collect()
+}
+
+/// Asserts that `Transaction::verify()` rejects every tampered copy of the transaction
+/// (see `tampered_messages()`). A tampered message is also considered rejected
+/// if it cannot be parsed with `from_raw`.
+///
+/// # Panics
+///
+/// - Panics if the transaction itself does not pass `verify()`.
+/// - Panics if a tampered copy of the transaction passes `verify()`. The panic message
+///   contains the applied tampering and the hex-encoded tampered message.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # use exonum::crypto;
+/// # use exonum::messages::Message;
+/// # use exonum_testkit::doc_helpers::MyTransaction;
+/// # use exonum_testkit::tamper::assert_rejects_tampering;
+/// #
+/// # fn main() {
+/// let (pubkey, key) = crypto::gen_keypair();
+/// let tx = MyTransaction::new(&pubkey, "Alice", &key);
+/// assert_rejects_tampering(&tx, MyTransaction::from_raw);
+/// # }
+/// ```
+pub fn assert_rejects_tampering<T, F>(tx: &T, from_raw: F)
+where
+    T: Transaction,
+    F: Fn(RawMessage) -> Result<T, EncodingError>,
+{
+    assert!(tx.verify(), "Transaction does not pass verify() before tampering");
+    for (tampering, raw) in tampered_messages(tx.raw()) {
+        let hex = raw.as_ref().to_hex();
+        if let Ok(tampered_tx) = from_raw(raw) {
+            assert!(
+                !tampered_tx.verify(),
+                "Tampered transaction passes verify() ({:?}): {}",
+                tampering,
+                hex
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tampered_messages() {
+        let bytes: Vec<u8> = (0..100).collect();
+        let raw = RawMessage::new(MessageBuffer::from_vec(bytes.clone()));
+        let tampered = tampered_messages(&raw);
+        assert_eq!(tampered.len(), 102);
+
+        let (tampering, ref message) = tampered[3];
+        assert_eq!(tampering, Tampering::FlippedBit(3));
+        let mut expected = bytes.clone();
+        expected[3] = 2;
+        assert_eq!(message.as_ref().as_ref(), &expected[..]);
+
+        let (tampering, ref message) = tampered[100];
+        assert_eq!(tampering, Tampering::ZeroSignature);
+        let message: &[u8] = message.as_ref().as_ref();
+        assert_eq!(&message[..36], &bytes[..36]);
+        assert!(message[36..].iter().all(|&byte| byte == 0));
+
+        let (tampering, ref message) = tampered[101];
+        assert_eq!(tampering, Tampering::NoSignature);
+        assert_eq!(message.as_ref().as_ref(), &bytes[..36]);
+    }
+}
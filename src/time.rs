@@ -0,0 +1,165 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Time sources used by the testkit to timestamp created blocks.
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the time recorded in the precommits of blocks created by the testkit.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum_testkit;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+/// use exonum_testkit::TestKitBuilder;
+/// use exonum_testkit::time::TimeProvider;
+///
+/// #[derive(Debug)]
+/// struct FixedTime;
+///
+/// impl TimeProvider for FixedTime {
+///     fn current_time(&self) -> SystemTime {
+///         UNIX_EPOCH + Duration::from_secs(1_000_000)
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut testkit = TestKitBuilder::validator()
+///     .with_time_provider(FixedTime)
+///     .create();
+/// testkit.create_block();
+/// # }
+/// ```
+pub trait TimeProvider: Send + Sync + fmt::Debug {
+    /// Returns the current time.
+    fn current_time(&self) -> SystemTime;
+
+    /// Returns an independent time provider for a copy of the testkit created
+    /// by `TestKit::fork()`. By default, the copy uses a `MockTimeProvider` set
+    /// to the current time of this provider.
+    fn fork(&self) -> Box<TimeProvider> {
+        Box::new(MockTimeProvider::new(self.current_time()))
+    }
+}
+
+/// Time provider returning the system time. Used by the testkit by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTimeProvider;
+
+impl TimeProvider for SystemTimeProvider {
+    fn current_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn fork(&self) -> Box<TimeProvider> {
+        Box::new(SystemTimeProvider)
+    }
+}
+
+/// Time provider for tests, which allows to set the current time programmatically.
+///
+/// Clones of a `MockTimeProvider` share the same time, so a clone can be passed
+/// to the testkit while the original is used to advance the time. Unlike clones,
+/// providers returned by `fork()` have independent time.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// use std::time::Duration;
+/// use exonum::blockchain::Schema;
+/// use exonum_testkit::TestKitBuilder;
+/// use exonum_testkit::time::{MockTimeProvider, TimeProvider};
+///
+/// # fn main() {
+/// let time_provider = MockTimeProvider::default();
+/// let mut testkit = TestKitBuilder::validator()
+///     .with_time_provider(time_provider.clone())
+///     .create();
+///
+/// time_provider.add_time(Duration::from_secs(86_400));
+/// testkit.create_block();
+///
+/// let snapshot = testkit.snapshot();
+/// let precommits = Schema::new(&snapshot).precommits(&testkit.last_block_hash());
+/// assert_eq!(precommits.get(0).unwrap().time(), time_provider.current_time());
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockTimeProvider {
+    time: Arc<RwLock<SystemTime>>,
+}
+
+impl MockTimeProvider {
+    /// Creates a new `MockTimeProvider` with the specified current time.
+    pub fn new(time: SystemTime) -> Self {
+        MockTimeProvider { time: Arc::new(RwLock::new(time)) }
+    }
+
+    /// Sets the current time.
+    pub fn set_time(&self, time: SystemTime) {
+        let mut inner = self.time.write().expect(
+            "Cannot acquire write lock on mock time",
+        );
+        *inner = time;
+    }
+
+    /// Advances the current time by the specified duration.
+    pub fn add_time(&self, duration: Duration) {
+        let mut inner = self.time.write().expect(
+            "Cannot acquire write lock on mock time",
+        );
+        *inner += duration;
+    }
+}
+
+impl Default for MockTimeProvider {
+    /// Creates a `MockTimeProvider` with the current time set to the Unix epoch.
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH)
+    }
+}
+
+impl TimeProvider for MockTimeProvider {
+    fn current_time(&self) -> SystemTime {
+        *self.time.read().expect("Cannot acquire read lock on mock time")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_time_provider() {
+        let provider = MockTimeProvider::default();
+        assert_eq!(provider.current_time(), UNIX_EPOCH);
+
+        let provider_clone = provider.clone();
+        provider.add_time(Duration::from_secs(10));
+        assert_eq!(provider_clone.current_time(), UNIX_EPOCH + Duration::from_secs(10));
+
+        let time = UNIX_EPOCH + Duration::from_secs(1_000);
+        provider_clone.set_time(time);
+        assert_eq!(provider.current_time(), time);
+
+        let fork = provider.fork();
+        provider.add_time(Duration::from_secs(10));
+        assert_eq!(fork.current_time(), time);
+    }
+}
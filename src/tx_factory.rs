@@ -0,0 +1,160 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Named transaction constructors and accounts signing the transactions.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use exonum::blockchain::Transaction;
+use exonum::crypto::{PublicKey, SecretKey};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+/// Named keypair managed by the testkit. See `TestKit::account()`.
+///
+/// An account is serialized as its public key, so it can be used directly in transaction
+/// parameters, e.g., `json!({ "to": bob, "amount": 10 })`.
+#[derive(Debug, Clone)]
+pub struct Account {
+    name: String,
+    public_key: PublicKey,
+    secret_key: SecretKey,
+}
+
+impl Account {
+    pub(crate) fn new(name: &str, (public_key, secret_key): (PublicKey, SecretKey)) -> Self {
+        Account {
+            name: name.to_owned(),
+            public_key,
+            secret_key,
+        }
+    }
+
+    /// Returns the name of the account.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the public key of the account.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the secret key of the account.
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+}
+
+impl Serialize for Account {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.public_key.serialize(serializer)
+    }
+}
+
+/// Registry of transaction constructors, which create signed transactions by name from
+/// the author account and JSON parameters. See `TestKitBuilder::with_tx_factory()`
+/// and `TestKit::tx()`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate exonum;
+/// # extern crate exonum_testkit;
+/// # #[macro_use] extern crate serde_json;
+/// # use exonum::crypto::PublicKey;
+/// # use exonum::encoding::serialize::ToHex;
+/// # use exonum::messages::Message;
+/// # use exonum_testkit::{TestKitBuilder, TxFactory};
+/// # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+/// #
+/// # fn main() {
+/// let factory = TxFactory::new().with_transaction("greet", |author, params| {
+///     let to: PublicKey = serde_json::from_value(params["to"].clone()).unwrap();
+///     let msg = format!("{} to {}", params["greeting"].as_str().unwrap(), to.to_hex());
+///     Box::new(MyTransaction::new(author.public_key(), &msg, author.secret_key()))
+/// });
+/// let mut testkit = TestKitBuilder::validator()
+///     .with_service(MyService)
+///     .with_tx_factory(factory)
+///     .create();
+/// let (alice, bob) = (testkit.account("alice"), testkit.account("bob"));
+/// let tx = testkit.tx("greet", &alice, json!({ "to": bob, "greeting": "Hello" }));
+/// let tx_hash = tx.hash();
+/// testkit.create_block_with_transactions(vec![tx]);
+/// assert!(testkit.tx_location(&tx_hash).is_some());
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct TxFactory {
+    constructors: BTreeMap<String, Arc<Fn(&Account, &Value) -> Box<Transaction> + Send + Sync>>,
+}
+
+impl fmt::Debug for TxFactory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TxFactory")
+            .field("transactions", &self.constructors.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl TxFactory {
+    /// Creates a factory without registered transactions.
+    pub fn new() -> Self {
+        TxFactory::default()
+    }
+
+    /// Registers a constructor of transactions with the given name. The constructor
+    /// may panic if the parameters are invalid.
+    pub fn with_transaction<F>(mut self, name: &str, constructor: F) -> Self
+    where
+        F: Fn(&Account, &Value) -> Box<Transaction> + Send + Sync + 'static,
+    {
+        self.constructors.insert(name.to_owned(), Arc::new(constructor));
+        self
+    }
+
+    /// Creates a transaction with the given name.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if no transaction with the given name is registered.
+    pub fn create(&self, name: &str, author: &Account, params: &Value) -> Box<Transaction> {
+        let constructor = self.constructors.get(name).unwrap_or_else(|| {
+            panic!("Transaction `{}` is not registered in the factory", name)
+        });
+        constructor(author, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto;
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn test_account_serialization() {
+        let keypair = crypto::gen_keypair();
+        let account = Account::new("alice", keypair.clone());
+        assert_eq!(account.name(), "alice");
+        assert_eq!(
+            serde_json::to_value(&account).unwrap(),
+            serde_json::to_value(&keypair.0).unwrap()
+        );
+    }
+}
@@ -0,0 +1,593 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Randomized transaction workloads for load and soak tests.
+//!
+//! `Workload` generates transactions with user-provided generators, which are chosen
+//! pseudo-randomly according to their weights, and commits them in blocks. After each block,
+//! the workload checks its invariants; violations are collected into the `WorkloadReport`
+//! together with execution statistics and the throughput.
+//!
+//! With `with_minimization()`, a failing run is shrunk to a minimal sequence of blocks
+//! and transactions reproducing the failure, which is printed as a transaction log
+//! (see the `replay` module).
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate exonum;
+//! # extern crate exonum_testkit;
+//! # use exonum::blockchain::Transaction;
+//! # use exonum::crypto::{self, PublicKey};
+//! # use exonum::storage::{MapIndex, Snapshot};
+//! # use exonum_testkit::TestKitBuilder;
+//! # use exonum_testkit::doc_helpers::{MyService, MyTransaction};
+//! # use exonum_testkit::workload::Workload;
+//! # use std::sync::{Arc, Mutex};
+//! #
+//! # fn main() {
+//! # fn all_messages_set(snapshot: &Snapshot) -> bool {
+//! #     let messages: MapIndex<_, PublicKey, String> =
+//! #         MapIndex::new("documentation.messages", snapshot);
+//! #     messages.values().all(|message| !message.is_empty())
+//! # }
+//! let mut testkit = TestKitBuilder::validator().with_service(MyService).create();
+//! let keys = Arc::new(Mutex::new(Vec::new()));
+//! let mut workload = Workload::new()
+//!     .with_blocks(20)
+//!     .with_txs_per_block(10)
+//!     .with_generator("register", 30, {
+//!         let keys = Arc::clone(&keys);
+//!         move |_, _| {
+//!             let (pubkey, key) = crypto::gen_keypair();
+//!             keys.lock().unwrap().push((pubkey, key.clone()));
+//!             Some(Box::new(MyTransaction::new(&pubkey, "Alice", &key)) as Box<Transaction>)
+//!         }
+//!     })
+//!     .with_generator("update", 70, move |rng, _| {
+//!         let keys = keys.lock().unwrap();
+//!         if keys.is_empty() {
+//!             return None;
+//!         }
+//!         let (ref pubkey, ref key) = keys[rng.next_below(keys.len())];
+//!         let msg = rng.next_u64().to_string();
+//!         Some(Box::new(MyTransaction::new(pubkey, &msg, key)) as Box<Transaction>)
+//!     })
+//!     .with_invariant("messages are set", all_messages_set);
+//! let report = workload.run(&mut testkit);
+//! assert!(report.is_successful(), "{:?}", report.violations());
+//! println!("Throughput: {:.1} tx/s", report.throughput());
+//! # }
+//! ```
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use exonum::blockchain::Transaction;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawMessage};
+use exonum::storage::Snapshot;
+
+use {ExecutionStatus, TestKit};
+use faults::Rng;
+use replay::{LoggedBlock, TransactionLog};
+
+type Generator = Box<FnMut(&mut WorkloadRng, &Snapshot) -> Option<Box<Transaction>>>;
+
+/// Pseudo-random generator passed to transaction generators of a `Workload`.
+/// The sequence of generated numbers is determined by the seed of the workload.
+#[derive(Debug)]
+pub struct WorkloadRng {
+    inner: Rng,
+}
+
+impl WorkloadRng {
+    /// Returns a pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.inner.next_u64()
+    }
+
+    /// Returns a pseudo-random number in the `[0, bound)` range.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if `bound` is zero.
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "Bound should be positive");
+        self.inner.next_below(bound)
+    }
+}
+
+/// Generator of randomized transaction streams. See the module docs for an example.
+pub struct Workload {
+    generators: Vec<(String, u32, Generator)>,
+    invariants: Vec<(String, Box<Fn(&Snapshot) -> bool>)>,
+    blocks: usize,
+    txs_per_block: usize,
+    seed: Option<u64>,
+    minimization: bool,
+}
+
+impl fmt::Debug for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let generators: Vec<_> = self.generators
+            .iter()
+            .map(|&(ref name, weight, _)| (name, weight))
+            .collect();
+        let invariants: Vec<_> = self.invariants.iter().map(|&(ref name, _)| name).collect();
+        f.debug_struct("Workload")
+            .field("generators", &generators)
+            .field("invariants", &invariants)
+            .field("blocks", &self.blocks)
+            .field("txs_per_block", &self.txs_per_block)
+            .field("seed", &self.seed)
+            .field("minimization", &self.minimization)
+            .finish()
+    }
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Workload::new()
+    }
+}
+
+impl Workload {
+    /// Creates a workload without generators, which runs for 100 blocks
+    /// with 10 transactions per block.
+    pub fn new() -> Self {
+        Workload {
+            generators: Vec::new(),
+            invariants: Vec::new(),
+            blocks: 100,
+            txs_per_block: 10,
+            seed: None,
+            minimization: false,
+        }
+    }
+
+    /// Adds a named generator of transactions with the given weight. The probability
+    /// of choosing the generator for each transaction is proportional to its weight.
+    ///
+    /// The generator receives the pseudo-random generator of the workload and the blockchain
+    /// state before the block, in which the transaction will be included. It may return
+    /// `None` if no transaction can be generated, e.g., if the state lacks accounts
+    /// to transfer funds between.
+    pub fn with_generator<F>(mut self, name: &str, weight: u32, generator: F) -> Self
+    where
+        F: FnMut(&mut WorkloadRng, &Snapshot) -> Option<Box<Transaction>> + 'static,
+    {
+        self.generators.push((name.to_owned(), weight, Box::new(generator)));
+        self
+    }
+
+    /// Adds a named invariant checked on the blockchain state after each block.
+    /// Unlike `TestKit::add_invariant()`, violations do not panic, but are recorded
+    /// in the report.
+    pub fn with_invariant<F>(mut self, name: &str, invariant: F) -> Self
+    where
+        F: Fn(&Snapshot) -> bool + 'static,
+    {
+        self.invariants.push((name.to_owned(), Box::new(invariant)));
+        self
+    }
+
+    /// Sets the number of blocks created by the workload. The default value is 100.
+    pub fn with_blocks(mut self, blocks: usize) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// Sets the number of generated transactions per block. The default value is 10.
+    pub fn with_txs_per_block(mut self, txs_per_block: usize) -> Self {
+        self.txs_per_block = txs_per_block;
+        self
+    }
+
+    /// Sets the seed of the pseudo-random generator used to choose generators
+    /// and passed to them. By default, the seed of the testkit is used
+    /// (see `TestKit::seed()`).
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Enables minimization of failing runs. If an invariant of the workload is violated
+    /// or block creation panics (e.g., because of a failed determinism check, see
+    /// `TestKitBuilder::with_determinism_check()`), the workload delta-debugs the sequence
+    /// of generated blocks and transactions, removing them while the failure persists.
+    /// The minimal sequence is printed to the standard error as a transaction log
+    /// and is available with `WorkloadReport::reproducer()`.
+    ///
+    /// Candidate sequences are replayed on copies of the testkit made with `TestKit::fork()`
+    /// before the run, so minimization does not affect the testkit state; thus, services
+    /// of the testkit should be added with `TestKitBuilder::with_service_factory()`.
+    /// A panic during block creation is resumed after minimization.
+    pub fn with_minimization(mut self) -> Self {
+        self.minimization = true;
+        self
+    }
+
+    /// Runs the workload on the testkit. Generated transactions, which pass `verify()`,
+    /// are added to the mempool and committed with `TestKit::create_block()`, so
+    /// transactions already in the mempool are committed as well.
+    ///
+    /// # Panics
+    ///
+    /// - Panics if the workload has no generators or all generators have zero weight.
+    pub fn run(&mut self, testkit: &mut TestKit) -> WorkloadReport {
+        let total_weight: u32 = self.generators.iter().map(|&(_, weight, _)| weight).sum();
+        assert!(total_weight > 0, "Workload should have generators with positive weight");
+
+        let seed = self.seed.unwrap_or_else(|| testkit.seed());
+        let mut rng = WorkloadRng { inner: Rng::new(seed) };
+        let mut report = WorkloadReport::default();
+        // State before the run and transactions of created blocks, which are used
+        // to minimize failures.
+        let mut base = if self.minimization {
+            Some(testkit.fork())
+        } else {
+            None
+        };
+        let mut recorded: Vec<Vec<RawMessage>> = Vec::new();
+        let mut first_failure = None;
+        let start = Instant::now();
+        for block in 0..self.blocks {
+            let snapshot = testkit.snapshot();
+            let mut block_txs: Vec<_> = if block == 0 && base.is_some() {
+                testkit.mempool().values().map(|tx| tx.raw().clone()).collect()
+            } else {
+                Vec::new()
+            };
+            for _ in 0..self.txs_per_block {
+                let index = self.choose_generator(&mut rng, total_weight);
+                let (ref name, _, ref mut generator) = self.generators[index];
+                let tx = match generator(&mut rng, &*snapshot) {
+                    Some(tx) => tx,
+                    None => continue,
+                };
+                let raw = tx.raw().clone();
+                if tx.verify() && testkit.insert_into_mempool(tx).is_ok() {
+                    *report.generated.entry(name.clone()).or_insert(0) += 1;
+                    if base.is_some() {
+                        block_txs.push(raw);
+                    }
+                } else {
+                    report.rejected += 1;
+                }
+            }
+            recorded.push(block_txs);
+
+            let result = match base {
+                Some(ref mut base) => {
+                    match panic::catch_unwind(AssertUnwindSafe(|| testkit.create_block())) {
+                        Ok(result) => result,
+                        Err(panic) => {
+                            self.minimize(base, &recorded, &Failure::Panic);
+                            panic::resume_unwind(panic);
+                        }
+                    }
+                }
+                None => testkit.create_block(),
+            };
+            report.blocks += result.blocks();
+            for tx in result.transactions() {
+                match *tx.status() {
+                    ExecutionStatus::Applied => report.applied += 1,
+                    ExecutionStatus::NoOp => report.no_ops += 1,
+                    ExecutionStatus::Panicked(_) => report.panicked += 1,
+                }
+            }
+
+            let snapshot = testkit.snapshot();
+            for &(ref name, ref invariant) in &self.invariants {
+                if !invariant(&*snapshot) {
+                    report.violations.push(InvariantViolation {
+                        name: name.clone(),
+                        height: testkit.height(),
+                    });
+                    if first_failure.is_none() {
+                        first_failure = Some((Failure::Invariant(name.clone()), block + 1));
+                    }
+                }
+            }
+        }
+        report.elapsed = start.elapsed();
+
+        if let (Some(mut base), Some((failure, blocks))) = (base, first_failure) {
+            recorded.truncate(blocks);
+            report.reproducer = Some(self.minimize(&mut base, &recorded, &failure));
+        }
+        report
+    }
+
+    /// Shrinks the recorded blocks to a minimal sequence reproducing the failure
+    /// and prints it.
+    fn minimize(
+        &self,
+        base: &mut TestKit,
+        blocks: &[Vec<RawMessage>],
+        failure: &Failure,
+    ) -> TransactionLog {
+        // Removing whole blocks is cheaper, so it is done first.
+        let blocks = ddmin(blocks.to_vec(), |blocks| self.reproduces(base, blocks, failure));
+
+        // Empty blocks are retained while removing transactions to preserve heights.
+        let block_count = blocks.len();
+        let regroup = |txs: &[(usize, RawMessage)]| {
+            let mut blocks = vec![Vec::new(); block_count];
+            for &(index, ref raw) in txs {
+                blocks[index].push(raw.clone());
+            }
+            blocks
+        };
+        let txs: Vec<_> = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, block)| block.iter().map(move |raw| (index, raw.clone())))
+            .collect();
+        let txs = ddmin(txs, |txs| self.reproduces(base, &regroup(txs), failure));
+        let blocks = ddmin(regroup(&txs), |blocks| self.reproduces(base, blocks, failure));
+
+        let height = base.height();
+        let log = TransactionLog::new(
+            blocks
+                .into_iter()
+                .enumerate()
+                .map(|(index, transactions)| {
+                    LoggedBlock {
+                        height: Some(Height(height.0 + index as u64 + 1)),
+                        transactions,
+                        state_hash: None,
+                    }
+                })
+                .collect(),
+        );
+        let tx_count: usize = log.blocks().iter().map(|block| block.transactions.len()).sum();
+        eprintln!(
+            "Workload failure ({}) is reproduced by {} blocks with {} transactions:\n{}",
+            failure,
+            log.blocks().len(),
+            tx_count,
+            log
+        );
+        log
+    }
+
+    /// Checks whether the blocks reproduce the failure on a copy of the base testkit.
+    fn reproduces(
+        &self,
+        base: &mut TestKit,
+        blocks: &[Vec<RawMessage>],
+        failure: &Failure,
+    ) -> bool {
+        let mut testkit = base.fork();
+        for raws in blocks {
+            let txs: Vec<_> = raws.iter()
+                .filter_map(|raw| testkit.blockchain.tx_from_raw(raw.clone()))
+                .collect();
+            let created = panic::catch_unwind(AssertUnwindSafe(|| {
+                testkit.create_block_with_transactions(txs);
+            }));
+            match *failure {
+                Failure::Panic => {
+                    if created.is_err() {
+                        return true;
+                    }
+                }
+                Failure::Invariant(ref name) => {
+                    if created.is_err() {
+                        return false;
+                    }
+                    let snapshot = testkit.snapshot();
+                    let violated = self.invariants.iter().any(|&(ref other, ref invariant)| {
+                        other == name && !invariant(&*snapshot)
+                    });
+                    if violated {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn choose_generator(&self, rng: &mut WorkloadRng, total_weight: u32) -> usize {
+        let mut point = rng.next_below(total_weight as usize) as u32;
+        for (index, &(_, weight, _)) in self.generators.iter().enumerate() {
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        unreachable!()
+    }
+}
+
+/// Failure of a workload run, which is reproduced during minimization.
+#[derive(Debug, Clone, PartialEq)]
+enum Failure {
+    Invariant(String),
+    Panic,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Failure::Invariant(ref name) => write!(f, "invariant `{}` is violated", name),
+            Failure::Panic => f.write_str("block creation has panicked"),
+        }
+    }
+}
+
+/// Removes chunks of items while the failure persists, halving the chunk size
+/// when no chunk can be removed.
+fn ddmin<T, F>(mut items: Vec<T>, mut fails: F) -> Vec<T>
+where
+    T: Clone,
+    F: FnMut(&[T]) -> bool,
+{
+    let mut chunk = cmp::max(items.len() / 2, 1);
+    while !items.is_empty() {
+        let mut removed = false;
+        let mut start = 0;
+        while start < items.len() {
+            let end = cmp::min(start + chunk, items.len());
+            let candidate: Vec<_> = items[..start].iter().chain(&items[end..]).cloned().collect();
+            if fails(&candidate) {
+                items = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if !removed {
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+    }
+    items
+}
+
+/// Invariant of a `Workload` violated after a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantViolation {
+    /// Name of the invariant.
+    pub name: String,
+    /// Height of the block, after which the invariant does not hold.
+    pub height: Height,
+}
+
+/// Statistics of a workload run.
+#[derive(Debug, Clone, Default)]
+pub struct WorkloadReport {
+    blocks: usize,
+    generated: BTreeMap<String, usize>,
+    rejected: usize,
+    applied: usize,
+    no_ops: usize,
+    panicked: usize,
+    violations: Vec<InvariantViolation>,
+    elapsed: Duration,
+    reproducer: Option<TransactionLog>,
+}
+
+impl WorkloadReport {
+    /// Returns the number of created blocks.
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Returns the number of transactions added to the mempool by each generator.
+    pub fn generated(&self) -> &BTreeMap<String, usize> {
+        &self.generated
+    }
+
+    /// Returns the number of generated transactions, which have failed `verify()` or have
+    /// been already known to the testkit.
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    /// Returns the number of committed transactions, which have changed the blockchain state.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    /// Returns the number of committed transactions, which have not changed
+    /// the blockchain state.
+    pub fn no_ops(&self) -> usize {
+        self.no_ops
+    }
+
+    /// Returns the number of transactions, which have panicked during execution
+    /// (see `PanicPolicy::Record`).
+    pub fn panicked(&self) -> usize {
+        self.panicked
+    }
+
+    /// Returns invariant violations in the order of detection.
+    pub fn violations(&self) -> &[InvariantViolation] {
+        &self.violations
+    }
+
+    /// Returns the wall time of the run, including the generation of transactions.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of committed transactions per second of the run.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64 + f64::from(self.elapsed.subsec_nanos()) * 1e-9;
+        if secs == 0.0 {
+            0.0
+        } else {
+            (self.applied + self.no_ops) as f64 / secs
+        }
+    }
+
+    /// Returns the minimal sequence of blocks reproducing the first invariant violation,
+    /// if the violation has occurred and minimization is enabled
+    /// (see `Workload::with_minimization()`). The blocks can be replayed with
+    /// `TransactionLog::replay()` on a testkit in the state before the run.
+    pub fn reproducer(&self) -> Option<&TransactionLog> {
+        self.reproducer.as_ref()
+    }
+
+    /// Returns `true` if no invariant has been violated and no transaction has panicked.
+    pub fn is_successful(&self) -> bool {
+        self.violations.is_empty() && self.panicked == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator_weights() {
+        let workload = Workload::new()
+            .with_generator("rare", 1, |_, _| None)
+            .with_generator("disabled", 0, |_, _| None)
+            .with_generator("frequent", 9, |_, _| None);
+        let mut rng = WorkloadRng { inner: Rng::new(0) };
+        let mut counts = [0; 3];
+        for _ in 0..1_000 {
+            counts[workload.choose_generator(&mut rng, 10)] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        assert!(counts[0] > 50 && counts[0] < 150, "{:?}", counts);
+        assert_eq!(counts[0] + counts[2], 1_000);
+    }
+
+    #[test]
+    fn test_ddmin() {
+        let items: Vec<u32> = (0..100).collect();
+        let mut calls = 0;
+        let minimal = ddmin(items, |items| {
+            calls += 1;
+            items.contains(&17) && items.contains(&64)
+        });
+        assert_eq!(minimal, vec![17, 64]);
+        assert!(calls < 100, "{}", calls);
+
+        assert_eq!(ddmin(vec![1, 2, 3], |items| items.is_empty()), Vec::<u32>::new());
+        assert_eq!(ddmin(vec![1, 2, 3], |items| items.len() == 3), vec![1, 2, 3]);
+    }
+}
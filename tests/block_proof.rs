@@ -0,0 +1,135 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for consensus messages generated for committed blocks.
+
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::ValidatorKeys;
+use exonum::crypto;
+use exonum::helpers::{Height, ValidatorId};
+use exonum::messages::Message;
+use exonum_testkit::{ProofTampering, TestKit, TestKitBuilder};
+
+fn init_testkit() -> TestKit {
+    TestKitBuilder::validator().with_validators(4).create()
+}
+
+fn validator_keys(testkit: &TestKit) -> Vec<ValidatorKeys> {
+    testkit.actual_configuration().validator_keys
+}
+
+#[test]
+fn test_block_proof() {
+    let mut testkit = init_testkit();
+    testkit.create_blocks_until(Height(3));
+
+    let proof = testkit.block_proof(Height(2)).unwrap();
+    assert_eq!(proof.block.height(), Height(2));
+    assert_eq!(proof.precommits.len(), 4);
+    let propose = proof.propose.clone().unwrap();
+    assert_eq!(propose.height(), Height(2));
+    assert_eq!(propose.validator(), proof.block.proposer_id());
+    assert_eq!(propose.prev_hash(), proof.block.prev_hash());
+    for precommit in &proof.precommits {
+        assert_eq!(precommit.propose_hash(), &propose.hash());
+        assert_eq!(precommit.block_hash(), &proof.block.hash());
+    }
+    assert!(proof.verify(&validator_keys(&testkit)));
+
+    assert!(testkit.block_proof(Height(4)).is_none());
+}
+
+#[test]
+fn test_block_proof_with_foreign_validators() {
+    let mut testkit = init_testkit();
+    testkit.create_block();
+    let proof = testkit.block_proof(Height(1)).unwrap();
+
+    let foreign_keys: Vec<_> = (0..4)
+        .map(|_| {
+            ValidatorKeys {
+                consensus_key: crypto::gen_keypair().0,
+                service_key: crypto::gen_keypair().0,
+            }
+        })
+        .collect();
+    assert!(!proof.verify(&foreign_keys));
+
+    // Precommits of a single validator are not enough.
+    let mut partial = proof.clone();
+    partial.precommits.retain(|precommit| precommit.validator() == ValidatorId(0));
+    assert!(!partial.verify(&validator_keys(&testkit)));
+}
+
+#[test]
+fn test_block_proof_after_rollback() {
+    let mut testkit = init_testkit();
+    testkit.create_block();
+    testkit.rollback(1);
+    assert!(testkit.block_proof(Height(1)).is_none());
+
+    testkit.create_block_fast(Vec::new());
+    let proof = testkit.block_proof(Height(1)).unwrap();
+    assert!(proof.propose.is_none());
+    assert!(proof.precommits.is_empty());
+}
+
+#[test]
+fn test_tampered_block_proof() {
+    let mut testkit = init_testkit();
+    testkit.create_block();
+    let keys = validator_keys(&testkit);
+
+    let tamperings = vec![
+        ProofTampering::InvalidSignature(ValidatorId(2)),
+        ProofTampering::WrongBlockHash(ValidatorId(1)),
+        ProofTampering::TruncatedPrecommits(2),
+    ];
+    for tampering in tamperings {
+        let proof = testkit.tampered_block_proof(Height(1), tampering).unwrap();
+        assert!(!proof.verify(&keys), "{:?}", tampering);
+    }
+    // The stored proof is not affected.
+    assert!(testkit.block_proof(Height(1)).unwrap().verify(&keys));
+}
+
+#[test]
+fn test_create_block_with_tampered_proof() {
+    let mut testkit = init_testkit();
+    testkit.create_block_with_tampered_proof(ProofTampering::InvalidSignature(ValidatorId(3)));
+    testkit.create_block();
+
+    let keys = validator_keys(&testkit);
+    let proof = testkit.block_proof(Height(1)).unwrap();
+    assert_eq!(proof.precommits.len(), 4);
+    let precommit = proof
+        .precommits
+        .iter()
+        .find(|precommit| precommit.validator() == ValidatorId(3))
+        .unwrap();
+    assert!(!precommit.verify_signature(&keys[3].consensus_key));
+    assert!(!proof.verify(&keys));
+    // Tampering affects only one block.
+    assert!(testkit.block_proof(Height(2)).unwrap().verify(&keys));
+}
+
+#[test]
+#[should_panic(expected = "no precommit by validator")]
+fn test_tampering_absent_precommit() {
+    let mut testkit = init_testkit();
+    testkit.create_block();
+    testkit.tampered_block_proof(Height(1), ProofTampering::InvalidSignature(ValidatorId(5)));
+}
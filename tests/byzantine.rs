@@ -0,0 +1,117 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the simulation of Byzantine validators.
+
+extern crate exonum;
+extern crate exonum_testkit;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use exonum::crypto;
+use exonum::helpers::{Height, ValidatorId};
+use exonum::messages::Message;
+use exonum_testkit::{Evidence, TestKit, TestKitBuilder};
+use exonum_testkit::time::MockTimeProvider;
+
+fn init_testkit() -> TestKit {
+    TestKitBuilder::validator()
+        .with_validators(4)
+        .with_seed(1)
+        .with_time_provider(MockTimeProvider::new(UNIX_EPOCH + Duration::from_secs(1_000)))
+        .create()
+}
+
+#[test]
+fn test_double_sign() {
+    let mut testkit = init_testkit();
+    testkit.create_block();
+
+    let malicious = testkit.malicious_node(ValidatorId(1));
+    let consensus_key = *testkit.network().consensus_public_key_of(ValidatorId(1)).unwrap();
+    let evidence = malicious.double_sign(&[], &[crypto::hash(&[1])]);
+    assert!(evidence.verify(&consensus_key));
+    assert!(!evidence.verify(
+        testkit.network().consensus_public_key_of(ValidatorId(0)).unwrap(),
+    ));
+
+    match evidence {
+        Evidence::DoubleSign(ref first, ref second) => {
+            assert_eq!(first.height(), Height(2));
+            assert_eq!(first.validator(), ValidatorId(1));
+            assert_eq!(first.prev_hash(), &testkit.last_block_hash());
+            assert_ne!(first.transactions(), second.transactions());
+        }
+        ref other => panic!("Unexpected evidence: {:?}", other),
+    }
+    assert_eq!(evidence.messages().len(), 2);
+    // Producing evidence does not affect the blockchain.
+    assert_eq!(testkit.height(), Height(1));
+}
+
+#[test]
+fn test_conflicting_precommits() {
+    let testkit = init_testkit();
+    let malicious = testkit.malicious_node(ValidatorId(3));
+    let consensus_key = *testkit.network().consensus_public_key_of(ValidatorId(3)).unwrap();
+    let (first_block, second_block) = (crypto::hash(&[1]), crypto::hash(&[2]));
+
+    let evidence = malicious.conflicting_precommits(&first_block, &second_block);
+    assert!(evidence.verify(&consensus_key));
+    match evidence {
+        Evidence::ConflictingPrecommits(ref first, ref second) => {
+            assert_eq!(first.block_hash(), &first_block);
+            assert_eq!(second.block_hash(), &second_block);
+            assert_eq!(first.propose_hash(), second.propose_hash());
+        }
+        ref other => panic!("Unexpected evidence: {:?}", other),
+    }
+}
+
+#[test]
+fn test_invalid_propose() {
+    let testkit = init_testkit();
+    let malicious = testkit.malicious_node(ValidatorId(2));
+    let consensus_key = *testkit.network().consensus_public_key_of(ValidatorId(2)).unwrap();
+
+    let evidence = malicious.invalid_propose(&[]);
+    assert!(evidence.verify(&consensus_key));
+    match evidence {
+        Evidence::InvalidPropose(ref propose) => {
+            assert_ne!(propose.prev_hash(), &testkit.last_block_hash());
+        }
+        ref other => panic!("Unexpected evidence: {:?}", other),
+    }
+}
+
+#[test]
+fn test_evidence_is_deterministic() {
+    let precommit_hashes = || {
+        let testkit = init_testkit();
+        let malicious = testkit.malicious_node(ValidatorId(1));
+        match malicious.conflicting_precommits(&crypto::hash(&[1]), &crypto::hash(&[2])) {
+            Evidence::ConflictingPrecommits(first, second) => (first.hash(), second.hash()),
+            other => panic!("Unexpected evidence: {:?}", other),
+        }
+    };
+    assert_eq!(precommit_hashes(), precommit_hashes());
+}
+
+#[test]
+#[should_panic(expected = "Precommits for the same block do not conflict")]
+fn test_non_conflicting_precommits() {
+    let testkit = init_testkit();
+    let block_hash = crypto::hash(&[1]);
+    testkit.malicious_node(ValidatorId(1)).conflicting_precommits(&block_hash, &block_hash);
+}
@@ -21,7 +21,7 @@ extern crate serde_json;
 
 use exonum::helpers::{Height, ValidatorId};
 use exonum_testkit::TestKitBuilder;
-use exonum::blockchain::Schema;
+use exonum::blockchain::{ConsensusConfig, Schema};
 use exonum::storage::StorageValue;
 
 #[test]
@@ -169,3 +169,168 @@ fn test_another_configuration_change_proposal() {
     };
     testkit.commit_configuration_change(second_proposal);
 }
+
+#[test]
+fn test_rollback_configuration_change() {
+    let mut testkit = TestKitBuilder::validator().create();
+    testkit.create_block();
+    let initial_cfg = testkit.actual_configuration();
+
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        cfg.set_actual_from(Height(3));
+        cfg.set_service_config("service", "config");
+        cfg
+    };
+    let stored = proposal.stored_configuration().clone();
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(3));
+    assert_eq!(testkit.actual_configuration(), stored);
+
+    // Rollback should revert whole blocks, including configuration commits.
+    testkit.rollback(2);
+    assert_eq!(testkit.height(), Height(1));
+    assert_eq!(testkit.actual_configuration(), initial_cfg);
+    assert_eq!(
+        Schema::new(&testkit.snapshot_at(Height(1))).actual_configuration(),
+        initial_cfg
+    );
+}
+
+#[test]
+fn test_change_consensus_config() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let initial_consensus = testkit.actual_configuration().consensus;
+
+    let cfg_change_height = Height(5);
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        let mut consensus = cfg.consensus_configuration().clone();
+        consensus.txs_block_limit = initial_consensus.txs_block_limit + 1;
+        cfg.set_consensus_configuration(consensus);
+        cfg.set_actual_from(cfg_change_height);
+        cfg
+    };
+    let stored = proposal.stored_configuration().clone();
+    testkit.commit_configuration_change(proposal);
+
+    testkit.create_blocks_until(Height(3));
+    assert_eq!(testkit.actual_configuration().consensus, initial_consensus);
+    testkit.create_blocks_until(cfg_change_height.previous());
+    assert_eq!(testkit.actual_configuration().consensus, stored.consensus);
+    assert_eq!(
+        testkit.actual_configuration().consensus.txs_block_limit,
+        initial_consensus.txs_block_limit + 1
+    );
+}
+
+#[test]
+fn test_genesis_consensus_config() {
+    let mut consensus = ConsensusConfig::default();
+    consensus.txs_block_limit = 3;
+    consensus.round_timeout *= 2;
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(2)
+        .with_consensus_config(consensus.clone())
+        .create();
+    assert_eq!(testkit.actual_configuration().consensus, consensus);
+
+    testkit.create_blocks_until(Height(2));
+    assert_eq!(testkit.actual_configuration().consensus, consensus);
+    let proposal = testkit.configuration_change_proposal();
+    assert_eq!(proposal.consensus_configuration().txs_block_limit, 3);
+}
+
+#[test]
+fn test_add_and_remove_validators() {
+    let mut testkit = TestKitBuilder::validator().with_validators(2).create();
+
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        let added = cfg.add_validator().clone();
+        assert_eq!(added.validator_id(), Some(ValidatorId(2)));
+        cfg.set_actual_from(Height(3));
+        cfg
+    };
+    let added = proposal.validators()[2].clone();
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(2));
+
+    assert_eq!(testkit.network().validators().len(), 3);
+    assert_eq!(testkit.validator(ValidatorId(2)), &added);
+    assert_eq!(testkit.actual_configuration().validator_keys.len(), 3);
+
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        let removed = cfg.remove_validator(ValidatorId(0));
+        assert_eq!(removed.validator_id(), None);
+        assert_eq!(cfg.us().validator_id(), None);
+        cfg.set_actual_from(Height(5));
+        cfg
+    };
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(4));
+
+    assert_eq!(testkit.network().us().validator_id(), None);
+    assert_eq!(testkit.network().validators().len(), 2);
+    assert_eq!(
+        testkit.validator(ValidatorId(1)).public_keys(),
+        added.public_keys()
+    );
+    // Blocks are still created by the updated validator set.
+    testkit.create_block();
+    assert_eq!(testkit.height(), Height(5));
+}
+
+#[test]
+#[should_panic(expected = "Cannot remove the only validator")]
+fn test_remove_last_validator() {
+    let testkit = TestKitBuilder::validator().create();
+    let mut cfg = testkit.configuration_change_proposal();
+    cfg.remove_validator(ValidatorId(0));
+}
+
+#[test]
+fn test_activate_following_configuration() {
+    use std::sync::{Arc, Mutex};
+
+    let mut testkit = TestKitBuilder::validator().create();
+    let activations = Arc::new(Mutex::new(Vec::new()));
+    {
+        let activations = Arc::clone(&activations);
+        testkit.on_configuration_activated(move |block, configuration| {
+            activations.lock().unwrap().push(
+                (block.height(), configuration.actual_from),
+            );
+        });
+    }
+
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        cfg.set_actual_from(Height(5));
+        cfg.set_service_config("service", "config");
+        cfg
+    };
+    let stored = proposal.stored_configuration().clone();
+    testkit.commit_configuration_change(proposal);
+    assert_eq!(testkit.following_configuration(), None);
+    testkit.create_block();
+    assert_eq!(testkit.following_configuration(), Some(stored.clone()));
+    assert!(activations.lock().unwrap().is_empty());
+
+    assert_eq!(testkit.activate_following_configuration(), stored);
+    assert_eq!(testkit.height(), Height(4));
+    assert_eq!(testkit.actual_configuration(), stored);
+    assert_eq!(testkit.following_configuration(), None);
+    assert_eq!(*activations.lock().unwrap(), vec![(Height(4), Height(5))]);
+
+    testkit.create_blocks_until(Height(10));
+    assert_eq!(activations.lock().unwrap().len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "No configuration is scheduled for activation")]
+fn test_activate_absent_configuration() {
+    let mut testkit = TestKitBuilder::validator().create();
+    testkit.activate_following_configuration();
+}
@@ -0,0 +1,126 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the block commit hooks of the testkit.
+
+extern crate exonum;
+extern crate exonum_testkit;
+
+use std::sync::{Arc, Mutex};
+
+use exonum::blockchain::Schema;
+use exonum::helpers::Height;
+use exonum_testkit::TestKitBuilder;
+
+#[test]
+fn test_commit_hooks_order() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    {
+        let log = Arc::clone(&log);
+        testkit.on_before_commit(move |block, fork| {
+            let schema = Schema::new(fork);
+            assert_eq!(schema.last_block().unwrap().height(), block.height());
+            log.lock().unwrap().push(("before", block.height()));
+        });
+    }
+    {
+        let log = Arc::clone(&log);
+        testkit.on_after_commit(move |block, snapshot| {
+            let schema = Schema::new(snapshot);
+            assert_eq!(schema.last_block().unwrap(), *block);
+            log.lock().unwrap().push(("after", block.height()));
+        });
+    }
+
+    testkit.create_block();
+    testkit.fast_forward_to(Height(2));
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            ("before", Height(1)),
+            ("after", Height(1)),
+            ("before", Height(2)),
+            ("after", Height(2)),
+        ]
+    );
+}
+
+#[test]
+fn test_before_commit_hook_sees_uncommitted_block() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let committed_heights = Arc::new(Mutex::new(Vec::new()));
+
+    {
+        let committed_heights = Arc::clone(&committed_heights);
+        testkit.on_before_commit(move |block, _| {
+            // The block must not be visible to after-commit hooks yet.
+            assert!(!committed_heights.lock().unwrap().contains(&block.height()));
+        });
+    }
+    {
+        let committed_heights = Arc::clone(&committed_heights);
+        testkit.on_after_commit(move |block, _| {
+            committed_heights.lock().unwrap().push(block.height());
+        });
+    }
+
+    testkit.create_blocks_until(Height(3));
+    assert_eq!(
+        *committed_heights.lock().unwrap(),
+        vec![Height(1), Height(2), Height(3)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invariant violated")]
+fn test_panicking_before_commit_hook() {
+    let mut testkit = TestKitBuilder::validator().create();
+    testkit.on_before_commit(|block, _| if block.height() == Height(2) {
+        panic!("Invariant violated");
+    });
+    testkit.create_blocks_until(Height(2));
+}
+
+#[test]
+fn test_invariants() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let checked = Arc::new(Mutex::new(Vec::new()));
+    {
+        let checked = Arc::clone(&checked);
+        testkit.add_invariant("block hashes are stored", move |snapshot| {
+            let schema = Schema::new(snapshot);
+            let height = schema.last_block().unwrap().height();
+            checked.lock().unwrap().push(height);
+            schema.block_hashes_by_height().len() == height.0 + 1
+        });
+    }
+    testkit.create_block();
+    testkit.fast_forward_to(Height(3));
+    assert_eq!(*checked.lock().unwrap(), vec![Height(1), Height(2), Height(3)]);
+}
+
+#[test]
+#[should_panic(expected = "Invariant `height limit` is violated after block 3")]
+fn test_violated_invariant() {
+    let mut testkit = TestKitBuilder::validator().create();
+    testkit.add_invariant("height limit", |snapshot| {
+        Schema::new(snapshot).block_hashes_by_height().len() <= 3
+    });
+    testkit.create_blocks_until(Height(2));
+    let mut fork = testkit.fork();
+    // Invariants are copied to the forked testkit.
+    fork.create_block();
+}
@@ -0,0 +1,175 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the differential testing of service versions.
+
+#[macro_use]
+extern crate exonum;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot, StorageValue};
+use exonum_testkit::{ComparisonTestKit, StateDivergence};
+
+const SERVICE_ID: u16 = 1;
+const TX_DEPOSIT_ID: u16 = 1;
+const BALANCE: &str = "wallet.balance";
+
+message! {
+    struct TxDeposit {
+        const TYPE = SERVICE_ID;
+        const ID = TX_DEPOSIT_ID;
+
+        author: &PublicKey,
+        amount: u64,
+    }
+}
+
+impl Transaction for TxDeposit {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut balance = Entry::new(BALANCE, fork);
+        let value = balance.get().unwrap_or(0) + self.amount();
+        balance.set(value);
+    }
+}
+
+// The same message as `TxDeposit`, which caps the balance. Used by the new version
+// of the service.
+message! {
+    struct TxCappedDeposit {
+        const TYPE = SERVICE_ID;
+        const ID = TX_DEPOSIT_ID;
+
+        author: &PublicKey,
+        amount: u64,
+    }
+}
+
+impl Transaction for TxCappedDeposit {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut balance = Entry::new(BALANCE, fork);
+        let value = balance.get().unwrap_or(0) + self.amount();
+        balance.set(value.min(100));
+    }
+}
+
+struct WalletService {
+    capped: bool,
+    hashed: bool,
+}
+
+impl Service for WalletService {
+    fn service_name(&self) -> &'static str {
+        "wallet"
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        if self.hashed {
+            vec![Entry::new(BALANCE, snapshot).hash()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        if self.capped {
+            Ok(Box::new(TxCappedDeposit::from_raw(raw)?))
+        } else {
+            Ok(Box::new(TxDeposit::from_raw(raw)?))
+        }
+    }
+}
+
+fn service(capped: bool, hashed: bool) -> Box<Service> {
+    Box::new(WalletService { capped, hashed })
+}
+
+#[test]
+fn test_same_behavior() {
+    let mut testkit = ComparisonTestKit::new(service(false, false), service(true, false));
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxDeposit::new(&pubkey, 50, &key));
+    testkit.create_block();
+    testkit.create_block_with_transactions(txvec![TxDeposit::new(&pubkey, 40, &key)]);
+    testkit.assert_no_divergence();
+
+    assert_eq!(testkit.height(), Height(3));
+    assert_eq!(
+        testkit.old_testkit().last_block_hash(),
+        testkit.new_testkit().last_block_hash()
+    );
+    testkit
+        .snapshots()
+        .map(|snapshot| Entry::<_, u64>::new(BALANCE, snapshot).get())
+        .assert_inv("Balances are equal", |&balance| balance == Some(90));
+}
+
+#[test]
+fn test_entry_divergence() {
+    let mut testkit = ComparisonTestKit::new(service(false, false), service(true, false));
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxDeposit::new(&pubkey, 60, &key));
+    assert_eq!(testkit.divergence(), None);
+    testkit.create_block_with_transaction(TxDeposit::new(&pubkey, 61, &key));
+    testkit.create_block();
+
+    let expected = StateDivergence::Entry {
+        height: Height(2),
+        index: BALANCE.to_owned(),
+        key: Vec::new(),
+        old: Some(121_u64.into_bytes()),
+        new: Some(100_u64.into_bytes()),
+    };
+    assert_eq!(testkit.divergence(), Some(&expected));
+    assert_eq!(testkit.height(), Height(3));
+}
+
+#[test]
+fn test_genesis_divergence() {
+    // Service state hashes are aggregated in the core schema.
+    let testkit = ComparisonTestKit::new(service(false, false), service(false, true));
+    match *testkit.divergence().unwrap() {
+        StateDivergence::Entry { height, ref index, .. } => {
+            assert_eq!(height, Height(0));
+            assert!(index.starts_with("core."));
+        }
+        ref divergence => panic!("Unexpected divergence: {:?}", divergence),
+    }
+}
+
+#[test]
+#[should_panic(expected = "Service versions have diverged")]
+fn test_assert_no_divergence() {
+    let mut testkit = ComparisonTestKit::new(service(false, false), service(true, false));
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxDeposit::new(&pubkey, 200, &key));
+    testkit.assert_no_divergence();
+}
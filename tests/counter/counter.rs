@@ -19,14 +19,16 @@ extern crate iron;
 extern crate router;
 
 use exonum::blockchain::{ApiContext, Blockchain, Service, Transaction};
-use exonum::messages::{Message, RawTransaction};
+use exonum::messages::{Message, RawMessage, RawTransaction};
 use exonum::node::{ApiSender, TransactionSend};
 use exonum::storage::{Entry, Fork, Snapshot};
 use exonum::crypto::{Hash, PublicKey};
 use exonum::encoding;
 use exonum::api::{Api, ApiError};
 use self::iron::Handler;
+use self::iron::headers::Cookie;
 use self::iron::prelude::*;
+use self::iron::status::Status;
 use self::router::Router;
 use serde_json;
 
@@ -129,6 +131,12 @@ pub struct TransactionResponse {
     pub tx_hash: Hash,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct UploadResponse {
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
 #[derive(Clone)]
 struct CounterApi {
     channel: ApiSender,
@@ -150,6 +158,51 @@ impl CounterApi {
         }
     }
 
+    // Accepts an increment transaction serialized to bytes and encoded in hex.
+    fn increment_hex(&self, req: &mut Request) -> IronResult<Response> {
+        use std::io::Read;
+        use exonum::encoding::serialize::FromHex;
+        use exonum::messages::MessageBuffer;
+
+        let mut body = String::new();
+        req.body.read_to_string(&mut body).map_err(|e| {
+            ApiError::IncorrectRequest(Box::new(e))
+        })?;
+        let bytes = Vec::<u8>::from_hex(body.trim()).map_err(ApiError::FromHex)?;
+        let raw = RawMessage::new(MessageBuffer::from_vec(bytes));
+        let transaction = TxIncrement::from_raw(raw).map_err(|e| {
+            ApiError::IncorrectRequest(Box::new(e))
+        })?;
+
+        let transaction: Box<Transaction> = Box::new(transaction);
+        let tx_hash = transaction.hash();
+        self.channel.send(transaction).map_err(ApiError::from)?;
+        let json = TransactionResponse { tx_hash };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    // Returns the content type and the raw body of the request; used to test file uploads.
+    fn upload(&self, req: &mut Request) -> IronResult<Response> {
+        use std::io::Read;
+
+        let content_type = req.headers
+            .get_raw("Content-Type")
+            .map(|values| String::from_utf8_lossy(&values[0]).into_owned())
+            .unwrap_or_default();
+        let mut body = Vec::new();
+        req.body.read_to_end(&mut body).map_err(|e| {
+            ApiError::IncorrectRequest(Box::new(e))
+        })?;
+        let json = UploadResponse { content_type, body };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    // Returns the raw query string of the request; used to test query serialization.
+    fn get_query(&self, req: &mut Request) -> IronResult<Response> {
+        let query = req.url.query().unwrap_or("").to_owned();
+        self.ok_response(&serde_json::to_value(query).unwrap())
+    }
+
     fn count(&self) -> Option<u64> {
         let view = self.blockchain.snapshot();
         let schema = CounterSchema::new(&view);
@@ -161,6 +214,35 @@ impl CounterApi {
         self.ok_response(&serde_json::to_value(count).unwrap())
     }
 
+    // Returns the counter value if the request is authorized with the admin key passed
+    // either in the `Authorization` header or in the `admin_key` cookie.
+    fn get_admin_count(&self, req: &mut Request) -> IronResult<Response> {
+        let bearer = format!("Bearer {}", ADMIN_KEY);
+        let authorized_by_header = req.headers
+            .get_raw("Authorization")
+            .map_or(false, |values| values.iter().any(|v| *v == bearer.as_bytes()));
+        let admin_cookie = format!("admin_key={}", ADMIN_KEY);
+        let authorized_by_cookie = req.headers
+            .get::<Cookie>()
+            .map_or(false, |cookies| cookies.iter().any(|c| *c == admin_cookie));
+
+        if authorized_by_header || authorized_by_cookie {
+            self.get_count(req)
+        } else {
+            Ok(Response::with((Status::Unauthorized, "\"Unauthorized\"")))
+        }
+    }
+
+    fn get_initialized_count(&self, _: &mut Request) -> IronResult<Response> {
+        match self.count() {
+            Some(count) => self.ok_response(&serde_json::to_value(count).unwrap()),
+            None => {
+                let message = serde_json::to_value("Counter is not initialized").unwrap();
+                self.not_found_response(&message)
+            }
+        }
+    }
+
     fn reset(&self, req: &mut Request) -> IronResult<Response> {
         match req.get::<bodyparser::Struct<TxReset>>() {
             Ok(Some(transaction)) => {
@@ -185,6 +267,14 @@ impl CounterApi {
         let self_ = self.clone();
         let get_count = move |req: &mut Request| self_.get_count(req);
         router.get("/count", get_count, "get_count");
+
+        let self_ = self.clone();
+        let get_initialized_count = move |req: &mut Request| self_.get_initialized_count(req);
+        router.get(
+            "/initialized_count",
+            get_initialized_count,
+            "get_initialized_count",
+        );
     }
 }
 
@@ -194,9 +284,25 @@ impl Api for CounterApi {
         let increment = move |req: &mut Request| self_.increment(req);
         router.post("/count", increment, "increment");
 
+        let self_ = self.clone();
+        let increment_hex = move |req: &mut Request| self_.increment_hex(req);
+        router.post("/count/hex", increment_hex, "increment_hex");
+
         let self_ = self.clone();
         let get_count = move |req: &mut Request| self_.get_count(req);
         router.get("/count", get_count, "get_count");
+
+        let self_ = self.clone();
+        let get_admin_count = move |req: &mut Request| self_.get_admin_count(req);
+        router.get("/admin/count", get_admin_count, "get_admin_count");
+
+        let self_ = self.clone();
+        let get_query = move |req: &mut Request| self_.get_query(req);
+        router.get("/query", get_query, "get_query");
+
+        let self_ = self.clone();
+        let upload = move |req: &mut Request| self_.upload(req);
+        router.post("/upload", upload, "upload");
     }
 }
 
@@ -16,142 +16,1911 @@
 extern crate exonum;
 #[macro_use]
 extern crate exonum_testkit;
+extern crate futures;
+extern crate iron;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate tempdir;
+
+use std::{env, fs};
+use std::fs::File;
+use std::io::Read;
 
 use exonum::crypto::{self, PublicKey};
 use exonum::helpers::Height;
 use exonum::messages::Message;
-use exonum::encoding::serialize::FromHex;
+use exonum::encoding::serialize::{FromHex, ToHex};
 use exonum::encoding::serialize::json::ExonumJson;
-use exonum_testkit::{ApiKind, ComparableSnapshot, TestKit, TestKitApi, TestKitBuilder};
+use exonum_testkit::{ApiKind, ComparableSnapshot, StorageChange, TestKit, TestKitApi,
+                     TestKitBuilder, TestKitEvent};
+use exonum_testkit::replay::{Divergence, TransactionLog};
+use exonum_testkit::time::MockTimeProvider;
+use tempdir::TempDir;
+
+mod counter;
+use counter::{CounterSchema, CounterService, TransactionResponse, TxIncrement, TxReset,
+              UploadResponse, ADMIN_KEY};
+
+fn init_testkit() -> (TestKit, TestKitApi) {
+    let testkit = TestKitBuilder::validator()
+        .with_service_factory(|| Box::new(CounterService))
+        .create();
+    let api = testkit.api();
+    (testkit, api)
+}
+
+fn inc_count(api: &TestKitApi, by: u64) -> TxIncrement {
+    let (pubkey, key) = crypto::gen_keypair();
+    // Create a presigned transaction
+    let tx = TxIncrement::new(&pubkey, by, &key);
+
+    let tx_info: TransactionResponse = api.post(ApiKind::Service("counter"), "count", &tx);
+    assert_eq!(tx_info.tx_hash, tx.hash());
+    tx
+}
+
+#[test]
+fn test_inc_count_create_block() {
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+
+    // Create a presigned transaction
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+
+    // Check that the user indeed is persisted by the service
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+
+    testkit.create_block_with_transactions(txvec![
+        TxIncrement::new(&pubkey, 4, &key),
+        TxIncrement::new(&pubkey, 1, &key),
+    ]);
+
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 10);
+}
+
+#[should_panic(expected = "Transaction is already committed")]
+#[test]
+fn test_inc_count_create_block_with_committed_transaction() {
+    let (mut testkit, _) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    // Create a presigned transaction
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    // Create another block with the same transaction
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+}
+
+#[test]
+fn test_inc_count_api() {
+    let (mut testkit, api) = init_testkit();
+    inc_count(&api, 5);
+    testkit.create_block();
+
+    // Check that the user indeed is persisted by the service
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+}
+
+#[test]
+fn test_inc_count_with_multiple_transactions() {
+    let (mut testkit, api) = init_testkit();
+
+    for _ in 0..100 {
+        inc_count(&api, 1);
+        inc_count(&api, 2);
+        inc_count(&api, 3);
+        inc_count(&api, 4);
+
+        testkit.create_block();
+    }
+
+    assert_eq!(testkit.height(), Height(100));
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 1_000);
+}
+
+#[test]
+fn test_inc_count_with_manual_tx_control() {
+    let (mut testkit, api) = init_testkit();
+    let tx_a = inc_count(&api, 5);
+    let tx_b = inc_count(&api, 3);
+
+    // Empty block
+    testkit.create_block_with_tx_hashes(&[]);
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 0);
+
+    testkit.create_block_with_tx_hashes(&[tx_b.hash()]);
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 3);
+
+    testkit.create_block_with_tx_hashes(&[tx_a.hash()]);
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 8);
+}
+
+#[test]
+fn test_private_api() {
+    let (mut testkit, api) = init_testkit();
+    inc_count(&api, 5);
+    inc_count(&api, 3);
+
+    testkit.create_block();
+    let counter: u64 = api.get_private(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 8);
+
+    let (pubkey, key) = crypto::gen_keypair_from_seed(&crypto::Seed::from_slice(
+        &crypto::hash(b"correct horse battery staple")[..],
+    ).unwrap());
+    assert_eq!(pubkey, PublicKey::from_hex(ADMIN_KEY).unwrap());
+
+    let tx = TxReset::new(&pubkey, &key);
+    let tx_info: TransactionResponse = api.post_private(ApiKind::Service("counter"), "reset", &tx);
+    assert_eq!(tx_info.tx_hash, tx.hash());
+
+    testkit.create_block();
+    let counter: u64 = api.get_private(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 0);
+}
+
+#[test]
+fn test_private_api_error() {
+    let (mut testkit, api) = init_testkit();
+    let info: String = api.get_private_err(ApiKind::Service("counter"), "initialized_count");
+    assert_eq!(info, "Counter is not initialized");
+
+    inc_count(&api, 5);
+    testkit.create_block();
+    let counter: u64 = api.get_private(ApiKind::Service("counter"), "initialized_count");
+    assert_eq!(counter, 5);
+}
+
+#[test]
+fn test_request_headers() {
+    let (mut testkit, api) = init_testkit();
+    inc_count(&api, 5);
+    testkit.create_block();
+
+    let info: String = api.request(ApiKind::Service("counter"), "admin/count").get_err();
+    assert_eq!(info, "Unauthorized");
+    let info: String = api.request(ApiKind::Service("counter"), "admin/count")
+        .header("Authorization", "Bearer c0ffee")
+        .cookie("admin_key", "c0ffee")
+        .get_err();
+    assert_eq!(info, "Unauthorized");
+
+    let counter: u64 = api.request(ApiKind::Service("counter"), "admin/count")
+        .header("Authorization", "Bearer c0ffee")
+        .header("Authorization", &format!("Bearer {}", ADMIN_KEY))
+        .get();
+    assert_eq!(counter, 5);
+    let counter: u64 = api.request(ApiKind::Service("counter"), "admin/count")
+        .cookie("session", "c0ffee")
+        .cookie("admin_key", ADMIN_KEY)
+        .get();
+    assert_eq!(counter, 5);
+}
+
+#[test]
+fn test_api_middleware() {
+    use iron::{IronResult, Request, Response};
+
+    let (mut testkit, plain_api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+
+    let api = testkit
+        .api_builder()
+        .with_before(|req: &mut Request| -> IronResult<()> {
+            let bearer = format!("Bearer {}", ADMIN_KEY);
+            req.headers.set_raw("Authorization", vec![bearer.into_bytes()]);
+            Ok(())
+        })
+        .with_after(|_: &mut Request, mut resp: Response| -> IronResult<Response> {
+            resp.headers.set_raw("X-Request-Id", vec![b"42".to_vec()]);
+            Ok(resp)
+        })
+        .create();
+
+    let counter: u64 = api.get(ApiKind::Service("counter"), "admin/count");
+    assert_eq!(counter, 5);
+    let info: String = plain_api.get_err(ApiKind::Service("counter"), "admin/count");
+    assert_eq!(info, "Unauthorized");
+
+    let resp = api.request(ApiKind::Service("counter"), "count").get_response();
+    assert_eq!(resp.headers().get_raw("X-Request-Id").unwrap(), &[b"42".to_vec()]);
+    // Built-in endpoints are not wrapped.
+    let resp = api.request(ApiKind::System, "v1/healthcheck").get_response();
+    assert!(resp.headers().get_raw("X-Request-Id").is_none());
+}
+
+#[test]
+fn test_stress_requests() {
+    let (mut testkit, mut api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+
+    let report = api.stress("GET", "api/services/counter/count", 4, 10);
+    assert_eq!(report.requests(), 40);
+    assert_eq!(report.statuses().get(&200), Some(&40));
+    assert!(report.is_successful());
+    assert!(report.is_consistent());
+    assert_eq!(report.bodies().keys().collect::<Vec<_>>(), vec!["5"]);
+
+    let report = api.stress("GET", "api/services/counter/admin/count", 2, 5);
+    assert_eq!(report.requests(), 10);
+    assert!(!report.is_successful());
+    assert!(report.is_consistent());
+
+    let tx = TxIncrement::new(&pubkey, 1, &key);
+    let report = api.stress_post("api/services/counter/count", &tx, 3, 1);
+    assert_eq!(report.statuses().get(&200), Some(&3));
+    testkit.create_block();
+    // API handlers are restored after stress requests.
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 6);
+}
+
+#[test]
+fn test_background_block_producer() {
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn assert_send<T: Send>() {}
+    assert_send::<TestKit>();
+    assert_send::<TestKitApi>();
+
+    let (testkit, api) = init_testkit();
+    let (blocks_tx, blocks_rx) = mpsc::channel();
+    let producer = thread::spawn(move || {
+        let mut testkit = testkit;
+        for _ in 0..3 {
+            testkit.create_block();
+            blocks_tx.send(testkit.height()).unwrap();
+        }
+        testkit
+    });
+
+    let tx = inc_count(&api, 5);
+    let heights: Vec<_> = blocks_rx.iter().collect();
+    assert_eq!(heights, vec![Height(1), Height(2), Height(3)]);
+    let mut testkit = producer.join().unwrap();
+    testkit.create_block();
+
+    assert!(testkit.explorer().transaction(&tx.hash()).is_some());
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+}
+
+#[test]
+fn test_async_operations() {
+    use futures::Future;
+
+    let (testkit, api) = init_testkit();
+    let api = api.into_async();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+
+    let tx_info: TransactionResponse = api.post(ApiKind::Service("counter"), "count", &tx)
+        .wait()
+        .unwrap();
+    assert_eq!(tx_info.tx_hash, tx.hash());
+
+    let other_tx = TxIncrement::new(&pubkey, 3, &key);
+    let (testkit, counter) = testkit
+        .create_block_async()
+        .and_then(|(testkit, block)| {
+            assert_eq!(block.transactions().len(), 1);
+            testkit.create_block_with_transactions_async(txvec![other_tx])
+        })
+        .and_then(|(testkit, _)| {
+            api.get::<u64>(ApiKind::Service("counter"), "count")
+                .map(|counter| (testkit, counter))
+        })
+        .wait()
+        .unwrap();
+
+    assert_eq!(testkit.height(), Height(2));
+    assert_eq!(counter, 8);
+}
+
+#[test]
+#[should_panic(expected = "Unexpected response status")]
+fn test_async_api_panic() {
+    use futures::Future;
+
+    let (_, api) = init_testkit();
+    let api = api.into_async();
+    let _ = api.get::<u64>(ApiKind::Service("counter"), "admin/count").wait();
+}
+
+#[test]
+fn test_raw_responses() {
+    use iron::headers::ContentType;
+    use iron::status::Status;
+
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    let resp = api.request(ApiKind::Service("counter"), "count").post_response(&tx);
+    assert_eq!(resp.status(), Status::Ok);
+    assert_eq!(resp.json::<TransactionResponse>().tx_hash, tx.hash());
+    testkit.create_block();
+
+    let resp = api.request(ApiKind::Service("counter"), "admin/count").get_response();
+    assert_eq!(resp.status(), Status::Unauthorized);
+    assert_eq!(resp.body(), b"\"Unauthorized\"");
+
+    let resp = api.private_request(ApiKind::Service("counter"), "initialized_count")
+        .get_response();
+    assert_eq!(resp.status(), Status::Ok);
+    assert_eq!(resp.headers().get::<ContentType>(), Some(&ContentType::json()));
+    assert_eq!(resp.json::<u64>(), 5);
+}
+
+#[test]
+fn test_post_raw() {
+    use exonum::storage::StorageValue;
+    use iron::status::Status;
+
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    let hex_tx: String = tx.clone()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let resp = api.post_raw(
+        ApiKind::Service("counter"),
+        "count/hex",
+        "text/plain",
+        hex_tx.as_bytes(),
+    );
+    assert_eq!(resp.status(), Status::Ok);
+    assert_eq!(resp.json::<TransactionResponse>().tx_hash, tx.hash());
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+
+    let resp = api.post_raw(ApiKind::Service("counter"), "count/hex", "text/plain", b"c0ffeg");
+    assert_eq!(resp.status(), Status::BadRequest);
+
+    // Binary bodies are sent as is.
+    let body = tx.into_bytes();
+    let resp = api.post_raw(
+        ApiKind::Service("counter"),
+        "upload",
+        "application/octet-stream",
+        &body,
+    );
+    assert_eq!(resp.status(), Status::Ok);
+    let upload: UploadResponse = resp.json();
+    assert_eq!(upload.content_type, "application/octet-stream");
+    assert_eq!(upload.body, body);
+}
+
+#[test]
+fn test_post_multipart() {
+    use exonum_testkit::MultipartForm;
+    use iron::status::Status;
+
+    let (_, api) = init_testkit();
+    let document = [0x25, 0x50, 0x44, 0x46, 0xff, 0x00, 0xfe, 0x0d, 0x0a];
+    let form = MultipartForm::new().field("title", "Contract").file(
+        "document",
+        "contract.pdf",
+        "application/pdf",
+        &document,
+    );
+    let resp = api.post_multipart(ApiKind::Service("counter"), "upload", form);
+    assert_eq!(resp.status(), Status::Ok);
+
+    let upload: UploadResponse = resp.json();
+    let boundary = upload
+        .content_type
+        .trim_left_matches("multipart/form-data; boundary=")
+        .to_owned();
+    assert_ne!(boundary, upload.content_type);
+    let mut expected = format!(
+        "--{0}\r\n\
+         Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+         Contract\r\n\
+         --{0}\r\n\
+         Content-Disposition: form-data; name=\"document\"; filename=\"contract.pdf\"\r\n\
+         Content-Type: application/pdf\r\n\r\n",
+        boundary
+    ).into_bytes();
+    expected.extend_from_slice(&document);
+    expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    assert_eq!(upload.body, expected);
+}
+
+#[test]
+fn test_get_with_query() {
+    #[derive(Serialize)]
+    struct Query {
+        name: String,
+        limit: u64,
+        tags: Vec<String>,
+        cursor: Option<u64>,
+    }
+
+    let (_, api) = init_testkit();
+    let query = Query {
+        name: "Alice & Bob".to_owned(),
+        limit: 10,
+        tags: vec!["a/b".to_owned(), "c".to_owned()],
+        cursor: None,
+    };
+    let raw_query: String = api.get_with_query(ApiKind::Service("counter"), "query", &query);
+    assert_eq!(raw_query, "limit=10&name=Alice%20%26%20Bob&tags=a%2Fb&tags=c");
+
+    let raw_query: String = api.request(ApiKind::Service("counter"), "query?page=2")
+        .query(&query)
+        .get();
+    assert_eq!(
+        raw_query,
+        "page=2&limit=10&name=Alice%20%26%20Bob&tags=a%2Fb&tags=c"
+    );
+}
+
+#[test]
+fn test_api_fixture() {
+    use std::{env, fs, process};
+    use exonum_testkit::FixtureMode;
+
+    let path = env::temp_dir().join(format!("exonum-testkit-fixture-{}.json", process::id()));
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    let exercise_api = |mode| {
+        let (mut testkit, _) = init_testkit();
+        let api = testkit.api().with_fixture(&path, mode);
+        let tx_info: TransactionResponse = api.post(ApiKind::Service("counter"), "count", &tx);
+        assert_eq!(tx_info.tx_hash, tx.hash());
+        testkit.create_block();
+        api.get::<u64>(ApiKind::Service("counter"), "count")
+    };
+
+    assert_eq!(exercise_api(FixtureMode::Record), 5);
+    assert_eq!(exercise_api(FixtureMode::Check), 5);
+    let recorded = fs::read_to_string(&path).unwrap();
+    assert!(recorded.contains("\"endpoint\": \"api/services/counter/count\""));
+
+    // Replayed responses do not depend on the testkit state.
+    {
+        let (_, api) = init_testkit();
+        let api = api.with_fixture(&path, FixtureMode::Replay);
+        let tx_info: TransactionResponse = api.post(ApiKind::Service("counter"), "count", &tx);
+        assert_eq!(tx_info.tx_hash, tx.hash());
+        let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+        assert_eq!(counter, 5);
+    }
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "differs from API fixture")]
+fn test_api_fixture_mismatch() {
+    use std::{env, process};
+    use exonum_testkit::FixtureMode;
+
+    let path = env::temp_dir().join(format!(
+        "exonum-testkit-fixture-mismatch-{}.json",
+        process::id()
+    ));
+    {
+        let (_, api) = init_testkit();
+        let api = api.with_fixture(&path, FixtureMode::Record);
+        let _: u64 = api.get(ApiKind::Service("counter"), "count");
+    }
+
+    let (mut testkit, api) = init_testkit();
+    inc_count(&api, 3);
+    testkit.create_block();
+    let api = api.with_fixture(&path, FixtureMode::Check);
+    let _: u64 = api.get(ApiKind::Service("counter"), "count");
+}
+
+#[test]
+fn test_assert_schema() {
+    let (mut testkit, api) = init_testkit();
+    inc_count(&api, 5);
+    testkit.create_block();
+
+    let schema = serde_json::from_str(r#"{ "type": "integer", "minimum": 0 }"#).unwrap();
+    api.assert_schema(ApiKind::Service("counter"), "count", &schema);
+    let schema = serde_json::from_str(r#"{ "type": "string" }"#).unwrap();
+    api.request(ApiKind::Service("counter"), "admin/count")
+        .get_response()
+        .assert_schema(&schema);
+}
+
+#[test]
+#[should_panic(expected = "Response does not conform to JSON schema")]
+fn test_assert_schema_failure() {
+    let (mut testkit, api) = init_testkit();
+    inc_count(&api, 5);
+    testkit.create_block();
+
+    let schema = serde_json::from_str(r#"{ "type": "integer", "maximum": 3 }"#).unwrap();
+    api.assert_schema(ApiKind::Service("counter"), "count", &schema);
+}
+
+#[test]
+fn test_create_block_with_proposer() {
+    use exonum::blockchain::Schema;
+    use exonum::helpers::ValidatorId;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(4)
+        .with_service(CounterService)
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+
+    testkit.create_block_with_proposer(ValidatorId(2), txvec![TxIncrement::new(&pubkey, 5, &key)]);
+    let block = Schema::new(&testkit.snapshot()).last_block().unwrap();
+    assert_eq!(block.height(), Height(1));
+    assert_eq!(block.proposer_id(), ValidatorId(2));
+    assert_eq!(block.tx_count(), 1);
+
+    let api = testkit.api();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+
+    // Blocks are proposed by the leader by default.
+    testkit.create_block();
+    let block = Schema::new(&testkit.snapshot()).last_block().unwrap();
+    assert_eq!(block.proposer_id(), ValidatorId(0));
+}
+
+#[test]
+#[should_panic(expected = "is absent in test network")]
+fn test_create_block_with_unknown_proposer() {
+    use exonum::helpers::ValidatorId;
+
+    let (mut testkit, _) = init_testkit();
+    testkit.create_block_with_proposer(ValidatorId(1), txvec![]);
+}
+
+#[test]
+fn test_skip_leader() {
+    use exonum::helpers::{Round, ValidatorId};
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(3)
+        .with_service(CounterService)
+        .create();
+    let api = testkit.api();
+    inc_count(&api, 5);
+
+    testkit.skip_leader(ValidatorId(0));
+    testkit.create_block();
+    let block = testkit.explorer().block_with_txs(Height(1)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(1));
+    assert_eq!(block.len(), 1);
+    assert!(block.precommits.iter().all(|p| p.round() == Round(2)));
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+
+    // Skipping a validator other than the scheduled proposer does not affect the block.
+    testkit.skip_leader(ValidatorId(2));
+    testkit.create_block();
+    let block = testkit.explorer().block_with_txs(Height(2)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(0));
+    assert_eq!(block.precommits[0].round(), Round::first());
+
+    // Offline marks apply to a single height.
+    testkit.skip_leader(ValidatorId(0));
+    testkit.skip_leader(ValidatorId(1));
+    testkit.create_block();
+    testkit.create_block();
+    let explorer = testkit.explorer();
+    let block = explorer.block_with_txs(Height(3)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(2));
+    assert_eq!(block.precommits[0].round(), Round(3));
+    let block = explorer.block_with_txs(Height(4)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(0));
+}
+
+#[test]
+fn test_skip_rounds() {
+    use exonum::helpers::{Round, ValidatorId};
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(3)
+        .with_service(CounterService)
+        .create();
+    let api = testkit.api();
+    inc_count(&api, 5);
+
+    testkit.skip_rounds(1);
+    testkit.skip_rounds(3);
+    testkit.create_block();
+    let block = testkit.explorer().block_with_txs(Height(1)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(1));
+    assert_eq!(block.len(), 1);
+    assert!(block.precommits.iter().all(|p| p.round() == Round(5)));
+
+    // Skipped rounds combine with offline validators.
+    testkit.skip_rounds(2);
+    testkit.skip_leader(ValidatorId(2));
+    testkit.create_block();
+    let block = testkit.explorer().block_with_txs(Height(2)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(0));
+    assert_eq!(block.precommits[0].round(), Round(4));
+
+    // Skipped rounds apply to a single height.
+    testkit.create_block();
+    let block = testkit.explorer().block_with_txs(Height(3)).unwrap();
+    assert_eq!(block.header.proposer_id(), ValidatorId(0));
+    assert_eq!(block.precommits[0].round(), Round::first());
+}
+
+#[test]
+fn test_network_topology() {
+    use exonum::helpers::{Round, ValidatorId};
+
+    let mut testkit = TestKitBuilder::auditor()
+        .with_validators(4)
+        .with_service(CounterService)
+        .create();
+    {
+        let network = testkit.network();
+        assert_eq!(network.validators().len(), 4);
+        assert_eq!(network.auditors(), vec![network.us()]);
+        assert_eq!(network.majority_count(), 3);
+        assert_eq!(testkit.majority_count(), 3);
+        let leader = network.leader_for(Round::first());
+        assert_eq!(leader.validator_id(), Some(ValidatorId(0)));
+        let leader = network.leader_for(Round(6));
+        assert_eq!(leader.validator_id(), Some(ValidatorId(1)));
+    }
+
+    let expected_proposer = testkit
+        .network()
+        .leader_for(Round(2))
+        .validator_id()
+        .unwrap();
+    testkit.skip_leader(ValidatorId(0));
+    testkit.create_block();
+    let block = testkit.explorer().block(Height(1)).unwrap();
+    assert_eq!(block.proposer_id(), expected_proposer);
+
+    let validator = TestKitBuilder::validator().with_validators(2).create();
+    assert!(validator.network().auditors().is_empty());
+    assert_eq!(validator.network().majority_count(), 2);
+}
+
+#[test]
+#[should_panic(expected = "Cannot mark all validators as offline")]
+fn test_skip_all_leaders() {
+    use exonum::helpers::ValidatorId;
+
+    let mut testkit = TestKitBuilder::validator().with_validators(2).create();
+    testkit.skip_leader(ValidatorId(0));
+    testkit.skip_leader(ValidatorId(1));
+}
+
+#[test]
+fn test_create_block_at_time() {
+    use std::time::{Duration, UNIX_EPOCH};
+    use exonum::blockchain::Schema;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(4)
+        .with_service(CounterService)
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+
+    let time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    testkit.create_block_at_time(time, txvec![TxIncrement::new(&pubkey, 5, &key)]);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    let precommits = schema.precommits(&testkit.last_block_hash());
+    assert_eq!(precommits.len(), 4);
+    assert!(precommits.iter().all(|precommit| precommit.time() == time));
+
+    let api = testkit.api();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+}
+
+#[test]
+fn test_mempool_limit_reject_new() {
+    use exonum_testkit::EvictionPolicy;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_mempool_limit(2, EvictionPolicy::RejectNew)
+        .create();
+    let api = testkit.api();
+
+    let tx_a = inc_count(&api, 1);
+    let tx_b = inc_count(&api, 2);
+    let tx_c = inc_count(&api, 3);
+    testkit.poll_events();
+    assert_eq!(testkit.mempool().len(), 2);
+    assert!(!testkit.mempool().contains_key(&tx_c.hash()));
+    assert_eq!(testkit.evicted_transactions(), vec![tx_c.hash()]);
+
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 3);
+
+    // The mempool accepts transactions again after the block is committed
+    let tx_d = inc_count(&api, 4);
+    testkit.poll_events();
+    assert!(testkit.mempool().contains_key(&tx_d.hash()));
+    assert!(!testkit.mempool().contains_key(&tx_a.hash()));
+    assert!(!testkit.mempool().contains_key(&tx_b.hash()));
+}
+
+#[test]
+fn test_mempool_limit_evict_oldest() {
+    use exonum_testkit::EvictionPolicy;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_mempool_limit(2, EvictionPolicy::EvictOldest)
+        .create();
+    let api = testkit.api();
+
+    let tx_a = inc_count(&api, 1);
+    let tx_b = inc_count(&api, 2);
+    let tx_c = inc_count(&api, 3);
+    let tx_d = inc_count(&api, 4);
+    testkit.poll_events();
+    assert_eq!(testkit.evicted_transactions(), vec![tx_a.hash(), tx_b.hash()]);
+
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 7);
+    let snapshot = testkit.snapshot();
+    let schema = exonum::blockchain::Schema::new(&snapshot);
+    assert!(schema.transactions().contains(&tx_c.hash()));
+    assert!(schema.transactions().contains(&tx_d.hash()));
+}
+
+#[test]
+fn test_pool_ordering() {
+    use exonum_testkit::PoolOrdering;
+
+    let (mut testkit, api) = init_testkit();
+    let admin_tx = {
+        let (pubkey, key) = crypto::gen_keypair_from_seed(&crypto::Seed::from_slice(
+            &crypto::hash(b"correct horse battery staple")[..],
+        ).unwrap());
+        TxReset::new(&pubkey, &key)
+    };
+    let (pubkey, key) = crypto::gen_keypair();
+
+    // The reset is executed after the increment.
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    testkit.add_tx(tx.clone()).unwrap();
+    testkit.add_tx(admin_tx.clone()).unwrap();
+    testkit.set_pool_ordering(PoolOrdering::Explicit(vec![tx.hash(), admin_tx.hash()]));
+    let block = testkit.create_block();
+    let hashes: Vec<_> = block.transactions().iter().map(|tx| *tx.hash()).collect();
+    assert_eq!(hashes, vec![tx.hash(), admin_tx.hash()]);
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 0);
+
+    // Transactions are ordered by descending hashes.
+    let txs: Vec<_> = (0..5).map(|i| TxIncrement::new(&pubkey, i + 1, &key)).collect();
+    for tx in &txs {
+        testkit.add_tx(tx.clone()).unwrap();
+    }
+    testkit.set_pool_ordering(PoolOrdering::comparator(|a, b| b.hash().cmp(&a.hash())));
+    let block = testkit.create_block();
+    let hashes: Vec<_> = block.transactions().iter().map(|tx| *tx.hash()).collect();
+    let mut expected: Vec<_> = txs.iter().map(|tx| tx.hash()).collect();
+    expected.sort_by(|a, b| b.cmp(a));
+    assert_eq!(hashes, expected);
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 15);
+}
+
+#[test]
+fn test_sent_requests() {
+    use exonum_testkit::SentRequest;
+
+    let (mut testkit, api) = init_testkit();
+    let tx_a = inc_count(&api, 1);
+    assert!(testkit.sent_requests().is_empty());
+    testkit.poll_events();
+
+    testkit.create_block();
+    // Committed transactions are recorded even if they are ignored by the node.
+    let tx_b = inc_count(&api, 2);
+    api.send(tx_a.clone());
+    testkit.create_block();
+
+    let sent = testkit.sent_requests();
+    let hashes: Vec<_> = sent.iter().filter_map(SentRequest::tx_hash).collect();
+    assert_eq!(hashes, vec![tx_a.hash(), tx_b.hash(), tx_a.hash()]);
+    match sent[1] {
+        SentRequest::Transaction { ref raw, .. } => {
+            let tx = TxIncrement::from_raw(raw.clone()).unwrap();
+            assert_eq!(tx.by(), 2);
+        }
+        ref other => panic!("Unexpected request: {:?}", other),
+    }
+}
+
+#[test]
+fn test_network_runner() {
+    use exonum_testkit::TestNetworkRunner;
+
+    let mut network = TestNetworkRunner::with_nodes(3)
+        .with_service(|| CounterService)
+        .create();
+    let tx_a = inc_count(&network.api(0), 1);
+    let tx_b = inc_count(&network.api(1), 2);
+
+    // Node #0 does not know about `tx_b`, and other nodes do not know about the new block.
+    network.create_block(0);
+    assert_eq!(network.node(0).height(), Height(1));
+    assert_eq!(network.node(1).height(), Height(0));
+    let counter: u64 = network.api(1).get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 0);
+
+    network.sync_all();
+    for i in 0..network.len() {
+        assert_eq!(network.node(i).height(), Height(1));
+        assert_eq!(network.node(i).last_block_hash(), network.node(0).last_block_hash());
+        let counter: u64 = network.api(i).get(ApiKind::Service("counter"), "count");
+        assert_eq!(counter, 1);
+    }
+    assert!(!network.node(1).mempool().contains_key(&tx_a.hash()));
+    assert!(network.node(1).mempool().contains_key(&tx_b.hash()));
+
+    network.broadcast_transactions(1);
+    assert!(network.node(2).mempool().contains_key(&tx_b.hash()));
+    network.create_block(2);
+    network.sync_all();
+    for i in 0..network.len() {
+        let counter: u64 = network.api(i).get(ApiKind::Service("counter"), "count");
+        assert_eq!(counter, 3);
+        assert!(network.node(i).mempool().is_empty());
+    }
+}
+
+#[test]
+fn test_network_runner_partition() {
+    use std::sync::{Arc, Mutex};
+    use exonum_testkit::TestNetworkRunner;
+
+    let mut network = TestNetworkRunner::with_nodes(4)
+        .with_service(|| CounterService)
+        .create();
+    let committed = Arc::new(Mutex::new(Vec::new()));
+    {
+        let committed = Arc::clone(&committed);
+        network.node_mut(3).on_after_commit(move |block, _| {
+            committed.lock().unwrap().push(block.height());
+        });
+    }
+
+    network.partition(&[0, 1, 2], &[3]);
+    assert!(network.is_connected(0, 2));
+    assert!(!network.is_connected(0, 3));
+
+    let tx = inc_count(&network.api(0), 5);
+    network.broadcast_transactions(0);
+    assert!(network.node(2).mempool().contains_key(&tx.hash()));
+    assert!(!network.node(3).mempool().contains_key(&tx.hash()));
+
+    network.create_block(0);
+    network.create_block(0);
+    network.sync_all();
+    assert_eq!(network.node(2).height(), Height(2));
+    // The cut off node serves the stale state.
+    assert_eq!(network.node(3).height(), Height(0));
+    let counter: u64 = network.api(3).get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 0);
+    assert!(committed.lock().unwrap().is_empty());
+
+    network.heal();
+    network.sync_all();
+    assert_eq!(network.node(3).height(), Height(2));
+    let counter: u64 = network.api(3).get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+    assert_eq!(*committed.lock().unwrap(), vec![Height(1), Height(2)]);
+}
+
+#[test]
+#[should_panic(expected = "Node 3 cannot create a block: it is connected to 1 validators")]
+fn test_network_runner_minority_partition() {
+    use exonum_testkit::TestNetworkRunner;
+
+    let mut network = TestNetworkRunner::with_nodes(4)
+        .with_service(|| CounterService)
+        .create();
+    network.partition(&[0, 1, 2], &[3]);
+    inc_count(&network.api(3), 1);
+    network.create_block(3);
+}
+
+#[test]
+fn test_network_runner_even_partition() {
+    use std::panic::{self, AssertUnwindSafe};
+    use exonum_testkit::TestNetworkRunner;
+
+    let mut network = TestNetworkRunner::with_nodes(4)
+        .with_service(|| CounterService)
+        .create();
+    network.partition(&[0, 1], &[2, 3]);
+    // Neither part has a majority of validators.
+    for proposer in 0..network.len() {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| network.create_block(proposer)));
+        assert!(result.is_err());
+    }
+
+    network.heal();
+    network.create_block(2);
+    network.sync_all();
+    for i in 0..network.len() {
+        assert_eq!(network.node(i).height(), Height(1));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Node 1 is listed more than once")]
+fn test_network_runner_invalid_partition() {
+    use exonum_testkit::TestNetworkRunner;
+
+    let mut network = TestNetworkRunner::with_nodes(3).create();
+    network.partition(&[0, 1], &[1, 2]);
+}
+
+#[test]
+fn test_network_runner_latency() {
+    use exonum_testkit::{FaultPolicy, TestNetworkRunner};
+
+    let mut network = TestNetworkRunner::with_nodes(3)
+        .with_service(|| CounterService)
+        .with_fault_policy(FaultPolicy::new().with_latency(2).with_link_latency(0, 2, 1))
+        .create();
+    let tx = inc_count(&network.api(0), 1);
+    network.broadcast_transactions(0);
+    assert_eq!(network.in_flight_messages(), 2);
+    assert!(!network.node(1).mempool().contains_key(&tx.hash()));
+
+    network.tick();
+    assert!(!network.node(1).mempool().contains_key(&tx.hash()));
+    assert!(network.node(2).mempool().contains_key(&tx.hash()));
+    network.tick();
+    assert!(network.node(1).mempool().contains_key(&tx.hash()));
+    assert_eq!(network.in_flight_messages(), 0);
+}
+
+#[test]
+fn test_network_runner_lossy_transport() {
+    use exonum_testkit::{FaultPolicy, TestNetworkRunner};
+
+    let policy = FaultPolicy::new()
+        .with_drop_rate(0.5)
+        .with_latency(1)
+        .with_seed(3);
+    let mut network = TestNetworkRunner::with_nodes(2)
+        .with_service(|| CounterService)
+        .with_fault_policy(policy)
+        .create();
+    let tx = inc_count(&network.api(0), 1);
+
+    // Retry sending the transaction until it is delivered.
+    let mut attempts = 0;
+    while !network.node(1).mempool().contains_key(&tx.hash()) {
+        assert!(attempts < 100, "Transaction is not delivered");
+        network.send_transactions(0, 1);
+        network.tick();
+        attempts += 1;
+    }
+    // Duplicate deliveries do not affect the node.
+    for _ in 0..10 {
+        network.send_transactions(0, 1);
+        network.tick();
+    }
+    assert_eq!(network.node(1).mempool().len(), 1);
+
+    network.create_block(1);
+    network.sync_all();
+    for i in 0..network.len() {
+        let counter: u64 = network.api(i).get(ApiKind::Service("counter"), "count");
+        assert_eq!(counter, 1);
+    }
+
+    network.set_fault_policy(FaultPolicy::new().with_drop_rate(1.0));
+    inc_count(&network.api(0), 2);
+    let dropped = network.dropped_messages();
+    network.send_transactions(0, 1);
+    assert_eq!(network.dropped_messages(), dropped + 1);
+    assert_eq!(network.in_flight_messages(), 0);
+    assert!(network.node(1).mempool().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "have diverged at height 1")]
+fn test_network_runner_divergence() {
+    use exonum_testkit::TestNetworkRunner;
+
+    let mut network = TestNetworkRunner::with_nodes(2)
+        .with_service(|| CounterService)
+        .create();
+    inc_count(&network.api(0), 1);
+    inc_count(&network.api(1), 2);
+    network.create_block(0);
+    network.create_block(1);
+    network.create_block(1);
+    network.sync_all();
+}
+
+#[test]
+fn test_add_tx() {
+    use exonum_testkit::AddTxError;
+
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    assert_eq!(testkit.add_tx(tx.clone()), Ok(tx.hash()));
+    assert!(testkit.mempool().contains_key(&tx.hash()));
+    // Retry of the same transaction
+    assert_eq!(testkit.add_tx(tx.clone()), Err(AddTxError::Duplicate));
+
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+    assert_eq!(testkit.add_tx(tx.clone()), Err(AddTxError::AlreadyCommitted));
+
+    // Transaction with an incorrect signature
+    let (other_pubkey, _) = crypto::gen_keypair();
+    let bogus_tx = TxIncrement::new(&other_pubkey, 1, &key);
+    assert_eq!(testkit.add_tx(bogus_tx), Err(AddTxError::Invalid));
+
+    // Transaction sent via API is treated as a duplicate as well
+    let other_tx = inc_count(&api, 3);
+    assert_eq!(testkit.add_tx(other_tx), Err(AddTxError::Duplicate));
+}
+
+#[test]
+fn test_add_tx_unchecked() {
+    use exonum::messages::{MessageBuffer, RawMessage};
+    use exonum_testkit::AddTxError;
+
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, _) = crypto::gen_keypair();
+    let (_, other_key) = crypto::gen_keypair();
+
+    // Transaction with an incorrect signature
+    let bogus_tx = TxIncrement::new(&pubkey, 5, &other_key);
+    assert_eq!(testkit.add_tx(bogus_tx.clone()), Err(AddTxError::Invalid));
+    assert_eq!(testkit.add_tx_unchecked(bogus_tx.raw().clone()), Ok(bogus_tx.hash()));
+    assert_eq!(
+        testkit.add_tx_unchecked(bogus_tx.raw().clone()),
+        Err(AddTxError::Duplicate)
+    );
+
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+    assert_eq!(
+        testkit.add_tx_unchecked(bogus_tx.raw().clone()),
+        Err(AddTxError::AlreadyCommitted)
+    );
+
+    // Message of an unknown service
+    let raw = RawMessage::new(MessageBuffer::from_vec(vec![0; 100]));
+    assert_eq!(testkit.add_tx_unchecked(raw), Err(AddTxError::Malformed));
+}
+
+#[test]
+fn test_block_result_with_panicking_transaction() {
+    use exonum_testkit::{ExecutionStatus, PanicPolicy, TxStatus};
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_panic_policy(PanicPolicy::Record)
+        .create();
+    let api = testkit.api();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    // The counter overflows in the second transaction.
+    let overflowing_tx = TxIncrement::new(&pubkey, u64::max_value(), &key);
+    let other_tx = TxIncrement::new(&pubkey, 3, &key);
+    let result = testkit.create_block_with_transactions(txvec![
+        tx.clone(),
+        overflowing_tx.clone(),
+        other_tx.clone(),
+    ]);
+
+    assert_eq!(result.height(), Height(1));
+    let hashes: Vec<_> = result.transactions().iter().map(|tx| *tx.hash()).collect();
+    assert_eq!(hashes, vec![tx.hash(), overflowing_tx.hash(), other_tx.hash()]);
+    assert_eq!(result.status(&tx.hash()), Some(&ExecutionStatus::Applied));
+    match *result.status(&overflowing_tx.hash()).unwrap() {
+        ExecutionStatus::Panicked(ref message) => assert!(message.contains("overflow")),
+        ref status => panic!("Unexpected status: {:?}", status),
+    }
+    assert_eq!(result.status(&other_tx.hash()), Some(&ExecutionStatus::Applied));
+
+    // The panicking transaction is not committed.
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 8);
+    assert_eq!(testkit.tx_status(&overflowing_tx.hash()), TxStatus::Unknown);
+    let block = testkit.explorer().block(Height(1)).unwrap();
+    assert_eq!(block.tx_count(), 2);
+}
+
+#[test]
+#[should_panic(expected = "has panicked during execution in block 1")]
+fn test_panicking_transaction_aborts_by_default() {
+    let (mut testkit, _) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![
+        TxIncrement::new(&pubkey, 5, &key),
+        TxIncrement::new(&pubkey, u64::max_value(), &key),
+    ]);
+}
+
+#[test]
+fn test_profile_report() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_profiling()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    let other_tx = TxIncrement::new(&pubkey, 3, &key);
+    testkit.create_block_with_transactions(txvec![tx.clone(), other_tx.clone()]);
+    testkit.create_block();
+
+    let report = testkit.profile_report();
+    assert_eq!(report.blocks().len(), 2);
+    let block = &report.blocks()[0];
+    assert_eq!(block.height, Height(1));
+    let hashes: Vec<_> = block.transactions.iter().map(|tx| tx.hash).collect();
+    assert_eq!(hashes, vec![tx.hash(), other_tx.hash()]);
+    assert!(report.blocks()[1].transactions.is_empty());
+
+    let types = report.by_transaction_type();
+    assert_eq!(types.len(), 1);
+    assert_eq!((types[0].service_id, types[0].message_type), (1, 1));
+    assert_eq!(types[0].count, 2);
+    assert!(report.to_string().contains("service 1, type 1: 2 tx"));
+}
+
+#[test]
+#[should_panic(expected = "Profiling is not enabled")]
+fn test_profile_report_without_profiling() {
+    let (testkit, _) = init_testkit();
+    testkit.profile_report();
+}
+
+#[test]
+fn test_bench_kit() {
+    use exonum_testkit::bench::{self, BenchKit, TxBatch};
+
+    let (pubkey, key) = crypto::gen_keypair();
+    let fixture = bench::chain_fixture(
+        TestKitBuilder::validator().with_service_factory(|| Box::new(CounterService)),
+        Height(5),
+        |height| {
+            TxBatch::generate(2, |i| {
+                Box::new(TxIncrement::new(&pubkey, height.0 * 10 + i as u64, &key))
+            })
+        },
+    );
+    assert_eq!(fixture.height(), Height(5));
+    assert_eq!(CounterSchema::new(fixture.snapshot()).count(), Some(305));
+
+    let mut benchkit = BenchKit::new(fixture);
+    let batch = TxBatch::generate(3, |i| {
+        Box::new(TxIncrement::new(&pubkey, 1000 + i as u64, &key))
+    });
+    assert_eq!(batch.len(), 3);
+    for _ in 0..2 {
+        let (mut testkit, txs) = benchkit.setup_with_batch(&batch);
+        testkit.create_block_with_transactions(txs);
+        assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(3308));
+    }
+    benchkit.measure(&batch, |testkit, txs| {
+        testkit.create_block_with_transactions(txs);
+    });
+    assert_eq!(benchkit.fixture().height(), Height(5));
+}
+
+#[test]
+fn test_create_block_fast() {
+    use exonum::blockchain::Transaction;
+
+    let create_testkit = || {
+        TestKitBuilder::validator()
+            .with_seed(7)
+            .with_validators(2)
+            .with_service(CounterService)
+            .create()
+    };
+    let (pubkey, key) = crypto::gen_keypair();
+    let txs = || -> Vec<Box<Transaction>> {
+        (1..101)
+            .map(|i| Box::new(TxIncrement::new(&pubkey, i, &key)) as Box<Transaction>)
+            .collect()
+    };
+
+    let mut testkit = create_testkit();
+    let mut fast_testkit = create_testkit();
+    testkit.create_block_with_transactions(txs());
+    fast_testkit.create_block_fast(txs());
+    assert_eq!(fast_testkit.height(), Height(1));
+    assert_eq!(CounterSchema::new(fast_testkit.snapshot()).count(), Some(5050));
+    assert_eq!(fast_testkit.last_block_hash(), testkit.last_block_hash());
+
+    fast_testkit.create_block_fast(Vec::new());
+    assert_eq!(fast_testkit.height(), Height(2));
+    fast_testkit.rollback(1);
+    assert_eq!(fast_testkit.last_block_hash(), testkit.last_block_hash());
+}
+
+#[test]
+fn test_block_splitting() {
+    use exonum::blockchain::{ConsensusConfig, Transaction};
+    use exonum_testkit::TxStatus;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_consensus_config(ConsensusConfig {
+            txs_block_limit: 3,
+            ..ConsensusConfig::default()
+        })
+        .with_block_splitting()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    let txs: Vec<_> = (1..11).map(|i| TxIncrement::new(&pubkey, i, &key)).collect();
+
+    let result = testkit.create_block_with_transactions(
+        txs.iter().map(|tx| Box::new(tx.clone()) as Box<Transaction>),
+    );
+    assert_eq!(result.blocks(), 4);
+    assert_eq!(result.height(), Height(4));
+    assert_eq!(result.transactions().len(), 10);
+    assert_eq!(testkit.height(), Height(4));
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(55));
+    assert_eq!(testkit.explorer().block(Height(4)).unwrap().tx_count(), 1);
+    assert_eq!(
+        testkit.tx_status(&txs[9].hash()),
+        TxStatus::Committed {
+            height: Height(4),
+            position_in_block: 0,
+        }
+    );
+
+    let result = testkit.create_block();
+    assert_eq!(result.blocks(), 1);
+    assert_eq!(testkit.height(), Height(5));
+}
+
+#[test]
+fn test_block_size_limit() {
+    let tx_size = {
+        let (pubkey, key) = crypto::gen_keypair();
+        TxIncrement::new(&pubkey, 1, &key).raw().len()
+    };
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_block_size_limit(2 * tx_size + 1)
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    let api = testkit.api();
+    for i in 1..6 {
+        api.send(TxIncrement::new(&pubkey, i, &key));
+    }
+
+    let result = testkit.create_block();
+    assert_eq!(result.blocks(), 3);
+    assert_eq!(testkit.height(), Height(3));
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(15));
+}
+
+#[test]
+fn test_rollback_blocks() {
+    use exonum_testkit::PoolRetention;
+
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let committed_tx = TxIncrement::new(&pubkey, 3, &key);
+    testkit.create_block_with_transaction(committed_tx.clone());
+    let pending_tx = inc_count(&api, 5);
+    testkit.poll_events();
+
+    testkit.rollback_blocks(1, PoolRetention::Keep);
+    assert_eq!(testkit.height(), Height(0));
+    assert!(testkit.mempool().contains_key(&pending_tx.hash()));
+    assert!(!testkit.mempool().contains_key(&committed_tx.hash()));
+
+    testkit.create_block_with_transaction(committed_tx.clone());
+    testkit.rollback_blocks(1, PoolRetention::Restore);
+    assert!(testkit.mempool().contains_key(&pending_tx.hash()));
+    assert!(testkit.mempool().contains_key(&committed_tx.hash()));
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 8);
 
-mod counter;
-use counter::{CounterSchema, CounterService, TransactionResponse, TxIncrement, TxReset, ADMIN_KEY};
+    inc_count(&api, 1);
+    testkit.rollback_blocks(1, PoolRetention::Clear);
+    assert!(testkit.mempool().is_empty());
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 0);
+}
 
-fn init_testkit() -> (TestKit, TestKitApi) {
-    let testkit = TestKitBuilder::validator()
+#[test]
+fn test_rollback_retention() {
+    use exonum_testkit::PoolRetention;
+
+    let mut testkit = TestKitBuilder::validator()
         .with_service(CounterService)
+        .with_rollback_retention(PoolRetention::Restore)
         .create();
     let api = testkit.api();
-    (testkit, api)
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    testkit.create_block_with_transaction(tx.clone());
+    testkit.create_block();
+
+    testkit.rollback(2);
+    assert_eq!(testkit.height(), Height(0));
+    assert!(testkit.mempool().contains_key(&tx.hash()));
+    // Restored transactions are recommitted by the next block.
+    testkit.create_block();
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
+
+    testkit.rollback_blocks(1, PoolRetention::Keep);
+    assert!(testkit.mempool().is_empty());
 }
 
-fn inc_count(api: &TestKitApi, by: u64) -> TxIncrement {
+#[test]
+fn test_tx_status() {
+    use exonum_testkit::TxStatus;
+
+    let (mut testkit, api) = init_testkit();
     let (pubkey, key) = crypto::gen_keypair();
-    // Create a presigned transaction
-    let tx = TxIncrement::new(&pubkey, by, &key);
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    assert_eq!(testkit.tx_status(&tx.hash()), TxStatus::Unknown);
 
-    let tx_info: TransactionResponse = api.post(ApiKind::Service("counter"), "count", &tx);
-    assert_eq!(tx_info.tx_hash, tx.hash());
-    tx
+    let other_tx = inc_count(&api, 3);
+    testkit.poll_events();
+    assert_eq!(testkit.tx_status(&other_tx.hash()), TxStatus::InPool);
+
+    testkit.create_block_with_transactions(txvec![tx.clone(), other_tx.clone()]);
+    assert_eq!(
+        testkit.tx_status(&tx.hash()),
+        TxStatus::Committed {
+            height: Height(1),
+            position_in_block: 0,
+        }
+    );
+    assert_eq!(
+        testkit.tx_status(&other_tx.hash()),
+        TxStatus::Committed {
+            height: Height(1),
+            position_in_block: 1,
+        }
+    );
+
+    testkit.rollback(1);
+    assert_eq!(testkit.tx_status(&tx.hash()), TxStatus::Unknown);
 }
 
 #[test]
-fn test_inc_count_create_block() {
-    let (mut testkit, api) = init_testkit();
+fn test_core_schema_accessors() {
+    let (mut testkit, _) = init_testkit();
     let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxIncrement::new(&pubkey, 5, &key);
+    let other_tx = TxIncrement::new(&pubkey, 3, &key);
+    assert_eq!(testkit.last_block().height(), Height(0));
+    assert!(testkit.tx_location(&tx.hash()).is_none());
+    assert_eq!(testkit.block_of_tx(&tx.hash()), None);
 
-    // Create a presigned transaction
-    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    testkit.create_block();
+    testkit.create_block_with_transactions(txvec![tx.clone(), other_tx.clone()]);
+    let last_block = testkit.last_block();
+    assert_eq!(last_block.height(), Height(2));
+    assert_eq!(last_block.hash(), testkit.last_block_hash());
+    assert_eq!(last_block.tx_count(), 2);
+
+    let location = testkit.tx_location(&other_tx.hash()).unwrap();
+    assert_eq!(location.block_height(), Height(2));
+    assert_eq!(location.position_in_block(), 1);
+    assert_eq!(testkit.block_of_tx(&tx.hash()), Some(last_block));
+
+    testkit.rollback(1);
+    assert!(testkit.tx_location(&tx.hash()).is_none());
+    assert_eq!(testkit.block_of_tx(&tx.hash()), None);
+}
 
-    // Check that the user indeed is persisted by the service
+#[test]
+fn test_switch_branches() {
+    let (mut testkit, api) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx_a = TxIncrement::new(&pubkey, 2, &key);
+    let tx_b = TxIncrement::new(&pubkey, 3, &key);
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+    testkit.create_block_with_transaction(tx_a.clone());
+    testkit.create_block();
+    let last_hash = testkit.last_block_hash();
+
+    let first_branch = testkit.detach_branch(Height(1));
+    assert_eq!(first_branch.ancestor(), Height(1));
+    assert_eq!(first_branch.height(), Height(3));
+    assert_eq!(first_branch.len(), 2);
+    assert_eq!(testkit.height(), Height(1));
+    assert!(testkit.mempool().is_empty());
     let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 5);
+    assert_eq!(counter, 1);
 
-    testkit.create_block_with_transactions(txvec![
-        TxIncrement::new(&pubkey, 4, &key),
-        TxIncrement::new(&pubkey, 1, &key),
-    ]);
+    // Build a competing branch.
+    testkit.create_block_with_transaction(tx_b.clone());
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 4);
 
+    let second_branch = testkit.switch_to_branch(first_branch);
+    assert_eq!(second_branch.height(), Height(2));
+    assert_eq!(testkit.height(), Height(3));
+    assert_eq!(testkit.last_block_hash(), last_hash);
     let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 10);
+    assert_eq!(counter, 3);
+    // Transactions from the abandoned branch are returned to the mempool.
+    assert!(testkit.mempool().contains_key(&tx_b.hash()));
+
+    let first_branch = testkit.switch_to_branch(second_branch);
+    assert_eq!(first_branch.blocks()[0].transactions[0].hash(), tx_a.hash());
+    assert_eq!(testkit.height(), Height(2));
+    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 4);
+    assert!(testkit.mempool().contains_key(&tx_a.hash()));
+    assert!(!testkit.mempool().contains_key(&tx_b.hash()));
 }
 
-#[should_panic(expected = "Transaction is already committed")]
 #[test]
-fn test_inc_count_create_block_with_committed_transaction() {
+fn test_switch_branches_with_mempool_limit() {
+    use exonum_testkit::EvictionPolicy;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_mempool_limit(1, EvictionPolicy::RejectNew)
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx_a = TxIncrement::new(&pubkey, 1, &key);
+    let tx_b = TxIncrement::new(&pubkey, 2, &key);
+    testkit.create_block();
+    let empty_branch = testkit.detach_branch(Height(0));
+    testkit.create_block_with_transactions(txvec![tx_a.clone(), tx_b.clone()]);
+
+    testkit.switch_to_branch(empty_branch);
+    // Only one of the transactions from the abandoned branch fits into the mempool.
+    assert_eq!(testkit.mempool().len(), 1);
+    assert_eq!(testkit.evicted_transactions().len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "ancestor block of the branch is absent in the blockchain")]
+fn test_switch_to_unrelated_branch() {
     let (mut testkit, _) = init_testkit();
     let (pubkey, key) = crypto::gen_keypair();
-    // Create a presigned transaction
-    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
-    // Create another block with the same transaction
-    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    testkit.create_blocks_until(Height(2));
+    let branch = testkit.detach_branch(Height(1));
+
+    testkit.rollback(1);
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+    testkit.switch_to_branch(branch);
 }
 
 #[test]
-fn test_inc_count_api() {
+fn test_fork() {
     let (mut testkit, api) = init_testkit();
-    inc_count(&api, 5);
-    testkit.create_block();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    let tx = inc_count(&api, 1);
+    testkit.poll_events();
 
-    // Check that the user indeed is persisted by the service
+    let fork_hash = testkit.last_block_hash();
+    let mut other = testkit.fork();
+    assert_eq!(other.height(), Height(1));
+    assert_eq!(other.last_block_hash(), testkit.last_block_hash());
+    assert!(other.mempool().contains_key(&tx.hash()));
+    assert_eq!(CounterSchema::new(other.snapshot()).count(), Some(5));
+
+    // Scenario A: commit the transaction from the mempool.
+    testkit.create_block();
+    // Scenario B: commit another transaction instead.
+    other.create_block_with_tx_hashes(&[]);
+    other.create_block_with_transaction(TxIncrement::new(&pubkey, 3, &key));
+
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(6));
+    assert_eq!(CounterSchema::new(other.snapshot()).count(), Some(8));
+    assert_eq!(testkit.height(), Height(2));
+    assert_eq!(other.height(), Height(3));
+    assert!(testkit.mempool().is_empty());
+    assert!(other.mempool().contains_key(&tx.hash()));
     let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 5);
+    assert_eq!(counter, 6);
+    let counter: u64 = other.api().get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 8);
+
+    // The copy keeps the history of the original testkit.
+    other.rollback(2);
+    assert_eq!(other.last_block_hash(), fork_hash);
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(6));
 }
 
 #[test]
-fn test_inc_count_with_multiple_transactions() {
-    let (mut testkit, api) = init_testkit();
+fn test_fork_isolation() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, UNIX_EPOCH};
+    use exonum::blockchain::Schema;
+    use exonum_testkit::time::TimeProvider;
+
+    let created = Arc::new(AtomicUsize::new(0));
+    let time_provider = MockTimeProvider::default();
+    let mut testkit = TestKitBuilder::validator()
+        .with_service_factory({
+            let created = Arc::clone(&created);
+            move || {
+                created.fetch_add(1, Ordering::SeqCst);
+                Box::new(CounterService)
+            }
+        })
+        .with_time_provider(time_provider.clone())
+        .create();
+    assert_eq!(created.load(Ordering::SeqCst), 1);
 
-    for _ in 0..100 {
-        inc_count(&api, 1);
-        inc_count(&api, 2);
-        inc_count(&api, 3);
-        inc_count(&api, 4);
+    time_provider.add_time(Duration::from_secs(10));
+    let mut other = testkit.fork();
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+
+    // Advancing the time of the original testkit does not affect the copy.
+    time_provider.add_time(Duration::from_secs(10));
+    testkit.create_block();
+    other.create_block();
+    let block_time = |testkit: &TestKit| {
+        let snapshot = testkit.snapshot();
+        let precommits = Schema::new(&snapshot).precommits(&testkit.last_block_hash());
+        let time = precommits.get(0).unwrap().time();
+        time
+    };
+    assert_eq!(block_time(&testkit), time_provider.current_time());
+    assert_eq!(block_time(&other), UNIX_EPOCH + Duration::from_secs(10));
+}
+
+#[test]
+#[should_panic(expected = "Cannot fork a testkit with services added by")]
+fn test_fork_with_fixed_services() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .create();
+    testkit.fork();
+}
+
+#[test]
+fn test_service_config() {
+    let testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_service_config("counter", vec![1, 2, 3])
+        .create();
+    let config: Vec<u32> = testkit.configuration_change_proposal().service_config("counter");
+    assert_eq!(config, vec![1, 2, 3]);
+    assert_eq!(
+        testkit.actual_configuration().services["counter"],
+        serde_json::to_value(vec![1, 2, 3]).unwrap()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Cannot set configuration for absent service unknown")]
+fn test_service_config_for_absent_service() {
+    TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_service_config("unknown", 1)
+        .create();
+}
+
+#[test]
+fn test_rocksdb_storage() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service_factory(|| Box::new(CounterService))
+        .with_temp_rocksdb()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 3, &key));
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(8));
+    assert_eq!(CounterSchema::new(testkit.snapshot_at(Height(1))).count(), Some(5));
+
+    let mut other = testkit.fork();
+    other.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+    assert_eq!(CounterSchema::new(other.snapshot()).count(), Some(9));
+
+    testkit.rollback(1);
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(5));
+    assert_eq!(CounterSchema::new(other.snapshot()).count(), Some(9));
+}
+
+#[test]
+fn test_preloaded_rocksdb() {
+    let dir = TempDir::new("exonum-testkit-test").unwrap();
+    let (pubkey, key) = crypto::gen_keypair();
+    let last_hash = {
+        let mut testkit = TestKitBuilder::validator()
+            .with_service(CounterService)
+            .with_rocksdb(dir.path())
+            .create();
+        testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+        testkit.create_blocks_until(Height(3));
+        testkit.last_block_hash()
+    };
 
+    let bootstrap = || {
+        TestKitBuilder::validator()
+            .with_service(CounterService)
+            .with_preloaded_rocksdb(dir.path())
+            .create()
+    };
+    let mut testkit = bootstrap();
+    assert_eq!(testkit.height(), Height(3));
+    assert_eq!(testkit.last_block_hash(), last_hash);
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(5));
+
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 2, &key));
+    assert_eq!(testkit.height(), Height(4));
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(7));
+    assert_eq!(CounterSchema::new(testkit.snapshot_at(Height(3))).count(), Some(5));
+    testkit.rollback(1);
+    assert_eq!(testkit.height(), Height(3));
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+    drop(testkit);
+
+    // The preloaded database is not modified.
+    let testkit = bootstrap();
+    assert_eq!(testkit.last_block_hash(), last_hash);
+}
+
+#[test]
+#[should_panic(expected = "past block 1, from which the testkit has been bootstrapped")]
+fn test_rollback_past_preloaded_block() {
+    let dir = TempDir::new("exonum-testkit-test").unwrap();
+    {
+        let mut testkit = TestKitBuilder::validator()
+            .with_rocksdb(dir.path())
+            .create();
         testkit.create_block();
     }
 
-    assert_eq!(testkit.height(), Height(100));
-    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 1_000);
+    let mut testkit = TestKitBuilder::validator()
+        .with_preloaded_rocksdb(dir.path())
+        .create();
+    testkit.create_block();
+    testkit.rollback(2);
 }
 
 #[test]
-fn test_inc_count_with_manual_tx_control() {
-    let (mut testkit, api) = init_testkit();
-    let tx_a = inc_count(&api, 5);
-    let tx_b = inc_count(&api, 3);
+fn test_export() {
+    let dir = TempDir::new("exonum-testkit-test").unwrap();
+    let (pubkey, key) = crypto::gen_keypair();
+    let export = |name: &str| {
+        let mut testkit = TestKitBuilder::validator()
+            .with_seed(5)
+            .with_time_provider(MockTimeProvider::default())
+            .with_service(CounterService)
+            .create();
+        testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+        testkit.create_block();
+        let path = dir.path().join(name);
+        testkit.export(&path);
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        (testkit.last_block_hash(), contents)
+    };
 
-    // Empty block
-    testkit.create_block_with_tx_hashes(&[]);
-    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 0);
+    let (last_hash, first) = export("first.json");
+    let (_, second) = export("second.json");
+    assert_eq!(first, second);
+    assert!(first.contains(&serde_json::to_string(&last_hash).unwrap()));
 
-    testkit.create_block_with_tx_hashes(&[tx_b.hash()]);
-    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 3);
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CounterService)
+        .with_exported_state(dir.path().join("first.json"))
+        .create();
+    assert_eq!(testkit.height(), Height(2));
+    assert_eq!(testkit.last_block_hash(), last_hash);
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(5));
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(6));
+}
 
-    testkit.create_block_with_tx_hashes(&[tx_a.hash()]);
-    let counter: u64 = api.get(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 8);
+#[test]
+fn test_replay_transaction_log() {
+    let create_testkit = || {
+        TestKitBuilder::validator()
+            .with_seed(3)
+            .with_time_provider(MockTimeProvider::default())
+            .with_service(CounterService)
+            .create()
+    };
+    let (pubkey, key) = crypto::gen_keypair();
+    let mut testkit = create_testkit();
+    testkit.create_block_with_transactions(txvec![
+        TxIncrement::new(&pubkey, 5, &key),
+        TxIncrement::new(&pubkey, 2, &key),
+    ]);
+    testkit.create_block();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+
+    let explorer = testkit.explorer();
+    let mut log = String::new();
+    for height in 1..4 {
+        let block = explorer.block_with_txs(Height(height)).unwrap();
+        let txs: Vec<_> = block
+            .transactions
+            .iter()
+            .map(|tx| tx.raw().as_ref().to_hex())
+            .collect();
+        log += &format!(
+            "{{\"height\": {}, \"transactions\": {}, \"state_hash\": {}}}\n",
+            height,
+            serde_json::to_string(&txs).unwrap(),
+            serde_json::to_string(block.header.state_hash()).unwrap()
+        );
+    }
+    let log = TransactionLog::from_reader(log.as_bytes());
+    assert_eq!(log.blocks().len(), 3);
+
+    let mut replayed = create_testkit();
+    let report = log.replay(&mut replayed);
+    assert!(report.is_ok(), "{:?}", report.divergences());
+    assert_eq!(report.blocks(), 3);
+    assert_eq!(report.transactions(), 3);
+    assert_eq!(replayed.last_block_hash(), testkit.last_block_hash());
+
+    // Replay the log against the testkit with a different history.
+    let mut diverged = create_testkit();
+    diverged.create_block_with_transaction(TxIncrement::new(&pubkey, 1, &key));
+    let report = log.replay(&mut diverged);
+    assert_eq!(report.blocks(), 3);
+    assert_eq!(report.transactions(), 2);
+    assert_eq!(
+        report.first_divergence(),
+        Some(&Divergence::Height {
+            expected: Height(1),
+            actual: Height(2),
+        })
+    );
+    let rejected = TxIncrement::new(&pubkey, 1, &key).hash();
+    assert!(report.divergences().contains(
+        &Divergence::RejectedTransaction {
+            height: Height(4),
+            hash: rejected,
+        },
+    ));
+    match *report.divergences().last().unwrap() {
+        Divergence::StateHash { height, .. } => assert_eq!(height, Height(4)),
+        ref divergence => panic!("Unexpected divergence: {:?}", divergence),
+    }
 }
 
 #[test]
-fn test_private_api() {
+fn test_save_and_load() {
+    let path = env::temp_dir().join("exonum-testkit-test-save-and-load.json");
     let (mut testkit, api) = init_testkit();
-    inc_count(&api, 5);
-    inc_count(&api, 3);
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    testkit.create_blocks_until(Height(3));
+    let tx = inc_count(&api, 2);
+    testkit.save(&path);
+
+    let mut loaded = TestKitBuilder::from_saved(&path)
+        .with_service(CounterService)
+        .create();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.height(), Height(3));
+    assert_eq!(loaded.last_block_hash(), testkit.last_block_hash());
+    assert_eq!(loaded.network().validators(), testkit.network().validators());
+    assert!(loaded.mempool().contains_key(&tx.hash()));
+    let counter: u64 = loaded.api().get(ApiKind::Service("counter"), "count");
+    assert_eq!(counter, 5);
 
+    loaded.create_block();
     testkit.create_block();
-    let counter: u64 = api.get_private(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 8);
+    assert_eq!(loaded.last_block_hash(), testkit.last_block_hash());
+    assert_eq!(CounterSchema::new(loaded.snapshot()).count(), Some(7));
+
+    // The history of changes is restored as well.
+    loaded.rollback(3);
+    assert_eq!(CounterSchema::new(loaded.snapshot()).count(), Some(5));
+    assert_eq!(
+        CounterSchema::new(loaded.snapshot_at(Height(0))).count(),
+        None
+    );
+}
 
-    let (pubkey, key) = crypto::gen_keypair_from_seed(&crypto::Seed::from_slice(
-        &crypto::hash(b"correct horse battery staple")[..],
-    ).unwrap());
-    assert_eq!(pubkey, PublicKey::from_hex(ADMIN_KEY).unwrap());
+#[test]
+fn test_snapshot_at() {
+    let (mut testkit, _) = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    testkit.create_block();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 3, &key));
 
-    let tx = TxReset::new(&pubkey, &key);
-    let tx_info: TransactionResponse = api.post_private(ApiKind::Service("counter"), "reset", &tx);
-    assert_eq!(tx_info.tx_hash, tx.hash());
+    let counts: Vec<_> = (0..4)
+        .map(|height| CounterSchema::new(testkit.snapshot_at(Height(height))).count())
+        .collect();
+    assert_eq!(counts, vec![None, Some(5), Some(5), Some(8)]);
+
+    // Check that historical snapshots are consistent with rollbacks
+    testkit.rollback(2);
+    assert_eq!(CounterSchema::new(testkit.snapshot_at(Height(1))).count(), Some(5));
+    assert_eq!(CounterSchema::new(testkit.snapshot()).count(), Some(5));
+}
 
+#[test]
+#[should_panic(expected = "Cannot get snapshot at height 2")]
+fn test_snapshot_at_future_height() {
+    let (mut testkit, _) = init_testkit();
     testkit.create_block();
-    let counter: u64 = api.get_private(ApiKind::Service("counter"), "count");
-    assert_eq!(counter, 0);
+    testkit.snapshot_at(Height(2));
+}
+
+#[test]
+fn test_last_block_changes() {
+    use exonum::storage::StorageValue;
+
+    let (mut testkit, _) = init_testkit();
+    let service_changes = |testkit: &TestKit| -> Vec<StorageChange> {
+        testkit
+            .last_block_changes()
+            .into_iter()
+            .filter(|change| !change.index.starts_with("core."))
+            .collect()
+    };
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 5, &key));
+    assert_eq!(
+        service_changes(&testkit),
+        vec![
+            StorageChange {
+                index: "counter.count".to_owned(),
+                key: Vec::new(),
+                old: None,
+                new: Some(5_u64.into_bytes()),
+            },
+        ]
+    );
+
+    testkit.create_block();
+    assert_eq!(service_changes(&testkit), vec![]);
+    assert!(!testkit.last_block_changes().is_empty());
+
+    testkit.create_block_with_transaction(TxIncrement::new(&pubkey, 3, &key));
+    let changes = service_changes(&testkit);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].old, Some(5_u64.into_bytes()));
+    assert_eq!(changes[0].new, Some(8_u64.into_bytes()));
+}
+
+#[test]
+#[should_panic(expected = "Cannot get changes of block 0")]
+fn test_last_block_changes_at_genesis() {
+    let (testkit, _) = init_testkit();
+    testkit.last_block_changes();
 }
 
 #[test]
@@ -351,6 +2120,46 @@ fn test_snapshot_comparison_panic() {
         .assert("Counter has increased", |&old, &new| new == old + tx.by());
 }
 
+#[test]
+fn test_testkit_explorer() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_validators(4)
+        .with_service(CounterService)
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx_a = TxIncrement::new(&pubkey, 5, &key);
+    let tx_b = TxIncrement::new(&pubkey, 3, &key);
+    testkit.create_block();
+    testkit.create_block_with_transactions(txvec![tx_a.clone(), tx_b.clone()]);
+
+    let explorer = testkit.explorer();
+    let block = explorer.block(Height(2)).unwrap();
+    assert_eq!(block.tx_count(), 2);
+    assert_eq!(block.hash(), testkit.last_block_hash());
+    assert!(explorer.block(Height(3)).is_none());
+
+    let block = explorer.block_with_txs(Height(2)).unwrap();
+    assert_eq!(block.height(), Height(2));
+    assert_eq!(block.len(), 2);
+    assert_eq!(block.transactions[0].hash(), tx_a.hash());
+    assert_eq!(block.transactions[1].hash(), tx_b.hash());
+    assert_eq!(block.precommits.len(), 4);
+    assert!(explorer.block_with_txs(Height(1)).unwrap().is_empty());
+
+    let blocks = explorer.blocks(Height(1)..Height(10));
+    assert_eq!(
+        blocks.iter().map(|block| block.height()).collect::<Vec<_>>(),
+        vec![Height(1), Height(2)]
+    );
+
+    let tx = explorer.transaction(&tx_b.hash()).unwrap();
+    assert_eq!(tx.location.block_height(), Height(2));
+    assert_eq!(tx.location.position_in_block(), 1);
+    assert_eq!(tx.content.hash(), tx_b.hash());
+    let unknown_tx = TxIncrement::new(&pubkey, 1, &key);
+    assert!(explorer.transaction(&unknown_tx.hash()).is_none());
+}
+
 #[test]
 fn test_explorer_blocks() {
     use exonum::blockchain::Block;
@@ -563,3 +2372,91 @@ fn test_system_transaction() {
         panic!("Transaction should be committed");
     }
 }
+
+#[test]
+fn test_scenario() {
+    use exonum_testkit::scenario::{Scenario, ScenarioRunner};
+
+    let runner = ScenarioRunner::new().with_transaction("increment", |params| {
+        let (pubkey, key) = crypto::gen_keypair();
+        Box::new(TxIncrement::new(&pubkey, params["by"].as_u64().unwrap(), &key))
+    });
+    let scenario = Scenario::from_json(
+        r#"[
+            {"create_block": [
+                {"tx": "increment", "params": {"by": 5}},
+                {"tx": "increment", "params": {"by": 3}}
+            ]},
+            {"assert_height": 1},
+            {"assert_api": {"path": "services/counter/count", "equals": 8}},
+            {"create_block": []},
+            {"rollback": 2},
+            {"assert_height": 0},
+            {"assert_api": {"path": "services/counter/count", "equals": 0}}
+        ]"#,
+    );
+
+    let (mut testkit, _) = init_testkit();
+    runner.run(&scenario, &mut testkit);
+    assert_eq!(testkit.height(), Height(0));
+}
+
+#[test]
+#[should_panic(expected = "Step 2 of scenario failed")]
+fn test_scenario_failed_assertion() {
+    use exonum_testkit::scenario::{Scenario, ScenarioRunner};
+
+    let scenario = Scenario::from_json(
+        r#"[
+            {"create_block": []},
+            {"assert_api": {"path": "services/counter/count", "equals": 1}}
+        ]"#,
+    );
+    let (mut testkit, _) = init_testkit();
+    ScenarioRunner::new().run(&scenario, &mut testkit);
+}
+
+#[test]
+fn test_replay_with_testkit_seed() {
+    let mut testkit = TestKitBuilder::validator().with_validators(3).create();
+    let mut replay = TestKitBuilder::validator()
+        .with_validators(3)
+        .with_seed(testkit.seed())
+        .create();
+    assert_eq!(testkit.network().validators(), replay.network().validators());
+    assert_eq!(testkit.gen_keypair(), replay.gen_keypair());
+}
+
+#[test]
+fn test_events() {
+    let (mut testkit, api) = init_testkit();
+    let events = testkit.events();
+
+    let tx = inc_count(&api, 5);
+    testkit.create_block();
+    let block_hash = testkit.explorer().block(Height(1)).unwrap().hash();
+    testkit.rollback(1);
+
+    let events: Vec<_> = events.try_iter().collect();
+    assert_eq!(
+        events,
+        vec![
+            TestKitEvent::TransactionAdded { hash: tx.hash() },
+            TestKitEvent::BlockCommitted {
+                height: Height(1),
+                block_hash,
+                tx_hashes: vec![tx.hash()],
+            },
+            TestKitEvent::RolledBack { height: Height(0) },
+        ]
+    );
+}
+
+#[test]
+fn test_events_after_drop() {
+    let (mut testkit, _) = init_testkit();
+    drop(testkit.events());
+    let events = testkit.events();
+    testkit.create_block();
+    assert_eq!(events.try_iter().count(), 1);
+}
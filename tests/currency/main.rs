@@ -21,15 +21,27 @@
 extern crate exonum;
 #[macro_use]
 extern crate exonum_testkit;
+extern crate iron;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 
 use std::collections::BTreeSet;
 use std::iter::FromIterator;
+use std::sync::{Arc, Mutex};
 
+use exonum::blockchain::Transaction;
 use exonum::crypto::{self, PublicKey, SecretKey};
+use exonum::helpers::Height;
 use exonum::messages::Message;
-use exonum_testkit::{ApiKind, ComparableSnapshot, TestKit, TestKitApi, TestKitBuilder};
+use exonum::storage::Snapshot;
+use exonum_testkit::{ApiKind, ApiMethod, BodyMatcher, ComparableSnapshot, TestKit, TestKitApi,
+                     TestKitBuilder, TxFactory};
+use exonum_testkit::interleavings::Interleavings;
+use exonum_testkit::replay::TransactionLog;
+use exonum_testkit::workload::Workload;
+use iron::status::Status;
 
 mod cryptocurrency;
 use cryptocurrency::{CurrencySchema, CurrencyService, TransactionResponse, TxCreateWallet,
@@ -38,7 +50,7 @@ use cryptocurrency::{CurrencySchema, CurrencyService, TransactionResponse, TxCre
 fn init_testkit() -> TestKit {
     TestKitBuilder::validator()
         .with_validators(4)
-        .with_service(CurrencyService)
+        .with_service_factory(|| Box::new(CurrencyService))
         .create()
 }
 
@@ -77,6 +89,22 @@ fn get_all_wallets(api: &TestKitApi) -> Vec<Wallet> {
     api.get(ApiKind::Service("cryptocurrency"), "v1/wallets")
 }
 
+service_api_client! {
+    /// Typed client for the cryptocurrency service.
+    pub struct CurrencyClient("cryptocurrency");
+    /// Provides access to `CurrencyClient`.
+    pub trait CurrencyApi { fn cryptocurrency(); }
+
+    /// Returns the wallet with the given public key.
+    get fn wallet(pub_key: &PublicKey) -> Wallet = "v1/wallet/{}";
+    /// Returns all wallets.
+    get fn wallets() -> Vec<Wallet> = "v1/wallets";
+    /// Sends a transaction creating a wallet.
+    post fn create_wallet(tx: &TxCreateWallet) -> TransactionResponse = "v1/wallets/transaction";
+    /// Sends a transfer transaction.
+    post fn transfer(tx: &TxTransfer) -> TransactionResponse = "v1/wallets/transaction";
+}
+
 #[test]
 fn test_create_wallet() {
     let mut testkit = init_testkit();
@@ -92,6 +120,32 @@ fn test_create_wallet() {
     assert_eq!(wallet.balance(), 100);
 }
 
+#[test]
+fn test_service_api_client() {
+    use exonum_testkit::ServiceApiClient;
+
+    let mut testkit = init_testkit();
+    let api = testkit.api();
+    let (pubkey, key) = crypto::gen_keypair();
+    let (other_pubkey, other_key) = crypto::gen_keypair();
+
+    let tx = TxCreateWallet::new(&pubkey, "Alice", &key);
+    assert_eq!(api.cryptocurrency().create_wallet(&tx).tx_hash, tx.hash());
+    let tx = TxCreateWallet::new(&other_pubkey, "Bob", &other_key);
+    assert_eq!(api.cryptocurrency().create_wallet(&tx).tx_hash, tx.hash());
+    testkit.create_block();
+
+    let tx = TxTransfer::new(&pubkey, &other_pubkey, 10, 0, &key);
+    assert_eq!(api.cryptocurrency().transfer(&tx).tx_hash, tx.hash());
+    testkit.create_block();
+
+    let client = api.client::<CurrencyClient>();
+    assert_eq!(CurrencyClient::SERVICE_NAME, "cryptocurrency");
+    assert_eq!(client.wallet(&pubkey).balance(), 90);
+    assert_eq!(client.wallet(&other_pubkey).balance(), 110);
+    assert_eq!(client.wallets().len(), 2);
+}
+
 #[test]
 fn test_transfer() {
     let mut testkit = init_testkit();
@@ -243,6 +297,112 @@ fn test_transfer_overcharge() {
     assert_eq!(wallet.balance(), 100);
 }
 
+#[test]
+fn test_block_result() {
+    use exonum_testkit::ExecutionStatus;
+
+    let mut testkit = init_testkit();
+    let (alice_pubkey, alice_key) = crypto::gen_keypair();
+    let (bob_pubkey, bob_key) = crypto::gen_keypair();
+    let tx_alice = TxCreateWallet::new(&alice_pubkey, "Alice", &alice_key);
+    let tx_bob = TxCreateWallet::new(&bob_pubkey, "Bob", &bob_key);
+    let tx_overcharge = TxTransfer::new(&alice_pubkey, &bob_pubkey, 110, 0, &alice_key);
+    let tx_transfer = TxTransfer::new(&alice_pubkey, &bob_pubkey, 10, 0, &alice_key);
+
+    let result = testkit.create_block_with_transactions(txvec![
+        tx_alice.clone(),
+        tx_bob.clone(),
+        tx_overcharge.clone(),
+        tx_transfer.clone(),
+    ]);
+    assert_eq!(result.status(&tx_alice.hash()), Some(&ExecutionStatus::Applied));
+    assert_eq!(result.status(&tx_bob.hash()), Some(&ExecutionStatus::Applied));
+    // The service silently ignores the transfer exceeding the balance.
+    assert_eq!(result.status(&tx_overcharge.hash()), Some(&ExecutionStatus::NoOp));
+    assert_eq!(result.status(&tx_transfer.hash()), Some(&ExecutionStatus::Applied));
+
+    // A repeated wallet creation does nothing.
+    let tx_alice_again = TxCreateWallet::new(&alice_pubkey, "Alice Jr.", &alice_key);
+    let result = testkit.create_block_with_transaction(tx_alice_again.clone());
+    assert_eq!(result.height(), Height(2));
+    assert_eq!(result.transactions().len(), 1);
+    assert_eq!(result.transactions()[0].status(), &ExecutionStatus::NoOp);
+    assert_eq!(result.status(&tx_alice.hash()), None);
+
+    let result = testkit.create_block();
+    assert!(result.transactions().is_empty());
+}
+
+#[test]
+fn test_storage_stats() {
+    const WALLETS: &str = "cryptocurrency.wallets";
+
+    let mut testkit = init_testkit();
+    let keys: Vec<_> = (0..10).map(|_| crypto::gen_keypair()).collect();
+    testkit.create_block_with_transactions(keys.iter().map(|&(ref pubkey, ref key)| {
+        Box::new(TxCreateWallet::new(pubkey, "Wallet", key)) as Box<Transaction>
+    }));
+    let stats = testkit.storage_stats();
+    assert!(stats.index(WALLETS).entries >= 10);
+    assert!(stats.total_bytes() > stats.index(WALLETS).total_bytes());
+    assert_eq!(testkit.storage_stats_at(Height(0)).index(WALLETS).entries, 0);
+    assert_eq!(testkit.storage_stats_at(Height(1)), stats);
+
+    // Transfers change wallet balances, but do not grow the index.
+    testkit.create_block_with_transactions(keys.windows(2).map(|pair| {
+        let (ref from, ref key) = pair[0];
+        Box::new(TxTransfer::new(from, &pair[1].0, 10, 0, key)) as Box<Transaction>
+    }));
+    let growth = testkit.storage_stats().growth_since(&stats);
+    assert_eq!(growth.get(WALLETS), None);
+    assert!(growth.keys().all(|index| index.starts_with("core.")));
+}
+
+#[test]
+fn test_tx_factory() {
+    let factory = TxFactory::new()
+        .with_transaction("create_wallet", |author, params| {
+            let name = params["name"].as_str().unwrap();
+            Box::new(TxCreateWallet::new(author.public_key(), name, author.secret_key()))
+        })
+        .with_transaction("transfer", |author, params| {
+            let to: PublicKey = serde_json::from_value(params["to"].clone()).unwrap();
+            let amount = params["amount"].as_u64().unwrap();
+            Box::new(TxTransfer::new(author.public_key(), &to, amount, 0, author.secret_key()))
+        });
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(CurrencyService)
+        .with_tx_factory(factory)
+        .create();
+
+    let alice = testkit.account("alice");
+    let bob = testkit.account("bob");
+    assert_eq!(alice.name(), "alice");
+    assert_eq!(testkit.account("alice").public_key(), alice.public_key());
+    assert_ne!(alice.public_key(), bob.public_key());
+
+    let txs = vec![
+        testkit.tx("create_wallet", &alice, json!({ "name": "Alice" })),
+        testkit.tx("create_wallet", &bob, json!({ "name": "Bob" })),
+    ];
+    testkit.create_block_with_transactions(txs);
+    let tx = testkit.tx("transfer", &alice, json!({ "to": bob, "amount": 10 }));
+    testkit.create_block_with_transactions(vec![tx]);
+
+    let snapshot = testkit.snapshot();
+    let schema = CurrencySchema::new(&snapshot);
+    assert_eq!(schema.wallet(alice.public_key()).unwrap().balance(), 90);
+    assert_eq!(schema.wallet(bob.public_key()).unwrap().balance(), 110);
+}
+
+#[test]
+#[should_panic(expected = "Transaction `transfer` is not registered")]
+fn test_tx_factory_unknown_transaction() {
+    let mut testkit = init_testkit();
+    let alice = testkit.account("alice");
+    testkit.tx("transfer", &alice, json!({}));
+}
+
 #[test]
 fn test_transfers_in_single_block() {
     let mut testkit = init_testkit();
@@ -300,6 +460,44 @@ fn test_transfers_in_single_block() {
     );
 }
 
+#[test]
+fn test_probe_all_create_and_transfer() {
+    let mut testkit = init_testkit();
+    let api = testkit.api();
+
+    let (tx_alice, key_alice) = create_wallet(&api, "Alice");
+    let (pubkey_bob, key_bob) = crypto::gen_keypair();
+    let tx_bob = TxCreateWallet::new(&pubkey_bob, "Bob", &key_bob);
+    let tx_transfer = TxTransfer::new(
+        tx_alice.pub_key(),
+        tx_bob.pub_key(),
+        30, // amount
+        0, // seed
+        &key_alice,
+    );
+
+    // Create both wallets and transfer funds within a single hypothetical block.
+    let comp = testkit
+        .probe_all(txvec![
+            tx_alice.clone(),
+            tx_bob.clone(),
+            tx_transfer.clone(),
+        ])
+        .compare(testkit.snapshot());
+    let comp = comp.map(CurrencySchema::new);
+    comp.map(|s| s.wallet(tx_alice.pub_key()).map(|w| w.balance()))
+        .assert_before("No Alice's wallet", Option::is_none)
+        .assert_after("Alice's balance decreases", |&b| b == Some(70));
+    comp.map(|s| s.wallets().values().fold(0, |acc, w| acc + w.balance()))
+        .assert_after("Total balance is preserved", |&total| total == 200);
+
+    // Alice's transaction sent via API should remain in the mempool after probing.
+    assert!(testkit.mempool().contains_key(&tx_alice.hash()));
+    testkit.create_block();
+    let wallet = get_wallet(&api, tx_alice.pub_key());
+    assert_eq!(wallet.balance(), 100);
+}
+
 #[test]
 fn test_malformed_wallet_request() {
     let testkit = TestKitBuilder::validator()
@@ -326,6 +524,51 @@ fn test_unknown_wallet_request() {
     assert_eq!(info, "Wallet not found".to_string());
 }
 
+#[test]
+fn test_wallet_request_errors() {
+    let testkit = init_testkit();
+    let api = testkit.api();
+    let kind = || ApiKind::Service("cryptocurrency");
+
+    api.assert_error(
+        kind(),
+        ApiMethod::Get,
+        "v1/wallet/c0ffee",
+        Status::BadRequest,
+        BodyMatcher::contains("Invalid request param"),
+    );
+    let (pubkey, _) = crypto::gen_keypair();
+    api.assert_error(
+        kind(),
+        ApiMethod::Get,
+        &format!("v1/wallet/{}", pubkey.to_string()),
+        Status::NotFound,
+        BodyMatcher::Json(json!("Wallet not found")),
+    );
+    let resp = api.assert_error(
+        kind(),
+        ApiMethod::Get,
+        "v1/unknown",
+        Status::NotFound,
+        BodyMatcher::Any,
+    );
+    assert_eq!(resp.status(), Status::NotFound);
+}
+
+#[test]
+#[should_panic(expected = "Unexpected response status for GET")]
+fn test_wallet_request_unexpected_error() {
+    let testkit = init_testkit();
+    let api = testkit.api();
+    api.assert_error(
+        ApiKind::Service("cryptocurrency"),
+        ApiMethod::Get,
+        "v1/wallets",
+        Status::NotFound,
+        BodyMatcher::Any,
+    );
+}
+
 #[test]
 fn test_nonverified_transaction_in_create_block() {
     let mut testkit = init_testkit();
@@ -369,3 +612,190 @@ fn test_nonsigned_transaction_in_create_block() {
     let wallet = get_wallet(&api, tx_bob.pub_key());
     assert_eq!(wallet.balance(), 110);
 }
+
+#[test]
+fn test_workload() {
+    let mut testkit = init_testkit();
+    let keys = Arc::new(Mutex::new(Vec::new()));
+    let total_balance = |snapshot: &Snapshot| -> u64 {
+        CurrencySchema::new(snapshot)
+            .wallets()
+            .values()
+            .map(|wallet| wallet.balance())
+            .sum()
+    };
+
+    let mut workload = Workload::new()
+        .with_blocks(10)
+        .with_txs_per_block(20)
+        .with_seed(7)
+        .with_generator("create_wallet", 3, {
+            let keys = Arc::clone(&keys);
+            move |rng, _| {
+                let (pubkey, key) = crypto::gen_keypair();
+                keys.lock().unwrap().push((pubkey, key.clone()));
+                let name = format!("wallet #{}", rng.next_u64());
+                Some(Box::new(TxCreateWallet::new(&pubkey, &name, &key)) as Box<Transaction>)
+            }
+        })
+        .with_generator("transfer", 7, {
+            let keys = Arc::clone(&keys);
+            move |rng, snapshot| {
+                let schema = CurrencySchema::new(snapshot);
+                let keys: Vec<_> = keys.lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|&&(ref pubkey, _)| schema.wallet(pubkey).is_some())
+                    .cloned()
+                    .collect();
+                if keys.len() < 2 {
+                    return None;
+                }
+                let (ref from, ref key) = keys[rng.next_below(keys.len())];
+                let (ref to, _) = keys[rng.next_below(keys.len())];
+                let amount = rng.next_below(150) as u64;
+                let tx = TxTransfer::new(from, to, amount, rng.next_u64(), key);
+                Some(Box::new(tx) as Box<Transaction>)
+            }
+        })
+        .with_invariant("total balance", move |snapshot| {
+            let wallets = CurrencySchema::new(snapshot).wallets().iter().count() as u64;
+            total_balance(snapshot) == 100 * wallets
+        })
+        .with_invariant("no wallets", |snapshot| {
+            CurrencySchema::new(snapshot).wallets().iter().next().is_none()
+        });
+
+    let report = workload.run(&mut testkit);
+    assert_eq!(testkit.height(), Height(10));
+    assert_eq!(report.blocks(), 10);
+    assert!(report.generated()["create_wallet"] > 0);
+    assert!(report.generated()["transfer"] > 0);
+    assert!(report.applied() > 0);
+    assert_eq!(report.panicked(), 0);
+    // Only the deliberately broken invariant is violated.
+    assert!(!report.violations().is_empty());
+    assert!(report.violations().iter().all(|violation| violation.name == "no wallets"));
+    assert!(!report.is_successful());
+}
+
+#[test]
+fn test_workload_minimization() {
+    let mut testkit = init_testkit();
+    let keys = Arc::new(Mutex::new(Vec::new()));
+    let mut workload = Workload::new()
+        .with_blocks(8)
+        .with_txs_per_block(10)
+        .with_seed(11)
+        .with_minimization()
+        .with_generator("create_wallet", 1, {
+            let keys = Arc::clone(&keys);
+            move |_, _| {
+                let (pubkey, key) = crypto::gen_keypair();
+                keys.lock().unwrap().push((pubkey, key.clone()));
+                Some(Box::new(TxCreateWallet::new(&pubkey, "Alice", &key)) as Box<Transaction>)
+            }
+        })
+        .with_generator("transfer", 3, move |rng, _| {
+            let keys = keys.lock().unwrap();
+            if keys.len() < 2 {
+                return None;
+            }
+            let (ref from, ref key) = keys[rng.next_below(keys.len())];
+            let (ref to, _) = keys[rng.next_below(keys.len())];
+            let tx = TxTransfer::new(from, to, 10, rng.next_u64(), key);
+            Some(Box::new(tx) as Box<Transaction>)
+        })
+        .with_invariant("fewer than 3 wallets", |snapshot| {
+            CurrencySchema::new(snapshot).wallets().iter().count() < 3
+        });
+
+    let report = workload.run(&mut testkit);
+    // Minimization does not affect the testkit.
+    assert_eq!(testkit.height(), Height(8));
+    assert!(!report.is_successful());
+
+    let reproducer = report.reproducer().unwrap();
+    let tx_count: usize = reproducer
+        .blocks()
+        .iter()
+        .map(|block| block.transactions.len())
+        .sum();
+    assert_eq!(tx_count, 3);
+    assert!(reproducer.blocks().iter().all(|block| !block.transactions.is_empty()));
+
+    let mut replayed = init_testkit();
+    assert!(reproducer.replay(&mut replayed).is_ok());
+    let snapshot = replayed.snapshot();
+    assert_eq!(CurrencySchema::new(&snapshot).wallets().iter().count(), 3);
+
+    // The reproducer can be saved and read back.
+    let reproducer = TransactionLog::from_reader(reproducer.to_string().as_bytes());
+    assert_eq!(reproducer.blocks().len(), report.reproducer().unwrap().blocks().len());
+}
+
+#[test]
+fn test_workload_without_failures_has_no_reproducer() {
+    let mut testkit = init_testkit();
+    let report = Workload::new()
+        .with_blocks(2)
+        .with_minimization()
+        .with_generator("create_wallet", 1, |_, _| {
+            let (pubkey, key) = crypto::gen_keypair();
+            Some(Box::new(TxCreateWallet::new(&pubkey, "Bob", &key)) as Box<Transaction>)
+        })
+        .run(&mut testkit);
+    assert!(report.is_successful());
+    assert!(report.reproducer().is_none());
+}
+
+#[test]
+fn test_interleavings() {
+    let mut testkit = init_testkit();
+    let (alice_pubkey, alice_key) = crypto::gen_keypair();
+    let (bob_pubkey, bob_key) = crypto::gen_keypair();
+    let tx_transfer = TxTransfer::new(&alice_pubkey, &bob_pubkey, 50, 0, &alice_key);
+    testkit.add_invariant("no transfers to Bob", move |snapshot| {
+        CurrencySchema::new(snapshot)
+            .wallet(&bob_pubkey)
+            .map_or(true, |wallet| wallet.balance() == 100)
+    });
+
+    let interleavings = Interleavings::new(txvec![
+        TxCreateWallet::new(&alice_pubkey, "Alice", &alice_key),
+        TxCreateWallet::new(&bob_pubkey, "Bob", &bob_key),
+        tx_transfer.clone(),
+    ]);
+    assert_eq!(interleavings.total_schedules(), 24);
+    let report = interleavings.run(&mut testkit);
+    assert_eq!(testkit.height(), Height(0));
+    assert_eq!(report.schedules(), 24);
+    assert_eq!(report.coverage(), 1.0);
+    // The transfer succeeds only after both wallets are created.
+    assert_eq!(report.failures().len(), 8);
+    for failure in report.failures() {
+        assert_eq!(failure.blocks.last().unwrap().last(), Some(&tx_transfer.hash()));
+        assert!(failure.message.contains("no transfers to Bob"));
+    }
+    assert!(report.distinct_states() >= 2);
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn test_interleavings_sampling() {
+    let mut testkit = init_testkit();
+    let txs = (0..4).map(|i| {
+        let (pubkey, key) = crypto::gen_keypair();
+        Box::new(TxCreateWallet::new(&pubkey, &format!("wallet #{}", i), &key)) as Box<Transaction>
+    });
+    let report = Interleavings::new(txs)
+        .with_max_blocks(2)
+        .with_budget(10)
+        .with_seed(3)
+        .run(&mut testkit);
+    assert_eq!(report.total_schedules(), 96);
+    assert_eq!(report.schedules(), 10);
+    assert!(report.coverage() > 0.5);
+    assert_eq!(report.distinct_states(), 1);
+    assert!(report.is_ok());
+}
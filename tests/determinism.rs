@@ -0,0 +1,101 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the determinism check performed by the testkit.
+
+#[macro_use]
+extern crate exonum;
+#[macro_use]
+extern crate exonum_testkit;
+
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot};
+use exonum_testkit::TestKitBuilder;
+
+const SERVICE_ID: u16 = 1;
+const TX_STORE_ID: u16 = 1;
+
+static NEXT_VALUE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+message! {
+    struct TxStore {
+        const TYPE = SERVICE_ID;
+        const ID = TX_STORE_ID;
+
+        author: &PublicKey,
+        deterministic: bool,
+    }
+}
+
+impl Transaction for TxStore {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let value = if self.deterministic {
+            0
+        } else {
+            NEXT_VALUE.fetch_add(1, Ordering::SeqCst) as u64
+        };
+        Entry::new("store.value", fork).set(value);
+    }
+}
+
+struct StoreService;
+
+impl Service for StoreService {
+    fn service_name(&self) -> &'static str {
+        "store"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxStore::from_raw(raw)?))
+    }
+}
+
+#[test]
+fn test_determinism_check() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(StoreService)
+        .with_determinism_check()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxStore::new(&pubkey, true, &key));
+    testkit.create_block();
+}
+
+#[test]
+#[should_panic(expected = "Non-deterministic execution of block 1")]
+fn test_determinism_check_failure() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(StoreService)
+        .with_determinism_check()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![TxStore::new(&pubkey, false, &key)]);
+}
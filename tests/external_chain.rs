@@ -0,0 +1,120 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for services anchoring blocks to the mocked external blockchain.
+
+extern crate exonum;
+extern crate exonum_testkit;
+
+use std::sync::{Arc, Mutex};
+
+use exonum::blockchain::{Schema as CoreSchema, Service, ServiceContext, Transaction};
+use exonum::crypto::Hash;
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::RawTransaction;
+use exonum::storage::Snapshot;
+use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum_testkit::external_chain::{ExternalChain, ExternalChainError, ExternalChainMock};
+
+/// Service anchoring hashes of committed blocks to the external blockchain.
+struct AnchoringService {
+    chain: Arc<ExternalChain>,
+    anchors: Arc<Mutex<Vec<Hash>>>,
+}
+
+impl Service for AnchoringService {
+    fn service_name(&self) -> &'static str {
+        "anchoring"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        3
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Err(encoding::Error::IncorrectMessageType { message_type: raw.message_type() })
+    }
+
+    fn handle_commit(&self, context: &ServiceContext) {
+        let block_hash = CoreSchema::new(context.snapshot()).last_block().unwrap().hash();
+        // Anchoring is skipped if the external node is unavailable.
+        let result = self.chain.fee_estimate().and_then(|fee| {
+            self.chain.send_transaction(block_hash.as_ref(), fee)
+        });
+        match result {
+            Ok(txid) => self.anchors.lock().unwrap().push(txid),
+            Err(ExternalChainError::Unavailable) => {}
+            Err(e) => panic!("Cannot anchor block: {:?}", e),
+        }
+    }
+}
+
+fn init_testkit(chain: &ExternalChainMock) -> (TestKit, Arc<Mutex<Vec<Hash>>>) {
+    let anchors = Arc::new(Mutex::new(Vec::new()));
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(AnchoringService {
+            chain: Arc::new(chain.clone()),
+            anchors: Arc::clone(&anchors),
+        })
+        .create();
+    let chain = chain.clone();
+    testkit.on_after_commit(move |_, _| {
+        chain.mine_block();
+    });
+    (testkit, anchors)
+}
+
+#[test]
+fn test_anchoring_confirmations() {
+    let chain = ExternalChainMock::new();
+    let (mut testkit, anchors) = init_testkit(&chain);
+    testkit.create_blocks_until(Height(3));
+
+    let anchors = anchors.lock().unwrap().clone();
+    assert_eq!(anchors.len(), 3);
+    assert_eq!(chain.height(), Ok(3));
+    assert_eq!(chain.confirmations(&anchors[0]), Ok(Some(3)));
+    assert_eq!(chain.confirmations(&anchors[2]), Ok(Some(1)));
+    assert_eq!(
+        chain.transaction(&anchors[2]),
+        Some(testkit.last_block_hash().as_ref().to_vec())
+    );
+
+    chain.reorg(2);
+    assert_eq!(chain.mempool(), vec![anchors[1], anchors[2]]);
+    assert_eq!(chain.confirmations(&anchors[0]), Ok(Some(1)));
+    chain.mine_block();
+    assert_eq!(chain.confirmations(&anchors[2]), Ok(Some(1)));
+}
+
+#[test]
+fn test_anchoring_with_unavailable_chain() {
+    let chain = ExternalChainMock::new();
+    let (mut testkit, anchors) = init_testkit(&chain);
+    chain.set_available(false);
+    testkit.create_block();
+    assert!(anchors.lock().unwrap().is_empty());
+
+    chain.set_available(true);
+    chain.set_fee_estimate(10);
+    testkit.create_block();
+    let anchors = anchors.lock().unwrap().clone();
+    assert_eq!(anchors.len(), 1);
+    assert_eq!(chain.confirmations(&anchors[0]), Ok(Some(1)));
+}
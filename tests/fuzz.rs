@@ -0,0 +1,164 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the fuzzing of transaction deserialization and execution.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::messages::{Message, RawMessage, RawTransaction};
+use exonum::storage::{Fork, MapIndex, Snapshot};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum_testkit::fuzz::Fuzzer;
+
+const SERVICE_ID: u16 = 1;
+const TX_NOTE_ID: u16 = 1;
+
+message! {
+    struct TxNote {
+        const TYPE = SERVICE_ID;
+        const ID = TX_NOTE_ID;
+
+        author: &PublicKey,
+        priority: u64,
+        text: &str,
+    }
+}
+
+impl Transaction for TxNote {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut notes = MapIndex::new("notes.texts", fork);
+        notes.put(self.author(), self.text().to_owned());
+    }
+}
+
+// The same message as `TxNote`, the execution of which panics on unexpected priorities.
+message! {
+    struct TxRiskyNote {
+        const TYPE = SERVICE_ID;
+        const ID = TX_NOTE_ID;
+
+        author: &PublicKey,
+        priority: u64,
+        text: &str,
+    }
+}
+
+impl Transaction for TxRiskyNote {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, _: &mut Fork) {
+        assert!(self.priority() <= 1_000, "Unexpected priority");
+    }
+}
+
+/// Flaws injected into the service.
+#[derive(Clone, Copy, PartialEq)]
+enum Flaw {
+    None,
+    UncheckedParsing,
+    UncheckedExecution,
+}
+
+struct NotesService {
+    flaw: Flaw,
+}
+
+impl Service for NotesService {
+    fn service_name(&self) -> &'static str {
+        "notes"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        if self.flaw == Flaw::UncheckedParsing {
+            // Reads the priority without checking the message length.
+            let priority_offset = 10 + 32;
+            assert!(raw.len() >= priority_offset + 8, "Index out of bounds");
+        }
+        if self.flaw == Flaw::UncheckedExecution {
+            Ok(Box::new(TxRiskyNote::from_raw(raw)?))
+        } else {
+            Ok(Box::new(TxNote::from_raw(raw)?))
+        }
+    }
+}
+
+fn create_testkit(flaw: Flaw) -> TestKit {
+    TestKitBuilder::validator()
+        .with_service(NotesService { flaw })
+        .create()
+}
+
+fn seeds() -> (Vec<RawMessage>, crypto::SecretKey) {
+    let (pubkey, key) = crypto::gen_keypair();
+    let seeds = vec![
+        TxNote::new(&pubkey, 1, "Hello", &key).raw().clone(),
+        TxNote::new(&pubkey, 2, "", &key).raw().clone(),
+    ];
+    (seeds, key)
+}
+
+#[test]
+fn test_fuzzing() {
+    let mut testkit = create_testkit(Flaw::None);
+    let (seeds, key) = seeds();
+
+    let report = Fuzzer::new(seeds.clone()).with_iterations(500).run(&mut testkit);
+    assert_eq!(report.messages(), 500);
+    assert!(report.parsed() > 0);
+    assert_eq!(report.executed(), 0);
+
+    let report = Fuzzer::new(seeds)
+        .with_iterations(500)
+        .with_secret_key(key)
+        .run(&mut testkit);
+    assert!(report.executed() > 0);
+    assert!(report.executed() <= report.parsed());
+    // Executed transactions are rolled back.
+    assert_eq!(testkit.height().0, 0);
+}
+
+#[test]
+#[should_panic(expected = "Service panicked on parsing mutated message")]
+fn test_fuzzing_parsing_panic() {
+    let mut testkit = create_testkit(Flaw::UncheckedParsing);
+    let (seeds, _) = seeds();
+    Fuzzer::new(seeds).run(&mut testkit);
+}
+
+#[test]
+#[should_panic(expected = "Service panicked on executing mutated message")]
+fn test_fuzzing_execution_panic() {
+    let mut testkit = create_testkit(Flaw::UncheckedExecution);
+    let (seeds, key) = seeds();
+    Fuzzer::new(seeds).with_secret_key(key).run(&mut testkit);
+}
@@ -75,6 +75,22 @@ fn test_inflation() {
     assert_eq!(get_balance(&api, tx.pub_key()), 10);
 }
 
+#[test]
+fn test_inflation_fast_forward() {
+    let mut testkit = init_testkit();
+    let api = testkit.api();
+    let (tx, _) = create_wallet(&api, "Alice");
+
+    testkit.create_block();
+    testkit.fast_forward_to(Height(10_000));
+    assert_eq!(testkit.height(), Height(10_000));
+    assert_eq!(get_balance(&api, tx.pub_key()), 10_000);
+
+    // Check that blocks are created as usual after fast-forwarding
+    testkit.create_block();
+    assert_eq!(get_balance(&api, tx.pub_key()), 10_001);
+}
+
 #[test]
 fn test_transfer_scenarios() {
     let mut testkit = init_testkit();
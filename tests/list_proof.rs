@@ -0,0 +1,130 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for proofs of elements in service lists.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{Hash, Signature};
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::RawTransaction;
+use exonum::storage::{Fork, ProofListIndex, Snapshot};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum_testkit::proof::ProofError;
+
+const SERVICE_ID: u16 = 1;
+const TX_APPEND_ID: u16 = 1;
+const ENTRIES: &str = "history.entries";
+
+message! {
+    struct TxAppend {
+        const TYPE = SERVICE_ID;
+        const ID = TX_APPEND_ID;
+
+        value: u64,
+    }
+}
+
+impl Transaction for TxAppend {
+    fn verify(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        ProofListIndex::new(ENTRIES, fork).push(self.value());
+    }
+}
+
+/// Service appending values to a list, which is the only table in its state hash.
+struct HistoryService;
+
+impl Service for HistoryService {
+    fn service_name(&self) -> &'static str {
+        "history"
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        vec![ProofListIndex::<_, u64>::new(ENTRIES, snapshot).root_hash()]
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxAppend::from_raw(raw)?))
+    }
+}
+
+fn init_testkit() -> TestKit {
+    TestKitBuilder::validator()
+        .with_service(HistoryService)
+        .create()
+}
+
+fn append(testkit: &mut TestKit, values: &[u64]) {
+    let txs = values.iter().map(|&value| {
+        Box::new(TxAppend::new_with_signature(value, &Signature::zero())) as Box<Transaction>
+    });
+    testkit.create_block_with_transactions(txs);
+}
+
+#[test]
+fn test_list_proof() {
+    let mut testkit = init_testkit();
+    append(&mut testkit, &[10, 20, 30]);
+    append(&mut testkit, &[40, 50]);
+
+    let snapshot = testkit.snapshot();
+    let list = ProofListIndex::<_, u64>::new(ENTRIES, &snapshot);
+    let root_hash = list.root_hash();
+    for (index, value) in list.iter().enumerate() {
+        let proof = testkit.list_proof::<u64>(SERVICE_ID, 0, ENTRIES, index as u64);
+        assert_eq!(proof.to_table().block().height(), Height(2));
+        assert_eq!(proof.to_table().validate(&root_hash), Ok(()));
+        let proven = proof.to_value().validate(root_hash, list.len()).unwrap();
+        assert_eq!(proven, vec![(index as u64, &value)]);
+        assert_eq!(*proof.assert_valid(), value);
+    }
+
+    // The proof is rooted in the latest block, so it does not match the previous root.
+    append(&mut testkit, &[60]);
+    let proof = testkit.list_proof::<u64>(SERVICE_ID, 0, ENTRIES, 0);
+    assert_eq!(proof.to_table().validate(&root_hash), Err(ProofError::TableHashMismatch));
+    assert_eq!(*proof.assert_valid(), 10);
+
+    // Proof for a table not present in the state hash.
+    let proof = testkit.list_proof::<u64>(SERVICE_ID, 1, ENTRIES, 0);
+    assert_eq!(proof.validate(), Err(ProofError::MissingTable));
+}
+
+#[test]
+#[should_panic(expected = "Index 0 is out of range of list `history.entries` with length 0")]
+fn test_list_proof_empty_list() {
+    let mut testkit = init_testkit();
+    testkit.create_block();
+    testkit.list_proof::<u64>(SERVICE_ID, 0, ENTRIES, 0);
+}
+
+#[test]
+#[should_panic(expected = "Index 2 is out of range of list `history.entries` with length 2")]
+fn test_list_proof_out_of_range() {
+    let mut testkit = init_testkit();
+    append(&mut testkit, &[10, 20]);
+    testkit.list_proof::<u64>(SERVICE_ID, 0, ENTRIES, 2);
+}
@@ -0,0 +1,155 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the capture of log records emitted during block creation.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+#[macro_use]
+extern crate log;
+
+use exonum::blockchain::{Service, ServiceContext, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use log::Level;
+
+const SERVICE_ID: u16 = 1;
+const TX_DEPOSIT_ID: u16 = 1;
+const BALANCE: &str = "logging.balance";
+const MAX_BALANCE: u64 = 100;
+
+message! {
+    struct TxDeposit {
+        const TYPE = SERVICE_ID;
+        const ID = TX_DEPOSIT_ID;
+
+        author: &PublicKey,
+        amount: u64,
+    }
+}
+
+impl Transaction for TxDeposit {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut balance = Entry::new(BALANCE, fork);
+        let value = balance.get().unwrap_or(0) + self.amount();
+        if value > MAX_BALANCE {
+            warn!("Deposit of {} exceeds the maximum balance", self.amount());
+            return;
+        }
+        info!("Balance is now {}", value);
+        balance.set(value);
+    }
+}
+
+struct LoggingService;
+
+impl Service for LoggingService {
+    fn service_name(&self) -> &'static str {
+        "logging"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxDeposit::from_raw(raw)?))
+    }
+
+    fn handle_commit(&self, context: &ServiceContext) {
+        debug!("Committed block {}", context.height().0);
+    }
+}
+
+fn init_testkit() -> TestKit {
+    TestKitBuilder::validator()
+        .with_service(LoggingService)
+        .with_log_capture()
+        .create()
+}
+
+#[test]
+fn test_logs_for_tx() {
+    let mut testkit = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let tx = TxDeposit::new(&pubkey, 60, &key);
+    let other_tx = TxDeposit::new(&pubkey, 150, &key);
+    let result = testkit.create_block_with_transactions(txvec![tx.clone(), other_tx.clone()]);
+
+    let logs = testkit.logs_for_tx(&tx.hash());
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].level, Level::Info);
+    assert_eq!(logs[0].message, "Balance is now 60");
+    assert_eq!(logs[0].height, Height(1));
+    assert_eq!(logs[0].tx_hash, Some(tx.hash()));
+    let tx_result = result
+        .transactions()
+        .iter()
+        .find(|tx_result| *tx_result.hash() == tx.hash())
+        .unwrap();
+    assert_eq!(tx_result.logs(), &[logs[0].clone()]);
+
+    let logs = testkit.logs_for_tx(&other_tx.hash());
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].level, Level::Warn);
+    assert_eq!(logs[0].message, "Deposit of 150 exceeds the maximum balance");
+
+    let (_, other_key) = crypto::gen_keypair();
+    assert!(testkit.logs_for_tx(&TxDeposit::new(&pubkey, 1, &other_key).hash()).is_empty());
+}
+
+#[test]
+fn test_logs_for_block() {
+    let mut testkit = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block();
+    testkit.create_block_with_transaction(TxDeposit::new(&pubkey, 10, &key));
+
+    let messages = |testkit: &TestKit, height| -> Vec<String> {
+        testkit
+            .logs_for_block(height)
+            .into_iter()
+            .map(|record| record.message.clone())
+            .collect()
+    };
+    let block_logs = messages(&testkit, Height(2));
+    assert_eq!(block_logs[0], "Balance is now 10");
+    assert!(block_logs.contains(&"Committed block 2".to_owned()));
+    assert!(!messages(&testkit, Height(1)).contains(&"Balance is now 10".to_owned()));
+
+    testkit.rollback(1);
+    assert!(testkit.logs_for_block(Height(2)).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Log capture is not enabled")]
+fn test_logs_without_capture() {
+    let testkit = TestKitBuilder::validator()
+        .with_service(LoggingService)
+        .create();
+    testkit.logs_for_tx(&Hash::zero());
+}
@@ -0,0 +1,113 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the `#[exonum_testkit::test]` attribute.
+
+#![cfg(feature = "macros")]
+
+#[macro_use]
+extern crate exonum;
+#[macro_use(txvec)]
+extern crate exonum_testkit;
+
+use exonum::api::public::HealthCheckInfo;
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot};
+use exonum_testkit::{ApiKind, TestKit, TestKitApi, TestKitBuilder};
+
+const SERVICE_ID: u16 = 1;
+const TX_ADD_ID: u16 = 1;
+const TOTAL: &str = "sum.total";
+
+message! {
+    struct TxAdd {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ADD_ID;
+
+        author: &PublicKey,
+        amount: u64,
+    }
+}
+
+impl Transaction for TxAdd {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut total = Entry::new(TOTAL, fork);
+        let value = total.get().unwrap_or(0) + self.amount();
+        total.set(value);
+    }
+}
+
+struct SumService;
+
+impl Service for SumService {
+    fn service_name(&self) -> &'static str {
+        "sum"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxAdd::from_raw(raw)?))
+    }
+}
+
+fn total(testkit: &TestKit) -> u64 {
+    Entry::new(TOTAL, &testkit.snapshot()).get().unwrap_or(0)
+}
+
+#[exonum_testkit::test(services(SumService))]
+fn test_services(testkit: &mut TestKit) {
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transactions(txvec![
+        TxAdd::new(&pubkey, 3, &key),
+        TxAdd::new(&pubkey, 4, &key),
+    ]);
+    assert_eq!(testkit.height(), Height(1));
+    assert_eq!(total(testkit), 7);
+    assert_eq!(testkit.network().validators().len(), 1);
+}
+
+#[exonum_testkit::test(services(SumService), validators = 4, seed = 42)]
+fn test_validators_and_seed(testkit: &mut TestKit, api: TestKitApi) {
+    let other_testkit = TestKitBuilder::validator()
+        .with_validators(4)
+        .with_seed(42)
+        .with_service(SumService)
+        .create();
+    assert_eq!(testkit.network().validators().len(), 4);
+    assert_eq!(testkit.network().validators(), other_testkit.network().validators());
+
+    let info: HealthCheckInfo = api.get(ApiKind::System, "v1/healthcheck");
+    assert_eq!(info, HealthCheckInfo { connectivity: false });
+}
+
+#[exonum_testkit::test(services(SumService))]
+#[should_panic(expected = "Expected panic")]
+fn test_should_panic(_: &mut TestKit) {
+    panic!("Expected panic");
+}
@@ -0,0 +1,111 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for proofs of values in service maps.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey, SecretKey};
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Fork, ProofMapIndex, Snapshot};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum_testkit::proof::ProofError;
+
+const SERVICE_ID: u16 = 1;
+const TX_SET_ID: u16 = 1;
+const VALUES: &str = "registry.values";
+
+message! {
+    struct TxSet {
+        const TYPE = SERVICE_ID;
+        const ID = TX_SET_ID;
+
+        author: &PublicKey,
+        value: u64,
+    }
+}
+
+impl Transaction for TxSet {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        ProofMapIndex::new(VALUES, fork).put(self.author(), self.value());
+    }
+}
+
+/// Service storing a value per author in a map, which is the only table in its state hash.
+struct RegistryService;
+
+impl Service for RegistryService {
+    fn service_name(&self) -> &'static str {
+        "registry"
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        vec![ProofMapIndex::<_, PublicKey, u64>::new(VALUES, snapshot).root_hash()]
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxSet::from_raw(raw)?))
+    }
+}
+
+fn init_testkit() -> TestKit {
+    TestKitBuilder::validator()
+        .with_service(RegistryService)
+        .create()
+}
+
+fn set(testkit: &mut TestKit, keypair: &(PublicKey, SecretKey), value: u64) {
+    testkit.create_block_with_transaction(TxSet::new(&keypair.0, value, &keypair.1));
+}
+
+#[test]
+fn test_map_proof() {
+    let mut testkit = init_testkit();
+    let alice = crypto::gen_keypair();
+    let (bob, _) = crypto::gen_keypair();
+    set(&mut testkit, &alice, 100);
+
+    let proof = testkit.map_proof::<PublicKey, u64>(SERVICE_ID, 0, VALUES, alice.0);
+    assert_eq!(*proof.to_table().block(), testkit.explorer().block(Height(1)).unwrap());
+    assert_eq!(proof.assert_valid(), Some(&100));
+
+    // Proof of absence.
+    let proof = testkit.map_proof::<PublicKey, u64>(SERVICE_ID, 0, VALUES, bob);
+    assert_eq!(proof.assert_valid(), None);
+
+    // The proof is rooted in the latest block, so it does not match the previous root.
+    let root_hash = ProofMapIndex::<_, PublicKey, u64>::new(VALUES, &testkit.snapshot())
+        .root_hash();
+    set(&mut testkit, &alice, 200);
+    let proof = testkit.map_proof::<PublicKey, u64>(SERVICE_ID, 0, VALUES, alice.0);
+    assert_eq!(proof.to_table().validate(&root_hash), Err(ProofError::TableHashMismatch));
+    assert_eq!(proof.assert_valid(), Some(&200));
+
+    // Proof for a table not present in the state hash.
+    let proof = testkit.map_proof::<PublicKey, u64>(SERVICE_ID, 1, VALUES, alice.0);
+    assert_eq!(proof.validate(), Err(ProofError::MissingTable));
+}
@@ -0,0 +1,175 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for applications composed of several interacting services.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot, StorageValue};
+use exonum_testkit::{TestKit, TestKitBuilder};
+
+const ORDERS_ID: u16 = 1;
+const LEDGER_ID: u16 = 2;
+const ORDERED: &str = "orders.total";
+const PAID: &str = "ledger.paid";
+
+message! {
+    struct TxOrder {
+        const TYPE = ORDERS_ID;
+        const ID = 0;
+
+        author: &PublicKey,
+        amount: u64,
+    }
+}
+
+impl Transaction for TxOrder {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut total = Entry::new(ORDERED, fork);
+        let value = total.get().unwrap_or(0) + self.amount();
+        total.set(value);
+    }
+}
+
+message! {
+    struct TxPay {
+        const TYPE = LEDGER_ID;
+        const ID = 0;
+
+        author: &PublicKey,
+        amount: u64,
+        // Height of the block, which has triggered the payment.
+        height: u64,
+    }
+}
+
+impl Transaction for TxPay {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut paid = Entry::new(PAID, fork);
+        let value = paid.get().unwrap_or(0) + self.amount();
+        paid.set(value);
+    }
+}
+
+/// Service with a single entry, which is modified by its only transaction.
+struct EntryService {
+    id: u16,
+    name: &'static str,
+    entry: &'static str,
+}
+
+impl Service for EntryService {
+    fn service_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        vec![Entry::<_, u64>::new(self.entry, snapshot).hash()]
+    }
+
+    fn service_id(&self) -> u16 {
+        self.id
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        if self.id == ORDERS_ID {
+            Ok(Box::new(TxOrder::from_raw(raw)?))
+        } else {
+            Ok(Box::new(TxPay::from_raw(raw)?))
+        }
+    }
+}
+
+fn value(snapshot: &Snapshot, entry: &str) -> u64 {
+    Entry::new(entry, snapshot).get().unwrap_or(0)
+}
+
+fn init_testkit() -> TestKit {
+    TestKitBuilder::validator()
+        .with_service(EntryService {
+            id: ORDERS_ID,
+            name: "orders",
+            entry: ORDERED,
+        })
+        .with_service(EntryService {
+            id: LEDGER_ID,
+            name: "ledger",
+            entry: PAID,
+        })
+        .create()
+}
+
+#[test]
+fn test_route_after_commit() {
+    let mut testkit = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.route_after_commit(move |block, snapshot| {
+        let debt = value(snapshot, ORDERED) - value(snapshot, PAID);
+        if debt == 0 {
+            return Vec::new();
+        }
+        vec![Box::new(TxPay::new(&pubkey, debt, block.height().0, &key)) as Box<Transaction>]
+    });
+
+    let (author, author_key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxOrder::new(&author, 10, &author_key));
+    assert_eq!(testkit.mempool().len(), 1);
+    testkit.create_block();
+    assert!(testkit.mempool().is_empty());
+    testkit.create_block_with_transaction(TxOrder::new(&author, 5, &author_key));
+    testkit.create_block();
+
+    let snapshot = testkit.snapshot();
+    assert_eq!(value(&*snapshot, ORDERED), 15);
+    assert_eq!(value(&*snapshot, PAID), 15);
+    testkit.assert_state_hash();
+}
+
+#[test]
+fn test_services_snapshot() {
+    let mut testkit = init_testkit();
+    let (pubkey, key) = crypto::gen_keypair();
+    let before = testkit.services_snapshot();
+    assert_eq!(before.height(), Height(0));
+    assert_eq!(before.service_names(), vec!["ledger", "orders"]);
+    assert!(before.state_hashes("exchange").is_none());
+
+    testkit.create_block_with_transaction(TxOrder::new(&pubkey, 10, &key));
+    let after = testkit.services_snapshot();
+    assert_eq!(after.changed_services(&before), vec!["orders"]);
+    assert_eq!(after.state_hash(), testkit.explorer().block(Height(1)).unwrap().state_hash());
+    assert_eq!(value(after.snapshot(), ORDERED), 10);
+    assert_eq!(value(before.snapshot(), ORDERED), 0);
+
+    testkit.create_block_with_transaction(TxPay::new(&pubkey, 10, 0, &key));
+    let last = testkit.services_snapshot();
+    assert_eq!(last.changed_services(&before), vec!["ledger", "orders"]);
+    assert_eq!(last.changed_services(&after), vec!["ledger"]);
+    assert_ne!(last.state_hash(), after.state_hash());
+}
@@ -0,0 +1,126 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for checking order independence of transactions in the testkit.
+
+#[macro_use]
+extern crate exonum;
+#[macro_use]
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Schema, Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, ProofMapIndex, Snapshot};
+use exonum_testkit::TestKitBuilder;
+
+const SERVICE_ID: u16 = 1;
+const TX_STORE_ID: u16 = 1;
+
+message! {
+    struct TxStore {
+        const TYPE = SERVICE_ID;
+        const ID = TX_STORE_ID;
+
+        author: &PublicKey,
+        // If `true`, the stored value depends on the previously executed transactions.
+        cumulative: bool,
+    }
+}
+
+impl Transaction for TxStore {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let value = if self.cumulative {
+            let mut counter = Entry::new("store.counter", &mut *fork);
+            let value = counter.get().unwrap_or(0u64) + 1;
+            counter.set(value);
+            value
+        } else {
+            1
+        };
+        ProofMapIndex::new("store.values", fork).put(self.author(), value);
+    }
+}
+
+struct StoreService;
+
+impl Service for StoreService {
+    fn service_name(&self) -> &'static str {
+        "store"
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        let values: ProofMapIndex<_, PublicKey, u64> =
+            ProofMapIndex::new("store.values", snapshot);
+        vec![values.root_hash()]
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxStore::from_raw(raw)?))
+    }
+}
+
+fn transactions(cumulative: bool) -> Vec<Box<Transaction>> {
+    (0..3)
+        .map(|_| {
+            let (pubkey, key) = crypto::gen_keypair();
+            Box::new(TxStore::new(&pubkey, cumulative, &key)) as Box<Transaction>
+        })
+        .collect()
+}
+
+#[test]
+fn test_permutations_check() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(StoreService)
+        .create();
+    let txs = transactions(false);
+    let tx_hashes: Vec<_> = txs.iter().map(|tx| tx.hash()).collect();
+    testkit.create_block_with_permutations(txs);
+
+    let snapshot = testkit.snapshot();
+    let schema = Schema::new(&snapshot);
+    assert_eq!(schema.block_txs(testkit.height()).len(), 3);
+    for hash in &tx_hashes {
+        assert!(schema.transactions().contains(hash));
+        assert!(!testkit.mempool().contains_key(hash));
+    }
+}
+
+#[test]
+fn test_permutations_check_empty_block() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(StoreService)
+        .create();
+    testkit.create_block_with_permutations(txvec![]);
+    assert_eq!(testkit.height().0, 1);
+}
+
+#[test]
+#[should_panic(expected = "Execution of transactions depends on their order")]
+fn test_permutations_check_failure() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(StoreService)
+        .create();
+    testkit.create_block_with_permutations(transactions(true));
+}
@@ -0,0 +1,120 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the property-based testing support.
+
+#![cfg(feature = "proptest")]
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey, SecretKey};
+use exonum::encoding;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot};
+use exonum_testkit::{TestKit, TestKitBuilder};
+use exonum_testkit::proptest::{self, Config};
+
+const SERVICE_ID: u16 = 1;
+const TX_ADD_ID: u16 = 1;
+const TOTAL: &str = "sum.total";
+
+message! {
+    struct TxAdd {
+        const TYPE = SERVICE_ID;
+        const ID = TX_ADD_ID;
+
+        author: &PublicKey,
+        amount: u64,
+    }
+}
+
+impl Transaction for TxAdd {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let mut total = Entry::new(TOTAL, fork);
+        let value = total.get().unwrap_or(0) + self.amount();
+        total.set(value);
+    }
+}
+
+struct SumService;
+
+impl Service for SumService {
+    fn service_name(&self) -> &'static str {
+        "sum"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxAdd::from_raw(raw)?))
+    }
+}
+
+fn create_tx(keypair: &(PublicKey, SecretKey), seed: u64) -> Box<Transaction> {
+    Box::new(TxAdd::new(&keypair.0, seed % 1_000, &keypair.1))
+}
+
+fn create_testkit() -> TestKit {
+    TestKitBuilder::validator().with_service(SumService).create()
+}
+
+#[test]
+fn test_blocks_property() {
+    let keypairs = (0..3).map(|_| crypto::gen_keypair()).collect();
+    proptest::check(
+        Config::default(),
+        &proptest::blocks(create_tx, keypairs, 0..10),
+        create_testkit,
+        |testkit, blocks| {
+            proptest::create_blocks(testkit, blocks);
+            assert_eq!(testkit.height().0, blocks.len() as u64);
+
+            let expected: u64 = blocks
+                .iter()
+                .flat_map(|block| block.iter())
+                .map(|tx| TxAdd::from_raw(tx.raw().clone()).unwrap().amount())
+                .sum();
+            let total: Option<u64> = Entry::new(TOTAL, &testkit.snapshot()).get();
+            assert_eq!(total.unwrap_or(0), expected);
+        },
+    );
+}
+
+#[test]
+#[should_panic(expected = "Property does not hold")]
+fn test_failing_property() {
+    let keypairs = (0..3).map(|_| crypto::gen_keypair()).collect();
+    proptest::check(
+        Config::default(),
+        &proptest::blocks(create_tx, keypairs, 1..10),
+        create_testkit,
+        |testkit, blocks| {
+            proptest::create_blocks(testkit, blocks);
+            assert_eq!(testkit.height().0, 0, "Blocks have been created");
+        },
+    );
+}
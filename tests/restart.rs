@@ -0,0 +1,90 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the node restart emulation.
+
+extern crate exonum;
+extern crate exonum_testkit;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use exonum::blockchain::{Service, ServiceContext, Transaction};
+use exonum::crypto::Hash;
+use exonum::encoding;
+use exonum::helpers::Height;
+use exonum::messages::RawTransaction;
+use exonum::storage::Snapshot;
+use exonum_testkit::TestKitBuilder;
+
+/// Service counting committed blocks in memory.
+struct InMemoryService {
+    commits: Arc<AtomicUsize>,
+}
+
+impl Service for InMemoryService {
+    fn service_name(&self) -> &'static str {
+        "in_memory"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        1024
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Err(encoding::Error::IncorrectMessageType { message_type: raw.message_type() })
+    }
+
+    fn handle_commit(&self, _: &ServiceContext) {
+        self.commits.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_restart() {
+    let instances: Arc<Mutex<Vec<Arc<AtomicUsize>>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut testkit = {
+        let instances = Arc::clone(&instances);
+        TestKitBuilder::validator()
+            .with_service_factory(move || {
+                let commits = Arc::new(AtomicUsize::new(0));
+                instances.lock().unwrap().push(Arc::clone(&commits));
+                Box::new(InMemoryService { commits }) as Box<Service>
+            })
+            .create()
+    };
+    let commits = |index: usize| instances.lock().unwrap()[index].load(Ordering::SeqCst);
+
+    testkit.create_blocks_until(Height(2));
+    let last_hash = testkit.last_block_hash();
+    assert_eq!(instances.lock().unwrap().len(), 1);
+    assert_eq!(commits(0), 2);
+
+    testkit.restart();
+    assert_eq!(instances.lock().unwrap().len(), 2);
+    assert_eq!(testkit.height(), Height(2));
+    assert_eq!(testkit.last_block_hash(), last_hash);
+
+    testkit.create_block();
+    assert_eq!(commits(0), 2);
+    assert_eq!(commits(1), 1);
+
+    // The history of the blockchain survives the restart.
+    testkit.rollback(2);
+    assert_eq!(testkit.height(), Height(1));
+}
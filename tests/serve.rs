@@ -0,0 +1,158 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for serving testkit APIs over HTTP.
+
+extern crate exonum;
+extern crate exonum_testkit;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use exonum::helpers::Height;
+use exonum_testkit::TestKitBuilder;
+
+/// Sends an HTTP request and returns the response with headers.
+fn http_request(addr: SocketAddr, method: &str, path: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    ).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+fn http_get(addr: SocketAddr, path: &str) -> String {
+    http_request(addr, "GET", path, "")
+}
+
+fn http_post(addr: SocketAddr, path: &str, body: &str) -> String {
+    http_request(addr, "POST", path, body)
+}
+
+/// Returns a local address with a port that is currently free.
+fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+#[test]
+fn test_serve() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let localhost: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let server = testkit.serve(localhost, localhost);
+    assert_ne!(server.public_addr().port(), 0);
+    assert_ne!(server.public_addr(), server.private_addr());
+
+    let response = http_get(server.public_addr(), "/api/system/v1/healthcheck");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let response = http_get(server.public_addr(), "/api/unknown");
+    assert!(response.starts_with("HTTP/1.1 404"));
+
+    // Blocks can be created while the server is running.
+    testkit.create_block();
+    let response = http_get(server.public_addr(), "/api/system/v1/healthcheck");
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    server.shutdown();
+}
+
+#[test]
+fn test_serve_metrics() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let localhost: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let server = testkit.serve(localhost, localhost);
+    testkit.create_blocks_until(Height(2));
+    http_get(server.public_addr(), "/api/system/v1/healthcheck");
+    http_get(server.public_addr(), "/api/system/v1/healthcheck");
+
+    let response = http_get(server.public_addr(), "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("\nexonum_testkit_height 2\n"));
+    assert!(response.contains("\nexonum_testkit_mempool_size 0\n"));
+    assert!(response.contains(
+        "exonum_testkit_requests_total{api=\"public\",endpoint=\"/api/system/v1/healthcheck\"} 2",
+    ));
+    assert!(response.contains("\nexonum_testkit_block_execution_seconds_count 2\n"));
+    server.shutdown();
+}
+
+#[test]
+fn test_serve_with_control() {
+    let mut testkit = TestKitBuilder::validator().create();
+    let (public_addr, private_addr) = (free_addr(), free_addr());
+
+    let client = thread::spawn(move || {
+        // Wait until the server is started.
+        while TcpStream::connect(private_addr).is_err() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let responses = vec![
+            http_post(private_addr, "/api/testkit/v1/blocks", ""),
+            http_post(private_addr, "/api/testkit/v1/blocks", ""),
+            http_get(private_addr, "/api/testkit/v1/status"),
+            http_post(private_addr, "/api/testkit/v1/rollback", r#"{"blocks":1}"#),
+            http_post(private_addr, "/api/testkit/v1/rollback", r#"{"blocks":5}"#),
+            http_post(private_addr, "/api/testkit/v1/rollback", ""),
+            // The control API is not exposed on the public address.
+            http_get(public_addr, "/api/testkit/v1/status"),
+        ];
+        // Shut the server down before checking responses, so that the test does not hang
+        // if a check fails.
+        http_post(private_addr, "/api/testkit/v1/shutdown", "");
+        responses
+    });
+
+    testkit.serve_with_control(public_addr, private_addr);
+    let responses = client.join().unwrap();
+    assert_eq!(testkit.height(), Height(1));
+
+    assert!(responses[0].starts_with("HTTP/1.1 200"));
+    assert!(responses[0].ends_with(r#"{"height":1}"#));
+    assert!(responses[1].ends_with(r#"{"height":2}"#));
+    assert!(responses[2].ends_with(r#"{"height":2}"#));
+    assert!(responses[3].ends_with(r#"{"height":1}"#));
+    assert!(responses[4].starts_with("HTTP/1.1 400"));
+    assert!(responses[5].starts_with("HTTP/1.1 400"));
+    assert!(responses[6].starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn test_serve_shutdown_releases_addresses() {
+    let testkit = TestKitBuilder::validator().create();
+    let (public_addr, private_addr) = (free_addr(), free_addr());
+    let server = testkit.serve(public_addr, private_addr);
+    let response = http_get(public_addr, "/api/system/v1/healthcheck");
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    drop(server);
+    assert!(TcpStream::connect(public_addr).is_err());
+    // The addresses can be reused right away.
+    let server = testkit.serve(public_addr, private_addr);
+    let response = http_get(public_addr, "/api/system/v1/healthcheck");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    server.shutdown();
+    TcpListener::bind(public_addr).unwrap();
+    TcpListener::bind(private_addr).unwrap();
+}
@@ -73,3 +73,61 @@ impl Service for HandleCommitService {
         context.transaction_sender().send(Box::new(tx)).unwrap();
     }
 }
+
+const VALIDATOR_SERVICE_ID: u16 = 513;
+const TX_VALIDATOR_COMMIT_ID: u16 = 1;
+
+message! {
+    struct TxValidatorCommit {
+        const TYPE = VALIDATOR_SERVICE_ID;
+        const ID = TX_VALIDATOR_COMMIT_ID;
+
+        height: Height,
+    }
+}
+
+impl Transaction for TxValidatorCommit {
+    fn verify(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, _fork: &mut Fork) {}
+}
+
+/// Service generating transactions on `handle_commit` events only if the node
+/// is a validator, like services that broadcast transactions signed by validator keys.
+pub struct ValidatorCommitService;
+
+impl Service for ValidatorCommitService {
+    fn service_name(&self) -> &'static str {
+        "validator_commit"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        Vec::new()
+    }
+
+    fn service_id(&self) -> u16 {
+        VALIDATOR_SERVICE_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        let tx: Box<Transaction> = match raw.message_type() {
+            TX_VALIDATOR_COMMIT_ID => Box::new(TxValidatorCommit::from_raw(raw)?),
+            _ => {
+                return Err(encoding::Error::IncorrectMessageType {
+                    message_type: raw.message_type(),
+                });
+            }
+        };
+        Ok(tx)
+    }
+
+    fn handle_commit(&self, context: &ServiceContext) {
+        if context.validator_id().is_none() {
+            return;
+        }
+        let tx = TxValidatorCommit::new_with_signature(context.height(), &Signature::zero());
+        context.transaction_sender().send(Box::new(tx)).unwrap();
+    }
+}
@@ -18,14 +18,15 @@ extern crate exonum_testkit;
 extern crate serde;
 extern crate serde_json;
 
+use exonum::blockchain::Schema;
 use exonum::crypto::Signature;
 use exonum::messages::Message;
-use exonum::helpers::Height;
+use exonum::helpers::{Height, ValidatorId};
 use exonum_testkit::TestKitBuilder;
 
 mod hooks;
 // HACK: Silent "dead_code" warning.
-pub use hooks::{HandleCommitService, TxAfterCommit};
+pub use hooks::{HandleCommitService, TxAfterCommit, TxValidatorCommit, ValidatorCommitService};
 
 #[test]
 fn test_handle_commit() {
@@ -39,3 +40,121 @@ fn test_handle_commit() {
         assert!(testkit.mempool().contains_key(&tx.hash()));
     }
 }
+
+#[test]
+fn test_broadcast_txs_since_last_block() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(HandleCommitService)
+        .create();
+    assert!(testkit.broadcast_txs_since_last_block().is_empty());
+
+    testkit.create_block();
+    let tx = TxAfterCommit::new_with_signature(Height(1), &Signature::zero());
+    let broadcast: Vec<_> = testkit
+        .broadcast_txs_since_last_block()
+        .iter()
+        .map(|tx| tx.hash())
+        .collect();
+    assert_eq!(broadcast, vec![tx.hash()]);
+
+    // The broadcast transaction is committed in the next block.
+    testkit.create_block();
+    let snapshot = testkit.snapshot();
+    assert_eq!(
+        Schema::new(&snapshot).block_txs(Height(2)).iter().collect::<Vec<_>>(),
+        vec![tx.hash()]
+    );
+    let tx = TxAfterCommit::new_with_signature(Height(2), &Signature::zero());
+    let broadcast: Vec<_> = testkit
+        .broadcast_txs_since_last_block()
+        .iter()
+        .map(|tx| tx.hash())
+        .collect();
+    assert_eq!(broadcast, vec![tx.hash()]);
+}
+
+#[test]
+fn test_handle_commit_auditor() {
+    let mut testkit = TestKitBuilder::auditor()
+        .with_validators(2)
+        .with_service(ValidatorCommitService)
+        .create();
+    assert_eq!(testkit.network().us().validator_id(), None);
+    for i in 1..5 {
+        testkit.create_block();
+        let tx = TxValidatorCommit::new_with_signature(Height(i), &Signature::zero());
+        assert!(!testkit.mempool().contains_key(&tx.hash()));
+    }
+    assert!(testkit.mempool().is_empty());
+
+    // Services without the check generate transactions on auditor nodes as well.
+    let mut testkit = TestKitBuilder::auditor()
+        .with_validators(2)
+        .with_service(HandleCommitService)
+        .create();
+    testkit.create_block();
+    let tx = TxAfterCommit::new_with_signature(Height(1), &Signature::zero());
+    assert!(testkit.mempool().contains_key(&tx.hash()));
+}
+
+#[test]
+fn test_handle_commit_after_role_change() {
+    let mut testkit = TestKitBuilder::auditor()
+        .with_service(ValidatorCommitService)
+        .create();
+    let tx_at = |height| TxValidatorCommit::new_with_signature(Height(height), &Signature::zero());
+
+    // Promote our node to validators starting from height 3. The configuration is actual
+    // for services as soon as the block preceding `actual_from` is committed.
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        let mut validators = cfg.validators().to_vec();
+        validators.push(cfg.us().clone());
+        cfg.set_validators(validators);
+        cfg.set_actual_from(Height(3));
+        cfg
+    };
+    testkit.commit_configuration_change(proposal);
+    testkit.create_block();
+    assert!(!testkit.mempool().contains_key(&tx_at(1).hash()));
+    testkit.create_block();
+    assert!(testkit.mempool().contains_key(&tx_at(2).hash()));
+    assert_eq!(testkit.network().us().validator_id(), Some(ValidatorId(1)));
+
+    // Demote our node back to auditors starting from height 6.
+    let proposal = {
+        let mut cfg = testkit.configuration_change_proposal();
+        let validators = cfg.validators()[..1].to_vec();
+        cfg.set_validators(validators);
+        cfg.set_actual_from(Height(6));
+        cfg
+    };
+    testkit.commit_configuration_change(proposal);
+    testkit.create_blocks_until(Height(4));
+    assert!(testkit.mempool().contains_key(&tx_at(4).hash()));
+    testkit.create_block();
+    assert!(!testkit.mempool().contains_key(&tx_at(5).hash()));
+    assert_eq!(testkit.network().us().validator_id(), None);
+}
+
+#[test]
+fn test_create_blocks_until_condition() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(HandleCommitService)
+        .create();
+    let tx = TxAfterCommit::new_with_signature(Height(3), &Signature::zero());
+    // The transaction is generated after committing the block #3 and is committed
+    // in the next block.
+    let committed = testkit.create_blocks_until_condition(Height(10), |snapshot| {
+        Schema::new(snapshot).transactions().contains(&tx.hash())
+    });
+    assert!(committed);
+    assert_eq!(testkit.height(), Height(4));
+
+    let tx = TxAfterCommit::new_with_signature(Height(100), &Signature::zero());
+    let committed = testkit.create_blocks_until_condition(Height(10), |snapshot| {
+        Schema::new(snapshot).transactions().contains(&tx.hash())
+    });
+    assert!(!committed);
+    assert_eq!(testkit.height(), Height(10));
+}
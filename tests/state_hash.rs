@@ -0,0 +1,218 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the state hash check performed by the testkit.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use exonum::blockchain::{Service, Transaction};
+use exonum::crypto::{self, Hash, PublicKey};
+use exonum::encoding;
+use exonum::messages::{Message, RawTransaction};
+use exonum::storage::{Entry, Fork, Snapshot};
+use exonum::helpers::Height;
+use exonum_testkit::TestKitBuilder;
+
+/// Service with a state hash that changes on every call.
+struct NondeterministicService {
+    calls: AtomicUsize,
+}
+
+impl NondeterministicService {
+    fn new() -> Self {
+        NondeterministicService { calls: AtomicUsize::new(0) }
+    }
+}
+
+impl Service for NondeterministicService {
+    fn service_name(&self) -> &'static str {
+        "nondeterministic"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        let calls = self.calls.fetch_add(1, Ordering::SeqCst);
+        vec![crypto::hash(&[calls as u8])]
+    }
+
+    fn service_id(&self) -> u16 {
+        1
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Err(encoding::Error::IncorrectMessageType { message_type: raw.message_type() })
+    }
+}
+
+/// Service with a constant state hash.
+struct ConstantService;
+
+impl Service for ConstantService {
+    fn service_name(&self) -> &'static str {
+        "constant"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        vec![crypto::hash(b"foo"), crypto::hash(b"bar")]
+    }
+
+    fn service_id(&self) -> u16 {
+        2
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Err(encoding::Error::IncorrectMessageType { message_type: raw.message_type() })
+    }
+}
+
+const FORGETFUL_ID: u16 = 3;
+
+message! {
+    struct TxStore {
+        const TYPE = FORGETFUL_ID;
+        const ID = 0;
+
+        author: &PublicKey,
+        value: u64,
+        // Whether to store the value into the index included into the state hash.
+        included: bool,
+    }
+}
+
+impl Transaction for TxStore {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, fork: &mut Fork) {
+        let name = if self.included() {
+            "forgetful.included"
+        } else {
+            "forgetful.omitted"
+        };
+        Entry::new(name, fork).set(self.value());
+    }
+}
+
+/// Service, which leaves one of its indexes out of the state hash.
+struct ForgetfulService;
+
+impl Service for ForgetfulService {
+    fn service_name(&self) -> &'static str {
+        "forgetful"
+    }
+
+    fn state_hash(&self, snapshot: &Snapshot) -> Vec<Hash> {
+        vec![Entry::<_, u64>::new("forgetful.included", snapshot).hash()]
+    }
+
+    fn service_id(&self) -> u16 {
+        FORGETFUL_ID
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Ok(Box::new(TxStore::from_raw(raw)?))
+    }
+}
+
+/// Service, which has removed the second table from its state hash.
+struct ShrunkService;
+
+impl Service for ShrunkService {
+    fn service_name(&self) -> &'static str {
+        "constant"
+    }
+
+    fn state_hash(&self, _: &Snapshot) -> Vec<Hash> {
+        vec![crypto::hash(b"foo")]
+    }
+
+    fn service_id(&self) -> u16 {
+        2
+    }
+
+    fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<Transaction>, encoding::Error> {
+        Err(encoding::Error::IncorrectMessageType { message_type: raw.message_type() })
+    }
+}
+
+#[test]
+fn test_state_hash_check() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(ConstantService)
+        .with_state_hash_check()
+        .create();
+    testkit.create_blocks_until(Height(3));
+    testkit.fast_forward_to(Height(5));
+}
+
+#[test]
+#[should_panic(expected = "State hash of table #0 of service `nondeterministic`")]
+fn test_state_hash_check_failure() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(NondeterministicService::new())
+        .with_state_hash_check()
+        .create();
+    testkit.create_block();
+}
+
+#[test]
+fn test_state_hash_check_disabled() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(NondeterministicService::new())
+        .create();
+    testkit.create_block();
+}
+
+#[test]
+fn test_state_hash_check_changed_index() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(ForgetfulService)
+        .with_state_hash_check()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxStore::new(&pubkey, 1, true, &key));
+    testkit.create_block_with_transaction(TxStore::new(&pubkey, 2, true, &key));
+}
+
+#[test]
+#[should_panic(expected = "`forgetful` has changed indexes [\"forgetful.omitted\"] in block 1")]
+fn test_state_hash_check_omitted_index() {
+    let mut testkit = TestKitBuilder::validator()
+        .with_service(ForgetfulService)
+        .with_state_hash_check()
+        .create();
+    let (pubkey, key) = crypto::gen_keypair();
+    testkit.create_block_with_transaction(TxStore::new(&pubkey, 1, false, &key));
+}
+
+#[test]
+#[should_panic(expected = "Service `constant` returns 1 state hashes")]
+fn test_state_hash_check_stale_table() {
+    static CREATED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    let mut testkit = TestKitBuilder::validator()
+        .with_service_factory(|| if CREATED.fetch_add(1, Ordering::SeqCst) == 0 {
+            Box::new(ConstantService) as Box<Service>
+        } else {
+            Box::new(ShrunkService)
+        })
+        .create();
+    testkit.create_block();
+    testkit.restart();
+    testkit.assert_state_hash();
+}
@@ -0,0 +1,86 @@
+// Copyright 2017 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the signature tampering checks.
+
+#[macro_use]
+extern crate exonum;
+extern crate exonum_testkit;
+
+use exonum::blockchain::Transaction;
+use exonum::crypto::{self, PublicKey};
+use exonum::messages::Message;
+use exonum::storage::Fork;
+use exonum_testkit::tamper::assert_rejects_tampering;
+
+const SERVICE_ID: u16 = 1;
+
+message! {
+    struct TxSigned {
+        const TYPE = SERVICE_ID;
+        const ID = 1;
+
+        author: &PublicKey,
+        text: &str,
+    }
+}
+
+impl Transaction for TxSigned {
+    fn verify(&self) -> bool {
+        self.verify_signature(self.author())
+    }
+
+    fn execute(&self, _: &mut Fork) {}
+}
+
+message! {
+    struct TxUnsigned {
+        const TYPE = SERVICE_ID;
+        const ID = 2;
+
+        author: &PublicKey,
+        text: &str,
+    }
+}
+
+impl Transaction for TxUnsigned {
+    // Forgets to check the signature.
+    fn verify(&self) -> bool {
+        !self.text().is_empty()
+    }
+
+    fn execute(&self, _: &mut Fork) {}
+}
+
+#[test]
+fn test_rejects_tampering() {
+    let (pubkey, key) = crypto::gen_keypair();
+    assert_rejects_tampering(&TxSigned::new(&pubkey, "Hello", &key), TxSigned::from_raw);
+    assert_rejects_tampering(&TxSigned::new(&pubkey, "", &key), TxSigned::from_raw);
+}
+
+#[test]
+#[should_panic(expected = "Tampered transaction passes verify()")]
+fn test_accepts_tampering() {
+    let (pubkey, key) = crypto::gen_keypair();
+    assert_rejects_tampering(&TxUnsigned::new(&pubkey, "Hello", &key), TxUnsigned::from_raw);
+}
+
+#[test]
+#[should_panic(expected = "Transaction does not pass verify() before tampering")]
+fn test_invalid_transaction() {
+    let (pubkey, _) = crypto::gen_keypair();
+    let (_, other_key) = crypto::gen_keypair();
+    assert_rejects_tampering(&TxSigned::new(&pubkey, "Hello", &other_key), TxSigned::from_raw);
+}